@@ -0,0 +1,7 @@
+-- Your SQL goes here
+CREATE TABLE kv_store (
+  namespace TEXT NOT NULL,
+  key TEXT NOT NULL,
+  value TEXT NOT NULL,
+  PRIMARY KEY (namespace, key)
+)
@@ -0,0 +1,149 @@
+//! Helpers to load sections of the dhall configuration file with
+//! error messages which are useful for whoever is running the bot.
+use std::path::Path;
+
+use serde::de::DeserializeOwned;
+use serde_dhall::SimpleValue;
+
+/// A piece of configuration living in the shared dhall config file.
+pub trait ConfigSection: DeserializeOwned {
+    /// Key under which this config lives in the dhall file.
+    /// None means the fields are at the top level of the file.
+    const SECTION: Option<&'static str>;
+
+    /// Short description of the expected dhall type, shown when
+    /// the config cannot be parsed.
+    const SCHEMA: &'static str;
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ConfigError {
+    #[error("Cannot read config {origin}: {source}")]
+    Unreadable {
+        origin: String,
+        source: serde_dhall::Error,
+    },
+
+    #[error("Missing section `{section}` in config {origin}. Expected: {section} = {schema}")]
+    MissingSection {
+        origin: String,
+        section: &'static str,
+        schema: &'static str,
+    },
+
+    #[error("Invalid section `{section}` in config {origin}: {source}. Expected: {schema}")]
+    InvalidSection {
+        origin: String,
+        section: &'static str,
+        schema: &'static str,
+        source: serde_dhall::Error,
+    },
+}
+
+/// Load the config section `T` from the dhall file at `path`
+pub fn load<T: ConfigSection>(path: impl AsRef<Path>) -> Result<T, ConfigError> {
+    let origin = path.as_ref().display().to_string();
+    let value = serde_dhall::from_file(path)
+        .parse::<SimpleValue>()
+        .map_err(|source| ConfigError::Unreadable {
+            origin: origin.clone(),
+            source,
+        })?;
+    section(value, origin)
+}
+
+/// Same as `load`, but falls back to the default value when the section is absent
+pub fn load_or_default<T: ConfigSection + Default>(
+    path: impl AsRef<Path>,
+) -> Result<T, ConfigError> {
+    match load(path) {
+        Err(ConfigError::MissingSection { .. }) => Ok(T::default()),
+        x => x,
+    }
+}
+
+/// Load the config section `T` from a dhall expression
+pub fn from_str<T: ConfigSection>(dhall: &str) -> Result<T, ConfigError> {
+    let origin = "<string>".to_string();
+    let value = serde_dhall::from_str(dhall)
+        .parse::<SimpleValue>()
+        .map_err(|source| ConfigError::Unreadable {
+            origin: origin.clone(),
+            source,
+        })?;
+    section(value, origin)
+}
+
+fn section<T: ConfigSection>(value: SimpleValue, origin: String) -> Result<T, ConfigError> {
+    let (section, value) = match T::SECTION {
+        None => ("<top level>", value),
+        Some(key) => {
+            let value = match value {
+                SimpleValue::Record(mut fields) => fields.remove(key),
+                _ => None,
+            };
+            match value {
+                Some(v) => (key, v),
+                None => {
+                    return Err(ConfigError::MissingSection {
+                        origin,
+                        section: key,
+                        schema: T::SCHEMA,
+                    })
+                }
+            }
+        }
+    };
+
+    serde_dhall::from_simple_value(value).map_err(|source| ConfigError::InvalidSection {
+        origin,
+        section,
+        schema: T::SCHEMA,
+        source,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[derive(Debug, PartialEq, Default, serde::Deserialize)]
+    struct Coucou {
+        name: String,
+    }
+
+    impl ConfigSection for Coucou {
+        const SECTION: Option<&'static str> = Some("coucou");
+        const SCHEMA: &'static str = "{ name : Text }";
+    }
+
+    #[test]
+    fn test_load_section() {
+        assert_eq!(
+            from_str::<Coucou>(r#"{ coucou = { name = "charlie" }, other = 1 }"#).unwrap(),
+            Coucou {
+                name: "charlie".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn test_missing_section() {
+        let err = from_str::<Coucou>("{ other = 1 }").unwrap_err().to_string();
+        assert!(err.contains("`coucou`"), "names the section: {err}");
+        assert!(
+            err.contains("{ name : Text }"),
+            "hints at the schema: {err}"
+        );
+    }
+
+    #[test]
+    fn test_invalid_section() {
+        let err = from_str::<Coucou>("{ coucou = { nom = 1 } }")
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("`coucou`"), "names the section: {err}");
+        assert!(err.contains("`name`"), "names the missing field: {err}");
+    }
+}
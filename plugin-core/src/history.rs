@@ -0,0 +1,147 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+
+use irc::proto::{Command, Message};
+
+use crate::utils::network::channel_key;
+use crate::utils::parser::command_prefix;
+
+const DEFAULT_HISTORY_SIZE: usize = 10;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HistoryLine {
+    pub nick: String,
+    pub text: String,
+}
+
+/// The last few lines said in each channel, excluding commands and
+/// the bot's own messages. This lets commands operate on the previous line
+/// when they're not given an explicit argument.
+/// Cloning is cheap and all clones share the same history.
+#[derive(Debug, Clone)]
+pub struct MessageHistory {
+    capacity: usize,
+    lines: Arc<Mutex<HashMap<String, VecDeque<HistoryLine>>>>,
+}
+
+impl Default for MessageHistory {
+    fn default() -> Self {
+        MessageHistory::new(DEFAULT_HISTORY_SIZE)
+    }
+}
+
+impl MessageHistory {
+    pub fn new(capacity: usize) -> Self {
+        MessageHistory {
+            capacity,
+            lines: Default::default(),
+        }
+    }
+
+    /// Record the message if it's a PRIVMSG to a channel which isn't a command,
+    /// and wasn't sent by `own_nick`.
+    pub fn record(&self, msg: &Message, own_nick: &str) {
+        let (channel, text) = match &msg.command {
+            Command::PRIVMSG(target, text) if target.starts_with('#') => (target, text),
+            _ => return,
+        };
+        let nick = match msg.source_nickname() {
+            Some(nick) if nick != own_nick => nick,
+            _ => return,
+        };
+        if command_prefix(text).is_ok() {
+            return;
+        }
+
+        let mut lines = self.lines.lock().expect("history lock");
+        let channel_lines = lines.entry(channel_key(msg, channel)).or_default();
+        channel_lines.push_back(HistoryLine {
+            nick: nick.to_string(),
+            text: text.to_string(),
+        });
+        while channel_lines.len() > self.capacity {
+            channel_lines.pop_front();
+        }
+    }
+
+    /// The last line said in the channel of the key,
+    /// see [`crate::utils::network::channel_key`]
+    pub fn last(&self, channel: &str) -> Option<HistoryLine> {
+        self.lines
+            .lock()
+            .expect("history lock")
+            .get(channel)
+            .and_then(|lines| lines.back().cloned())
+    }
+
+    /// All the recorded lines for the channel of the key, most recent first
+    pub fn lines(&self, channel: &str) -> Vec<HistoryLine> {
+        self.lines
+            .lock()
+            .expect("history lock")
+            .get(channel)
+            .map(|lines| lines.iter().rev().cloned().collect())
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::utils::network::with_network;
+    use pretty_assertions::assert_eq;
+
+    fn privmsg(nick: &str, target: &str, text: &str) -> Message {
+        format!(":{nick}!{nick}@host PRIVMSG {target} :{text}")
+            .parse()
+            .unwrap()
+    }
+
+    fn line(nick: &str, text: &str) -> HistoryLine {
+        HistoryLine {
+            nick: nick.to_string(),
+            text: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_keeps_last_lines_per_channel() {
+        let history = MessageHistory::new(2);
+        history.record(&privmsg("alice", "#coucou", "one"), "golem");
+        history.record(&privmsg("bob", "#coucou", "two"), "golem");
+        history.record(&privmsg("alice", "#other", "elsewhere"), "golem");
+        history.record(&privmsg("charlie", "#coucou", "three"), "golem");
+
+        assert_eq!(
+            history.lines("#coucou"),
+            vec![line("charlie", "three"), line("bob", "two")]
+        );
+        assert_eq!(history.last("#other"), Some(line("alice", "elsewhere")));
+        assert_eq!(history.last("#nope"), None);
+    }
+
+    #[test]
+    fn test_ignores_own_messages_and_commands() {
+        let history = MessageHistory::new(5);
+        history.record(&privmsg("alice", "#coucou", "hello"), "golem");
+        history.record(&privmsg("golem", "#coucou", "I'm a bot"), "golem");
+        history.record(&privmsg("bob", "#coucou", "λcrypto btc"), "golem");
+        history.record(&privmsg("bob", "golem", "private message"), "golem");
+
+        assert_eq!(history.lines("#coucou"), vec![line("alice", "hello")]);
+    }
+
+    #[test]
+    fn test_keeps_servers_apart() {
+        let history = MessageHistory::new(5);
+        let oftc = with_network(privmsg("bob", "#coucou", "on oftc"), Some("irc.oftc.net"));
+        history.record(&privmsg("alice", "#coucou", "on libera"), "golem");
+        history.record(&oftc, "golem");
+
+        assert_eq!(history.lines("#coucou"), vec![line("alice", "on libera")]);
+        assert_eq!(
+            history.last("irc.oftc.net #coucou"),
+            Some(line("bob", "on oftc"))
+        );
+    }
+}
@@ -1,4 +1,14 @@
+#[macro_use]
+extern crate diesel;
+
+pub mod config;
+pub mod history;
+pub mod metrics;
+pub mod store;
+#[cfg(any(test, feature = "test-util"))]
+pub mod test_util;
 mod types;
 pub mod utils;
 
-pub use types::{Error, Result, WrapError, Plugin, Config, Initialised};
+pub use types::{Error, Result, WrapError, Plugin, Config, Initialised, CommandHelp, USER_AGENT};
+pub use history::MessageHistory;
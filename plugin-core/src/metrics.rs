@@ -0,0 +1,123 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use prometheus::{Encoder, Opts, Registry, TextEncoder};
+
+pub use prometheus::{IntCounter, IntCounterVec};
+
+/// Content type of `BotMetrics::render`
+pub const CONTENT_TYPE: &str = prometheus::TEXT_FORMAT;
+
+/// Counters of the bot, exported in the prometheus text format by the
+/// metrics plugin. The core counters are updated by the bot itself, and
+/// plugins can get their own with `counter`.
+/// Cloning is cheap and all clones share the same counters.
+#[derive(Clone)]
+pub struct BotMetrics {
+    registry: Registry,
+    /// messages received from IRC
+    pub messages_received: IntCounter,
+    /// errors returned by `in_message`, by plugin
+    pub plugin_errors: IntCounterVec,
+    /// replies sent from `in_message`, by plugin
+    pub commands_handled: IntCounterVec,
+    /// counters registered by the plugins, by name
+    plugin_counters: Arc<Mutex<HashMap<String, IntCounter>>>,
+}
+
+impl Default for BotMetrics {
+    fn default() -> Self {
+        BotMetrics::new()
+    }
+}
+
+impl BotMetrics {
+    pub fn new() -> Self {
+        let registry =
+            Registry::new_custom(Some("golem".to_string()), None).expect("valid metrics prefix");
+        let messages_received =
+            IntCounter::new("messages_received_total", "Messages received from IRC")
+                .expect("valid counter");
+        let plugin_errors = IntCounterVec::new(
+            Opts::new("plugin_errors_total", "Errors returned by the plugins"),
+            &["plugin"],
+        )
+        .expect("valid counter");
+        let commands_handled = IntCounterVec::new(
+            Opts::new("commands_handled_total", "Replies sent by the plugins"),
+            &["plugin"],
+        )
+        .expect("valid counter");
+        for c in [&plugin_errors, &commands_handled] {
+            registry
+                .register(Box::new(c.clone()))
+                .expect("core counters registered once");
+        }
+        registry
+            .register(Box::new(messages_received.clone()))
+            .expect("core counters registered once");
+
+        BotMetrics {
+            registry,
+            messages_received,
+            plugin_errors,
+            commands_handled,
+            plugin_counters: Default::default(),
+        }
+    }
+
+    /// A counter for a plugin, `name` is prefixed with `golem_`.
+    /// Asking twice for the same name gives the same counter, so that
+    /// several instances of a plugin can share it.
+    pub fn counter(&self, name: &str, help: &str) -> prometheus::Result<IntCounter> {
+        let mut counters = self.plugin_counters.lock().expect("metrics lock");
+        if let Some(counter) = counters.get(name) {
+            return Ok(counter.clone());
+        }
+        let counter = IntCounter::new(name, help)?;
+        self.registry.register(Box::new(counter.clone()))?;
+        counters.insert(name.to_string(), counter.clone());
+        Ok(counter)
+    }
+
+    /// All the counters, in the prometheus text format
+    pub fn render(&self) -> prometheus::Result<String> {
+        let mut buffer = vec![];
+        TextEncoder::new().encode(&self.registry.gather(), &mut buffer)?;
+        Ok(String::from_utf8_lossy(&buffer).into_owned())
+    }
+}
+
+impl std::fmt::Debug for BotMetrics {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BotMetrics").finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let metrics = BotMetrics::new();
+        metrics.messages_received.inc();
+        metrics
+            .commands_handled
+            .with_label_values(&["crypto"])
+            .inc();
+        let sent = metrics
+            .counter("notifications_sent_total", "Notifications")
+            .unwrap();
+        sent.inc();
+        metrics
+            .counter("notifications_sent_total", "Notifications")
+            .expect("same counter")
+            .inc();
+
+        let rendered = metrics.render().unwrap();
+        assert!(rendered.contains("golem_messages_received_total 1"));
+        assert!(rendered.contains("golem_commands_handled_total{plugin=\"crypto\"} 1"));
+        assert!(rendered.contains("golem_notifications_sent_total 2"));
+    }
+}
@@ -0,0 +1,192 @@
+//! Access to the database shared by the plugins, and a small key-value
+//! store for plugins which only need to remember a few values.
+use std::sync::RwLock;
+
+use anyhow::Context;
+use diesel::connection::SimpleConnection;
+use diesel::prelude::*;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// Used when no path is configured
+pub const DEFAULT_DB_PATH: &str = "rustygolem.sqlite";
+
+/// How long a connection waits for the other ones to release the database
+/// before failing with "database is locked"
+const BUSY_TIMEOUT_MS: u32 = 5000;
+
+diesel_migrations::embed_migrations!("./migrations/");
+
+/// Configured path of the database, empty until set at startup
+static DB_PATH: RwLock<String> = RwLock::new(String::new());
+
+table! {
+    kv_store (namespace, key) {
+        namespace -> Text,
+        key -> Text,
+        value -> Text,
+    }
+}
+
+use self::kv_store::dsl;
+
+/// Set the path of the sqlite database for the whole bot.
+/// An empty path restores the default one.
+pub fn set_db_path(path: &str) {
+    *DB_PATH.write().expect("db path lock") = path.to_string();
+}
+
+pub fn db_path() -> String {
+    let path = DB_PATH.read().expect("db path lock");
+    if path.is_empty() {
+        DEFAULT_DB_PATH.to_string()
+    } else {
+        path.clone()
+    }
+}
+
+/// Each plugin opens its own connections, so they wait for each other
+/// instead of failing, and readers don't block the writer.
+pub fn establish_connection() -> anyhow::Result<SqliteConnection> {
+    let db_url = db_path();
+    let conn = SqliteConnection::establish(&db_url)
+        .with_context(|| format!("cannot connect to db at {}", db_url))?;
+    conn.batch_execute(&format!(
+        "PRAGMA busy_timeout = {}; PRAGMA journal_mode = WAL;",
+        BUSY_TIMEOUT_MS
+    ))
+    .with_context(|| format!("cannot configure db at {}", db_url))?;
+    Ok(conn)
+}
+
+/// Creates the tables of plugin-core, the bot runs it at startup
+/// with the migrations of the other crates.
+pub fn run_migrations(conn: &SqliteConnection) -> anyhow::Result<()> {
+    embedded_migrations::run(conn).context("Cannot run migration")
+}
+
+/// Run `f` with a connection to the database, without blocking the async runtime
+pub async fn with_connection<F, T>(f: F) -> anyhow::Result<T>
+where
+    F: FnOnce(&SqliteConnection) -> anyhow::Result<T> + Send + 'static,
+    T: Send + 'static,
+{
+    tokio::task::spawn_blocking(move || {
+        let conn = establish_connection()?;
+        f(&conn)
+    })
+    .await
+    .context("database task failed")?
+}
+
+/// Values serialized as json, keyed by strings. Each plugin should use
+/// its own namespace so that keys don't collide. The table is created by
+/// [`run_migrations`].
+#[derive(Debug, Clone)]
+pub struct KvStore {
+    namespace: String,
+}
+
+impl KvStore {
+    pub fn new(namespace: &str) -> Self {
+        KvStore {
+            namespace: namespace.to_string(),
+        }
+    }
+
+    pub async fn get<T>(&self, key: &str) -> anyhow::Result<Option<T>>
+    where
+        T: DeserializeOwned,
+    {
+        let (namespace, key) = (self.namespace.clone(), key.to_string());
+        let raw = with_connection(move |conn| get_raw(conn, &namespace, &key)).await?;
+        raw.map(|raw| serde_json::from_str(&raw).context("invalid stored value"))
+            .transpose()
+    }
+
+    pub async fn set<T>(&self, key: &str, value: &T) -> anyhow::Result<()>
+    where
+        T: Serialize,
+    {
+        let raw = serde_json::to_string(value).context("cannot serialize value")?;
+        let (namespace, key) = (self.namespace.clone(), key.to_string());
+        with_connection(move |conn| set_raw(conn, &namespace, &key, &raw)).await
+    }
+
+    /// Returns false if there was nothing to delete
+    pub async fn delete(&self, key: &str) -> anyhow::Result<bool> {
+        let (namespace, key) = (self.namespace.clone(), key.to_string());
+        with_connection(move |conn| delete_raw(conn, &namespace, &key)).await
+    }
+}
+
+fn get_raw(conn: &SqliteConnection, namespace: &str, key: &str) -> anyhow::Result<Option<String>> {
+    dsl::kv_store
+        .filter(dsl::namespace.eq(namespace))
+        .filter(dsl::key.eq(key))
+        .select(dsl::value)
+        .first::<String>(conn)
+        .optional()
+        .with_context(|| format!("Cannot load {}/{}", namespace, key))
+}
+
+fn set_raw(conn: &SqliteConnection, namespace: &str, key: &str, value: &str) -> anyhow::Result<()> {
+    diesel::replace_into(dsl::kv_store)
+        .values((
+            dsl::namespace.eq(namespace),
+            dsl::key.eq(key),
+            dsl::value.eq(value),
+        ))
+        .execute(conn)
+        .with_context(|| format!("Cannot save {}/{}", namespace, key))?;
+    Ok(())
+}
+
+fn delete_raw(conn: &SqliteConnection, namespace: &str, key: &str) -> anyhow::Result<bool> {
+    let deleted = diesel::delete(
+        dsl::kv_store
+            .filter(dsl::namespace.eq(namespace))
+            .filter(dsl::key.eq(key)),
+    )
+    .execute(conn)
+    .with_context(|| format!("Cannot delete {}/{}", namespace, key))?;
+    Ok(deleted > 0)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_kv_store() {
+        let conn = SqliteConnection::establish(":memory:").unwrap();
+        run_migrations(&conn).unwrap();
+
+        assert_eq!(get_raw(&conn, "karma", "charlie").unwrap(), None);
+        set_raw(&conn, "karma", "charlie", "1").unwrap();
+        set_raw(&conn, "karma", "charlie", "2").unwrap();
+        set_raw(&conn, "seen", "charlie", "\"hier\"").unwrap();
+        assert_eq!(
+            get_raw(&conn, "karma", "charlie").unwrap(),
+            Some("2".to_string())
+        );
+
+        assert!(delete_raw(&conn, "karma", "charlie").unwrap());
+        assert!(!delete_raw(&conn, "karma", "charlie").unwrap());
+        assert_eq!(
+            get_raw(&conn, "seen", "charlie").unwrap(),
+            Some("\"hier\"".to_string()),
+            "namespaces are separate"
+        );
+    }
+
+    #[test]
+    fn test_db_path() {
+        assert_eq!(db_path(), DEFAULT_DB_PATH);
+        set_db_path("/var/lib/golem/golem.sqlite");
+        assert_eq!(db_path(), "/var/lib/golem/golem.sqlite");
+        set_db_path("");
+        assert_eq!(db_path(), DEFAULT_DB_PATH);
+    }
+}
@@ -0,0 +1,103 @@
+//! Helpers to build irc messages and inspect the replies in plugin tests.
+//! Enable the `test-util` feature in the dev-dependencies to use them.
+use irc::proto::{Command, Message};
+
+/// A PRIVMSG sent by `from` to `target`, which can be a channel or a nickname
+pub fn privmsg(from: &str, target: &str, text: &str) -> Message {
+    Message::with_tags(
+        None,
+        Some(&format!("{from}!{from}@localhost")),
+        "PRIVMSG",
+        vec![target, text],
+    )
+    .expect("valid PRIVMSG")
+}
+
+/// A NOTICE sent by `from` to `target`
+pub fn notice(from: &str, target: &str, text: &str) -> Message {
+    Message::with_tags(
+        None,
+        Some(&format!("{from}!{from}@localhost")),
+        "NOTICE",
+        vec![target, text],
+    )
+    .expect("valid NOTICE")
+}
+
+/// `from` joining `channel`
+pub fn join(from: &str, channel: &str) -> Message {
+    Message::with_tags(
+        None,
+        Some(&format!("{from}!{from}@localhost")),
+        "JOIN",
+        vec![channel],
+    )
+    .expect("valid JOIN")
+}
+
+/// The target and text of a PRIVMSG or NOTICE, None for any other command
+pub fn message_text(msg: &Message) -> Option<(&str, &str)> {
+    match &msg.command {
+        Command::PRIVMSG(target, text) | Command::NOTICE(target, text) => Some((target, text)),
+        _ => None,
+    }
+}
+
+/// The text of a plugin reply, panicking if there is no reply
+/// or if it isn't a PRIVMSG or a NOTICE
+#[track_caller]
+pub fn reply_text(reply: Option<Message>) -> String {
+    let reply = reply.expect("expected a reply");
+    match message_text(&reply) {
+        Some((_target, text)) => text.to_string(),
+        None => panic!("expected a PRIVMSG or NOTICE reply, got {:?}", reply),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_builders() {
+        let msg = privmsg("charlie", "#coucou", "λcrypto btc");
+        assert_eq!(
+            msg.to_string(),
+            ":charlie!charlie@localhost PRIVMSG #coucou :λcrypto btc\r\n"
+        );
+        assert_eq!(msg.source_nickname(), Some("charlie"));
+        assert_eq!(msg.response_target(), Some("#coucou"));
+        assert_eq!(
+            privmsg("charlie", "golem", "coucou").response_target(),
+            Some("charlie"),
+            "private messages are answered to the sender"
+        );
+        assert_eq!(
+            message_text(&notice("golem", "#coucou", "hello")),
+            Some(("#coucou", "hello"))
+        );
+        assert_eq!(
+            join("charlie", "#coucou").to_string(),
+            ":charlie!charlie@localhost JOIN #coucou\r\n"
+        );
+    }
+
+    #[test]
+    fn test_reply_text() {
+        assert_eq!(
+            reply_text(Some(privmsg("golem", "#coucou", "hello"))),
+            "hello"
+        );
+        assert_eq!(
+            message_text(&Command::PING("x".to_string(), None).into()),
+            None
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "expected a reply")]
+    fn test_reply_text_no_reply() {
+        reply_text(None);
+    }
+}
@@ -20,6 +20,9 @@ pub enum Error {
 
     #[error("Generic error")]
     Generic(#[from] anyhow::Error),
+
+    #[error("Config error: {0}")]
+    Config(#[from] crate::config::ConfigError),
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
@@ -30,8 +33,46 @@ pub trait WrapError<T> {
     fn wrap(self) -> Result<T>;
 }
 
+/// User agent of the http requests made by the bot
+pub const USER_AGENT: &str = "rustygolem: https://github.com/CoucouInc/rustygolem";
+
 pub struct Config {
     pub config_path: String,
+    /// irc nickname of the bot
+    pub nickname: String,
+    /// nicks currently used by the bot, which can differ from `nickname`
+    pub own_nicks: crate::utils::nicks::OwnNicks,
+    /// irc nicknames allowed to use privileged commands
+    pub owners: crate::utils::owners::Owners,
+    /// irc nicknames of other bots, usually ignored by the plugins
+    pub blacklisted_users: Vec<String>,
+    /// recent lines of each channel, shared with the bot which fills it
+    pub history: crate::history::MessageHistory,
+    /// http client shared by the plugins, so that connections are pooled
+    pub http_client: reqwest::Client,
+    /// counters exported by the metrics plugin, shared with the bot
+    pub metrics: crate::metrics::BotMetrics,
+}
+
+/// Description of a command, shown by λhelp
+#[derive(Debug, Clone, PartialEq)]
+pub struct CommandHelp {
+    /// the command word, without the prefix, like `crypto`
+    pub name: &'static str,
+    /// arguments after the command, like `<coin> [devise]`
+    pub args: &'static str,
+    /// one line explanation
+    pub description: &'static str,
+}
+
+impl CommandHelp {
+    pub fn new(name: &'static str, args: &'static str, description: &'static str) -> Self {
+        CommandHelp {
+            name,
+            args,
+            description,
+        }
+    }
 }
 
 pub struct Initialised {
@@ -67,10 +108,22 @@ pub trait Plugin: Sync + Send {
 
     /// Method invoked whenever a message is received from IRC
     /// Returns Some(Message) if a response message should be sent, None otherwise
+    ///
+    /// Every command reaches the plugins, not only PRIVMSG: JOIN, PART, QUIT,
+    /// NICK, KICK, NOTICE and the numeric responses too, so plugins should
+    /// ignore what they don't handle. The PRIVMSG and NOTICE echoed back from
+    /// the bot itself are the only ones filtered out.
     async fn in_message(&self, msg: &Message) -> Result<Option<Message>> {
         Ok(None)
     }
 
+    /// Invoked on every incoming message before it is dispatched to the plugins,
+    /// including this one. Returns Some(Message) to replace the incoming message,
+    /// for example to expand a shortcut into a full command.
+    fn rewrite_message(&self, msg: &Message) -> Option<Message> {
+        None
+    }
+
     /// Method invoked whenever the bot sends a message to IRC.
     async fn out_message(&self, msg: &Message) -> Result<()> {
         Ok(())
@@ -83,4 +136,9 @@ pub trait Plugin: Sync + Send {
     fn ignore_blacklisted_users(&self) -> bool {
         true
     }
+
+    /// The commands the plugin responds to, listed by λhelp
+    fn commands(&self) -> Vec<CommandHelp> {
+        vec![]
+    }
 }
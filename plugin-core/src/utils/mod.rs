@@ -1 +1,5 @@
+pub mod backoff;
+pub mod network;
+pub mod nicks;
+pub mod owners;
 pub mod parser;
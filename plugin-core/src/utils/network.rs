@@ -0,0 +1,113 @@
+//! The bot can connect to several servers with the same plugins. The
+//! messages of the extra servers are tagged with the name of their server,
+//! so that the plugins keep the state of each server apart, and so that
+//! the bot sends the messages of the run loops to the right server.
+use irc::proto::message::Tag;
+use irc::proto::{Command, Message};
+
+/// Only used inside the bot, it's removed before sending a message
+pub const NETWORK_TAG: &str = "rustygolem/network";
+
+/// The server the message comes from, or goes to. None for the main server.
+pub fn network(msg: &Message) -> Option<&str> {
+    msg.tags.iter().flatten().find_map(|tag| match tag {
+        Tag(key, Some(value)) if key == NETWORK_TAG => Some(value.as_str()),
+        _ => None,
+    })
+}
+
+/// The message tagged with its server, replacing any previous one.
+/// The messages of the main server aren't tagged.
+pub fn with_network(msg: Message, network: Option<&str>) -> Message {
+    let mut msg = without_network(msg);
+    if let Some(network) = network {
+        msg.tags
+            .get_or_insert_with(Vec::new)
+            .push(Tag(NETWORK_TAG.to_string(), Some(network.to_string())));
+    }
+    msg
+}
+
+/// The message as it should be sent to the server
+pub fn without_network(mut msg: Message) -> Message {
+    if let Some(tags) = &mut msg.tags {
+        tags.retain(|Tag(key, _)| key != NETWORK_TAG);
+        if tags.is_empty() {
+            msg.tags = None;
+        }
+    }
+    msg
+}
+
+/// Key for the state of a channel, like what is stored in the database.
+/// That's the channel itself on the main server, so that the state from
+/// before there were several servers is still valid, and the server then
+/// the channel for the other ones. Channel names can't contain spaces.
+pub fn channel_key(msg: &Message, channel: &str) -> String {
+    match network(msg) {
+        Some(network) => format!("{network} {channel}"),
+        None => channel.to_string(),
+    }
+}
+
+/// The server and the channel of a key made by `channel_key`
+pub fn split_channel_key(key: &str) -> (Option<&str>, &str) {
+    match key.split_once(' ') {
+        Some((network, channel)) => (Some(network), channel),
+        None => (None, key),
+    }
+}
+
+/// A message for the channel of a key made by `channel_key`, tagged
+/// so that it's sent to the server of that channel
+pub fn privmsg_to(key: &str, text: String) -> Message {
+    let (network, channel) = split_channel_key(key);
+    with_network(Command::PRIVMSG(channel.to_string(), text).into(), network)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::privmsg;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_network_tag() {
+        let msg = privmsg("charlie", "#coucou", "hello");
+        assert_eq!(network(&msg), None);
+        assert_eq!(channel_key(&msg, "#coucou"), "#coucou");
+
+        let tagged = with_network(msg.clone(), Some("irc.oftc.net"));
+        assert_eq!(network(&tagged), Some("irc.oftc.net"));
+        assert_eq!(channel_key(&tagged, "#coucou"), "irc.oftc.net #coucou");
+        assert_eq!(
+            network(&with_network(tagged.clone(), Some("irc.libera.chat"))),
+            Some("irc.libera.chat"),
+            "replaces the previous server"
+        );
+        assert_eq!(without_network(tagged), msg, "nothing left to send");
+
+        let account = "@account=charlie :charlie!c@host PRIVMSG #coucou :hello"
+            .parse::<Message>()
+            .unwrap();
+        let tagged = with_network(account.clone(), Some("irc.oftc.net"));
+        assert_eq!(without_network(tagged), account, "other tags are kept");
+    }
+
+    #[test]
+    fn test_channel_key() {
+        assert_eq!(split_channel_key("#coucou"), (None, "#coucou"));
+        assert_eq!(
+            split_channel_key("irc.oftc.net #coucou"),
+            (Some("irc.oftc.net"), "#coucou")
+        );
+
+        let msg = privmsg_to("irc.oftc.net #coucou", "rappel".to_string());
+        assert_eq!(network(&msg), Some("irc.oftc.net"));
+        assert_eq!(
+            without_network(msg).command,
+            Command::PRIVMSG("#coucou".to_string(), "rappel".to_string())
+        );
+        assert_eq!(network(&privmsg_to("#coucou", "rappel".to_string())), None);
+    }
+}
@@ -0,0 +1,69 @@
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+/// The nicks currently used by the bot, kept up to date by the bot. They
+/// differ from the configured nickname when an alt nick was used, or once
+/// the bot changed its nick, like when taking back the configured one.
+#[derive(Debug, Clone, Default)]
+pub struct OwnNicks {
+    /// used until a server reports the nick
+    configured: String,
+    /// current nick on each server
+    by_server: Arc<RwLock<HashMap<String, String>>>,
+}
+
+impl OwnNicks {
+    pub fn new(configured: &str) -> Self {
+        OwnNicks {
+            configured: configured.to_string(),
+            by_server: Default::default(),
+        }
+    }
+
+    pub fn set(&self, server: &str, nick: &str) {
+        let current = self.by_server.read().expect("own nicks lock");
+        if current.get(server).map(String::as_str) == Some(nick) {
+            return;
+        }
+        drop(current);
+        self.by_server
+            .write()
+            .expect("own nicks lock")
+            .insert(server.to_string(), nick.to_string());
+    }
+
+    /// Whether `nick` is the bot, on any of the servers
+    pub fn is_own_nick(&self, nick: &str) -> bool {
+        let by_server = self.by_server.read().expect("own nicks lock");
+        if by_server.is_empty() {
+            return nick.eq_ignore_ascii_case(&self.configured);
+        }
+        by_server.values().any(|own| own.eq_ignore_ascii_case(nick))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_own_nicks() {
+        let nicks = OwnNicks::new("golem");
+        assert!(
+            nicks.is_own_nick("Golem"),
+            "configured nick until connected"
+        );
+        assert!(!nicks.is_own_nick("golem_"));
+
+        let shared = nicks.clone();
+        nicks.set("irc.libera.chat", "golem_");
+        assert!(shared.is_own_nick("golem_"));
+        assert!(!shared.is_own_nick("golem"), "alt nick in use");
+
+        nicks.set("irc.libera.chat", "golem");
+        nicks.set("irc.oftc.net", "golem__");
+        assert!(shared.is_own_nick("golem"));
+        assert!(shared.is_own_nick("golem__"));
+        assert!(!shared.is_own_nick("golem_"));
+    }
+}
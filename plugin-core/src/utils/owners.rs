@@ -0,0 +1,124 @@
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+
+use irc::proto::Message;
+
+use crate::utils::network::network;
+
+/// The irc nicknames allowed to use privileged commands, shared by the bot
+/// and the plugins.
+#[derive(Debug, Clone, Default)]
+pub struct Owners {
+    names: Vec<String>,
+    /// the servers tagging the messages with the account of the sender,
+    /// see `network`
+    require_account: Arc<Mutex<HashSet<Option<String>>>>,
+}
+
+impl Owners {
+    pub fn new(names: Vec<String>) -> Self {
+        Owners {
+            names,
+            require_account: Default::default(),
+        }
+    }
+
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+
+    /// Called by the bot when a server acks the account-tag capability. From
+    /// then on the owners must be logged in on that server, the other ones
+    /// may not tag anything.
+    pub fn require_account(&self, network: Option<&str>) {
+        self.require_account
+            .lock()
+            .expect("owners lock")
+            .insert(network.map(String::from));
+    }
+
+    /// Whether the message comes from one of the owners
+    pub fn is_owner(&self, msg: &Message) -> bool {
+        let require_account = self
+            .require_account
+            .lock()
+            .expect("owners lock")
+            .contains(&network(msg).map(String::from));
+        is_owner(msg, &self.names, require_account)
+    }
+}
+
+/// Whether the message comes from one of the owners.
+/// With `require_account`, the message must also carry the services account
+/// of an owner, so that taking the nick of an owner isn't enough. That needs
+/// the account-tag capability, otherwise nothing is tagged.
+pub fn is_owner(msg: &Message, owners: &[String], require_account: bool) -> bool {
+    let is_owner_name = |name: &str| owners.iter().any(|o| o.eq_ignore_ascii_case(name));
+    let nick_ok = msg.source_nickname().map_or(false, is_owner_name);
+    let account = msg.tags.iter().flatten().find_map(|tag| match tag {
+        irc::proto::message::Tag(key, Some(value)) if key == "account" => Some(value.as_str()),
+        _ => None,
+    });
+    let account_ok = match account {
+        Some(account) => is_owner_name(account),
+        None => !require_account,
+    };
+    nick_ok && account_ok
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::privmsg;
+    use crate::utils::network::with_network;
+
+    fn tagged(nick: &str, account: &str) -> Message {
+        format!("@account={account} :{nick}!{nick}@localhost PRIVMSG #coucou :λadmin")
+            .parse::<Message>()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_is_owner() {
+        let owners = vec!["Geekingfrog".to_string()];
+
+        assert!(is_owner(
+            &privmsg("geekingfrog", "#coucou", "λadmin"),
+            &owners,
+            false
+        ));
+        assert!(!is_owner(
+            &privmsg("charlie", "#coucou", "λadmin"),
+            &owners,
+            false
+        ));
+        assert!(
+            !is_owner(&privmsg("Geekingfrog", "#coucou", "λadmin"), &owners, true),
+            "not logged in"
+        );
+        assert!(is_owner(
+            &tagged("Geekingfrog", "geekingfrog"),
+            &owners,
+            true
+        ));
+        assert!(
+            !is_owner(&tagged("Geekingfrog", "charlie"), &owners, false),
+            "nick taken by someone else"
+        );
+    }
+
+    #[test]
+    fn test_require_account() {
+        let owners = Owners::new(vec!["Geekingfrog".to_string()]);
+        let shared = owners.clone();
+        let msg = privmsg("Geekingfrog", "#coucou", "λtopic");
+        assert!(shared.is_owner(&msg));
+        owners.require_account(None);
+        assert!(!shared.is_owner(&msg), "not logged in");
+        assert!(shared.is_owner(&tagged("Geekingfrog", "Geekingfrog")));
+        assert!(
+            shared.is_owner(&with_network(msg, Some("irc.oftc.net"))),
+            "the other server doesn't tag the accounts"
+        );
+    }
+}
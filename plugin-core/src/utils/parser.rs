@@ -1,12 +1,39 @@
 use nom::{
-    bytes::complete::tag,
+    bytes::complete::{tag, take_while1},
     character::complete::{alphanumeric1, char, multispace0, multispace1},
     combinator::{all_consuming, map, opt, recognize},
-    error::ParseError,
+    error::{ErrorKind, ParseError},
     multi::many1,
     sequence::{delimited, pair, preceded, terminated, tuple},
     Finish, IResult,
 };
+use std::sync::RwLock;
+
+/// Prefixes used when none are configured
+pub const DEFAULT_COMMAND_PREFIXES: [&str; 2] = ["&", "λ"];
+
+/// Configured command prefixes, empty until set at startup
+static COMMAND_PREFIXES: RwLock<Vec<String>> = RwLock::new(Vec::new());
+
+/// Set the prefixes recognized by `command_prefix` for the whole bot.
+/// An empty list restores the default ones.
+pub fn set_command_prefixes(prefixes: &[String]) {
+    *COMMAND_PREFIXES.write().expect("command prefixes lock") = prefixes.to_vec();
+}
+
+/// The prefixes recognized by `command_prefix`, the first one is the one
+/// to show in the replies
+pub fn command_prefixes() -> Vec<String> {
+    let prefixes = COMMAND_PREFIXES.read().expect("command prefixes lock");
+    if prefixes.is_empty() {
+        DEFAULT_COMMAND_PREFIXES
+            .iter()
+            .map(|p| p.to_string())
+            .collect()
+    } else {
+        prefixes.clone()
+    }
+}
 
 pub fn with_target<'a, O, F: 'a, E: ParseError<&'a str>>(
     inner: F,
@@ -26,12 +53,38 @@ pub fn word<'a, E: ParseError<&'a str>>(input: &'a str) -> IResult<&'a str, &'a
     recognize(many1(alphanumeric1))(input)
 }
 
-/// Utility to parse common command prefix
+/// Utility to parse common command prefix, as configured
+/// with `set_command_prefixes`
 pub fn command_prefix(input: &str) -> nom::IResult<&str, &str> {
-    nom::branch::alt((
-        nom::bytes::complete::is_a("&"),
-        nom::bytes::complete::is_a("λ"),
-    ))(input)
+    let prefixes = COMMAND_PREFIXES.read().expect("command prefixes lock");
+    if prefixes.is_empty() {
+        prefix_in(&DEFAULT_COMMAND_PREFIXES, input)
+    } else {
+        prefix_in(&prefixes, input)
+    }
+}
+
+/// One or more of the given prefixes at the start of the input
+fn prefix_in<'input, P: AsRef<str>>(
+    prefixes: &[P],
+    input: &'input str,
+) -> nom::IResult<&'input str, &'input str> {
+    let mut rest = input;
+    while let Some(p) = prefixes
+        .iter()
+        .map(AsRef::as_ref)
+        .find(|p| !p.is_empty() && rest.starts_with(p))
+    {
+        rest = &rest[p.len()..];
+    }
+    let len = input.len() - rest.len();
+    if len == 0 {
+        return Err(nom::Err::Error(nom::error::Error::new(
+            input,
+            ErrorKind::IsA,
+        )));
+    }
+    Ok((rest, &input[..len]))
 }
 
 /// Parse a single command with an optional target
@@ -51,11 +104,56 @@ pub fn single_command<'input>(
         .unwrap_or_default()
 }
 
+/// Parse a command taking a single argument, like a nickname,
+/// with an optional target: `λcmd arg [> target]`
+/// Returns None if the parser fails
+pub fn command_with_arg<'input>(
+    cmd_name: &'static str,
+    input: &'input str,
+) -> Option<(&'input str, Option<&'input str>)> {
+    let arg = take_while1(|c: char| c.is_alphanumeric() || c == '_' || c == '-');
+    let cmd = preceded(
+        tuple((command_prefix, tag(cmd_name), multispace1)),
+        with_target(arg),
+    );
+
+    all_consuming(terminated(cmd, multispace0))(input)
+        .finish()
+        .map(|x| x.1)
+        .ok()
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
     use pretty_assertions::assert_eq;
 
+    #[test]
+    fn test_prefix_in() {
+        assert_eq!(
+            prefix_in(&DEFAULT_COMMAND_PREFIXES, "λurl"),
+            Ok(("url", "λ"))
+        );
+        assert_eq!(
+            prefix_in(&DEFAULT_COMMAND_PREFIXES, "&url"),
+            Ok(("url", "&"))
+        );
+        assert!(prefix_in(&DEFAULT_COMMAND_PREFIXES, "!url").is_err());
+
+        let custom = ["!", "."];
+        assert_eq!(prefix_in(&custom, "!url"), Ok(("url", "!")));
+        assert_eq!(prefix_in(&custom, ".url"), Ok(("url", ".")));
+        assert!(
+            prefix_in(&custom, "λurl").is_err(),
+            "the default prefixes are replaced"
+        );
+        assert_eq!(
+            prefix_in(&["bot:"], "bot:url"),
+            Ok(("url", "bot:")),
+            "longer prefixes"
+        );
+    }
+
     #[test]
     fn test_parse_single_command() {
         assert_eq!(
@@ -88,4 +186,19 @@ mod test {
             "also parses with target"
         );
     }
+
+    #[test]
+    fn test_parse_command_with_arg() {
+        assert_eq!(command_with_arg("uptime", "λuptime"), None, "need an arg");
+        assert_eq!(
+            command_with_arg("uptime", "λuptime some_streamer"),
+            Some(("some_streamer", None))
+        );
+        assert_eq!(
+            command_with_arg("uptime", "λuptime gikiam > charlie "),
+            Some(("gikiam", Some("charlie")))
+        );
+        assert_eq!(command_with_arg("uptime", "λuptime a b"), None);
+        assert_eq!(command_with_arg("uptime", "λuptimegikiam"), None);
+    }
 }
@@ -0,0 +1,6 @@
+CREATE TABLE dm_subscriptions (
+  irc_nick TEXT NOT NULL,
+  kind TEXT NOT NULL,
+  target TEXT NOT NULL,
+  PRIMARY KEY(irc_nick, kind, target)
+);
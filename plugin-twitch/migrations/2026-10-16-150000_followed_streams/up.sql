@@ -0,0 +1,5 @@
+CREATE TABLE followed_streams (
+  nickname TEXT NOT NULL,
+  irc_channel TEXT NOT NULL,
+  PRIMARY KEY(nickname, irc_channel)
+);
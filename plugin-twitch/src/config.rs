@@ -1,8 +1,12 @@
 use std::path::Path;
 
+use plugin_core::config::{ConfigError, ConfigSection};
 use serde::Deserialize;
 use twitch_api2::{
-    eventsub::stream::{StreamOfflineV1Payload, StreamOnlineV1Payload},
+    eventsub::{
+        channel::ChannelUpdateV1Payload,
+        stream::{StreamOfflineV1Payload, StreamOnlineV1Payload},
+    },
     twitch_oauth2::{ClientId, ClientSecret},
     types::Nickname,
 };
@@ -42,30 +46,25 @@ pub struct Config {
     pub app_secret: String,
     pub watched_streams: Vec<StreamSpec>,
     pub callback_uri: Obfuscated,
+    /// Where to warn operators when twitch subscriptions go stale
+    pub ops_channel: Option<String>,
 }
 
-// tmp struct to parse the config from a file with other stuff in it
-#[derive(Deserialize)]
-struct TC {
-    twitch: Config,
+impl ConfigSection for Config {
+    const SECTION: Option<&'static str> = Some("twitch");
+    const SCHEMA: &'static str = "{ client_id : Text, client_secret : Text, app_secret : Text, \
+        callback_uri : Text, ops_channel : Optional Text, \
+        watched_streams : List { nickname : Text, irc_nick : Text, irc_channels : List Text } }";
 }
 
 impl Config {
-    // pub fn from_file<P>(p: P) -> Result<Self, serde_dhall::Error>
-    // where
-    //     P: AsRef<Path>,
-    // {
-    //     Ok(serde_dhall::from_file(p).parse()?)
-    // }
-
     /// read config from a file where it's under a key
     /// named "twitch"
-    pub fn from_file_keyed<P>(p: P) -> Result<Self, serde_dhall::Error>
+    pub fn from_file_keyed<P>(p: P) -> Result<Self, ConfigError>
     where
         P: AsRef<Path>,
     {
-        let tmp: TC = serde_dhall::from_file(p).parse()?;
-        Ok(tmp.twitch)
+        plugin_core::config::load(p)
     }
 }
 
@@ -73,4 +72,32 @@ impl Config {
 pub enum Message {
     StreamOnline(StreamOnlineV1Payload),
     StreamOffline(StreamOfflineV1Payload),
+    ChannelUpdate(ChannelUpdateV1Payload),
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_malformed_config() {
+        let dhall = r#"
+            { twitch =
+                { client_id = "id"
+                , client_secret = "secret"
+                , app_secret = "app secret"
+                , callback_uri = "https://coucou.com/touitche"
+                , ops_channel = None Text
+                }
+            }
+        "#;
+        let err = plugin_core::config::from_str::<Config>(dhall)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("`twitch`"), "names the section: {err}");
+        assert!(
+            err.contains("`watched_streams`"),
+            "names the missing field: {err}"
+        );
+    }
 }
@@ -0,0 +1,9 @@
+use anyhow::{Context, Result};
+use diesel::prelude::*;
+diesel_migrations::embed_migrations!("./migrations/");
+
+pub use plugin_core::store::with_connection;
+
+pub fn run_migrations(connection: &SqliteConnection) -> Result<()> {
+    embedded_migrations::run(connection).context("Cannot run migration")
+}
@@ -23,6 +23,9 @@ pub enum TwitchError {
 
     #[error("HttpError {0}")]
     HttpError(StatusCode),
+
+    #[error("Body is not valid utf-8: {0}")]
+    InvalidBody(#[from] std::str::Utf8Error),
 }
 
 impl std::convert::From<StatusCode> for TwitchError {
@@ -59,6 +62,9 @@ impl IntoResponse for TwitchError {
                 (StatusCode::BAD_REQUEST, format!("{e}")).into_response()
             }
             TwitchError::HttpError(code) => code.into_response(),
+            e@TwitchError::InvalidBody(_) => {
+                (StatusCode::BAD_REQUEST, format!("{e}")).into_response()
+            }
         }
     }
 }
@@ -0,0 +1,177 @@
+//! Streams followed at runtime with `λtwitch follow`, on top of the
+//! ones listed in the config.
+use diesel::prelude::*;
+
+use twitch_api2::types::Nickname;
+
+use crate::config::StreamSpec;
+use crate::schema::followed_streams;
+
+#[derive(Debug, Queryable, Insertable)]
+#[table_name = "followed_streams"]
+pub struct FollowedStream {
+    pub nickname: String,
+    pub irc_channel: String,
+}
+
+/// Returns false if the stream was already followed in this channel
+pub fn follow(conn: &SqliteConnection, nickname: &str, irc_channel: &str) -> QueryResult<bool> {
+    let inserted = diesel::insert_or_ignore_into(followed_streams::table)
+        .values(&FollowedStream {
+            nickname: nickname.to_lowercase(),
+            irc_channel: irc_channel.to_string(),
+        })
+        .execute(conn)?;
+    Ok(inserted > 0)
+}
+
+/// Returns false if the stream wasn't followed in this channel
+pub fn unfollow(conn: &SqliteConnection, nickname: &str, irc_channel: &str) -> QueryResult<bool> {
+    use followed_streams::dsl;
+    let deleted = diesel::delete(
+        dsl::followed_streams
+            .filter(dsl::nickname.eq(nickname.to_lowercase()))
+            .filter(dsl::irc_channel.eq(irc_channel)),
+    )
+    .execute(conn)?;
+    Ok(deleted > 0)
+}
+
+pub fn load(conn: &SqliteConnection) -> QueryResult<Vec<FollowedStream>> {
+    use followed_streams::dsl;
+    dsl::followed_streams
+        .order((dsl::nickname, dsl::irc_channel))
+        .load(conn)
+}
+
+/// Add the channel to the spec of the given stream, creating it if needed.
+/// Returns false if the stream was already watched for that channel.
+pub fn add_channel(streams: &mut Vec<StreamSpec>, nickname: &str, irc_channel: &str) -> bool {
+    match streams.iter_mut().find(|s| s.nickname.as_str() == nickname) {
+        Some(spec) if spec.irc_channels.iter().any(|c| c == irc_channel) => false,
+        Some(spec) => {
+            spec.irc_channels.push(irc_channel.to_string());
+            true
+        }
+        None => {
+            streams.push(StreamSpec {
+                nickname: Nickname::new(nickname),
+                // no way to know, so assume the same nick on irc
+                irc_nick: nickname.to_string(),
+                irc_channels: vec![irc_channel.to_string()],
+            });
+            true
+        }
+    }
+}
+
+/// Remove the channel from the spec of the given stream, and drop the
+/// spec if there is no channel left to notify.
+/// Returns false if the stream wasn't watched for that channel.
+pub fn remove_channel(streams: &mut Vec<StreamSpec>, nickname: &str, irc_channel: &str) -> bool {
+    let spec = match streams.iter_mut().find(|s| s.nickname.as_str() == nickname) {
+        Some(spec) => spec,
+        None => return false,
+    };
+    let len = spec.irc_channels.len();
+    spec.irc_channels.retain(|c| c != irc_channel);
+    let removed = spec.irc_channels.len() != len;
+    streams.retain(|s| !s.irc_channels.is_empty());
+    removed
+}
+
+/// The streams from the config, plus the ones followed at runtime
+pub fn merge(config_streams: &[StreamSpec], followed: Vec<FollowedStream>) -> Vec<StreamSpec> {
+    let mut streams = config_streams.to_vec();
+    for f in followed {
+        add_channel(&mut streams, &f.nickname, &f.irc_channel);
+    }
+    streams
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn test_connection() -> SqliteConnection {
+        let conn = SqliteConnection::establish(":memory:").unwrap();
+        crate::db::run_migrations(&conn).unwrap();
+        conn
+    }
+
+    fn spec(nickname: &str, channels: &[&str]) -> StreamSpec {
+        StreamSpec {
+            nickname: Nickname::new(nickname),
+            irc_nick: nickname.to_string(),
+            irc_channels: channels.iter().map(|c| c.to_string()).collect(),
+        }
+    }
+
+    fn channels(streams: &[StreamSpec]) -> Vec<(&str, Vec<&str>)> {
+        streams
+            .iter()
+            .map(|s| {
+                (
+                    s.nickname.as_str(),
+                    s.irc_channels.iter().map(|c| c.as_str()).collect(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_follow_store() {
+        let conn = test_connection();
+        assert!(follow(&conn, "Gikiam", "#gougoutest").unwrap());
+        assert!(
+            !follow(&conn, "gikiam", "#gougoutest").unwrap(),
+            "already followed, twitch logins are case insensitive"
+        );
+        assert!(follow(&conn, "gikiam", "#arch-fr-free").unwrap());
+
+        assert!(unfollow(&conn, "gikiam", "#gougoutest").unwrap());
+        assert!(!unfollow(&conn, "gikiam", "#gougoutest").unwrap());
+
+        let followed = load(&conn).unwrap();
+        assert_eq!(followed.len(), 1);
+        assert_eq!(followed[0].nickname, "gikiam");
+        assert_eq!(followed[0].irc_channel, "#arch-fr-free");
+    }
+
+    #[test]
+    fn test_merge() {
+        let config = vec![spec("gikiam", &["#gougoutest"])];
+        let followed = vec![
+            FollowedStream {
+                nickname: "gikiam".to_string(),
+                irc_channel: "#arch-fr-free".to_string(),
+            },
+            FollowedStream {
+                nickname: "other".to_string(),
+                irc_channel: "#gougoutest".to_string(),
+            },
+        ];
+        let streams = merge(&config, followed);
+        assert_eq!(
+            channels(&streams),
+            vec![
+                ("gikiam", vec!["#gougoutest", "#arch-fr-free"]),
+                ("other", vec!["#gougoutest"]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_add_remove_channel() {
+        let mut streams = vec![spec("gikiam", &["#gougoutest"])];
+        assert!(!add_channel(&mut streams, "gikiam", "#gougoutest"));
+        assert!(add_channel(&mut streams, "other", "#gougoutest"));
+        assert!(remove_channel(&mut streams, "other", "#gougoutest"));
+        assert_eq!(
+            channels(&streams),
+            vec![("gikiam", vec!["#gougoutest"])],
+            "no channel left to notify for other"
+        );
+        assert!(!remove_channel(&mut streams, "other", "#gougoutest"));
+    }
+}
@@ -1,6 +1,15 @@
+#[macro_use]
+extern crate diesel;
+
 mod plugin;
 mod config;
 mod webhook_server;
 mod errors;
+mod db;
+mod notify;
+mod follow;
+mod ops;
+mod schema;
 
+pub use db::run_migrations;
 pub use plugin::Twitch;
@@ -0,0 +1,139 @@
+//! Users can ask to be notified in private when some event happens,
+//! for example when a given stream goes live.
+use diesel::prelude::*;
+
+use crate::schema::dm_subscriptions;
+
+/// What kind of event the subscription is about. Stored as text in the db.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Kind {
+    /// target is the twitch login of the stream
+    Twitch,
+}
+
+impl Kind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Kind::Twitch => "twitch",
+        }
+    }
+}
+
+#[derive(Debug, Insertable)]
+#[table_name = "dm_subscriptions"]
+struct NewSubscription<'a> {
+    irc_nick: &'a str,
+    kind: &'a str,
+    target: &'a str,
+}
+
+/// Returns false if the subscription already existed
+pub fn subscribe(
+    conn: &SqliteConnection,
+    irc_nick: &str,
+    kind: Kind,
+    target: &str,
+) -> QueryResult<bool> {
+    let inserted = diesel::insert_or_ignore_into(dm_subscriptions::table)
+        .values(&NewSubscription {
+            irc_nick,
+            kind: kind.as_str(),
+            target: &target.to_lowercase(),
+        })
+        .execute(conn)?;
+    Ok(inserted > 0)
+}
+
+/// Returns false if there was no such subscription
+pub fn unsubscribe(
+    conn: &SqliteConnection,
+    irc_nick: &str,
+    kind: Kind,
+    target: &str,
+) -> QueryResult<bool> {
+    use dm_subscriptions::dsl;
+    let deleted = diesel::delete(
+        dsl::dm_subscriptions
+            .filter(dsl::irc_nick.eq(irc_nick))
+            .filter(dsl::kind.eq(kind.as_str()))
+            .filter(dsl::target.eq(target.to_lowercase())),
+    )
+    .execute(conn)?;
+    Ok(deleted > 0)
+}
+
+/// irc nicknames of everyone who wants to be notified about `target`
+pub fn subscribers(conn: &SqliteConnection, kind: Kind, target: &str) -> QueryResult<Vec<String>> {
+    use dm_subscriptions::dsl;
+    dsl::dm_subscriptions
+        .select(dsl::irc_nick)
+        .filter(dsl::kind.eq(kind.as_str()))
+        .filter(dsl::target.eq(target.to_lowercase()))
+        .order(dsl::irc_nick)
+        .load(conn)
+}
+
+/// Everyone an event should be sent to: the configured channels,
+/// and in private to each subscriber.
+pub fn notification_targets(channels: &[String], subscribers: Vec<String>) -> Vec<String> {
+    let mut targets = channels.to_vec();
+    for nick in subscribers {
+        if !targets.contains(&nick) {
+            targets.push(nick);
+        }
+    }
+    targets
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn test_connection() -> SqliteConnection {
+        let conn = SqliteConnection::establish(":memory:").unwrap();
+        crate::db::run_migrations(&conn).unwrap();
+        conn
+    }
+
+    #[test]
+    fn test_subscription_store() {
+        let conn = test_connection();
+        assert!(subscribe(&conn, "charlie", Kind::Twitch, "Gikiam").unwrap());
+        assert!(
+            !subscribe(&conn, "charlie", Kind::Twitch, "gikiam").unwrap(),
+            "already subscribed, twitch logins are case insensitive"
+        );
+        assert!(subscribe(&conn, "alice", Kind::Twitch, "gikiam").unwrap());
+        assert!(subscribe(&conn, "alice", Kind::Twitch, "other").unwrap());
+
+        assert_eq!(
+            subscribers(&conn, Kind::Twitch, "gikiam").unwrap(),
+            vec!["alice".to_string(), "charlie".to_string()]
+        );
+
+        assert!(unsubscribe(&conn, "charlie", Kind::Twitch, "gikiam").unwrap());
+        assert!(
+            !unsubscribe(&conn, "charlie", Kind::Twitch, "gikiam").unwrap(),
+            "not subscribed anymore"
+        );
+        assert_eq!(
+            subscribers(&conn, Kind::Twitch, "gikiam").unwrap(),
+            vec!["alice".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_notification_targets() {
+        let channels = vec!["#gougoutest".to_string(), "#arch-fr-free".to_string()];
+        assert_eq!(
+            notification_targets(&channels, vec![]),
+            channels,
+            "no subscriber, only the channels"
+        );
+        assert_eq!(
+            notification_targets(&channels, vec!["charlie".to_string(), "alice".to_string()]),
+            vec!["#gougoutest", "#arch-fr-free", "charlie", "alice"],
+            "subscribers get a private message in addition to the channels"
+        );
+    }
+}
@@ -0,0 +1,154 @@
+//! Keep track of who is operator in each channel, from the NAMES reply
+//! sent when joining and from the subsequent mode changes.
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+
+use irc::proto::{ChannelMode, Command, Message, Mode, Response};
+use plugin_core::utils::network::{channel_key, network, split_channel_key};
+
+#[derive(Debug, Default)]
+pub struct ChannelOps {
+    /// keyed by `channel_key`
+    ops: Mutex<HashMap<String, HashSet<String>>>,
+}
+
+impl ChannelOps {
+    /// `channel` is a key made by `channel_key`
+    pub fn is_op(&self, channel: &str, nick: &str) -> bool {
+        self.ops
+            .lock()
+            .expect("channel ops lock")
+            .get(channel)
+            .map(|nicks| nicks.contains(nick))
+            .unwrap_or(false)
+    }
+
+    pub fn track(&self, msg: &Message) {
+        let mut ops = self.ops.lock().expect("channel ops lock");
+        match &msg.command {
+            Command::Response(Response::RPL_NAMREPLY, args) => {
+                if let [.., chan, names] = &args[..] {
+                    let chan_ops = ops.entry(channel_key(msg, chan)).or_default();
+                    for name in names.split_whitespace() {
+                        if let Some(nick) = name.strip_prefix('@') {
+                            chan_ops.insert(nick.to_string());
+                        }
+                    }
+                }
+            }
+            Command::ChannelMODE(chan, modes) => {
+                for mode in modes {
+                    match mode {
+                        Mode::Plus(ChannelMode::Oper, Some(nick)) => {
+                            ops.entry(channel_key(msg, chan))
+                                .or_default()
+                                .insert(nick.to_string());
+                        }
+                        Mode::Minus(ChannelMode::Oper, Some(nick)) => {
+                            if let Some(chan_ops) = ops.get_mut(&channel_key(msg, chan)) {
+                                chan_ops.remove(nick);
+                            }
+                        }
+                        _ => (),
+                    }
+                }
+            }
+            Command::PART(chan, _) => {
+                let chan_ops = ops.get_mut(&channel_key(msg, chan));
+                if let (Some(nick), Some(chan_ops)) = (msg.source_nickname(), chan_ops) {
+                    chan_ops.remove(nick);
+                }
+            }
+            Command::KICK(chan, nick, _) => {
+                if let Some(chan_ops) = ops.get_mut(&channel_key(msg, chan)) {
+                    chan_ops.remove(nick);
+                }
+            }
+            Command::QUIT(_) => {
+                if let Some(nick) = msg.source_nickname() {
+                    for chan_ops in same_network(&mut ops, msg) {
+                        chan_ops.remove(nick);
+                    }
+                }
+            }
+            Command::NICK(new_nick) => {
+                if let Some(old_nick) = msg.source_nickname() {
+                    for chan_ops in same_network(&mut ops, msg) {
+                        if chan_ops.remove(old_nick) {
+                            chan_ops.insert(new_nick.to_string());
+                        }
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+/// The ops of the channels on the server of the message, a nick change
+/// or a quit on one server says nothing about the other ones.
+fn same_network<'a>(
+    ops: &'a mut HashMap<String, HashSet<String>>,
+    msg: &'a Message,
+) -> impl Iterator<Item = &'a mut HashSet<String>> {
+    ops.iter_mut()
+        .filter(move |(key, _)| split_channel_key(key).0 == network(msg))
+        .map(|(_, chan_ops)| chan_ops)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use plugin_core::utils::network::with_network;
+
+    fn track(ops: &ChannelOps, raw: &str) {
+        ops.track(&raw.parse::<Message>().unwrap());
+    }
+
+    #[test]
+    fn test_track_ops() {
+        let ops = ChannelOps::default();
+        track(
+            &ops,
+            ":irc.example.com 353 golem = #gougoutest :golem @charlie +alice @bob",
+        );
+        assert!(ops.is_op("#gougoutest", "charlie"));
+        assert!(ops.is_op("#gougoutest", "bob"));
+        assert!(!ops.is_op("#gougoutest", "alice"), "voiced isn't op");
+        assert!(!ops.is_op("#other", "charlie"), "ops are per channel");
+
+        track(&ops, ":charlie!c@host MODE #gougoutest +o alice");
+        assert!(ops.is_op("#gougoutest", "alice"));
+        track(&ops, ":charlie!c@host MODE #gougoutest -o bob");
+        assert!(!ops.is_op("#gougoutest", "bob"));
+
+        track(&ops, ":alice!a@host NICK alicia");
+        assert!(!ops.is_op("#gougoutest", "alice"));
+        assert!(ops.is_op("#gougoutest", "alicia"));
+
+        track(&ops, ":alicia!a@host PART #gougoutest");
+        assert!(!ops.is_op("#gougoutest", "alicia"));
+        track(&ops, ":charlie!c@host QUIT :bye");
+        assert!(!ops.is_op("#gougoutest", "charlie"));
+    }
+
+    #[test]
+    fn test_ops_per_server() {
+        let ops = ChannelOps::default();
+        let names = ":irc.example.com 353 golem = #gougoutest :golem @charlie";
+        track(&ops, names);
+        ops.track(&with_network(
+            names.parse::<Message>().unwrap(),
+            Some("irc.oftc.net"),
+        ));
+        assert!(ops.is_op("#gougoutest", "charlie"));
+        assert!(ops.is_op("irc.oftc.net #gougoutest", "charlie"));
+
+        track(&ops, ":charlie!c@host QUIT :bye");
+        assert!(!ops.is_op("#gougoutest", "charlie"));
+        assert!(
+            ops.is_op("irc.oftc.net #gougoutest", "charlie"),
+            "still there on the other server"
+        );
+    }
+}
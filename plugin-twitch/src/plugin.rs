@@ -1,6 +1,10 @@
 use async_trait::async_trait;
 // use irc::client::prelude::Message;
-use plugin_core::{Initialised, Plugin, Result};
+use plugin_core::metrics::IntCounter;
+use plugin_core::utils::backoff::Backoff;
+use plugin_core::utils::network::{channel_key, privmsg_to, split_channel_key};
+use plugin_core::utils::owners::Owners;
+use plugin_core::{CommandHelp, Initialised, Plugin, Result};
 use twitch_api2::twitch_oauth2::{ClientId, ClientSecret};
 
 use std::sync::Mutex;
@@ -13,6 +17,7 @@ use irc::proto::Message as IrcMessage;
 use twitch_api2::{
     eventsub::{
         self,
+        channel::{ChannelUpdateV1, ChannelUpdateV1Payload},
         stream::{StreamOfflineV1, StreamOfflineV1Payload, StreamOnlineV1, StreamOnlineV1Payload},
         EventSubscription, EventType,
     },
@@ -27,11 +32,20 @@ use twitch_api2::{
 };
 
 use crate::{
-    config::{Config, Message},
+    config::{Config, Message, StreamSpec},
+    db, follow,
+    notify::{self, Kind},
+    ops::ChannelOps,
     webhook_server,
 };
 
 use futures::{StreamExt, TryStreamExt};
+use nom::branch::alt;
+use nom::bytes::complete::{tag, take_while1};
+use nom::character::complete::{multispace0, multispace1};
+use nom::combinator::{all_consuming, map};
+use nom::sequence::{preceded, terminated, tuple};
+use nom::Finish;
 use plugin_core::utils::parser;
 
 #[derive(Debug)]
@@ -53,6 +67,82 @@ impl Subscription {
     }
 }
 
+/// How long to wait before announcing a stream going live. Streams sometimes
+/// go online/offline rapidly, and the channel shouldn't be spammed for that.
+const ONLINE_DEBOUNCE: Duration = Duration::from_secs(30);
+
+/// How often to check that twitch still has all the subscriptions we need.
+const SUBSCRIPTION_CHECK_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Attempts to get the first app access token before giving up on the plugin,
+/// so that twitch being briefly unreachable doesn't prevent the bot to start.
+const TOKEN_ATTEMPTS: u32 = 5;
+/// Delays between the attempts to get a token, at startup and when refreshing
+const TOKEN_RETRY_MIN_DELAY: Duration = Duration::from_secs(2);
+const TOKEN_RETRY_MAX_DELAY: Duration = Duration::from_secs(5 * 60);
+
+/// A watched stream is fully subscribed when there are valid subscriptions
+/// for stream.online, stream.offline and channel.update events.
+fn is_fully_subscribed(subs: &[Subscription], user_id: &UserId) -> bool {
+    let has_valid = |pred: fn(&EventType) -> bool| {
+        subs.iter()
+            .any(|s| &s.user_id == user_id && s.is_valid() && pred(&s.type_))
+    };
+    has_valid(|t| matches!(t, EventType::StreamOnline))
+        && has_valid(|t| matches!(t, EventType::StreamOffline))
+        && has_valid(|t| matches!(t, EventType::ChannelUpdate))
+}
+
+/// None, with a warning, when twitch sends an unexpected timestamp
+fn parse_started_at(started_at: &str) -> Option<time::OffsetDateTime> {
+    match time::OffsetDateTime::parse(started_at, &time::format_description::well_known::Rfc3339) {
+        Ok(t) => Some(t),
+        Err(err) => {
+            log::warn!("Invalid started_at timestamp {started_at:?}: {err}");
+            None
+        }
+    }
+}
+
+/// The hour of the start, or the timestamp as given by twitch when it can't be parsed
+fn format_started_at(started_at: &str) -> String {
+    let time_fmt = time::macros::format_description!("[hour]:[minute] [period]");
+    parse_started_at(started_at)
+        .and_then(|t| t.format(time_fmt).ok())
+        .unwrap_or_else(|| started_at.to_string())
+}
+
+/// Like 2h13m, or 13m for the first hour
+fn format_uptime(elapsed: time::Duration) -> String {
+    let minutes = elapsed.whole_minutes().max(0);
+    match (minutes / 60, minutes % 60) {
+        (0, m) => format!("{m}m"),
+        (h, m) => format!("{h}h{m:02}m"),
+    }
+}
+
+/// Announce a stream going live, the game and title can be empty
+fn format_online(irc_nick: &str, url: &str, game: &str, title: &str) -> String {
+    let mut message = format!("Le stream de {irc_nick} est maintenant live at {url}");
+    if !game.is_empty() {
+        message.push_str(&format!(" ({game})"));
+    }
+    match title.trim() {
+        "" => message.push('!'),
+        title => message.push_str(&format!(": {title}")),
+    }
+    message
+}
+
+/// Announce a change of game or title, the game can be empty
+fn format_channel_update(irc_nick: &str, game: &str, title: &str) -> String {
+    if game.is_empty() {
+        format!("{irc_nick} changed the title: {title}")
+    } else {
+        format!("{irc_nick} switched to {game}: {title}")
+    }
+}
+
 struct WrappedToken {
     // tok: AppAccessToken,
     // need a TokioMutex because the refresh_token method is async and
@@ -74,7 +164,23 @@ struct WrappedToken {
 
 impl WrappedToken {
     async fn new(client_id: ClientId, client_secret: ClientSecret) -> Result<Self> {
-        let token = Self::get_token(client_id.clone(), client_secret.clone()).await?;
+        let mut backoff = Backoff::new(TOKEN_RETRY_MIN_DELAY, TOKEN_RETRY_MAX_DELAY);
+        let mut attempt = 1;
+        let token = loop {
+            match Self::get_token(client_id.clone(), client_secret.clone()).await {
+                Ok(token) => break token,
+                Err(err) if attempt < TOKEN_ATTEMPTS => {
+                    let delay = backoff.next_delay();
+                    log::warn!(
+                        "Cannot get twitch token (attempt {attempt}/{TOKEN_ATTEMPTS}), retrying in {}s: {err:?}",
+                        delay.as_secs()
+                    );
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                Err(err) => return Err(err),
+            }
+        };
 
         Ok(Self {
             tok: Arc::new(Mutex::new(token)),
@@ -105,28 +211,40 @@ impl WrappedToken {
         Ok(token)
     }
 
-    /// spawn a task in the background that ensure the given token is not expired
+    /// How long before the current token expires, minus some margin
+    fn refresh_delay(tok: &Mutex<AppAccessToken>) -> Duration {
+        tok.lock()
+            .unwrap()
+            .expires_in()
+            .saturating_sub(Duration::from_secs(60))
+    }
+
+    /// spawn a task in the background that ensure the given token is not expired.
+    /// When refreshing fails, it's retried soon after rather than when the
+    /// token would have expired.
     fn spawn_refresh(&self) -> tokio::task::JoinHandle<()> {
         let tok = Arc::clone(&self.tok);
         let client_id = self.client_id.clone();
         let client_secret = self.client_secret.clone();
         tokio::spawn(async move {
+            let mut backoff = Backoff::new(TOKEN_RETRY_MIN_DELAY, TOKEN_RETRY_MAX_DELAY);
+            let mut d = Self::refresh_delay(&tok);
             loop {
-                let d = { tok.lock().unwrap().expires_in() - Duration::from_secs(60) };
                 log::debug!("Going to sleep {}s before refreshing token.", d.as_secs());
                 tokio::time::sleep(d).await;
-                {
-                    match Self::get_token(client_id.clone(), client_secret.clone()).await {
-                        Ok(new_token) => {
-                            log::info!("Successfully acquired a new token");
-                            let mut old_tok = tok.lock().unwrap();
-                            let _ = std::mem::replace(&mut *old_tok, new_token);
-                        }
-                        Err(err) => {
-                            // don't really have a way to recover from that, it's going
-                            // to crash elsewhere in the meantime :s
-                            log::error!("Error while refreshing twitch token: {err:?}");
-                        }
+                match Self::get_token(client_id.clone(), client_secret.clone()).await {
+                    Ok(new_token) => {
+                        log::info!("Successfully acquired a new token");
+                        let _ = std::mem::replace(&mut *tok.lock().unwrap(), new_token);
+                        backoff.reset();
+                        d = Self::refresh_delay(&tok);
+                    }
+                    Err(err) => {
+                        d = backoff.next_delay();
+                        log::error!(
+                            "Error while refreshing twitch token, retrying in {}s: {err:?}",
+                            d.as_secs()
+                        );
                     }
                 }
             }
@@ -163,16 +281,26 @@ pub struct Twitch {
     token: WrappedToken,
     state: State,
 
+    /// streams from the config, plus the ones followed with `λtwitch follow`
+    watched_streams: Mutex<Vec<StreamSpec>>,
+    /// can manage the watched streams in any channel
+    owners: Owners,
+    ops: ChannelOps,
+
     // messages coming in as responses to twitch webhook, and that need to be sent
     // to the irc network
     twitch_rx: TokioMutex<mpsc::Receiver<Message>>,
+    /// stream announcements sent to irc
+    notifications_sent: IntCounter,
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone)]
 pub struct State {
     // keys corresponding to Config.watched_streams
     // to identify which watched streams are currently online.
     online_streams: Arc<Mutex<HashMap<Nickname, Stream>>>,
+    // online announcements waiting for ONLINE_DEBOUNCE before being sent
+    pending_online: Arc<Mutex<HashMap<Nickname, tokio::task::JoinHandle<()>>>>,
 }
 
 impl State {
@@ -190,6 +318,57 @@ impl State {
             .insert(nick, stream);
     }
 
+    fn is_online(&self, nick: &Nickname) -> bool {
+        self.online_streams
+            .lock()
+            .expect("twitch state lock")
+            .contains_key(nick)
+    }
+
+    /// Run the announcement after the given delay, unless it's cancelled before
+    fn delay_announcement<F>(&self, nick: Nickname, delay: Duration, announce: F)
+    where
+        F: std::future::Future<Output = ()> + Send + 'static,
+    {
+        let handle = tokio::spawn(async move {
+            tokio::time::sleep(delay).await;
+            announce.await
+        });
+        let previous = self
+            .pending_online
+            .lock()
+            .expect("twitch state lock")
+            .insert(nick, handle);
+        if let Some(previous) = previous {
+            previous.abort();
+        }
+    }
+
+    /// Returns true if there was an announcement not sent yet
+    fn cancel_announcement(&self, nick: &Nickname) -> bool {
+        let pending = self
+            .pending_online
+            .lock()
+            .expect("twitch state lock")
+            .remove(nick);
+        match pending {
+            Some(handle) if !handle.is_finished() => {
+                handle.abort();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn is_pending(&self, nick: &Nickname) -> bool {
+        self.pending_online
+            .lock()
+            .expect("twitch state lock")
+            .get(nick)
+            .map(|handle| !handle.is_finished())
+            .unwrap_or(false)
+    }
+
     fn remove_stream(&self, nick: &Nickname) -> Option<Stream> {
         self.online_streams
             .lock()
@@ -205,6 +384,11 @@ impl Plugin for Twitch {
         let config =
             Config::from_file_keyed(config_path).context(format!("Cannot read {config_path}"))?;
 
+        let followed =
+            db::with_connection(|conn| follow::load(conn).context("Cannot load followed streams"))
+                .await?;
+        let watched_streams = follow::merge(&config.watched_streams, followed);
+
         let client = HelixClient::new();
 
         let token = WrappedToken::new(config.client_id.clone(), config.client_secret.clone())
@@ -219,7 +403,17 @@ impl Plugin for Twitch {
             token,
             client,
             state: Default::default(),
+            watched_streams: Mutex::new(watched_streams),
+            owners: core_config.owners.clone(),
+            ops: Default::default(),
             twitch_rx: TokioMutex::new(twitch_rx),
+            notifications_sent: core_config
+                .metrics
+                .counter(
+                    "twitch_notifications_sent_total",
+                    "Twitch stream announcements sent to irc",
+                )
+                .context("Cannot register twitch metrics")?,
         };
 
         Ok(Initialised {
@@ -237,8 +431,22 @@ impl Plugin for Twitch {
         // hold that lock forever
         let mut twitch_rx = self.twitch_rx.lock().await;
 
-        while let Some(twitch_msg) = twitch_rx.recv().await {
-            self.process_twitch_message(&tx, twitch_msg).await?;
+        let mut check_interval = tokio::time::interval(SUBSCRIPTION_CHECK_INTERVAL);
+        // the first tick completes immediately, and subscriptions have just been synced
+        check_interval.tick().await;
+
+        loop {
+            tokio::select! {
+                mb_msg = twitch_rx.recv() => match mb_msg {
+                    Some(twitch_msg) => self.process_twitch_message(&tx, twitch_msg).await?,
+                    None => break,
+                },
+                _ = check_interval.tick() => {
+                    if let Err(err) = self.check_subscriptions(&tx).await {
+                        log::error!("Error while checking twitch subscriptions: {err:?}");
+                    }
+                }
+            }
         }
         Ok(())
     }
@@ -247,6 +455,32 @@ impl Plugin for Twitch {
         "twitch"
     }
 
+    fn commands(&self) -> Vec<CommandHelp> {
+        vec![
+            CommandHelp::new("streams", "", "les streams suivis qui sont en live"),
+            CommandHelp::new(
+                "uptime",
+                "<stream>",
+                "depuis combien de temps le stream est en live",
+            ),
+            CommandHelp::new(
+                "twitch",
+                "follow|unfollow <stream>",
+                "suit un stream dans le channel, pour les ops",
+            ),
+            CommandHelp::new(
+                "notify",
+                "twitch <stream>",
+                "te prévient en privé quand le stream commence",
+            ),
+            CommandHelp::new(
+                "unnotify",
+                "twitch <stream>",
+                "ne plus être prévenu pour ce stream",
+            ),
+        ]
+    }
+
     async fn in_message(&self, msg: &IrcMessage) -> Result<Option<IrcMessage>> {
         self.in_message(msg).await
     }
@@ -267,6 +501,10 @@ impl Twitch {
             Message::StreamOffline(offline) => {
                 self.on_stream_offline(tx, offline).await?;
             }
+
+            Message::ChannelUpdate(update) => {
+                self.on_channel_update(tx, update).await?;
+            }
         }
         Ok(())
     }
@@ -277,9 +515,8 @@ impl Twitch {
         online: StreamOnlineV1Payload,
     ) -> Result<()> {
         let target = self
-            .config
-            .watched_streams
-            .iter()
+            .watched_streams()
+            .into_iter()
             .find(|s| s.nickname == online.broadcaster_user_login);
         log::info!("Stream online payload {online:?}");
         match target {
@@ -297,28 +534,41 @@ impl Twitch {
                     ),
                     Some(stream) => {
                         let url = format!("https://www.twitch.tv/{}", &target.nickname);
-                        let game = &stream.game_name.to_string();
-                        let game = if game.is_empty() {
-                            "".to_string()
-                        } else {
-                            format!("({})", game)
-                        };
-
                         let irc_nick = self.to_irc_nick(nick.as_str());
-                        let message = format!(
-                            "Le stream de {} est maintenant live at {} {}!",
-                            irc_nick, url, game
+                        let message = format_online(
+                            &irc_nick,
+                            &url,
+                            &stream.game_name.to_string(),
+                            &stream.title,
                         );
 
                         log::info!("Stream online: {}", &message);
-                        self.state.add_stream(nick, stream);
-                        for chan in &target.irc_channels {
-                            let cmd = Command::PRIVMSG(chan.clone(), message.clone()).into();
-                            log::info!("Stream online command to chan: {}, {:?}", &chan, &cmd);
-                            tx.send(cmd)
-                                .await
-                                .with_context(|| format!("can't send message to {}", &chan))?;
-                        }
+                        let subscribers = self.subscribers(nick.as_str()).await;
+                        let targets =
+                            notify::notification_targets(&target.irc_channels, subscribers);
+                        let tx = tx.clone();
+                        let notifications_sent = self.notifications_sent.clone();
+                        // only live once announced, a flap never shows up in λstreams
+                        let state = self.state.clone();
+                        let live_nick = nick.clone();
+                        self.state
+                            .delay_announcement(nick, ONLINE_DEBOUNCE, async move {
+                                state.add_stream(live_nick, stream);
+                                for chan in targets {
+                                    let cmd = privmsg_to(&chan, message.clone());
+                                    log::info!(
+                                        "Stream online command to chan: {}, {:?}",
+                                        &chan,
+                                        &cmd
+                                    );
+                                    match tx.send(cmd).await {
+                                        Ok(()) => notifications_sent.inc(),
+                                        Err(err) => {
+                                            log::error!("can't send message to {chan}: {err:?}")
+                                        }
+                                    }
+                                }
+                            });
                     }
                 }
             }
@@ -332,15 +582,21 @@ impl Twitch {
         offline: StreamOfflineV1Payload,
     ) -> Result<()> {
         let target = self
-            .config
-            .watched_streams
-            .iter()
+            .watched_streams()
+            .into_iter()
             .find(|s| s.nickname == offline.broadcaster_user_login);
         match target {
             None => log::warn!(
                 "Got a notification for {} but not found in config",
                 offline.broadcaster_user_login
             ),
+            Some(target) if self.state.cancel_announcement(&target.nickname) => {
+                log::info!(
+                    "{} went offline right after going online, not announcing anything",
+                    target.nickname
+                );
+                self.state.remove_stream(&target.nickname);
+            }
             Some(target) => {
                 match self.state.remove_stream(&target.nickname) {
                     None => {
@@ -354,9 +610,10 @@ impl Twitch {
                                     format!("{} a arreté de streamer pour le moment. N'oubliez pas de like&subscribe.", nick);
                         log::info!("Stream offline: {}", &message);
                         for chan in &target.irc_channels {
-                            tx.send(Command::PRIVMSG(chan.clone(), message.clone()).into())
+                            tx.send(privmsg_to(chan, message.clone()))
                                 .await
                                 .with_context(|| format!("can't send message to {}", &chan))?;
+                            self.notifications_sent.inc();
                         }
                     }
                 }
@@ -365,14 +622,58 @@ impl Twitch {
         Ok(())
     }
 
+    /// Only announced while the stream is live, nobody cares about
+    /// the title of an offline channel.
+    async fn on_channel_update(
+        &self,
+        tx: &mpsc::Sender<irc::proto::Message>,
+        update: ChannelUpdateV1Payload,
+    ) -> Result<()> {
+        let target = self
+            .watched_streams()
+            .into_iter()
+            .find(|s| s.nickname == update.broadcaster_user_login);
+        let target = match target {
+            None => {
+                log::warn!(
+                    "Got a channel update for {} but not found in config",
+                    update.broadcaster_user_login
+                );
+                return Ok(());
+            }
+            Some(target) => target,
+        };
+
+        if !self.state.is_online(&target.nickname) || self.state.is_pending(&target.nickname) {
+            log::debug!(
+                "Ignoring channel update for offline stream {}",
+                target.nickname
+            );
+            return Ok(());
+        }
+
+        let message = format_channel_update(
+            &self.to_irc_nick(target.nickname.as_str()),
+            update.category_name.as_str(),
+            update.title.as_str(),
+        );
+        log::info!("Channel update: {}", &message);
+        for chan in &target.irc_channels {
+            tx.send(privmsg_to(chan, message.clone()))
+                .await
+                .with_context(|| format!("can't send message to {}", &chan))?;
+            self.notifications_sent.inc();
+        }
+        Ok(())
+    }
+
     /// Returns a hashmap indexed by nickname and live stream information
     /// Abscence of a key indicates the stream is not live.
     async fn get_live_streams(&self) -> Result<HashMap<Nickname, Stream>> {
         let user_logins = self
-            .config
-            .watched_streams
-            .iter()
-            .map(|s| s.nickname.clone())
+            .watched_streams()
+            .into_iter()
+            .map(|s| s.nickname)
             .collect();
 
         let resp = self
@@ -410,12 +711,31 @@ impl Twitch {
     }
 
     async fn in_message(&self, msg: &IrcMessage) -> Result<Option<IrcMessage>> {
+        self.ops.track(msg);
+
         let response_target = match msg.response_target() {
             None => return Ok(None),
             Some(target) => target,
         };
 
         if let Command::PRIVMSG(_source, privmsg) = &msg.command {
+            if let Some(cmd) = parse_follow(privmsg) {
+                let channel = channel_key(msg, response_target);
+                let message = self.follow_command(msg, &channel, cmd).await;
+                return Ok(Some(
+                    Command::PRIVMSG(response_target.to_string(), message).into(),
+                ));
+            }
+
+            if let (Some(cmd), Some(irc_nick)) = (parse_notify(privmsg), msg.source_nickname()) {
+                let message = self
+                    .notify_command(&channel_key(msg, irc_nick), cmd)
+                    .await?;
+                return Ok(Some(
+                    Command::PRIVMSG(response_target.to_string(), message).into(),
+                ));
+            }
+
             if let Some(mb_target) = parser::single_command("streams", privmsg) {
                 let prefix = mb_target.map(|t| format!("{}: ", t)).unwrap_or_default();
                 let live_streams = self.state.online_streams.lock().expect("twitch state lock");
@@ -428,10 +748,241 @@ impl Twitch {
                     Command::PRIVMSG(response_target.to_string(), message).into(),
                 ));
             }
+
+            if let Some((login, mb_target)) = parser::command_with_arg("uptime", privmsg) {
+                let prefix = mb_target.map(|t| format!("{}: ", t)).unwrap_or_default();
+                let message = format!("{prefix}{}", self.uptime(login));
+                return Ok(Some(
+                    Command::PRIVMSG(response_target.to_string(), message).into(),
+                ));
+            }
         }
         Ok(None)
     }
 
+    fn uptime(&self, login: &str) -> String {
+        let login = login.to_lowercase();
+        let live_streams = self.state.online_streams.lock().expect("twitch state lock");
+        match live_streams.get(&Nickname::new(login.as_str())) {
+            None => format!("{login} is not live right now"),
+            Some(stream) => {
+                let nick = self.to_irc_nick(stream.user_name.as_str());
+                match parse_started_at(stream.started_at.as_str()) {
+                    Some(started_at) => format!(
+                        "{nick} is live for {} (https://www.twitch.tv/{})",
+                        format_uptime(time::OffsetDateTime::now_utc() - started_at),
+                        stream.user_login
+                    ),
+                    None => format!(
+                        "{nick} is live (https://www.twitch.tv/{})",
+                        stream.user_login
+                    ),
+                }
+            }
+        }
+    }
+
+    fn watched_streams(&self) -> Vec<StreamSpec> {
+        self.watched_streams
+            .lock()
+            .expect("watched streams lock")
+            .clone()
+    }
+
+    /// Only the owners and the channel operators can change what's watched
+    fn can_follow(&self, msg: &IrcMessage, channel: &str) -> bool {
+        self.owners.is_owner(msg)
+            || msg
+                .source_nickname()
+                .map(|nick| self.ops.is_op(channel, nick))
+                .unwrap_or(false)
+    }
+
+    /// (un)follow a stream in the given channel, see `channel_key`. Errors are reported
+    /// in the reply, there's no reason to crash the bot because twitch is flaky.
+    async fn follow_command(&self, msg: &IrcMessage, channel: &str, cmd: FollowCmd<'_>) -> String {
+        if !split_channel_key(channel).1.starts_with('#') {
+            return "Ça ne marche que dans un channel.".to_string();
+        }
+        if !self.can_follow(msg, channel) {
+            return "Seuls les ops peuvent changer les streams surveillés.".to_string();
+        }
+
+        let res = match cmd {
+            FollowCmd::Follow(stream) => self.follow(&stream.to_lowercase(), channel).await,
+            FollowCmd::Unfollow(stream) => self.unfollow(&stream.to_lowercase(), channel).await,
+        };
+        res.unwrap_or_else(|err| {
+            log::error!("Error while updating the watched streams: {err:?}");
+            "Oops, twitch ne veut pas, réessaye plus tard.".to_string()
+        })
+    }
+
+    async fn follow(&self, stream: &str, channel: &str) -> Result<String> {
+        if self
+            .watched_streams()
+            .iter()
+            .any(|s| s.nickname.as_str() == stream && s.irc_channels.iter().any(|c| c == channel))
+        {
+            return Ok(format!("Je surveille déjà le stream de {stream} ici."));
+        }
+
+        let nick = Nickname::new(stream);
+        if self.get_users(vec![nick.clone()], vec![]).await?.is_empty() {
+            return Ok(format!("Connais pas {stream} sur twitch."));
+        }
+
+        let (s, c) = (stream.to_string(), channel.to_string());
+        db::with_connection(move |conn| {
+            follow::follow(conn, &s, &c).with_context(|| format!("Cannot follow {s} in {c}"))
+        })
+        .await
+        .context("Cannot follow stream")?;
+
+        follow::add_channel(
+            &mut self.watched_streams.lock().expect("watched streams lock"),
+            stream,
+            channel,
+        );
+        self.sync_subscriptions().await?;
+        if let Some(live) = self.get_live_stream(nick.clone()).await? {
+            self.state.add_stream(nick, live);
+        }
+
+        Ok(format!(
+            "Je préviendrai ici quand {stream} sera en live (https://www.twitch.tv/{stream})."
+        ))
+    }
+
+    async fn unfollow(&self, stream: &str, channel: &str) -> Result<String> {
+        let (s, c) = (stream.to_string(), channel.to_string());
+        let deleted = db::with_connection(move |conn| {
+            follow::unfollow(conn, &s, &c).with_context(|| format!("Cannot unfollow {s} in {c}"))
+        })
+        .await
+        .context("Cannot unfollow stream")?;
+
+        if !deleted {
+            let in_config = self.config.watched_streams.iter().any(|s| {
+                s.nickname.as_str() == stream && s.irc_channels.iter().any(|c| c == channel)
+            });
+            return Ok(if in_config {
+                format!("Le stream de {stream} est dans la config, je ne peux pas l'oublier.")
+            } else {
+                format!("Je ne surveille pas le stream de {stream} ici.")
+            });
+        }
+
+        let still_watched = {
+            let mut streams = self.watched_streams.lock().expect("watched streams lock");
+            follow::remove_channel(&mut streams, stream, channel);
+            streams.iter().any(|s| s.nickname.as_str() == stream)
+        };
+        if !still_watched {
+            self.state.remove_stream(&Nickname::new(stream));
+        }
+        self.sync_subscriptions().await?;
+
+        Ok(format!("Je ne préviendrai plus ici pour {stream}."))
+    }
+
+    /// (un)subscribe the given irc user to private notifications for a watched stream.
+    /// The user is keyed like a channel, so that the notification goes to the right server.
+    async fn notify_command(&self, irc_nick: &str, cmd: NotifyCmd<'_>) -> Result<String> {
+        let stream = match cmd {
+            NotifyCmd::Subscribe(s) | NotifyCmd::Unsubscribe(s) => s.to_lowercase(),
+        };
+        if !self
+            .watched_streams()
+            .iter()
+            .any(|s| s.nickname.as_str() == stream)
+        {
+            return Ok(format!("Je ne surveille pas le stream de {stream}."));
+        }
+
+        let subscribe = matches!(cmd, NotifyCmd::Subscribe(_));
+        let nick = irc_nick.to_string();
+        let target = stream.clone();
+        let changed = db::with_connection(move |conn| {
+            let changed = if subscribe {
+                notify::subscribe(conn, &nick, Kind::Twitch, &target)
+            } else {
+                notify::unsubscribe(conn, &nick, Kind::Twitch, &target)
+            };
+            changed.with_context(|| format!("Cannot update notifications of {nick} for {target}"))
+        })
+        .await
+        .context("Cannot update notification")?;
+
+        let message = match (subscribe, changed) {
+            (true, true) => format!("Je te préviendrai en privé quand {stream} sera en live."),
+            (true, false) => format!("Tu es déjà prévenu quand {stream} est en live."),
+            (false, true) => format!("Tu ne seras plus prévenu pour {stream}."),
+            (false, false) => format!("Tu n'étais pas prévenu pour {stream}."),
+        };
+        Ok(message)
+    }
+
+    /// irc nicks who asked to be notified in private when the given stream goes live.
+    /// A db problem shouldn't prevent the announcement in the channels, so errors
+    /// are only logged.
+    async fn subscribers(&self, stream: &str) -> Vec<String> {
+        let stream = stream.to_string();
+        let res = db::with_connection(move |conn| {
+            notify::subscribers(conn, Kind::Twitch, &stream)
+                .with_context(|| format!("Cannot get subscribers for {stream}"))
+        })
+        .await;
+        match res {
+            Ok(subscribers) => subscribers,
+            Err(err) => {
+                log::error!("{err:?}");
+                vec![]
+            }
+        }
+    }
+
+    /// Dead man's switch: if twitch stops sending events (expired subscription,
+    /// unreachable webhook…) the bot would silently never announce anything.
+    /// So periodically check that every watched stream is still fully subscribed,
+    /// and resync otherwise.
+    async fn check_subscriptions(&self, tx: &mpsc::Sender<irc::proto::Message>) -> Result<()> {
+        let subs = self.list_subscriptions().await?;
+        let users = self
+            .get_users(
+                self.watched_streams()
+                    .into_iter()
+                    .map(|u| u.nickname)
+                    .collect(),
+                vec![],
+            )
+            .await?;
+
+        let stale = users
+            .iter()
+            .filter(|u| !is_fully_subscribed(&subs, &u.id))
+            .map(|u| u.login.to_string())
+            .collect::<Vec<_>>();
+
+        if stale.is_empty() {
+            log::debug!("All twitch subscriptions are valid");
+            return Ok(());
+        }
+
+        log::error!("Missing or invalid twitch subscriptions for {stale:?}, resyncing");
+        if let Some(chan) = &self.config.ops_channel {
+            let message = format!(
+                "Souscriptions twitch manquantes pour {}, resynchronisation en cours.",
+                stale.join(", ")
+            );
+            tx.send(privmsg_to(chan, message))
+                .await
+                .with_context(|| format!("can't send message to {}", &chan))?;
+        }
+
+        self.sync_subscriptions().await
+    }
+
     /// Make sure the bot is subscribed to stream.online and stream.offline
     /// for all the given user names (should not be capitalized)
     /// Also unsubscribe from existing subscriptions for user not listed in `user_names`
@@ -439,19 +990,17 @@ impl Twitch {
         let subs = self.list_subscriptions().await?;
 
         let users = self
-            .config
-            .watched_streams
-            .iter()
-            .map(|u| &u.nickname)
+            .watched_streams()
+            .into_iter()
+            .map(|u| u.nickname)
             .collect::<Vec<_>>();
         log::info!("Syncing subscription for users {:?}", users);
 
         let users = self
             .get_users(
-                self.config
-                    .watched_streams
-                    .iter()
-                    .map(|u| u.nickname.clone())
+                self.watched_streams()
+                    .into_iter()
+                    .map(|u| u.nickname)
                     .collect(),
                 vec![],
             )
@@ -510,23 +1059,24 @@ impl Twitch {
     }
 
     pub async fn list_subscriptions(&self) -> Result<Vec<Subscription>> {
-        // TODO: handle pagination
-        let resp = self
-            .client
-            .req_get(
-                helix::eventsub::GetEventSubSubscriptionsRequest::builder().build(),
-                &self.token.get(),
-            )
-            .await
-            .map_err(|e| plugin_core::Error::Wrapped {
-                source: Box::new(e),
-                ctx: "cannot list subscriptions".to_string(),
-            })?;
-        // dbg!(&resp);
+        let subs = collect_pages(|cursor| async move {
+            let mut req = helix::eventsub::GetEventSubSubscriptionsRequest::builder().build();
+            req.after = cursor;
+            let resp = self
+                .client
+                .req_get(req, &self.token.get())
+                .await
+                .map_err(|e| plugin_core::Error::Wrapped {
+                    source: Box::new(e),
+                    ctx: "cannot list subscriptions".to_string(),
+                })?;
+            Ok((resp.data.subscriptions, resp.pagination))
+        })
+        .await?;
 
-        let subs = resp
-            .data
-            .subscriptions
+        // filtered once all the pages are there, an empty page must be
+        // told apart from a page without any stream subscription
+        let subs = subs
             .into_iter()
             .filter_map(|sub| {
                 let status = sub.status;
@@ -546,6 +1096,7 @@ impl Twitch {
             })
             .collect::<Vec<_>>();
 
+        log::debug!("found {} subscriptions", subs.len());
         Ok(subs)
     }
 
@@ -567,7 +1118,8 @@ impl Twitch {
         Ok(())
     }
 
-    /// Ensure we're subscribed to the given user's stream.{online,offline} events
+    /// Ensure we're subscribed to the given user's stream.{online,offline}
+    /// and channel.update events
     async fn sync_user_subscription(&self, subs: &[Subscription], user: User) -> Result<()> {
         let sub_online = subs
             .iter()
@@ -613,6 +1165,28 @@ impl Twitch {
             }
         };
 
+        let sub_update = subs
+            .iter()
+            .find(|s| s.user_id == user.id && matches!(s.type_, EventType::ChannelUpdate));
+        match sub_update {
+            Some(_) => log::info!(
+                "channel update subscription already exists for user_login {}",
+                user.login
+            ),
+            None => {
+                let event = ChannelUpdateV1::builder()
+                    .broadcaster_user_id(user.id.clone())
+                    .build();
+                self.subscribe(event).await.with_context(|| {
+                    format!(
+                        "failed to create channel.update subscription for (user_id, user_name) ({}, {})",
+                        user.id, user.login
+                    )
+                })?;
+                log::info!("Subscribed channel.update for channel {}", user.login);
+            }
+        };
+
         Ok(())
     }
 
@@ -669,13 +1243,7 @@ impl Twitch {
         } else {
             format!("({})", game)
         };
-        let time_fmt = time::macros::format_description!("[hour]:[minute] [period]");
-        let parsed = time::OffsetDateTime::parse(
-            stream.started_at.as_str(),
-            &time::format_description::well_known::Rfc3339,
-        )
-        .expect("valid RFC3339 timestamp for started_at");
-        let started_at = parsed.format(time_fmt).unwrap();
+        let started_at = format_started_at(stream.started_at.as_str());
         format!(
             "{} {} started at {started_at} (https://www.twitch.tv/{})",
             self.to_irc_nick(stream.user_name.as_str()),
@@ -689,8 +1257,7 @@ impl Twitch {
         // twitch nicknames as sent in the webhook events have casing
         // but the login nicknames otherwise don't
         let twitch_nick = twitch_nick.to_lowercase();
-        self.config
-            .watched_streams
+        self.watched_streams()
             .iter()
             .find_map(|s| {
                 if s.nickname.as_str() == twitch_nick {
@@ -702,3 +1269,308 @@ impl Twitch {
             .unwrap_or_else(|| twitch_nick.to_string())
     }
 }
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum NotifyCmd<'input> {
+    Subscribe(&'input str),
+    Unsubscribe(&'input str),
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum FollowCmd<'input> {
+    Follow(&'input str),
+    Unfollow(&'input str),
+}
+
+/// λtwitch follow <stream> or λtwitch unfollow <stream>
+fn parse_follow(input: &str) -> Option<FollowCmd> {
+    let stream = take_while1(|c: char| c.is_alphanumeric() || c == '_');
+    let cmd = preceded(
+        tuple((parser::command_prefix, tag("twitch"), multispace1)),
+        map(
+            tuple((alt((tag("follow"), tag("unfollow"))), multispace1, stream)),
+            |(cmd, _, stream)| {
+                if cmd == "follow" {
+                    FollowCmd::Follow(stream)
+                } else {
+                    FollowCmd::Unfollow(stream)
+                }
+            },
+        ),
+    );
+
+    all_consuming(terminated(cmd, multispace0))(input)
+        .finish()
+        .map(|x| x.1)
+        .ok()
+}
+
+/// λnotify twitch <stream> or λunnotify twitch <stream>
+fn parse_notify(input: &str) -> Option<NotifyCmd> {
+    let stream = take_while1(|c: char| c.is_alphanumeric() || c == '_');
+    let cmd = preceded(
+        parser::command_prefix,
+        map(
+            tuple((
+                alt((tag("notify"), tag("unnotify"))),
+                multispace1,
+                tag("twitch"),
+                multispace1,
+                stream,
+            )),
+            |(cmd, _, _, _, stream)| {
+                if cmd == "notify" {
+                    NotifyCmd::Subscribe(stream)
+                } else {
+                    NotifyCmd::Unsubscribe(stream)
+                }
+            },
+        ),
+    );
+
+    all_consuming(terminated(cmd, multispace0))(input)
+        .finish()
+        .map(|x| x.1)
+        .ok()
+}
+
+/// Calls `fetch_page` with the cursor returned by the previous page
+/// until helix doesn't give any cursor anymore, and gather all the results.
+/// The pages must be the ones returned by helix, an empty one stops.
+async fn collect_pages<T, C, F, Fut>(mut fetch_page: F) -> Result<Vec<T>>
+where
+    F: FnMut(Option<C>) -> Fut,
+    Fut: std::future::Future<Output = Result<(Vec<T>, Option<C>)>>,
+{
+    let mut results = Vec::new();
+    let mut cursor = None;
+    loop {
+        let (page, next) = fetch_page(cursor).await?;
+        // an empty page with a cursor shouldn't happen, but it would loop forever
+        let is_empty = page.is_empty();
+        results.extend(page);
+        match next {
+            Some(next) if !is_empty => cursor = Some(next),
+            _ => break,
+        }
+    }
+    Ok(results)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_notify() {
+        assert_eq!(
+            parse_notify("λnotify twitch gikiam"),
+            Some(NotifyCmd::Subscribe("gikiam"))
+        );
+        assert_eq!(
+            parse_notify("λunnotify twitch some_streamer "),
+            Some(NotifyCmd::Unsubscribe("some_streamer"))
+        );
+        assert_eq!(parse_notify("λnotify twitch"), None, "need a stream");
+        assert_eq!(parse_notify("λnotify crypto btc"), None);
+    }
+
+    #[tokio::test]
+    async fn test_collect_pages() {
+        let requested_cursors = Mutex::new(Vec::new());
+        let subs = collect_pages(|cursor: Option<String>| {
+            requested_cursors.lock().unwrap().push(cursor.clone());
+            async move {
+                match cursor.as_deref() {
+                    None => Ok((
+                        vec![
+                            sub("1", EventType::StreamOnline, eventsub::Status::Enabled),
+                            sub("1", EventType::StreamOffline, eventsub::Status::Enabled),
+                        ],
+                        Some("page2".to_string()),
+                    )),
+                    Some("page2") => Ok((
+                        vec![sub("2", EventType::StreamOnline, eventsub::Status::Enabled)],
+                        None,
+                    )),
+                    Some(c) => panic!("unexpected cursor {c}"),
+                }
+            }
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(
+            subs.iter().map(|s| s.user_id.as_str()).collect::<Vec<_>>(),
+            vec!["1", "1", "2"]
+        );
+        assert_eq!(
+            requested_cursors.into_inner().unwrap(),
+            vec![None, Some("page2".to_string())]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_collect_pages_stops_on_empty_page() {
+        let subs: Vec<Subscription> =
+            collect_pages(|_cursor| async { Ok((vec![], Some("again".to_string()))) })
+                .await
+                .unwrap();
+        assert!(subs.is_empty());
+    }
+
+    #[test]
+    fn test_parse_follow() {
+        assert_eq!(
+            parse_follow("λtwitch follow gikiam"),
+            Some(FollowCmd::Follow("gikiam"))
+        );
+        assert_eq!(
+            parse_follow("λtwitch unfollow some_streamer "),
+            Some(FollowCmd::Unfollow("some_streamer"))
+        );
+        assert_eq!(parse_follow("λtwitch follow"), None, "need a stream");
+        assert_eq!(parse_follow("λtwitch follow a b"), None);
+        assert_eq!(parse_follow("λtwitch gikiam"), None);
+    }
+
+    fn sub(user_id: &str, type_: EventType, status: eventsub::Status) -> Subscription {
+        let id = format!("{user_id}-{type_:?}");
+        Subscription {
+            id: EventSubId::new(id.as_str()),
+            user_id: UserId::new(user_id),
+            type_,
+            status,
+        }
+    }
+
+    #[test]
+    fn test_is_fully_subscribed() {
+        let user_id = UserId::new("1234");
+        let online = || sub("1234", EventType::StreamOnline, eventsub::Status::Enabled);
+        let offline = || sub("1234", EventType::StreamOffline, eventsub::Status::Enabled);
+        let update = || sub("1234", EventType::ChannelUpdate, eventsub::Status::Enabled);
+
+        assert!(
+            is_fully_subscribed(&[online(), offline(), update()], &user_id),
+            "valid online, offline and update subscriptions"
+        );
+
+        assert!(
+            !is_fully_subscribed(&[online(), offline()], &user_id),
+            "missing channel update subscription"
+        );
+
+        assert!(
+            !is_fully_subscribed(&[online()], &user_id),
+            "missing offline subscription"
+        );
+
+        assert!(
+            !is_fully_subscribed(
+                &[
+                    online(),
+                    sub(
+                        "1234",
+                        EventType::StreamOffline,
+                        eventsub::Status::WebhookCallbackVerificationFailed
+                    )
+                ],
+                &user_id
+            ),
+            "invalid offline subscription"
+        );
+
+        assert!(
+            !is_fully_subscribed(
+                &[
+                    online(),
+                    sub("5678", EventType::StreamOffline, eventsub::Status::Enabled)
+                ],
+                &user_id
+            ),
+            "offline subscription for someone else"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_cancel_announcement() {
+        let state = State::default();
+        let nick = Nickname::new("gikiam");
+        let (tx, mut rx) = mpsc::channel(1);
+        state.delay_announcement(nick.clone(), Duration::from_millis(50), async move {
+            tx.send(()).await.unwrap();
+        });
+        assert!(state.is_pending(&nick));
+        assert!(state.cancel_announcement(&nick), "offline came first");
+        assert!(rx.recv().await.is_none(), "never announced");
+    }
+
+    #[tokio::test]
+    async fn test_delayed_announcement() {
+        let state = State::default();
+        let nick = Nickname::new("gikiam");
+        let (tx, mut rx) = mpsc::channel(1);
+        state.delay_announcement(nick.clone(), Duration::from_millis(10), async move {
+            tx.send(()).await.unwrap();
+        });
+        assert_eq!(rx.recv().await, Some(()));
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert!(
+            !state.cancel_announcement(&nick),
+            "already announced, the offline event is a real one"
+        );
+    }
+
+    #[test]
+    fn test_format_uptime() {
+        assert_eq!(format_uptime(time::Duration::seconds(42)), "0m");
+        assert_eq!(format_uptime(time::Duration::minutes(13)), "13m");
+        assert_eq!(format_uptime(time::Duration::minutes(2 * 60 + 13)), "2h13m");
+        assert_eq!(
+            format_uptime(time::Duration::minutes(25 * 60 + 5)),
+            "25h05m"
+        );
+        assert_eq!(
+            format_uptime(time::Duration::seconds(-5)),
+            "0m",
+            "clock skew with twitch"
+        );
+    }
+
+    #[test]
+    fn test_format_started_at() {
+        assert_eq!(format_started_at("2022-03-05T21:42:10Z"), "21:42 PM");
+        assert_eq!(format_started_at("yesterday"), "yesterday");
+        assert_eq!(parse_started_at(""), None);
+    }
+
+    #[test]
+    fn test_format_online() {
+        let url = "https://www.twitch.tv/gikiam";
+        assert_eq!(
+            format_online("gikiam", url, "Celeste", "any% practice"),
+            "Le stream de gikiam est maintenant live at https://www.twitch.tv/gikiam (Celeste): any% practice"
+        );
+        assert_eq!(
+            format_online("gikiam", url, "", "chatting"),
+            "Le stream de gikiam est maintenant live at https://www.twitch.tv/gikiam: chatting"
+        );
+        assert_eq!(
+            format_online("gikiam", url, "Celeste", "  "),
+            "Le stream de gikiam est maintenant live at https://www.twitch.tv/gikiam (Celeste)!"
+        );
+    }
+
+    #[test]
+    fn test_format_channel_update() {
+        assert_eq!(
+            format_channel_update("gikiam", "Celeste", "any% practice"),
+            "gikiam switched to Celeste: any% practice"
+        );
+        assert_eq!(
+            format_channel_update("gikiam", "", "chatting"),
+            "gikiam changed the title: chatting"
+        );
+    }
+}
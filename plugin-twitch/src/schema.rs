@@ -0,0 +1,14 @@
+table! {
+    dm_subscriptions (irc_nick, kind, target) {
+        irc_nick -> Text,
+        kind -> Text,
+        target -> Text,
+    }
+}
+
+table! {
+    followed_streams (nickname, irc_channel) {
+        nickname -> Text,
+        irc_channel -> Text,
+    }
+}
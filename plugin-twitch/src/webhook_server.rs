@@ -1,6 +1,7 @@
 use crate::errors::{self, TwitchError, TwitchSigError};
 use axum::{
-    extract::FromRequestParts,
+    body::Bytes,
+    extract::{DefaultBodyLimit, FromRequestParts},
     http::{request::Parts, status::StatusCode},
     response::IntoResponse,
     routing, Router,
@@ -14,6 +15,10 @@ use crate::config::{Config, Message};
 
 type HmacSha256 = Hmac<sha2::Sha256>;
 
+/// Twitch notifications are a few KB at most, anything bigger
+/// is rejected with a 413 before being buffered entirely.
+const MAX_BODY_SIZE: usize = 64 * 1024;
+
 fn decode_hex(s: &str) -> std::result::Result<Vec<u8>, ParseIntError> {
     (0..s.len())
         .step_by(2)
@@ -93,12 +98,13 @@ pub struct ServerStateAxum {
 async fn webhook_post2(
     sig_verifier: SigVerifierAxum,
     axum::extract::State(state): axum::extract::State<ServerStateAxum>,
-    body: String,
+    body: Bytes,
 ) -> Result<axum::response::Response, TwitchError> {
+    let body = std::str::from_utf8(&body)?;
     log::debug!("got something from twitch: {:?}", body);
     sig_verifier.verify(&state.app_secret, body.as_bytes())?;
 
-    let payload = twitch_api2::eventsub::Payload::parse(&body).expect("good twitch response");
+    let payload = twitch_api2::eventsub::Payload::parse(body).expect("good twitch response");
     // dbg!(&payload);
     match payload {
         eventsub::Payload::VerificationRequest(verif_req) => {
@@ -129,6 +135,18 @@ async fn webhook_post2(
                 })?;
             Ok(().into_response())
         }
+        eventsub::Payload::ChannelUpdateV1(update) => {
+            log::debug!("channel update event: {:#?}", update);
+            state
+                .send_chan
+                .send(Message::ChannelUpdate(update.event))
+                .await
+                .map_err(|err| {
+                    log::error!("{:?}", err);
+                    StatusCode::INTERNAL_SERVER_ERROR
+                })?;
+            Ok(().into_response())
+        }
         _ => {
             log::info!("Received unsupported payload: {:#?}", payload);
             Err(StatusCode::NOT_IMPLEMENTED.into())
@@ -137,12 +155,69 @@ async fn webhook_post2(
 }
 
 pub(crate) fn init_router(config: &Config, tx: mpsc::Sender<Message>) -> Router<()> {
+    router(config.app_secret.clone(), tx)
+}
+
+fn router(app_secret: String, tx: mpsc::Sender<Message>) -> Router<()> {
     let server_state = ServerStateAxum {
-        app_secret: Arc::new(config.app_secret.clone()),
+        app_secret: Arc::new(app_secret),
         send_chan: tx,
     };
 
     axum::Router::new()
         .route("/touitche/coucou", routing::post(webhook_post2))
+        .layer(DefaultBodyLimit::max(MAX_BODY_SIZE))
         .with_state(server_state.clone())
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use axum::{body::Body, http::Request};
+    use tower::ServiceExt;
+
+    const SECRET: &str = "coucou secret";
+
+    fn encode_hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{b:02x}")).collect()
+    }
+
+    /// A request correctly signed with SECRET
+    fn signed_request(body: Vec<u8>) -> Request<Body> {
+        let (msg_id, msg_ts) = ("some-id", "2023-07-01T12:00:00Z");
+        let mut mac = HmacSha256::new_from_slice(SECRET.as_bytes()).unwrap();
+        mac.update(msg_id.as_bytes());
+        mac.update(msg_ts.as_bytes());
+        mac.update(&body);
+        let sig = encode_hex(&mac.finalize().into_bytes());
+
+        Request::post("/touitche/coucou")
+            .header("Twitch-Eventsub-Message-Signature", format!("sha256={sig}"))
+            .header("Twitch-Eventsub-Message-Id", msg_id)
+            .header("Twitch-Eventsub-Message-Timestamp", msg_ts)
+            .body(Body::from(body))
+            .unwrap()
+    }
+
+    async fn post(body: Vec<u8>) -> StatusCode {
+        let (tx, _rx) = mpsc::channel(1);
+        router(SECRET.to_string(), tx)
+            .oneshot(signed_request(body))
+            .await
+            .unwrap()
+            .status()
+    }
+
+    #[tokio::test]
+    async fn test_oversized_body() {
+        assert_eq!(
+            post(vec![b'a'; MAX_BODY_SIZE + 1]).await,
+            StatusCode::PAYLOAD_TOO_LARGE
+        );
+    }
+
+    #[tokio::test]
+    async fn test_non_utf8_body() {
+        assert_eq!(post(vec![0xff, 0xfe, 0xfd]).await, StatusCode::BAD_REQUEST);
+    }
+}
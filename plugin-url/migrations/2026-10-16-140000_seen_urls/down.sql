@@ -0,0 +1 @@
+DROP TABLE seen_urls;
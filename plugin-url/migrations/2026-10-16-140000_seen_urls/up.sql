@@ -0,0 +1,5 @@
+CREATE TABLE seen_urls (
+  id INTEGER PRIMARY KEY NOT NULL,
+  channel TEXT NOT NULL,
+  url TEXT NOT NULL
+);
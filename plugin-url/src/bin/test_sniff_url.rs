@@ -4,7 +4,7 @@ use std::error::Error;
 async fn main() -> Result<(), Box<dyn Error>> {
     let resp = reqwest::get("https://apnews.com/article/greta-thunberg-german-mine-protest-a870ba0ba69c7816cc04f13b8be2cb94")
         .await?;
-    let res = plugin_url::sniff_title(resp).await?;
+    let res = plugin_url::sniff_title(resp, plugin_url::DEFAULT_MAX_TITLE_LENGTH).await?;
     println!("mb title is: {res}");
 
     // let url = "mock url";
@@ -0,0 +1,116 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
+
+/// Bounded cache of strings, the entries expire after `ttl`, and the least
+/// recently used one is evicted when it's full. The lock is only taken
+/// inside `get` and `insert`, never across an await point.
+pub(crate) struct TtlCache {
+    entries: Mutex<HashMap<String, Entry>>,
+    capacity: usize,
+    ttl: Duration,
+}
+
+struct Entry {
+    value: String,
+    inserted_at: Instant,
+    used_at: Instant,
+}
+
+impl TtlCache {
+    /// A capacity of 0 disables the cache
+    pub(crate) fn new(capacity: usize, ttl: Duration) -> Self {
+        TtlCache {
+            entries: Default::default(),
+            capacity,
+            ttl,
+        }
+    }
+
+    pub(crate) fn get(&self, key: &str) -> Option<String> {
+        self.get_at(key, Instant::now())
+    }
+
+    pub(crate) fn insert(&self, key: String, value: String) {
+        self.insert_at(key, value, Instant::now())
+    }
+
+    fn get_at(&self, key: &str, now: Instant) -> Option<String> {
+        let mut entries = self.entries.lock();
+        match entries.get_mut(key) {
+            Some(entry) if now.duration_since(entry.inserted_at) < self.ttl => {
+                entry.used_at = now;
+                Some(entry.value.clone())
+            }
+            Some(_) => {
+                entries.remove(key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    fn insert_at(&self, key: String, value: String, now: Instant) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut entries = self.entries.lock();
+        entries.retain(|_, entry| now.duration_since(entry.inserted_at) < self.ttl);
+        if entries.len() >= self.capacity && !entries.contains_key(&key) {
+            let lru = entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.used_at)
+                .map(|(key, _)| key.clone());
+            if let Some(lru) = lru {
+                entries.remove(&lru);
+            }
+        }
+        entries.insert(
+            key,
+            Entry {
+                value,
+                inserted_at: now,
+                used_at: now,
+            },
+        );
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_expiration() {
+        let cache = TtlCache::new(10, Duration::from_secs(60));
+        let now = Instant::now();
+        cache.insert_at("a".to_string(), "1".to_string(), now);
+        assert_eq!(
+            cache.get_at("a", now + Duration::from_secs(59)),
+            Some("1".to_string())
+        );
+        assert_eq!(cache.get_at("a", now + Duration::from_secs(60)), None);
+        assert_eq!(cache.entries.lock().len(), 0, "expired entries are dropped");
+    }
+
+    #[test]
+    fn test_eviction() {
+        let cache = TtlCache::new(2, Duration::from_secs(60));
+        let now = Instant::now();
+        let at = |secs| now + Duration::from_secs(secs);
+        cache.insert_at("a".to_string(), "1".to_string(), at(0));
+        cache.insert_at("b".to_string(), "2".to_string(), at(1));
+        assert_eq!(cache.get_at("a", at(2)), Some("1".to_string()));
+        cache.insert_at("c".to_string(), "3".to_string(), at(3));
+
+        assert_eq!(cache.get_at("b", at(4)), None, "least recently used");
+        assert_eq!(cache.get_at("a", at(4)), Some("1".to_string()));
+        assert_eq!(cache.get_at("c", at(4)), Some("3".to_string()));
+
+        let disabled = TtlCache::new(0, Duration::from_secs(60));
+        disabled.insert("a".to_string(), "1".to_string());
+        assert_eq!(disabled.get("a"), None);
+    }
+}
@@ -0,0 +1,301 @@
+use async_trait::async_trait;
+use plugin_core::{Error, Result};
+use serde::de::{DeserializeOwned, IgnoredAny};
+use serde::Deserialize;
+use url::Url;
+
+use crate::handlers::{TitleSniffer, UrlHandler};
+use crate::UrlTitle;
+
+const GITHUB_API_URL: &str = "https://api.github.com";
+
+/// First path segments of github.com which aren't users or organisations
+const RESERVED_SEGMENTS: [&str; 12] = [
+    "about",
+    "apps",
+    "collections",
+    "explore",
+    "features",
+    "login",
+    "marketplace",
+    "notifications",
+    "orgs",
+    "search",
+    "settings",
+    "topics",
+];
+
+/// Describe repositories, issues and pull requests with the github api.
+/// The page title is used instead when the api fails, for example
+/// when the rate limit is reached.
+pub(crate) struct GithubHandler {
+    client: reqwest::Client,
+    /// without it, the api only allows 60 calls an hour
+    token: Option<String>,
+    fallback: TitleSniffer,
+}
+
+#[derive(Debug, Deserialize)]
+struct Repo {
+    full_name: String,
+    description: Option<String>,
+    stargazers_count: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct Issue {
+    number: u64,
+    title: String,
+    state: String,
+    user: Option<User>,
+    /// only set for pull requests
+    pull_request: Option<IgnoredAny>,
+}
+
+#[derive(Debug, Deserialize)]
+struct User {
+    login: String,
+}
+
+#[async_trait]
+impl UrlHandler for GithubHandler {
+    fn name(&self) -> &'static str {
+        "github"
+    }
+
+    fn matches(&self, url: &Url) -> bool {
+        is_github_url(url)
+    }
+
+    async fn describe(&self, url: &Url) -> Result<UrlTitle> {
+        let described = match extract_github_id(url) {
+            Some(GithubId::Repo(owner, repo)) => self
+                .api_call::<Repo>(&format!("repos/{owner}/{repo}"))
+                .await
+                .map(|r| format_repo(&r, url)),
+            Some(GithubId::Issue(owner, repo, number)) => self
+                .api_call::<Issue>(&format!("repos/{owner}/{repo}/issues/{number}"))
+                .await
+                .map(|i| format_issue(&i, url)),
+            None => return self.fallback.describe(url).await,
+        };
+        match described {
+            Ok(title) => Ok(UrlTitle::Found(title)),
+            Err(err) => {
+                log::warn!("Cannot describe {url} with the github api: {err}");
+                self.fallback.describe(url).await
+            }
+        }
+    }
+}
+
+impl GithubHandler {
+    pub(crate) fn new(
+        client: reqwest::Client,
+        token: Option<String>,
+        fallback: TitleSniffer,
+    ) -> Self {
+        GithubHandler {
+            client,
+            token,
+            fallback,
+        }
+    }
+
+    async fn api_call<T: DeserializeOwned>(&self, path: &str) -> Result<T> {
+        let mut req = self
+            .client
+            .get(format!("{GITHUB_API_URL}/{path}"))
+            .header(reqwest::header::ACCEPT, "application/vnd.github+json");
+        if let Some(token) = &self.token {
+            req = req.bearer_auth(token);
+        }
+        req.send()
+            .await
+            .and_then(|x| x.error_for_status())
+            .map_err(|err| Error::Wrapped {
+                source: Box::new(err),
+                ctx: format!("Failed to fetch {path} from github"),
+            })?
+            .json()
+            .await
+            .map_err(|err| Error::Wrapped {
+                source: Box::new(err),
+                ctx: format!("Failed to parse {path} from github"),
+            })
+    }
+}
+
+fn format_repo(repo: &Repo, url: &Url) -> String {
+    match repo.description.as_deref().filter(|d| !d.is_empty()) {
+        Some(description) => format!(
+            "{}: {} (★ {}) [{}]",
+            repo.full_name, description, repo.stargazers_count, url
+        ),
+        None => format!("{} (★ {}) [{}]", repo.full_name, repo.stargazers_count, url),
+    }
+}
+
+fn format_issue(issue: &Issue, url: &Url) -> String {
+    let kind = if issue.pull_request.is_some() {
+        "PR"
+    } else {
+        "Issue"
+    };
+    let author = issue
+        .user
+        .as_ref()
+        .map(|u| format!(", by {}", u.login))
+        .unwrap_or_default();
+    format!(
+        "{} #{}: {} ({}{}) [{}]",
+        kind, issue.number, issue.title, issue.state, author, url
+    )
+}
+
+fn is_github_url(url: &Url) -> bool {
+    matches!(
+        url.host(),
+        Some(url::Host::Domain("github.com")) | Some(url::Host::Domain("www.github.com"))
+    )
+}
+
+#[derive(PartialEq, Eq, Debug)]
+enum GithubId<'url> {
+    /// owner and name
+    Repo(&'url str, &'url str),
+    /// owner, name and number, for both issues and pull requests
+    Issue(&'url str, &'url str, u64),
+}
+
+fn extract_github_id(url: &Url) -> Option<GithubId<'_>> {
+    let mut segments = url.path_segments()?.filter(|s| !s.is_empty());
+    let owner = segments.next()?;
+    if RESERVED_SEGMENTS.contains(&owner) {
+        return None;
+    }
+    let repo = segments.next()?;
+    match segments.next() {
+        None => Some(GithubId::Repo(owner, repo)),
+        Some("issues") | Some("pull") => {
+            let number = segments.next()?.parse().ok()?;
+            Some(GithubId::Issue(owner, repo, number))
+        }
+        // a file or a commit, better described by the page title
+        Some(_) => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_is_github_url() {
+        assert!(is_github_url(
+            &Url::parse("https://github.com/CoucouInc/rustygolem").unwrap()
+        ));
+        assert!(is_github_url(
+            &Url::parse("https://www.github.com/CoucouInc").unwrap()
+        ));
+        assert!(!is_github_url(
+            &Url::parse("https://gist.github.com/someone").unwrap()
+        ));
+        assert!(!is_github_url(
+            &Url::parse("https://www.youtube.com/watch?v=0F5GQAnj0lo").unwrap()
+        ));
+    }
+
+    #[test]
+    fn test_extract_github_id() {
+        assert_eq!(
+            extract_github_id(&Url::parse("https://github.com/CoucouInc/rustygolem").unwrap()),
+            Some(GithubId::Repo("CoucouInc", "rustygolem"))
+        );
+        assert_eq!(
+            extract_github_id(&Url::parse("https://github.com/CoucouInc/rustygolem/").unwrap()),
+            Some(GithubId::Repo("CoucouInc", "rustygolem"))
+        );
+        assert_eq!(
+            extract_github_id(
+                &Url::parse("https://github.com/CoucouInc/rustygolem/issues/12").unwrap()
+            ),
+            Some(GithubId::Issue("CoucouInc", "rustygolem", 12))
+        );
+        assert_eq!(
+            extract_github_id(
+                &Url::parse("https://github.com/CoucouInc/rustygolem/pull/34/files").unwrap()
+            ),
+            Some(GithubId::Issue("CoucouInc", "rustygolem", 34))
+        );
+        assert_eq!(
+            extract_github_id(&Url::parse("https://github.com/CoucouInc").unwrap()),
+            None
+        );
+        assert_eq!(
+            extract_github_id(&Url::parse("https://github.com/settings/profile").unwrap()),
+            None
+        );
+        assert_eq!(
+            extract_github_id(
+                &Url::parse("https://github.com/CoucouInc/rustygolem/issues").unwrap()
+            ),
+            None
+        );
+        assert_eq!(
+            extract_github_id(
+                &Url::parse("https://github.com/CoucouInc/rustygolem/blob/master/README.md")
+                    .unwrap()
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn test_format() {
+        let url = Url::parse("https://github.com/CoucouInc/rustygolem").unwrap();
+        let repo = Repo {
+            full_name: "CoucouInc/rustygolem".to_string(),
+            description: Some("coucou".to_string()),
+            stargazers_count: 3,
+        };
+        assert_eq!(
+            format_repo(&repo, &url),
+            "CoucouInc/rustygolem: coucou (★ 3) [https://github.com/CoucouInc/rustygolem]"
+        );
+        let repo = Repo {
+            description: None,
+            ..repo
+        };
+        assert_eq!(
+            format_repo(&repo, &url),
+            "CoucouInc/rustygolem (★ 3) [https://github.com/CoucouInc/rustygolem]"
+        );
+
+        let url = Url::parse("https://github.com/CoucouInc/rustygolem/pull/34").unwrap();
+        let pr = Issue {
+            number: 34,
+            title: "Add a thing".to_string(),
+            state: "open".to_string(),
+            user: Some(User {
+                login: "charlie".to_string(),
+            }),
+            pull_request: Some(IgnoredAny),
+        };
+        assert_eq!(
+            format_issue(&pr, &url),
+            "PR #34: Add a thing (open, by charlie) [https://github.com/CoucouInc/rustygolem/pull/34]"
+        );
+        let issue = Issue {
+            pull_request: None,
+            user: None,
+            state: "closed".to_string(),
+            ..pr
+        };
+        assert_eq!(
+            format_issue(&issue, &url),
+            "Issue #34: Add a thing (closed) [https://github.com/CoucouInc/rustygolem/pull/34]"
+        );
+    }
+}
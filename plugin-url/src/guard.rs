@@ -0,0 +1,197 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::Duration;
+
+use url::Url;
+
+/// Redirections followed before giving up on a url
+pub(crate) const DEFAULT_MAX_REDIRECTS: usize = 5;
+
+/// GET the url, following the redirections by hand so that each of them
+/// is checked like the url itself: a public url could redirect to the
+/// local network. At most `max_redirects` of them are followed, the
+/// response url is the one at the end of the chain.
+/// The error is the reason to give instead of a title.
+pub(crate) async fn get(
+    url: &Url,
+    max_redirects: usize,
+) -> std::result::Result<reqwest::Response, String> {
+    let mut visited = vec![];
+    let mut url = url.clone();
+    loop {
+        let resp = pinned_client(&url)
+            .await?
+            .get(url.clone())
+            .timeout(Duration::from_secs(10))
+            .send()
+            .await
+            .map_err(|err| format!("Problème avec l'url {}: {}", url, err))?;
+
+        let next = if resp.status().is_redirection() {
+            resp.headers()
+                .get(reqwest::header::LOCATION)
+                .and_then(|location| location.to_str().ok())
+                .and_then(|location| url.join(location).ok())
+        } else {
+            None
+        };
+        let next = match next {
+            Some(next) => next,
+            None => return Ok(resp),
+        };
+
+        visited.push(url);
+        if visited.contains(&next) {
+            return Err(format!("Redirections en boucle pour {}", visited[0]));
+        }
+        if visited.len() > max_redirects {
+            return Err(format!(
+                "Trop de redirections (plus de {}) pour {}",
+                max_redirects, visited[0]
+            ));
+        }
+        url = next;
+    }
+}
+
+/// A client for the url, connecting only to the addresses checked by
+/// `check_target`. It doesn't resolve the name again, otherwise a dns server
+/// could answer with a public address for the check, then with a private
+/// one for the request (dns rebinding). It doesn't follow the redirections
+/// either, `get` checks them first.
+async fn pinned_client(url: &Url) -> std::result::Result<reqwest::Client, String> {
+    let addrs = check_target(url).await?;
+    let mut builder = reqwest::Client::builder()
+        .user_agent(plugin_core::USER_AGENT)
+        .redirect(reqwest::redirect::Policy::none());
+    if let Some(url::Host::Domain(domain)) = url.host() {
+        builder = builder.resolve_to_addrs(domain, &addrs);
+    }
+    builder
+        .build()
+        .map_err(|err| format!("Problème avec l'url {}: {}", url, err))
+}
+
+/// Refuses urls pointing to the local network, either directly or through
+/// their dns name. Returns the addresses of the url, all of them public.
+async fn check_target(url: &Url) -> std::result::Result<Vec<SocketAddr>, String> {
+    let refusal = || format!("Désolé, je ne vais pas voir {url}, c'est une adresse interne");
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(format!(
+            "Je ne regarde que les urls http et https, pas {url}"
+        ));
+    }
+    let port = url.port_or_known_default().unwrap_or(80);
+    let addrs = match url.host() {
+        Some(url::Host::Ipv4(ip)) => vec![SocketAddr::from((ip, port))],
+        Some(url::Host::Ipv6(ip)) => vec![SocketAddr::from((ip, port))],
+        Some(url::Host::Domain(domain)) => tokio::net::lookup_host((domain, port))
+            .await
+            .map_err(|err| format!("Problème avec l'url {}: {}", url, err))?
+            .collect(),
+        None => return Err(refusal()),
+    };
+    if addrs.iter().any(|addr| is_private_ip(addr.ip())) {
+        log::warn!("Refusing to fetch {url}, it points to a private address");
+        Err(refusal())
+    } else {
+        Ok(addrs)
+    }
+}
+
+/// Loopback, private, link-local (including the cloud metadata services
+/// on 169.254.169.254), and the other addresses which aren't on the internet
+fn is_private_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_private_ipv4(ip),
+        IpAddr::V6(ip) => is_private_ipv6(ip),
+    }
+}
+
+fn is_private_ipv4(ip: Ipv4Addr) -> bool {
+    let [a, b, ..] = ip.octets();
+    ip.is_private()
+        || ip.is_loopback()
+        || ip.is_link_local()
+        || ip.is_unspecified()
+        || ip.is_broadcast()
+        // "this network", 0.0.0.0/8
+        || a == 0
+        // carrier grade nat, 100.64.0.0/10
+        || (a == 100 && (b & 0xc0) == 64)
+}
+
+fn is_private_ipv6(ip: Ipv6Addr) -> bool {
+    let first_segment = ip.segments()[0];
+    ip.is_loopback()
+        || ip.is_unspecified()
+        // unique local, fc00::/7
+        || (first_segment & 0xfe00) == 0xfc00
+        // link local, fe80::/10
+        || (first_segment & 0xffc0) == 0xfe80
+        || ip.to_ipv4_mapped().map_or(false, is_private_ipv4)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn private(ip: &str) -> bool {
+        is_private_ip(ip.parse().unwrap())
+    }
+
+    #[test]
+    fn test_private_ip() {
+        for ip in [
+            "127.0.0.1",
+            "10.0.0.3",
+            "172.16.4.2",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "255.255.255.255",
+            "::1",
+            "::",
+            "fd00::1",
+            "fe80::1",
+            "::ffff:127.0.0.1",
+        ] {
+            assert!(private(ip), "{ip} is private");
+        }
+        for ip in [
+            "93.184.216.34",
+            "172.32.0.1",
+            "100.128.0.1",
+            "2606:4700::1111",
+        ] {
+            assert!(!private(ip), "{ip} is public");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_check_target() {
+        let check = |url: &str| {
+            let url = Url::parse(url).unwrap();
+            async move { check_target(&url).await }
+        };
+        assert_eq!(
+            check("http://169.254.169.254/latest/meta-data").await,
+            Err(
+                "Désolé, je ne vais pas voir http://169.254.169.254/latest/meta-data, c'est une adresse interne"
+                    .to_string()
+            )
+        );
+        assert!(check("http://192.168.1.1:8080/admin").await.is_err());
+        assert!(check("http://[::1]/").await.is_err());
+        assert!(
+            check("http://localhost:9090/metrics").await.is_err(),
+            "resolved"
+        );
+        assert!(check("ftp://93.184.216.34/").await.is_err());
+        assert_eq!(
+            check("http://93.184.216.34/").await,
+            Ok(vec!["93.184.216.34:80".parse().unwrap()])
+        );
+    }
+}
@@ -0,0 +1,250 @@
+use async_trait::async_trait;
+use plugin_core::Result;
+use url::Url;
+
+use crate::media::{describe_media, humanize_size};
+use crate::{guard, read_title, UrlTitle};
+
+/// Describes the urls of a given site
+#[async_trait]
+pub(crate) trait UrlHandler: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Whether this handler knows how to describe the url
+    fn matches(&self, url: &Url) -> bool;
+
+    async fn describe(&self, url: &Url) -> Result<UrlTitle>;
+}
+
+/// The site specific handlers, tried in registration order,
+/// and the handler used when none of them matches.
+pub(crate) struct HandlerRegistry {
+    handlers: Vec<Box<dyn UrlHandler>>,
+    fallback: Box<dyn UrlHandler>,
+}
+
+impl HandlerRegistry {
+    pub(crate) fn new(fallback: impl UrlHandler + 'static) -> Self {
+        HandlerRegistry {
+            handlers: Vec::new(),
+            fallback: Box::new(fallback),
+        }
+    }
+
+    pub(crate) fn register(&mut self, handler: impl UrlHandler + 'static) {
+        self.handlers.push(Box::new(handler));
+    }
+
+    pub(crate) fn select(&self, url: &Url) -> &dyn UrlHandler {
+        self.handlers
+            .iter()
+            .find(|h| h.matches(url))
+            .map_or(self.fallback.as_ref(), |h| h.as_ref())
+    }
+}
+
+/// Fetch the page and look for its <title>, refusing the urls
+/// of the local network
+pub(crate) struct TitleSniffer {
+    pub(crate) max_title_length: usize,
+    /// in bytes, pages announcing a bigger Content-Length aren't read
+    pub(crate) max_page_size: u64,
+    pub(crate) max_redirects: usize,
+}
+
+#[async_trait]
+impl UrlHandler for TitleSniffer {
+    fn name(&self) -> &'static str {
+        "title"
+    }
+
+    fn matches(&self, _url: &Url) -> bool {
+        true
+    }
+
+    async fn describe(&self, url: &Url) -> Result<UrlTitle> {
+        log::info!("Querying url {}", url);
+        let resp = match guard::get(url, self.max_redirects).await {
+            Ok(r) => r,
+            Err(reason) => return Ok(UrlTitle::Missing(reason)),
+        };
+        let redirection = redirection_note(url, resp.url());
+
+        let status_code = resp.status();
+        if status_code != reqwest::StatusCode::OK {
+            return Ok(UrlTitle::Missing(format!(
+                "Oops, wrong status code, got {}",
+                status_code
+            )));
+        }
+
+        match resp
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|h| h.to_str().ok())
+        {
+            Some(ct) if ct.contains("text") || ct.contains("html") => (),
+            Some(ct) => match ct.parse::<mime::Mime>() {
+                Ok(mime) => {
+                    let title = describe_media(resp, url, &mime).await?;
+                    return Ok(with_note(title, redirection));
+                }
+                Err(_) => {
+                    return Ok(UrlTitle::Missing(format!(
+                        "Cannot extract title from content type {ct} for {url}"
+                    )))
+                }
+            },
+            _ => {
+                return Ok(UrlTitle::Missing(format!(
+                    "No valid content type found for {url}"
+                )))
+            }
+        };
+
+        if let Some(too_large) = check_content_length(resp.content_length(), self.max_page_size) {
+            return Ok(UrlTitle::Missing(format!("{too_large} for {url}")));
+        }
+
+        // To avoid someone pointing the bot at a gigantic file, filling up memory or disk
+        let title = read_title(resp, url.as_str(), self.max_title_length).await?;
+        Ok(with_note(title, redirection))
+    }
+}
+
+/// Where the url led to, when it was redirected elsewhere
+fn redirection_note(requested: &Url, landed: &Url) -> Option<String> {
+    if requested == landed {
+        None
+    } else {
+        Some(format!("(→ {landed})"))
+    }
+}
+
+fn with_note(title: UrlTitle, note: Option<String>) -> UrlTitle {
+    match (title, note) {
+        (UrlTitle::Found(title), Some(note)) => UrlTitle::Found(format!("{title} {note}")),
+        (title, _) => title,
+    }
+}
+
+/// The reason to skip a page announcing more than `max_page_size` bytes
+fn check_content_length(content_length: Option<u64>, max_page_size: u64) -> Option<String> {
+    match content_length {
+        Some(length) if length > max_page_size => {
+            Some(format!("resource too large ({})", humanize_size(length)))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cache::TtlCache;
+    use crate::github::GithubHandler;
+    use crate::mastodon::MastodonHandler;
+    use crate::template::Template;
+    use crate::twitter::TwitterHandler;
+    use crate::youtube::YoutubeHandler;
+    use pretty_assertions::assert_eq;
+    use std::time::Duration;
+
+    fn selected(registry: &HandlerRegistry, url: &str) -> &'static str {
+        registry.select(&Url::parse(url).unwrap()).name()
+    }
+
+    #[test]
+    fn test_handler_selection() {
+        let client = reqwest::Client::new();
+        let sniffer = || TitleSniffer {
+            max_title_length: crate::DEFAULT_MAX_TITLE_LENGTH,
+            max_page_size: 1024,
+            max_redirects: guard::DEFAULT_MAX_REDIRECTS,
+        };
+        let mut registry = HandlerRegistry::new(sniffer());
+        assert_eq!(selected(&registry, "https://github.com/CoucouInc"), "title");
+
+        registry.register(YoutubeHandler::new(
+            client.clone(),
+            "key".to_string(),
+            Template::new(crate::youtube::DEFAULT_VIDEO_TEMPLATE),
+            TtlCache::new(0, Duration::ZERO),
+        ));
+        registry.register(GithubHandler::new(client.clone(), None, sniffer()));
+        registry.register(TwitterHandler::new(sniffer()));
+        registry.register(MastodonHandler::new(sniffer()));
+
+        assert_eq!(
+            selected(&registry, "https://www.youtube.com/watch?v=0F5GQAnj0lo"),
+            "youtube"
+        );
+        assert_eq!(
+            selected(&registry, "https://youtu.be/haLBM94SENg"),
+            "youtube"
+        );
+        assert_eq!(
+            selected(&registry, "https://github.com/CoucouInc/rustygolem"),
+            "github"
+        );
+        assert_eq!(
+            selected(&registry, "https://gist.github.com/someone"),
+            "title",
+            "subdomains are different sites"
+        );
+        assert_eq!(
+            selected(
+                &registry,
+                "https://x.com/someone/status/1594351283725926400"
+            ),
+            "twitter"
+        );
+        assert_eq!(
+            selected(&registry, "https://mamot.fr/@someone/109372881231283"),
+            "mastodon"
+        );
+        assert_eq!(selected(&registry, "http://127.0.0.1:8080/"), "title");
+    }
+
+    #[test]
+    fn test_redirection_note() {
+        let url = |u: &str| Url::parse(u).unwrap();
+        assert_eq!(
+            redirection_note(&url("https://example.com/a"), &url("https://example.com/a")),
+            None
+        );
+        assert_eq!(
+            redirection_note(
+                &url("http://example.com/a"),
+                &url("https://www.example.com/b")
+            ),
+            Some("(→ https://www.example.com/b)".to_string())
+        );
+        assert_eq!(
+            with_note(
+                UrlTitle::Found("Example [http://example.com/a]".to_string()),
+                Some("(→ https://www.example.com/b)".to_string())
+            ),
+            UrlTitle::Found(
+                "Example [http://example.com/a] (→ https://www.example.com/b)".to_string()
+            )
+        );
+        assert_eq!(
+            with_note(
+                UrlTitle::Missing("No title found".to_string()),
+                Some("(→ https://www.example.com/b)".to_string())
+            ),
+            UrlTitle::Missing("No title found".to_string())
+        );
+    }
+
+    #[test]
+    fn test_check_content_length() {
+        assert_eq!(check_content_length(None, 1024), None, "missing header");
+        assert_eq!(check_content_length(Some(1024), 1024), None);
+        assert_eq!(
+            check_content_length(Some(52 * 1024 * 1024 + 400 * 1024), 1024),
+            Some("resource too large (52.4 MB)".to_string())
+        );
+    }
+}
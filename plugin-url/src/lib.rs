@@ -1,14 +1,14 @@
+#[macro_use]
+extern crate diesel;
+
 use encoding_rs::{CoderResult, Encoding};
-use google_youtube3::api::{PlaylistListResponse, SearchListResponse, VideoListResponse};
+use futures::stream::{self, StreamExt};
+use google_youtube3::api::SearchListResponse;
 use mime::Mime;
 use reqwest::header::HeaderValue;
-use serde::{de::DeserializeOwned, Deserialize};
-use std::{
-    borrow::Cow,
-    collections::{HashMap, VecDeque},
-    sync::Arc,
-    time::Duration,
-};
+use serde::Deserialize;
+use std::{sync::Arc, time::Duration};
+use tokio::sync::mpsc;
 
 use async_trait::async_trait;
 use irc::proto::{Command, Message};
@@ -22,60 +22,297 @@ use nom::{
     AsChar, Finish, IResult, InputTakeAtPosition,
 };
 use parking_lot::Mutex;
-use plugin_core::{Error, Initialised, Plugin, Result};
+use plugin_core::config::ConfigSection;
+use plugin_core::utils::network::{channel_key, split_channel_key};
+use plugin_core::utils::nicks::OwnNicks;
+use plugin_core::{CommandHelp, Error, Initialised, Plugin, Result};
 use url::Url;
 
+mod cache;
+mod db;
+mod github;
+mod guard;
+mod handlers;
+mod mastodon;
+mod media;
 mod parsing_utils;
+mod schema;
+mod seen_urls;
+mod shorteners;
+mod template;
+mod twitter;
+mod youtube;
+mod youtube_live;
+
+pub use db::run_migrations;
+
+use cache::TtlCache;
+use github::GithubHandler;
+use handlers::{HandlerRegistry, TitleSniffer};
+use mastodon::MastodonHandler;
+use seen_urls::SeenUrls;
+use shorteners::ShortenerHandler;
+use template::Template;
+use twitter::TwitterHandler;
+use youtube::{format_search_result, incomplete_yt_response, YoutubeHandler};
+use youtube_live::{LiveSpec, LiveWatcher};
+
+/// Only the first urls of a message are stored, so that a single message
+/// can't evict all the history.
+const DEFAULT_MAX_URLS_PER_MESSAGE: usize = 5;
+
+/// Longer titles are truncated
+pub const DEFAULT_MAX_TITLE_LENGTH: usize = 100;
+
+/// Youtube api responses kept to save the quota
+const DEFAULT_YOUTUBE_CACHE_SIZE: usize = 500;
+const DEFAULT_YOUTUBE_CACHE_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// Pages announcing a bigger Content-Length aren't read at all
+const DEFAULT_MAX_PAGE_SIZE_MB: u64 = 10;
+
+/// Urls listed by λurls are truncated to this many chars
+const MAX_LISTED_URL_LENGTH: usize = 50;
+
+/// Titles are announced for at most this many urls of a message
+const DEFAULT_AUTO_TITLES_PER_MESSAGE: usize = 3;
+
+/// How many urls of a message are fetched at the same time
+const AUTO_TITLES_CONCURRENCY: usize = 3;
+
+/// Fetching a single url, including reading the page, can't take longer than this
+const FETCH_TIMEOUT: Duration = Duration::from_secs(10);
 
 #[derive(Deserialize)]
-struct YtConfig {
+struct UrlConfig {
     youtube_api_key: Option<String>,
+    max_urls_per_message: Option<usize>,
+    /// don't reply when no title can be found for a url
+    quiet_url_errors: Option<bool>,
+    /// titles are announced automatically for the first urls of each message,
+    /// 0 disables the announcements
+    auto_titles_per_message: Option<usize>,
+    /// titles are only announced automatically in these channels,
+    /// None is everywhere
+    auto_title_channels: Option<Vec<String>>,
+    /// and never in these ones
+    no_auto_title_channels: Option<Vec<String>>,
+    /// in characters, longer titles are truncated
+    max_title_length: Option<usize>,
+    /// in MB, pages announcing a bigger size are skipped
+    max_page_size_mb: Option<u64>,
+    /// redirections followed when fetching a page
+    max_redirects: Option<usize>,
+    /// how youtube videos are described, with placeholders like {title}
+    youtube_template: Option<String>,
+    /// youtube api responses kept in memory, 0 disables the cache
+    youtube_cache_size: Option<usize>,
+    youtube_cache_ttl_secs: Option<u64>,
+    /// raises the rate limit of the github api
+    github_token: Option<String>,
+    /// urls of these hosts are described by the page they redirect to
+    shortener_hosts: Option<Vec<String>>,
+    /// youtube channels whose lives are announced
+    youtube_live_streams: Option<Vec<LiveSpec>>,
+    youtube_live_interval_secs: Option<u64>,
+}
+
+impl ConfigSection for UrlConfig {
+    const SECTION: Option<&'static str> = None;
+    const SCHEMA: &'static str =
+        "{ youtube_api_key : Optional Text, max_urls_per_message : Optional Natural, quiet_url_errors : Optional Bool, auto_titles_per_message : Optional Natural, auto_title_channels : Optional (List Text), no_auto_title_channels : Optional (List Text), max_title_length : Optional Natural, max_page_size_mb : Optional Natural, max_redirects : Optional Natural, youtube_template : Optional Text, youtube_cache_size : Optional Natural, youtube_cache_ttl_secs : Optional Natural, github_token : Optional Text, shortener_hosts : Optional (List Text), youtube_live_streams : Optional (List { channel_id : Text, name : Text, irc_channels : List Text }), youtube_live_interval_secs : Optional Natural }";
 }
 
 pub struct UrlPlugin {
-    seen_urls: Arc<Mutex<HashMap<String, VecDeque<Url>>>>,
+    seen_urls: Arc<Mutex<SeenUrls>>,
     client: reqwest::Client,
     yt_api_key: Option<String>,
+    handlers: HandlerRegistry,
+    /// None without youtube api key or watched channels
+    live_watcher: Option<LiveWatcher>,
+    /// to know when the bot leaves a channel
+    own_nicks: OwnNicks,
+    /// urls posted by these users (typically other bots) are stored,
+    /// but their commands are ignored
+    blacklisted_users: Vec<String>,
+    max_urls_per_message: usize,
+    quiet_url_errors: bool,
+    auto_titles_per_message: usize,
+    /// None when the titles are announced in all the channels
+    auto_title_channels: Option<Vec<String>>,
+    no_auto_title_channels: Vec<String>,
+    /// keep the urls in the db, so they survive restarts
+    persist: bool,
 }
 
 impl UrlPlugin {
-    fn new(config_path: &str) -> Result<Self> {
-        // let path = "golem_config.dhall";
-        let yt_config: YtConfig =
-            serde_dhall::from_file(config_path)
-                .parse()
-                .map_err(|err| Error::Wrapped {
-                    source: Box::new(err),
-                    ctx: format!("Failed to read config at {config_path}"),
-                })?;
-        if yt_config.youtube_api_key.is_some() {
+    fn new(config: &plugin_core::Config) -> Result<Self> {
+        let url_config: UrlConfig = plugin_core::config::load(&config.config_path)?;
+        if url_config.youtube_api_key.is_some() {
             log::info!("Url plugin initialized with youtube api credentials.");
         } else {
             log::warn!("Url plugin is missing youtube api key.");
         }
 
+        let client = config.http_client.clone();
+        let max_title_length = url_config
+            .max_title_length
+            .unwrap_or(DEFAULT_MAX_TITLE_LENGTH);
+        let max_page_size = url_config
+            .max_page_size_mb
+            .unwrap_or(DEFAULT_MAX_PAGE_SIZE_MB)
+            * 1024
+            * 1024;
+        let max_redirects = url_config
+            .max_redirects
+            .unwrap_or(guard::DEFAULT_MAX_REDIRECTS);
+        let sniffer = || TitleSniffer {
+            max_title_length,
+            max_page_size,
+            max_redirects,
+        };
+        let mut handlers = HandlerRegistry::new(sniffer());
+        let video_template = Template::new(
+            url_config
+                .youtube_template
+                .as_deref()
+                .unwrap_or(youtube::DEFAULT_VIDEO_TEMPLATE),
+        );
+        let unknown = video_template.unknown_placeholders(&youtube::VIDEO_PLACEHOLDERS);
+        if !unknown.is_empty() {
+            log::warn!(
+                "Unknown placeholders in youtube_template, they are left empty: {}. Known ones are {}",
+                unknown.join(", "),
+                youtube::VIDEO_PLACEHOLDERS.join(", ")
+            );
+        }
+        if let Some(key) = &url_config.youtube_api_key {
+            let cache = TtlCache::new(
+                url_config
+                    .youtube_cache_size
+                    .unwrap_or(DEFAULT_YOUTUBE_CACHE_SIZE),
+                url_config
+                    .youtube_cache_ttl_secs
+                    .map(Duration::from_secs)
+                    .unwrap_or(DEFAULT_YOUTUBE_CACHE_TTL),
+            );
+            handlers.register(YoutubeHandler::new(
+                client.clone(),
+                key.clone(),
+                video_template,
+                cache,
+            ));
+        }
+        handlers.register(GithubHandler::new(
+            client.clone(),
+            url_config.github_token,
+            sniffer(),
+        ));
+        handlers.register(ShortenerHandler::new(
+            url_config.shortener_hosts.unwrap_or_else(|| {
+                shorteners::DEFAULT_SHORTENERS
+                    .iter()
+                    .map(|h| h.to_string())
+                    .collect()
+            }),
+            sniffer(),
+        ));
+        handlers.register(TwitterHandler::new(sniffer()));
+        // matches statuses on any host, tried after the known sites
+        handlers.register(MastodonHandler::new(sniffer()));
+        let live_streams = url_config.youtube_live_streams.unwrap_or_default();
+        let live_watcher = match &url_config.youtube_api_key {
+            _ if live_streams.is_empty() => None,
+            None => {
+                log::warn!("Youtube lives can't be watched without youtube api key");
+                None
+            }
+            Some(key) => Some(LiveWatcher::new(
+                client.clone(),
+                key.clone(),
+                live_streams,
+                url_config
+                    .youtube_live_interval_secs
+                    .map(Duration::from_secs)
+                    .unwrap_or(youtube_live::DEFAULT_LIVE_INTERVAL),
+            )),
+        };
         Ok(UrlPlugin {
             seen_urls: Default::default(),
-            client: reqwest::Client::new(),
-            yt_api_key: yt_config.youtube_api_key,
+            client,
+            yt_api_key: url_config.youtube_api_key,
+            handlers,
+            live_watcher,
+            max_urls_per_message: url_config
+                .max_urls_per_message
+                .unwrap_or(DEFAULT_MAX_URLS_PER_MESSAGE),
+            quiet_url_errors: url_config.quiet_url_errors.unwrap_or(false),
+            auto_titles_per_message: url_config
+                .auto_titles_per_message
+                .unwrap_or(DEFAULT_AUTO_TITLES_PER_MESSAGE),
+            auto_title_channels: url_config.auto_title_channels,
+            no_auto_title_channels: url_config.no_auto_title_channels.unwrap_or_default(),
+            own_nicks: config.own_nicks.clone(),
+            blacklisted_users: config.blacklisted_users.clone(),
+            persist: true,
         })
     }
 
-    fn add_urls(&self, channel: &str, urls: Vec<Url>) {
-        let mut seen_urls = self.seen_urls.lock();
-        let e = seen_urls.entry(channel.to_string()).or_default();
-        for url in urls {
-            log::info!("Adding {url} to chan {channel}");
-            e.push_back(url);
-            if e.len() > 10 {
-                e.pop_front();
-            }
+    /// Returns the urls actually stored
+    fn add_urls(&self, channel: &str, mut urls: Vec<Url>) -> Vec<Url> {
+        urls.truncate(self.max_urls_per_message);
+        self.seen_urls.lock().add(channel, urls.clone());
+        urls
+    }
+
+    /// Remember the urls of a message, in memory and in the db
+    async fn store_urls(&self, channel: &str, urls: Vec<Url>) {
+        let stored = self.add_urls(channel, urls);
+        if !stored.is_empty() {
+            let channel = channel.to_string();
+            self.persist(move |conn| seen_urls::save(conn, &channel, &stored))
+                .await;
+        }
+    }
+
+    /// Run a db write in the background. Failures are only logged since the
+    /// urls are still available in memory.
+    async fn persist<F>(&self, f: F)
+    where
+        F: FnOnce(&diesel::SqliteConnection) -> anyhow::Result<()> + Send + 'static,
+    {
+        if !self.persist {
+            return;
+        }
+        if let Err(err) = db::with_connection(f).await {
+            log::error!("Cannot persist urls: {err:?}");
         }
     }
 
+    fn is_blacklisted(&self, msg: &Message) -> bool {
+        msg.source_nickname()
+            .map(|nick| self.blacklisted_users.iter().any(|u| u == nick))
+            .unwrap_or(false)
+    }
+
     async fn in_msg(&self, msg: &Message) -> Result<Option<Message>> {
+        if let Some(channel) = seen_urls::left_channel(msg, &self.own_nicks) {
+            log::info!("Left {channel}, forgetting its urls");
+            let channel = channel_key(msg, channel);
+            self.seen_urls.lock().forget(&channel);
+            self.persist(move |conn| seen_urls::delete(conn, &channel))
+                .await;
+            return Ok(None);
+        }
+
         if let Command::PRIVMSG(source, privmsg) = &msg.command {
-            self.add_urls(source, parse_urls(privmsg)?);
+            let urls = parse_urls(privmsg)?;
+            self.store_urls(&channel_key(msg, source), urls.clone())
+                .await;
+
+            if self.is_blacklisted(msg) {
+                return Ok(None);
+            }
 
             if let Some(cmd) = parse_command(privmsg) {
                 match cmd {
@@ -84,12 +321,41 @@ impl UrlPlugin {
                             None => return Ok(None),
                             Some(target) => target,
                         };
-                        let message = self.get_url(channel, mb_idx.unwrap_or(0)).await?;
+                        let key = channel_key(msg, channel);
+                        let message = match self.get_url(&key, mb_idx.unwrap_or(0)).await? {
+                            Some(m) => m,
+                            None => return Ok(None),
+                        };
+
+                        let target = mb_target.map(|t| format!("{t}: ")).unwrap_or_default();
+                        let msg = format!("{target}{message}");
+                        return Ok(Some(Command::PRIVMSG(channel.to_string(), msg).into()));
+                    }
+                    Cmd::UrlFromHost(host, mb_target) => {
+                        let channel = match msg.response_target() {
+                            None => return Ok(None),
+                            Some(target) => target,
+                        };
+                        let key = channel_key(msg, channel);
+                        let message = match self.get_url_from_host(&key, host).await? {
+                            Some(m) => m,
+                            None => return Ok(None),
+                        };
 
                         let target = mb_target.map(|t| format!("{t}: ")).unwrap_or_default();
                         let msg = format!("{target}{message}");
                         return Ok(Some(Command::PRIVMSG(channel.to_string(), msg).into()));
                     }
+                    Cmd::Urls(mb_target) => {
+                        let channel = match msg.response_target() {
+                            None => return Ok(None),
+                            Some(target) => target,
+                        };
+                        let target = mb_target.map(|t| format!("{t}: ")).unwrap_or_default();
+                        let list = self.list_urls(&channel_key(msg, channel));
+                        let msg = format!("{target}{list}");
+                        return Ok(Some(Command::PRIVMSG(channel.to_string(), msg).into()));
+                    }
                     Cmd::Search(term, _mb_target) => {
                         let channel = match msg.response_target() {
                             None => return Ok(None),
@@ -101,200 +367,126 @@ impl UrlPlugin {
                     }
                 }
             }
+
+            let channel = match msg.response_target() {
+                Some(channel) if self.auto_titles_enabled(channel) => channel,
+                _ => return Ok(None),
+            };
+            if let Some(titles) = self.describe_all(urls).await {
+                return Ok(Some(Command::PRIVMSG(channel.to_string(), titles).into()));
+            }
         }
         Ok(None)
     }
 
-    async fn get_url(&self, channel: &str, idx: usize) -> Result<String> {
+    /// The explicit λurl commands work everywhere
+    fn auto_titles_enabled(&self, channel: &str) -> bool {
+        let listed = |channels: &[String]| channels.iter().any(|c| c.eq_ignore_ascii_case(channel));
+        let allowed = self
+            .auto_title_channels
+            .as_ref()
+            .map_or(true, |channels| listed(channels));
+        allowed && !listed(&self.no_auto_title_channels)
+    }
+
+    /// Titles of the first urls, fetched concurrently.
+    /// Urls without a title are skipped, None if no title was found at all.
+    async fn describe_all(&self, mut urls: Vec<Url>) -> Option<String> {
+        urls.truncate(self.auto_titles_per_message);
+        let titles = stream::iter(urls)
+            .map(|url| self.describe_with_timeout(url))
+            .buffered(AUTO_TITLES_CONCURRENCY)
+            .filter_map(|title| async move { title })
+            .collect::<Vec<_>>()
+            .await;
+
+        if titles.is_empty() {
+            None
+        } else {
+            Some(titles.join(" | "))
+        }
+    }
+
+    async fn describe_with_timeout(&self, url: Url) -> Option<String> {
+        let handler = self.handlers.select(&url);
+        match tokio::time::timeout(FETCH_TIMEOUT, handler.describe(&url)).await {
+            Ok(Ok(UrlTitle::Found(title))) => Some(title),
+            Ok(Ok(UrlTitle::Missing(reason))) => {
+                log::debug!("No title for {url}: {reason}");
+                None
+            }
+            Ok(Err(err)) => {
+                log::warn!("Cannot describe {url}: {err:?}");
+                None
+            }
+            Err(_) => {
+                log::info!("Timeout while describing {url}");
+                None
+            }
+        }
+    }
+
+    /// None when the url has no title and errors are not reported
+    async fn get_url(&self, channel: &str, idx: usize) -> Result<Option<String>> {
         let mb_url = {
             let urls_guard = self.seen_urls.lock();
             urls_guard
-                .get(channel)
-                .and_then(|urls| urls.len().checked_sub(1 + idx).and_then(|i| urls.get(i)))
+                .get(channel, idx)
                 // clone the url so that we can release the lock.
                 // This avoid holding it across await points when fetching data for the url
                 .cloned()
         };
         let url = match mb_url {
             Some(u) => u,
-            None => return Ok(format!("No stored url found at index {idx}")),
+            None => return Ok(Some(format!("No stored url found at index {idx}"))),
         };
-
-        match &self.yt_api_key {
-            Some(yt_key) if is_yt_url(&url) => self.get_yt_url(&url, yt_key).await,
-            _ => self.get_regular_url(&url).await,
-        }
+        self.describe_url(url).await
     }
 
-    async fn get_regular_url(&self, url: &Url) -> Result<String> {
-        log::info!("Querying url {}", url);
-        let resp = self
-            .client
-            .get(url.clone())
-            .timeout(Duration::from_secs(10))
-            .send()
-            .await;
-
-        let resp = match resp {
-            Ok(r) => r,
-            Err(err) => return Ok(format!("Problème avec l'url {}: {}", url, err)),
-        };
-
-        let status_code = resp.status();
-        if status_code != reqwest::StatusCode::OK {
-            return Ok(format!("Oops, wrong status code, got {}", status_code));
+    /// Like `get_url`, for the most recent url of `host` or of its subdomains
+    async fn get_url_from_host(&self, channel: &str, host: &str) -> Result<Option<String>> {
+        let mb_url = self.seen_urls.lock().find_by_host(channel, host).cloned();
+        match mb_url {
+            Some(url) => self.describe_url(url).await,
+            None => Ok(Some(format!("No stored url found for {host}"))),
         }
-
-        match resp
-            .headers()
-            .get(reqwest::header::CONTENT_TYPE)
-            .and_then(|h| h.to_str().ok())
-        {
-            Some(ct) if ct.contains("text") || ct.contains("html") => (),
-            Some(ct) => {
-                return Ok(format!(
-                    "Cannot extract title from content type {ct} for {url}"
-                ))
-            }
-            _ => return Ok(format!("No valid content type found for {url}")),
-        };
-
-        self.sniff_title(resp).await
     }
 
-    // To avoid someone pointing the bot at a gigantic file, filling up memory or disk
-    async fn sniff_title(&self, resp: reqwest::Response) -> Result<String> {
-        sniff_title(resp).await
+    async fn describe_url(&self, url: Url) -> Result<Option<String>> {
+        let handler = self.handlers.select(&url);
+        log::debug!("Describing {url} with the {} handler", handler.name());
+        Ok(self.title_reply(handler.describe(&url).await?))
     }
 
-    async fn get_yt_url(&self, url: &Url, yt_api_key: &str) -> Result<String> {
-        let yt_id = match extract_yt_id(url) {
-            Some(x) => x,
-            None => {
-                return Ok(format!(
-                    "Ook Ook 🙈, pas possible de trouver quoi query pour {}",
-                    url
-                ))
-            }
-        };
-
-        log::debug!("fetching yt data for {yt_id:?}");
-        match yt_id {
-            YtId::Video(vid_id) => {
-                let vids: VideoListResponse =
-                    self.yt_api_call(yt_api_key, "videos", &vid_id).await?;
-                match vids.items.unwrap_or_default().first() {
-                    Some(vid) => {
-                        let snip = vid.snippet.as_ref().unwrap();
-                        let title = snip.title.as_deref().unwrap_or("");
-                        let chan = snip.channel_title.as_deref().unwrap_or("");
-                        let published_at = snip
-                            .published_at
-                            .as_deref()
-                            .map(|d| format!(" - {d}"))
-                            .unwrap_or_else(|| "".to_string());
-                        Ok(format!(
-                            "{} [{}{}] [{}]",
-                            &title, &chan, &published_at, &url
-                        ))
-                    }
-                    None => Ok(format!("Rien trouvé pour vidéo {vid_id}")),
-                }
-            }
-            YtId::Channel(chan_name) => {
-                let raw_resp = self
-                    .client
-                    .get("https://www.googleapis.com/youtube/v3/search")
-                    .query(&[("key", yt_api_key)])
-                    .query(&[("part", "snippet")])
-                    .query(&[("type", "channel")])
-                    .query(&[("q", chan_name)])
-                    .send()
-                    .await
-                    .map_err(|err| Error::Wrapped {
-                        source: Box::new(err),
-                        ctx: format!("Failed to fetch channel with id {chan_name}"),
-                    })?;
-
-                if raw_resp.status() == reqwest::StatusCode::NOT_FOUND {
-                    return Ok(format!("Pas trouvé de chan pour {chan_name}"));
-                }
-
-                if raw_resp.status() != reqwest::StatusCode::OK {
-                    return Ok(format!("Ooops, status code: {}", raw_resp.status()));
-                }
+    /// Numbered list of the stored urls, with the indices used by λurl
+    fn list_urls(&self, key: &str) -> String {
+        let seen_urls = self.seen_urls.lock();
+        let urls = seen_urls.list(key);
+        if urls.is_empty() {
+            return format!("No stored url for {}", split_channel_key(key).1);
+        }
 
-                let results: SearchListResponse =
-                    raw_resp.json().await.map_err(|err| Error::Wrapped {
-                        source: Box::new(err),
-                        ctx: format!("Cannot parse response when fetching channel {chan_name}"),
-                    })?;
-
-                match results.items.unwrap_or_default().first() {
-                    Some(search_result) => {
-                        let snip = search_result.snippet.as_ref().unwrap();
-                        let title = snip.channel_title.as_deref().unwrap_or("");
-                        let description = snip.description.as_deref().unwrap_or("");
-                        let published_at = snip
-                            .published_at
-                            .as_deref()
-                            .map(|d| format!(" - {d}"))
-                            .unwrap_or_else(|| "".to_string());
-                        if description.is_empty() {
-                            Ok(format!("Channel: {}{} [{}]", title, published_at, url))
-                        } else {
-                            Ok(format!(
-                                "Channel: {}{} ({}) [{}]",
-                                title, published_at, description, url
-                            ))
-                        }
-                    }
-                    None => Ok(format!("Pas trouvé de chan pour {chan_name}")),
+        urls.iter()
+            .enumerate()
+            .map(|(idx, url)| {
+                let url = url.as_str();
+                if url.chars().count() > MAX_LISTED_URL_LENGTH {
+                    let short = url.chars().take(MAX_LISTED_URL_LENGTH).collect::<String>();
+                    format!("{idx}: {short}[…]")
+                } else {
+                    format!("{idx}: {url}")
                 }
-            }
-            YtId::Playlist(playlist_id) => {
-                let playlists: PlaylistListResponse = self
-                    .yt_api_call(yt_api_key, "playlists", &playlist_id)
-                    .await?;
-                match playlists.items.unwrap_or_default().first() {
-                    Some(playlist) => {
-                        let snip = playlist.snippet.as_ref().unwrap();
-                        let title = snip.title.as_deref().unwrap_or("");
-                        Ok(format!("Playlist: {} [{}]", &title, &url))
-                    }
-                    None => Ok(format!("Pas de playlist trouvée pour {playlist_id}")),
-                }
-            }
-        }
+            })
+            .collect::<Vec<_>>()
+            .join(" | ")
     }
 
-    async fn yt_api_call<T, Q>(&self, yt_api_key: &str, resource: &str, resource_id: Q) -> Result<T>
-    where
-        T: DeserializeOwned,
-        Q: serde::Serialize + std::fmt::Display,
-    {
-        let mut url = Url::parse("https://www.googleapis.com/youtube/v3").unwrap();
-        url.path_segments_mut().unwrap().push(resource);
-
-        self.client
-            .get(url)
-            .query(&[("id", &resource_id)])
-            .query(&[("key", yt_api_key.to_owned())])
-            .query(&[("part", "snippet")])
-            .timeout(Duration::from_secs(10))
-            .send()
-            .await
-            .and_then(|x| x.error_for_status())
-            .map_err(|err| Error::Wrapped {
-                source: Box::new(err),
-                ctx: format!("Failed to fetch {resource} with id {resource_id}"),
-            })?
-            .json()
-            .await
-            .map_err(|err| Error::Wrapped {
-                source: Box::new(err),
-                ctx: format!("Failed to fetch {resource} with id {resource_id}"),
-            })
+    fn title_reply(&self, title: UrlTitle) -> Option<String> {
+        match title {
+            UrlTitle::Found(t) => Some(t),
+            UrlTitle::Missing(_) if self.quiet_url_errors => None,
+            UrlTitle::Missing(reason) => Some(reason),
+        }
     }
 
     async fn yt_search(&self, search_term: &str) -> Result<String> {
@@ -326,71 +518,9 @@ impl UrlPlugin {
 
         match jsonbody {
             Ok(search_resp) => match search_resp.items.as_ref().and_then(|v| v.first()) {
-                Some(search_result) => {
-                    let kind = search_result
-                        .id
-                        .as_ref()
-                        .and_then(|x| x.kind.as_ref())
-                        .unwrap();
-
-                    match &kind[..] {
-                        "youtube#channel" => {
-                            let channel_id = search_result
-                                .snippet
-                                .as_ref()
-                                .and_then(|x| x.channel_id.as_ref())
-                                .unwrap();
-                            let channel_title = search_result
-                                .snippet
-                                .as_ref()
-                                .and_then(|x| x.channel_title.as_deref())
-                                .unwrap_or("no channel found");
-                            Ok(format!("channel: [{channel_title}] https://www.youtube.com/channel/{channel_id}"))
-                        }
-                        "youtube#playlist" => {
-                            let title = search_result
-                                .snippet
-                                .as_ref()
-                                .unwrap()
-                                .title
-                                .as_ref()
-                                .unwrap();
-
-                            let playlist_id = search_result
-                                .id
-                                .as_ref()
-                                .and_then(|x| x.playlist_id.as_ref())
-                                .unwrap();
-
-                            Ok(format!("playlist: {title} https://www.youtube.com/playlist?list={playlist_id}"))
-                        }
-                        "youtube#video" => {
-                            let title = search_result
-                                .snippet
-                                .as_ref()
-                                .unwrap()
-                                .title
-                                .as_ref()
-                                .unwrap();
-
-                            let vid_id = search_result
-                                .id
-                                .as_ref()
-                                .and_then(|x| x.video_id.as_ref())
-                                .unwrap();
-
-                            let channel_title = search_result
-                                .snippet
-                                .as_ref()
-                                .and_then(|x| x.channel_title.as_deref())
-                                .unwrap_or("no channel found");
-
-                            Ok(format!("{title} [{channel_title}] https://www.youtube.com/watch?v={vid_id}"))
-                        }
-                        _ => return Ok(format!("Rien trouvé pour {search_term} /o\\")),
-                    }
-                }
-                None => return Ok(format!("Rien trouvé pour {search_term} /o\\")),
+                Some(search_result) => Ok(format_search_result(search_result, search_term)
+                    .unwrap_or_else(|| incomplete_yt_response(search_term))),
+                None => Ok(format!("Rien trouvé pour {search_term} /o\\")),
             },
             Err(err) => {
                 log::error!("Can't parse yt response for {search_term}\n{:?}", err);
@@ -406,7 +536,10 @@ impl UrlPlugin {
 #[async_trait]
 impl Plugin for UrlPlugin {
     async fn init(config: &plugin_core::Config) -> Result<Initialised> {
-        let plugin = UrlPlugin::new(&config.config_path)?;
+        let plugin = UrlPlugin::new(config)?;
+        let seen = db::with_connection(seen_urls::load).await?;
+        log::info!("Loaded stored urls for {} channels", seen.channel_count());
+        *plugin.seen_urls.lock() = seen;
         Ok(Initialised::from(plugin))
     }
 
@@ -414,10 +547,48 @@ impl Plugin for UrlPlugin {
         "url"
     }
 
+    async fn run(&self, bot_chan: mpsc::Sender<Message>) -> Result<()> {
+        match &self.live_watcher {
+            Some(watcher) => {
+                watcher.run(bot_chan).await?;
+                Err(Error::Synthetic("youtube live watcher stopped".to_string()))
+            }
+            None => Ok(()),
+        }
+    }
+
+    fn commands(&self) -> Vec<CommandHelp> {
+        vec![
+            CommandHelp::new(
+                "url",
+                "[n]",
+                "titre de la dernière url du channel, ou de la n-ième",
+            ),
+            CommandHelp::new(
+                "url",
+                "host:<domaine>",
+                "titre de la dernière url de ce site",
+            ),
+            CommandHelp::new("urls", "", "les dernières urls du channel"),
+            CommandHelp::new("yt_search", "<recherche>", "cherche une vidéo sur youtube"),
+        ]
+    }
+
     async fn in_message(&self, msg: &Message) -> Result<Option<Message>> {
         self.in_msg(msg).await
     }
 
+    /// The urls posted by the other plugins can be recalled with λurl too.
+    /// They aren't described, and the replies of this plugin never come here.
+    async fn out_message(&self, msg: &Message) -> Result<()> {
+        if let Command::PRIVMSG(target, privmsg) = &msg.command {
+            self.store_urls(&channel_key(msg, target), parse_urls(privmsg)?)
+                .await;
+        }
+        Ok(())
+    }
+
+    /// urls from other bots are still stored, but their commands are ignored in `in_msg`
     fn ignore_blacklisted_users(&self) -> bool {
         false
     }
@@ -455,7 +626,7 @@ fn parse_urls<'a>(msg: &'a str) -> Result<Vec<Url>> {
 fn parse_url(raw: &str) -> IResult<&str, Option<Url>> {
     map(
         take_while(|c: char| !SPACE_CHARS.contains(&c)),
-        |word| match Url::parse(word) {
+        |word| match Url::parse(trim_punctuation(word)) {
             Ok(u) if !u.cannot_be_a_base() && (u.scheme() == "http" || u.scheme() == "https") => {
                 Some(u)
             }
@@ -464,10 +635,30 @@ fn parse_url(raw: &str) -> IResult<&str, Option<Url>> {
     )(raw)
 }
 
+/// The punctuation right after a url, like in `live at https://…: title`,
+/// is part of the sentence. A closing parenthesis is kept when it closes
+/// one of the url, like https://en.wikipedia.org/wiki/Rust_(langage)
+fn trim_punctuation(word: &str) -> &str {
+    let mut word = word;
+    loop {
+        match word.chars().last() {
+            Some(':' | '!' | ',' | '.') => word = &word[..word.len() - 1],
+            Some(')') if word.matches(')').count() > word.matches('(').count() => {
+                word = &word[..word.len() - 1]
+            }
+            _ => return word,
+        }
+    }
+}
+
 #[derive(PartialEq, Eq, Debug)]
 enum Cmd<'msg> {
+    /// optional target nick
+    Urls(Option<&'msg str>),
     /// optional url index, optional target nick
     Url(Option<usize>, Option<&'msg str>),
+    /// host of the url, optional target nick
+    UrlFromHost(&'msg str, Option<&'msg str>),
     /// search term, optional target nick
     Search(&'msg str, Option<&'msg str>),
 }
@@ -477,6 +668,16 @@ fn parse_command(msg: &str) -> Option<Cmd<'_>> {
     let cmd = preceded(
         parsing_utils::command_prefix,
         alt((
+            map(parsing_utils::with_target(tag("urls")), |(_, mb_target)| {
+                Cmd::Urls(mb_target)
+            }),
+            map(
+                parsing_utils::with_target(preceded(
+                    tuple((tag("url"), multispace1, tag("host:"))),
+                    take_while1(|c: char| c.is_alphanumeric() || c == '.' || c == '-'),
+                )),
+                |(host, mb_target)| Cmd::UrlFromHost(host, mb_target),
+            ),
             map(
                 parsing_utils::with_target(pair(tag("url"), opt(preceded(multispace1, digit1)))),
                 |((_, mb_idx), mb_target)| {
@@ -515,62 +716,12 @@ fn parse_command(msg: &str) -> Option<Cmd<'_>> {
         .ok()
 }
 
-const YT_HOSTNAMES: [&str; 5] = [
-    "youtube.com",
-    "www.youtube.com",
-    "youtu.be",
-    "www.youtu.be",
-    "m.youtube.com",
-];
-
-fn is_yt_url(url: &Url) -> bool {
-    url.host()
-        .map(|h| match h {
-            url::Host::Domain(domain) => YT_HOSTNAMES.contains(&domain),
-            url::Host::Ipv4(_) | url::Host::Ipv6(_) => false,
-        })
-        .unwrap_or(false)
-}
-
-#[derive(PartialEq, Eq, Debug)]
-enum YtId<'url> {
-    Video(Cow<'url, str>),
-    Channel(&'url str),
-    Playlist(Cow<'url, str>),
-}
-
-fn extract_yt_id(url: &Url) -> Option<YtId<'_>> {
-    let mut segments = url.path_segments()?;
-    let first_segment = segments.next();
-    let second_segment = segments.next();
-
-    if matches!(url.host(), Some(url::Host::Domain("youtu.be"))) {
-        return first_segment.map(|v| YtId::Video(Cow::Borrowed(v)));
-    }
-
-    match first_segment {
-        Some("c") | Some("channel") | Some("user") => second_segment.map(YtId::Channel),
-        Some("watch") => {
-            url.query_pairs()
-                .find_map(|(k, v)| if k == "v" { Some(YtId::Video(v)) } else { None })
-        }
-        Some("shorts") => second_segment.map(|v| YtId::Video(Cow::Borrowed(v))),
-        Some("playlist") => url.query_pairs().find_map(|(k, v)| {
-            if k == "list" {
-                Some(YtId::Playlist(v))
-            } else {
-                None
-            }
-        }),
-        _ => None,
-    }
-}
-
 /// This is copy pasted and adapted from the method with the same name in reqwest:
 /// https://docs.rs/reqwest/latest/src/reqwest/async_impl/response.rs.html#184-207
 /// The difference is about reading only the beginning of the response up to a point
 /// to avoid a denial of service where the bot is pointed at a 100GB response.
-/// Defaults to utf-8
+/// Without charset in the content type, uses the one declared in a <meta> tag,
+/// and defaults to utf-8
 fn text_with_charset(bytes: &[u8], content_type: &Option<HeaderValue>) -> Result<String> {
     let ct = content_type
         .as_ref()
@@ -581,6 +732,7 @@ fn text_with_charset(bytes: &[u8], content_type: &Option<HeaderValue>) -> Result
         .as_ref()
         .and_then(|mime| mime.get_param("charset").map(|charset| charset.as_str()))
         .and_then(|encoding_name| Encoding::for_label(encoding_name.as_bytes()))
+        .or_else(|| meta_charset(bytes))
         .unwrap_or(encoding_rs::UTF_8)
         .new_decoder();
 
@@ -599,29 +751,63 @@ fn text_with_charset(bytes: &[u8], content_type: &Option<HeaderValue>) -> Result
     Ok(dst)
 }
 
-pub async fn sniff_title(mut resp: reqwest::Response) -> Result<String> {
-    let ct = resp.headers().get(reqwest::header::CONTENT_TYPE).cloned();
-    let url = resp.url().to_string();
+/// How far into the page a <meta> charset declaration is looked for
+const META_PRESCAN_LEN: usize = 1024;
+
+/// The encoding declared by either `<meta charset="…">` or
+/// `<meta http-equiv="Content-Type" content="text/html; charset=…">`
+/// at the beginning of the page.
+fn meta_charset(bytes: &[u8]) -> Option<&'static Encoding> {
+    let head = &bytes[..bytes.len().min(META_PRESCAN_LEN)];
+    // the declaration itself is ascii, whatever the encoding of the page
+    let head = String::from_utf8_lossy(head).to_ascii_lowercase();
+    head.split("<meta")
+        .skip(1)
+        .find_map(|tag| {
+            let tag = &tag[..tag.find('>').unwrap_or(tag.len())];
+            let idx = tag.find("charset")?;
+            let value = tag[idx + "charset".len()..]
+                .trim_start()
+                .strip_prefix('=')?
+                .trim_start()
+                .trim_start_matches(|c| c == '"' || c == '\'');
+            let is_end =
+                |c: char| c == '"' || c == '\'' || c == ';' || c == '/' || c.is_whitespace();
+            let label = value.split(is_end).next()?;
+            Encoding::for_label(label.as_bytes())
+        })
+        // a page can't really be utf-16 if the declaration is readable as ascii
+        .map(|encoding| encoding.output_encoding())
+}
 
-    // only bother to look further if the content type looks like html or text
-    match ct.as_ref().and_then(|h| h.to_str().ok()) {
-        Some(ct) if ct.contains("text") || ct.contains("html") => (),
-        Some(ct) => {
-            return Ok(format!(
-                "Cannot extract title from content type {ct} for {url}",
-            ))
+/// The title of a page, or the reason why it couldn't be found
+#[derive(Debug, PartialEq)]
+pub enum UrlTitle {
+    Found(String),
+    Missing(String),
+}
+
+impl std::fmt::Display for UrlTitle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UrlTitle::Found(s) | UrlTitle::Missing(s) => f.write_str(s),
         }
-        _ => return Ok(format!("No valid content type found for {url}")),
-    };
+    }
+}
 
-    // don't download more than `capa` bytes (to avoid dos)
-    let capa = 10 * 1024;
+/// The first `capa` bytes of the body. Never downloads more than that
+/// (to avoid dos), whatever the Content-Length says, since it can be
+/// missing or wrong.
+pub(crate) async fn read_head(
+    resp: &mut reqwest::Response,
+    capa: usize,
+) -> Result<bytes::BytesMut> {
     let mut read_buf = bytes::BytesMut::with_capacity(capa);
 
     while let Some(chunk) = resp.chunk().await.transpose() {
         let chunk = chunk.map_err(|err| Error::Wrapped {
             source: Box::new(err),
-            ctx: format!("Failed to read bytes from response for url {}", url),
+            ctx: format!("Failed to read bytes from response for url {}", resp.url()),
         })?;
 
         // make sure we don't read more than the allocated capacity
@@ -631,47 +817,375 @@ pub async fn sniff_title(mut resp: reqwest::Response) -> Result<String> {
             break;
         }
     }
+    Ok(read_buf)
+}
+
+pub async fn sniff_title(resp: reqwest::Response, max_title_length: usize) -> Result<UrlTitle> {
+    let url = resp.url().to_string();
+    read_title(resp, &url, max_title_length).await
+}
+
+/// Like `sniff_title`, giving the title for `url` instead of the response url
+pub(crate) async fn read_title(
+    mut resp: reqwest::Response,
+    url: &str,
+    max_title_length: usize,
+) -> Result<UrlTitle> {
+    let ct = resp.headers().get(reqwest::header::CONTENT_TYPE).cloned();
+
+    // only bother to look further if the content type looks like html or text
+    match ct.as_ref().and_then(|h| h.to_str().ok()) {
+        Some(ct) if ct.contains("text") || ct.contains("html") => (),
+        Some(ct) => {
+            return Ok(UrlTitle::Missing(format!(
+                "Cannot extract title from content type {ct} for {url}",
+            )))
+        }
+        _ => {
+            return Ok(UrlTitle::Missing(format!(
+                "No valid content type found for {url}"
+            )))
+        }
+    };
+
+    let read_buf = read_head(&mut resp, 10 * 1024).await?;
 
     // <title data-rh=\"true\">Greta Thunberg carried away by police at German mine protest | AP News</title>
     let fragment = text_with_charset(&read_buf, &ct)?;
+    Ok(extract_title(&fragment, url, max_title_length))
+}
 
+fn extract_title(fragment: &str, url: &str, max_title_length: usize) -> UrlTitle {
     let selector = scraper::Selector::parse("title").unwrap();
     // there can be a problem since `<title>coucou` is parsed as the
     // full title. So need to grab enough bytes from the network
     // to be reasonably sure that we got the full title
     // Also, ignore any parse error. The parser is very lenient and can
     // gives us a title even if there are other error in the document
-    if let Some(title) = scraper::Html::parse_document(&fragment)
+    let document = scraper::Html::parse_document(fragment);
+    let title = document
         .select(&selector)
         .next()
-    {
+        .map(|title| title.text().into_iter().collect::<String>())
+        .filter(|title| !title.trim().is_empty())
+        // pages with an empty or missing <title> often have these tags for previews
+        .or_else(|| meta_content(&document, r#"meta[property="og:title"]"#))
+        .or_else(|| meta_content(&document, r#"meta[name="twitter:title"]"#));
+
+    if let Some(title) = title {
         log::debug!("found title: {title:?}");
-        let title = title
-            .text()
-            .into_iter()
-            .collect::<String>()
-            .replace('\n', " ");
-
-        // Simply slicing the string like title[..100] will panic if
-        // it stops across an utf-8 codepoint boundary.
-        // So need to iterate across real chars to split properly.
-        let char_len = title.chars().count();
-        if char_len > 100 {
-            let f = title.chars().take(100).collect::<String>();
-            Ok(format!("{}[…] [{url}]", f))
-        } else {
-            Ok(format!("{title} [{url}]"))
-        }
+        let title = title.replace('\n', " ");
+        UrlTitle::Found(format!("{} [{url}]", shorten(&title, max_title_length)))
+    } else {
+        UrlTitle::Missing(format!("No title found at {url}"))
+    }
+}
+
+/// The text truncated to `max_length` characters, marked with […]
+pub fn shorten(text: &str, max_length: usize) -> String {
+    // Simply slicing the string like title[..100] will panic if
+    // it stops across an utf-8 codepoint boundary.
+    // So need to iterate across real chars to split properly.
+    if text.chars().count() > max_length {
+        let f = text.chars().take(max_length).collect::<String>();
+        format!("{}[…]", f)
     } else {
-        Ok(format!("No title found at {url}"))
+        text.to_string()
     }
 }
 
+/// The non blank content attribute of the first tag matching `selector`
+fn meta_content(document: &scraper::Html, selector: &str) -> Option<String> {
+    let selector = scraper::Selector::parse(selector).unwrap();
+    document
+        .select(&selector)
+        .next()
+        .and_then(|meta| meta.value().attr("content"))
+        .map(|content| content.trim().to_string())
+        .filter(|content| !content.is_empty())
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
     use pretty_assertions::assert_eq;
 
+    fn test_plugin() -> UrlPlugin {
+        let client = reqwest::Client::new();
+        UrlPlugin {
+            seen_urls: Default::default(),
+            handlers: HandlerRegistry::new(TitleSniffer {
+                max_title_length: DEFAULT_MAX_TITLE_LENGTH,
+                max_page_size: DEFAULT_MAX_PAGE_SIZE_MB * 1024 * 1024,
+                max_redirects: guard::DEFAULT_MAX_REDIRECTS,
+            }),
+            client,
+            yt_api_key: None,
+            live_watcher: None,
+            own_nicks: OwnNicks::new("golem"),
+            blacklisted_users: vec!["coucoubot".to_string()],
+            max_urls_per_message: 2,
+            quiet_url_errors: false,
+            auto_titles_per_message: 2,
+            auto_title_channels: None,
+            no_auto_title_channels: vec![],
+            persist: false,
+        }
+    }
+
+    /// Finds a title only for urls with a path, the others have none
+    struct FakeHandler;
+
+    #[async_trait]
+    impl handlers::UrlHandler for FakeHandler {
+        fn name(&self) -> &'static str {
+            "fake"
+        }
+
+        fn matches(&self, _url: &Url) -> bool {
+            true
+        }
+
+        async fn describe(&self, url: &Url) -> Result<UrlTitle> {
+            if url.path() == "/" {
+                Ok(UrlTitle::Missing(format!("No title found at {url}")))
+            } else {
+                Ok(UrlTitle::Found(format!("Title of {url}")))
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_own_urls_are_stored() {
+        let plugin = test_plugin();
+        let msg: Message = Command::PRIVMSG(
+            "#chan".to_string(),
+            "Le stream de gikiam est maintenant live at https://www.twitch.tv/gikiam!".to_string(),
+        )
+        .into();
+        plugin.out_message(&msg).await.unwrap();
+        assert_eq!(
+            plugin.seen_urls.lock().get("#chan", 0).map(|u| u.as_str()),
+            Some("https://www.twitch.tv/gikiam")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_announce_titles() {
+        let plugin = UrlPlugin {
+            handlers: HandlerRegistry::new(FakeHandler),
+            auto_titles_per_message: 3,
+            ..test_plugin()
+        };
+        let msg: Message =
+            ":charlie!c@host PRIVMSG #chan :http://a.com/1 http://b.com http://c.com/3 http://d.com/4"
+                .parse()
+                .unwrap();
+        let reply = plugin.in_msg(&msg).await.unwrap().expect("some titles");
+        assert_eq!(
+            reply.command,
+            Command::PRIVMSG(
+                "#chan".to_string(),
+                "Title of http://a.com/1 | Title of http://c.com/3".to_string()
+            ),
+            "urls without title are skipped, and only the first ones are fetched"
+        );
+
+        let msg: Message = ":charlie!c@host PRIVMSG #chan :http://b.com"
+            .parse()
+            .unwrap();
+        assert_eq!(plugin.in_msg(&msg).await.unwrap(), None, "no title at all");
+
+        let disabled = UrlPlugin {
+            handlers: HandlerRegistry::new(FakeHandler),
+            auto_titles_per_message: 0,
+            ..test_plugin()
+        };
+        let msg: Message = ":charlie!c@host PRIVMSG #chan :http://a.com/1"
+            .parse()
+            .unwrap();
+        assert_eq!(disabled.in_msg(&msg).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_auto_title_channels() {
+        let plugin = UrlPlugin {
+            handlers: HandlerRegistry::new(FakeHandler),
+            auto_title_channels: Some(vec!["#chan".to_string(), "#other".to_string()]),
+            no_auto_title_channels: vec!["#Other".to_string()],
+            ..test_plugin()
+        };
+        let titles = |channel: &str| {
+            let msg: Message = format!(":charlie!c@host PRIVMSG {channel} :http://a.com/1")
+                .parse()
+                .unwrap();
+            let plugin = &plugin;
+            async move { plugin.in_msg(&msg).await.unwrap() }
+        };
+        assert!(titles("#chan").await.is_some());
+        assert!(titles("#CHAN").await.is_some(), "case insensitive");
+        assert_eq!(titles("#elsewhere").await, None, "not allowed");
+        assert_eq!(titles("#other").await, None, "denied");
+        assert_eq!(
+            plugin
+                .seen_urls
+                .lock()
+                .get("#elsewhere", 0)
+                .map(|u| u.as_str()),
+            Some("http://a.com/1"),
+            "the urls are still stored"
+        );
+
+        let msg: Message = ":charlie!c@host PRIVMSG #elsewhere :λurl".parse().unwrap();
+        assert!(
+            plugin.in_msg(&msg).await.unwrap().is_some(),
+            "λurl works everywhere"
+        );
+    }
+
+    #[test]
+    fn test_quiet_url_errors() {
+        let url = "https://coucou.com/";
+        let no_title = extract_title("<html><body>coucou</body></html>", url, 100);
+        assert_eq!(
+            no_title,
+            UrlTitle::Missing("No title found at https://coucou.com/".to_string())
+        );
+        let title = extract_title("<html><title>Coucou</title></html>", url, 100);
+
+        let verbose = test_plugin();
+        assert_eq!(
+            verbose.title_reply(extract_title("<html></html>", url, 100)),
+            Some("No title found at https://coucou.com/".to_string())
+        );
+
+        let quiet = UrlPlugin {
+            quiet_url_errors: true,
+            ..test_plugin()
+        };
+        assert_eq!(quiet.title_reply(no_title), None, "no title, no reply");
+        assert_eq!(
+            quiet.title_reply(title),
+            Some("Coucou [https://coucou.com/]".to_string()),
+            "titles are still sent"
+        );
+    }
+
+    #[test]
+    fn test_title_fallbacks() {
+        let url = "https://apnews.com/";
+        let og_only = r#"<html><head>
+            <meta property="og:title" content=" Greta Thunberg carried away by police ">
+            </head></html>"#;
+        assert_eq!(
+            extract_title(og_only, url, 100),
+            UrlTitle::Found(
+                "Greta Thunberg carried away by police [https://apnews.com/]".to_string()
+            )
+        );
+
+        let empty_title = r#"<html><head><title>  </title>
+            <meta name="twitter:title" content="From twitter">
+            </head></html>"#;
+        assert_eq!(
+            extract_title(empty_title, url, 100),
+            UrlTitle::Found("From twitter [https://apnews.com/]".to_string()),
+            "blank title"
+        );
+
+        let both = r#"<html><head><title>The title</title>
+            <meta property="og:title" content="From og">
+            </head></html>"#;
+        assert_eq!(
+            extract_title(both, url, 100),
+            UrlTitle::Found("The title [https://apnews.com/]".to_string()),
+            "<title> comes first"
+        );
+
+        let blank_og = r#"<meta property="og:title" content="">"#;
+        assert_eq!(
+            extract_title(blank_og, url, 100),
+            UrlTitle::Missing("No title found at https://apnews.com/".to_string())
+        );
+    }
+
+    #[test]
+    fn test_max_title_length() {
+        let url = "https://coucou.com/";
+        let page = "<html><title>Ça sent le café brûlé à l'hôtel</title></html>";
+        assert_eq!(
+            extract_title(page, url, 9),
+            UrlTitle::Found("Ça sent l[…] [https://coucou.com/]".to_string()),
+            "truncated on a char boundary"
+        );
+        assert_eq!(
+            extract_title(page, url, 2),
+            UrlTitle::Found("Ça[…] [https://coucou.com/]".to_string()),
+            "multibyte first char"
+        );
+        assert_eq!(
+            extract_title(page, url, 100),
+            UrlTitle::Found("Ça sent le café brûlé à l'hôtel [https://coucou.com/]".to_string()),
+            "short enough"
+        );
+    }
+
+    #[test]
+    fn test_max_urls_per_message() {
+        let plugin = test_plugin();
+        plugin.add_urls(
+            "#chan",
+            parse_urls("http://a.com http://b.com http://c.com http://d.com").unwrap(),
+        );
+        let seen_urls = plugin.seen_urls.lock();
+        assert_eq!(
+            (seen_urls.get("#chan", 0), seen_urls.get("#chan", 1)),
+            (
+                Some(&Url::parse("http://b.com").unwrap()),
+                Some(&Url::parse("http://a.com").unwrap())
+            ),
+            "keeps the first urls of the message"
+        );
+        assert_eq!(seen_urls.get("#chan", 2), None);
+    }
+
+    #[tokio::test]
+    async fn test_blacklisted_users_urls_only() {
+        let plugin = test_plugin();
+        let msg: Message = ":coucoubot!bot@host PRIVMSG #chan :look http://coucou.com"
+            .parse()
+            .unwrap();
+        assert!(plugin.in_msg(&msg).await.unwrap().is_none());
+        assert_eq!(
+            plugin.seen_urls.lock().get("#chan", 0),
+            Some(&Url::parse("http://coucou.com").unwrap()),
+            "urls from bots are stored"
+        );
+
+        let msg: Message = ":coucoubot!bot@host PRIVMSG #chan :λurl".parse().unwrap();
+        assert!(
+            plugin.in_msg(&msg).await.unwrap().is_none(),
+            "commands from bots are ignored"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_urls_per_server() {
+        let plugin = test_plugin();
+        // from a bot, so that no title is fetched
+        let msg: Message = ":coucoubot!bot@host PRIVMSG #chan :look http://coucou.com"
+            .parse()
+            .unwrap();
+        let oftc = plugin_core::utils::network::with_network(msg, Some("irc.oftc.net"));
+        plugin.in_msg(&oftc).await.unwrap();
+        let seen_urls = plugin.seen_urls.lock();
+        assert_eq!(seen_urls.get("#chan", 0), None, "not the same #chan");
+        assert_eq!(
+            seen_urls.get("irc.oftc.net #chan", 0),
+            Some(&Url::parse("http://coucou.com").unwrap())
+        );
+    }
+
     #[test]
     fn test_simple_url() {
         assert_eq!(
@@ -733,6 +1247,26 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_url_trailing_punctuation() {
+        assert_eq!(
+            parse_urls("live at https://www.twitch.tv/gikiam: chatting").unwrap(),
+            vec![Url::parse("https://www.twitch.tv/gikiam").unwrap()]
+        );
+        assert_eq!(
+            parse_urls("(voir http://coucou.com/a.html), http://blah.com!").unwrap(),
+            vec![
+                Url::parse("http://coucou.com/a.html").unwrap(),
+                Url::parse("http://blah.com").unwrap(),
+            ]
+        );
+        assert_eq!(
+            parse_urls("https://fr.wikipedia.org/wiki/Rust_(langage).").unwrap(),
+            vec![Url::parse("https://fr.wikipedia.org/wiki/Rust_(langage)").unwrap()],
+            "the parenthesis belongs to the url"
+        );
+    }
+
     #[test]
     fn test_simple_command_no_match() {
         assert_eq!(parse_command("λlol"), None);
@@ -743,11 +1277,51 @@ mod test {
         assert_eq!(parse_command("λurl"), Some(Cmd::Url(None, None)));
     }
 
+    #[test]
+    fn test_command_list() {
+        assert_eq!(parse_command("λurls"), Some(Cmd::Urls(None)));
+        assert_eq!(
+            parse_command("λurls > charlie"),
+            Some(Cmd::Urls(Some("charlie")))
+        );
+        assert_eq!(parse_command("λurls 2"), None);
+    }
+
+    #[test]
+    fn test_list_urls() {
+        let plugin = test_plugin();
+        assert_eq!(plugin.list_urls("#chan"), "No stored url for #chan");
+
+        let long = format!("http://coucou.com/{}", "a".repeat(60));
+        plugin.add_urls("#chan", parse_urls(&long).unwrap());
+        plugin.add_urls("#chan", parse_urls("http://b.com").unwrap());
+        assert_eq!(
+            plugin.list_urls("#chan"),
+            format!(
+                "0: http://b.com/ | 1: http://coucou.com/{}[…]",
+                "a".repeat(32)
+            )
+        );
+    }
+
     #[test]
     fn test_command_with_idx() {
         assert_eq!(parse_command("λurl 2"), Some(Cmd::Url(Some(2), None)));
     }
 
+    #[test]
+    fn test_command_with_host() {
+        assert_eq!(
+            parse_command("λurl host:youtube.com > charlie"),
+            Some(Cmd::UrlFromHost("youtube.com", Some("charlie")))
+        );
+        assert_eq!(
+            parse_command("λurl host:github.com"),
+            Some(Cmd::UrlFromHost("github.com", None))
+        );
+        assert_eq!(parse_command("λurl host:"), None);
+    }
+
     #[test]
     fn test_command_with_target() {
         assert_eq!(
@@ -820,112 +1394,60 @@ mod test {
     }
 
     #[test]
-    fn test_is_yt_url() {
-        assert!(!is_yt_url(
-            &Url::parse("https://github.com/CoucouInc/rustygolem").unwrap()
-        ));
-
-        assert!(is_yt_url(
-            &Url::parse("https://youtube.com/c/BosnianApeSociety").unwrap()
-        ));
-
-        assert!(is_yt_url(
-            &Url::parse("https://www.youtube.com/watch?v=0F5GQAnj0lo").unwrap()
-        ));
-
-        assert!(is_yt_url(
-            &Url::parse("https://youtu.be/haLBM94SENg?t=256").unwrap()
-        ));
-
-        assert!(is_yt_url(
-            &Url::parse("https://m.youtube.com/watch?v=haLBM94SENg").unwrap()
-        ));
-
-        // https://m.youtube.com/watch?list=PLJcTRymdlUQPwx8qU4ln83huPx-6Y3XxH&v=5MKjPYuD60I&feature=emb_imp_woyt]
-    }
-
-    #[test]
-    fn test_extract_yt_id() {
-        assert_eq!(
-            extract_yt_id(&Url::parse("https://github.com/CoucouInc/rustygolem").unwrap()),
-            None
-        );
-
-        assert_eq!(
-            extract_yt_id(&Url::parse("https://www.youtube.com/results?search_query=mj").unwrap()),
-            None
-        );
-
-        assert_eq!(
-            extract_yt_id(&Url::parse("https://youtu.be/6gwBOTggfRc").unwrap()),
-            Some(YtId::Video("6gwBOTggfRc".into()))
-        );
-
-        assert_eq!(
-            extract_yt_id(&Url::parse("https://www.youtube.com/watch?v=ZZ3F3zWiEmc").unwrap()),
-            Some(YtId::Video("ZZ3F3zWiEmc".into()))
-        );
-
-        assert_eq!(
-            extract_yt_id(&Url::parse("https://www.youtube.com/shorts/EU4p-OC4O3o").unwrap()),
-            Some(YtId::Video("EU4p-OC4O3o".into()))
-        );
-
-        assert_eq!(
-            extract_yt_id(
-                &Url::parse("https://www.youtube.com/c/%E3%81%8B%E3%82%89%E3%82%81%E3%82%8B")
-                    .unwrap()
-            ),
-            // からめる
-            Some(YtId::Channel("%E3%81%8B%E3%82%89%E3%82%81%E3%82%8B"))
-        );
-
+    fn test_decode_text() {
+        let sparkle_heart = vec![240, 159, 146, 150];
         assert_eq!(
-            extract_yt_id(&Url::parse("https://www.youtube.com/c/inanutshell").unwrap()),
-            Some(YtId::Channel("inanutshell"))
+            text_with_charset(&sparkle_heart, &None).unwrap(),
+            "💖".to_string()
         );
+    }
 
-        assert_eq!(
-            extract_yt_id(&Url::parse("https://www.youtube.com/c/inanutshell/videos").unwrap()),
-            Some(YtId::Channel("inanutshell"))
-        );
+    #[test]
+    fn test_decode_meta_charset() {
+        let page = |head: &str, title: &str, encoding: &'static Encoding| {
+            let mut bytes = format!("<html><head>{head}<title>").into_bytes();
+            bytes.extend_from_slice(&encoding.encode(title).0);
+            bytes.extend_from_slice(b"</title></head></html>");
+            bytes
+        };
 
-        assert_eq!(
-            extract_yt_id(
-                &Url::parse("https://www.youtube.com/channel/UCworsKCR-Sx6R6-BnIjS2MA").unwrap()
-            ),
-            Some(YtId::Channel("UCworsKCR-Sx6R6-BnIjS2MA"))
+        let shift_jis = page(
+            r#"<meta charset="Shift_JIS">"#,
+            "日本語",
+            encoding_rs::SHIFT_JIS,
         );
-
-        assert_eq!(
-            extract_yt_id(&Url::parse("https://youtube.com/c/BosnianApeSociety").unwrap()),
-            Some(YtId::Channel("BosnianApeSociety"))
+        assert_eq!(meta_charset(&shift_jis), Some(encoding_rs::SHIFT_JIS));
+        assert!(text_with_charset(&shift_jis, &None)
+            .unwrap()
+            .contains("<title>日本語</title>"));
+
+        let cyrillic = page(
+            r#"<meta http-equiv="Content-Type" content="text/html; charset=windows-1251" />"#,
+            "Привет",
+            encoding_rs::WINDOWS_1251,
         );
-
-        assert_eq!(
-            extract_yt_id(
-                &Url::parse(
-                    "https://www.youtube.com/playlist?list=PLoBxKk9n0UWcv0HTYARFyCb0s9P21cDSd"
-                )
+        assert_eq!(meta_charset(&cyrillic), Some(encoding_rs::WINDOWS_1251));
+        assert!(text_with_charset(&cyrillic, &None)
+            .unwrap()
+            .contains("<title>Привет</title>"));
+
+        let header = Some(HeaderValue::from_static("text/html; charset=utf-8"));
+        assert!(
+            !text_with_charset(&cyrillic, &header)
                 .unwrap()
-            ),
-            Some(YtId::Playlist("PLoBxKk9n0UWcv0HTYARFyCb0s9P21cDSd".into()))
+                .contains("Привет"),
+            "the content type takes precedence"
         );
 
-        //
-
-        assert_eq!(
-            extract_yt_id(&Url::parse("https://www.youtube.com/user/VieDeChouhartem").unwrap()),
-            Some(YtId::Channel("VieDeChouhartem"))
+        let no_meta = page(
+            r#"<meta name="viewport" content="width=device-width">"#,
+            "x",
+            encoding_rs::UTF_8,
         );
-    }
-
-    #[test]
-    fn test_decode_text() {
-        let sparkle_heart = vec![240, 159, 146, 150];
+        assert_eq!(meta_charset(&no_meta), None);
         assert_eq!(
-            text_with_charset(&sparkle_heart, &None).unwrap(),
-            "💖".to_string()
+            meta_charset(br#"<meta charset="utf-16le">"#),
+            Some(encoding_rs::UTF_8)
         );
     }
 }
@@ -0,0 +1,193 @@
+use async_trait::async_trait;
+use plugin_core::Result;
+use serde::Deserialize;
+use url::Url;
+
+use crate::handlers::{TitleSniffer, UrlHandler};
+use crate::{guard, shorten, UrlTitle};
+
+/// Describe the mastodon statuses with the public api of their instance,
+/// which can be any host. The page title is used when the host doesn't
+/// answer like a mastodon instance.
+pub(crate) struct MastodonHandler {
+    fallback: TitleSniffer,
+}
+
+#[derive(Debug, Deserialize)]
+struct Status {
+    /// html
+    content: String,
+    /// the content warning, the content is hidden behind it
+    #[serde(default)]
+    spoiler_text: String,
+    account: Account,
+}
+
+#[derive(Debug, Deserialize)]
+struct Account {
+    /// user@instance, or only user for the local accounts
+    acct: String,
+    #[serde(default)]
+    display_name: String,
+}
+
+#[async_trait]
+impl UrlHandler for MastodonHandler {
+    fn name(&self) -> &'static str {
+        "mastodon"
+    }
+
+    fn matches(&self, url: &Url) -> bool {
+        is_mastodon_url(url)
+    }
+
+    async fn describe(&self, url: &Url) -> Result<UrlTitle> {
+        let status_id = match extract_status_id(url) {
+            Some(id) => id,
+            None => return self.fallback.describe(url).await,
+        };
+        match self.get_status(url, status_id).await {
+            Ok(status) => Ok(UrlTitle::Found(format_status(
+                &status,
+                url,
+                self.fallback.max_title_length,
+            ))),
+            Err(err) => {
+                log::info!("Cannot describe {url} with the mastodon api: {err}");
+                self.fallback.describe(url).await
+            }
+        }
+    }
+}
+
+impl MastodonHandler {
+    pub(crate) fn new(fallback: TitleSniffer) -> Self {
+        MastodonHandler { fallback }
+    }
+
+    /// The instance can be anywhere, it's checked like the fetched pages
+    async fn get_status(&self, url: &Url, status_id: &str) -> std::result::Result<Status, String> {
+        let mut api_url = url.clone();
+        api_url.set_path(&format!("/api/v1/statuses/{status_id}"));
+        api_url.set_query(None);
+        api_url.set_fragment(None);
+        let resp = guard::get(&api_url, self.fallback.max_redirects).await?;
+        if !resp.status().is_success() {
+            return Err(format!("wrong status code, got {}", resp.status()));
+        }
+        resp.json()
+            .await
+            .map_err(|err| format!("not a mastodon status: {err}"))
+    }
+}
+
+fn format_status(status: &Status, url: &Url, max_length: usize) -> String {
+    let author = match status.account.display_name.trim() {
+        "" => format!("@{}", status.account.acct),
+        name => format!("{name} (@{})", status.account.acct),
+    };
+    let text = match status.spoiler_text.trim() {
+        "" => html_to_text(&status.content),
+        spoiler => format!("CW: {spoiler}"),
+    };
+    match text.as_str() {
+        "" => format!("{author} [{url}]"),
+        text => format!("{author}: {} [{url}]", shorten(text, max_length)),
+    }
+}
+
+/// The statuses are made of paragraphs and line breaks,
+/// they are all put on a single line
+fn html_to_text(html: &str) -> String {
+    let html = html
+        .replace("</p><p>", " ")
+        .replace("<br>", " ")
+        .replace("<br/>", " ")
+        .replace("<br />", " ");
+    let fragment = scraper::Html::parse_fragment(&html);
+    let text = fragment.root_element().text().collect::<String>();
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Whether the url looks like a status on a mastodon instance, like
+/// https://mamot.fr/@someone/109372881231283. Whether the host really
+/// serves the mastodon api is only known when asking it.
+pub(crate) fn is_mastodon_url(url: &Url) -> bool {
+    extract_status_id(url).is_some()
+}
+
+/// From the urls /@user/<id> and /users/<user>/statuses/<id>
+fn extract_status_id(url: &Url) -> Option<&str> {
+    if !matches!(url.scheme(), "http" | "https") {
+        return None;
+    }
+    let segments = url
+        .path_segments()?
+        .filter(|s| !s.is_empty())
+        .collect::<Vec<_>>();
+    let id = match segments.as_slice() {
+        [user, id] if user.len() > 1 && user.starts_with('@') => id,
+        ["users", _, "statuses", id] => id,
+        _ => return None,
+    };
+    if id.chars().all(|c| c.is_ascii_digit()) {
+        Some(id)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_extract_status_id() {
+        let id = |url: &str| extract_status_id(&Url::parse(url).unwrap());
+        assert_eq!(
+            id("https://mamot.fr/@someone/109372881231283"),
+            Some("109372881231283")
+        );
+        assert_eq!(
+            id("https://mastodon.social/users/someone/statuses/1093728812/"),
+            Some("1093728812")
+        );
+        assert_eq!(id("https://mamot.fr/@someone"), None, "a profile");
+        assert_eq!(
+            id("https://medium.com/@someone/a-story-3f1e2d"),
+            None,
+            "not a status id"
+        );
+        assert_eq!(id("https://mamot.fr/@/123"), None);
+        assert_eq!(id("https://x.com/someone/status/123"), None);
+    }
+
+    #[test]
+    fn test_format_status() {
+        let url = Url::parse("https://mamot.fr/@charlie/123").unwrap();
+        let json = r#"{"id":"123","content":"<p>coucou <a href=\"https://mamot.fr/tags/rust\" class=\"mention hashtag\">#<span>rust</span></a></p><p>deuxième<br />ligne</p>","spoiler_text":"","account":{"acct":"charlie","display_name":"Charlie"}}"#;
+        let status: Status = serde_json::from_str(json).unwrap();
+        assert_eq!(
+            format_status(&status, &url, 100),
+            "Charlie (@charlie): coucou #rust deuxième ligne [https://mamot.fr/@charlie/123]"
+        );
+        assert_eq!(
+            format_status(&status, &url, 6),
+            "Charlie (@charlie): coucou[…] [https://mamot.fr/@charlie/123]"
+        );
+
+        let status = Status {
+            spoiler_text: "spoilers".to_string(),
+            account: Account {
+                acct: "charlie@mamot.fr".to_string(),
+                display_name: "".to_string(),
+            },
+            ..status
+        };
+        assert_eq!(
+            format_status(&status, &url, 100),
+            "@charlie@mamot.fr: CW: spoilers [https://mamot.fr/@charlie/123]"
+        );
+    }
+}
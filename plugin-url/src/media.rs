@@ -0,0 +1,188 @@
+use mime::Mime;
+use plugin_core::Result;
+use url::Url;
+
+use crate::{read_head, UrlTitle};
+
+/// Bytes read to find the dimensions of images and the pages of pdfs,
+/// the others are described from the headers alone
+const HEAD_SIZE: usize = 10 * 1024;
+
+/// Describe the files which aren't web pages from their type and size,
+/// like `Image PNG (800×600, 1.2 MB)`, for the given url
+pub(crate) async fn describe_media(
+    mut resp: reqwest::Response,
+    url: &Url,
+    mime: &Mime,
+) -> Result<UrlTitle> {
+    let size = resp.content_length();
+    let head = if mime.type_() == mime::IMAGE || mime.subtype() == mime::PDF {
+        read_head(&mut resp, HEAD_SIZE).await?
+    } else {
+        Default::default()
+    };
+    Ok(UrlTitle::Found(format!(
+        "{} [{}]",
+        describe(mime, &head, size),
+        url
+    )))
+}
+
+fn describe(mime: &Mime, head: &[u8], size: Option<u64>) -> String {
+    let kind = if mime.type_() == mime::IMAGE {
+        format!("Image {}", format_subtype(mime))
+    } else if mime.type_() == mime::VIDEO {
+        format!("Vidéo {}", format_subtype(mime))
+    } else if mime.type_() == mime::AUDIO {
+        format!("Audio {}", format_subtype(mime))
+    } else if mime.subtype() == mime::PDF {
+        "PDF".to_string()
+    } else {
+        mime.essence_str().to_string()
+    };
+    let details = if mime.type_() == mime::IMAGE {
+        image_dimensions(head).map(|(width, height)| format!("{width}×{height}"))
+    } else if mime.subtype() == mime::PDF {
+        pdf_pages(head).map(|n| format!("{n} page{}", if n > 1 { "s" } else { "" }))
+    } else {
+        None
+    };
+    let details = details
+        .into_iter()
+        .chain(size.map(humanize_size))
+        .collect::<Vec<_>>();
+    if details.is_empty() {
+        kind
+    } else {
+        format!("{kind} ({})", details.join(", "))
+    }
+}
+
+/// svg+xml -> SVG, x-matroska -> MATROSKA
+fn format_subtype(mime: &Mime) -> String {
+    let subtype = mime.subtype().as_str();
+    let subtype = subtype.strip_prefix("x-").unwrap_or(subtype);
+    subtype.split('+').next().unwrap_or(subtype).to_uppercase()
+}
+
+/// Width and height of png, gif and jpeg images, read from their first bytes
+fn image_dimensions(head: &[u8]) -> Option<(u32, u32)> {
+    let be = |bytes: &[u8]| bytes.iter().fold(0, |n, b| (n << 8) | *b as u32);
+    let le = |bytes: &[u8]| bytes.iter().rev().fold(0, |n, b| (n << 8) | *b as u32);
+
+    if head.starts_with(b"\x89PNG\r\n\x1a\n") {
+        return Some((be(head.get(16..20)?), be(head.get(20..24)?)));
+    }
+    if head.starts_with(b"GIF87a") || head.starts_with(b"GIF89a") {
+        return Some((le(head.get(6..8)?), le(head.get(8..10)?)));
+    }
+    if head.starts_with(&[0xff, 0xd8]) {
+        // walk the segments until the start of frame, which has the dimensions
+        let mut i = 2;
+        loop {
+            let segment = head.get(i..i + 9)?;
+            if segment[0] != 0xff {
+                return None;
+            }
+            match segment[1] {
+                0xc0..=0xcf if ![0xc4, 0xc8, 0xcc].contains(&segment[1]) => {
+                    return Some((be(&segment[7..9]), be(&segment[5..7])));
+                }
+                _ => i += 2 + be(&segment[2..4]) as usize,
+            }
+        }
+    }
+    None
+}
+
+/// Only linearized pdfs give their number of pages at the start of the file
+fn pdf_pages(head: &[u8]) -> Option<u32> {
+    let text = String::from_utf8_lossy(head);
+    let start = text.find("/Linearized")?;
+    let dict = &text[start..start + text[start..].find(">>")?];
+    dict.split('/')
+        .find_map(|entry| entry.strip_prefix("N "))
+        .and_then(|n| n.trim().parse().ok())
+}
+
+/// 1234567 -> 1.2 MB
+pub(crate) fn humanize_size(bytes: u64) -> String {
+    let units = ["kB", "MB", "GB"];
+    let mut size = bytes as f64;
+    let mut unit = "B";
+    for u in units {
+        if size < 1024.0 {
+            break;
+        }
+        size /= 1024.0;
+        unit = u;
+    }
+    if unit == "B" {
+        format!("{bytes} B")
+    } else {
+        format!("{size:.1} {unit}")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let mut bytes = b"\x89PNG\r\n\x1a\n\x00\x00\x00\x0dIHDR".to_vec();
+        bytes.extend(width.to_be_bytes());
+        bytes.extend(height.to_be_bytes());
+        bytes
+    }
+
+    #[test]
+    fn test_image_dimensions() {
+        assert_eq!(image_dimensions(&png(800, 600)), Some((800, 600)));
+        assert_eq!(
+            image_dimensions(b"GIF89a\x40\x01\xf0\x00"),
+            Some((320, 240))
+        );
+        let jpeg = [
+            0xff, 0xd8, // start of image
+            0xff, 0xe0, 0x00, 0x04, 0x00, 0x00, // app0, 2 bytes of data
+            0xff, 0xc0, 0x00, 0x11, 0x08, 0x01, 0xe0, 0x02, 0x80, // sof0, 640x480
+        ];
+        assert_eq!(image_dimensions(&jpeg), Some((640, 480)));
+        assert_eq!(image_dimensions(&jpeg[..12]), None, "truncated");
+        assert_eq!(image_dimensions(b"coucou"), None);
+    }
+
+    #[test]
+    fn test_pdf_pages() {
+        let head = b"%PDF-1.5\n%\xe2\xe3\xcf\xd3\n1 0 obj\n<</Linearized 1/L 123456/O 4/E 1234/N 12/T 123000/H [ 500 200]>>\nendobj";
+        assert_eq!(pdf_pages(head), Some(12));
+        assert_eq!(pdf_pages(b"%PDF-1.5\n1 0 obj\n<</Type/Catalog>>"), None);
+    }
+
+    #[test]
+    fn test_describe() {
+        let png_mime: Mime = "image/png".parse().unwrap();
+        assert_eq!(
+            describe(&png_mime, &png(800, 600), Some(1_234_567)),
+            "Image PNG (800×600, 1.2 MB)"
+        );
+        assert_eq!(describe(&png_mime, b"", None), "Image PNG");
+        assert_eq!(
+            describe(&"image/svg+xml".parse().unwrap(), b"<svg>", Some(2048)),
+            "Image SVG (2.0 kB)"
+        );
+        assert_eq!(
+            describe(&"video/x-matroska".parse().unwrap(), b"", Some(700 << 20)),
+            "Vidéo MATROSKA (700.0 MB)"
+        );
+        assert_eq!(
+            describe(&mime::APPLICATION_PDF, b"<</Linearized 1/N 1>>", None),
+            "PDF (1 page)"
+        );
+        assert_eq!(
+            describe(&"application/zip".parse().unwrap(), b"", Some(300)),
+            "application/zip (300 B)"
+        );
+    }
+}
@@ -29,13 +29,7 @@ pub fn word<'a, E: ParseError<&'a str>>(input: &'a str) -> IResult<&'a str, &'a
     take_till1(|c| c == ' ' || c == '\t' || c == '\n' || c == '\r')(input)
 }
 
-/// Utility to parse common command prefix
-pub(crate) fn command_prefix(input: &str) -> nom::IResult<&str, &str> {
-    nom::branch::alt((
-        nom::bytes::complete::is_a("&"),
-        nom::bytes::complete::is_a("λ"),
-    ))(input)
-}
+pub(crate) use plugin_core::utils::parser::command_prefix;
 
 #[cfg(test)]
 mod test {
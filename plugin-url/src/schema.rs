@@ -0,0 +1,7 @@
+table! {
+    seen_urls (id) {
+        id -> Integer,
+        channel -> Text,
+        url -> Text,
+    }
+}
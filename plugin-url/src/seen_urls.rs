@@ -0,0 +1,351 @@
+use std::collections::{HashMap, VecDeque};
+
+use anyhow::Context;
+use diesel::prelude::*;
+use irc::proto::{Command, Message};
+use plugin_core::utils::nicks::OwnNicks;
+use url::Url;
+
+use crate::schema::seen_urls::{self, dsl};
+
+/// How many urls are remembered for each channel
+const URLS_PER_CHANNEL: usize = 10;
+
+/// Past this many channels, the ones where no url was posted for the longest
+/// time are forgotten.
+const MAX_CHANNELS: usize = 100;
+
+/// Recent urls posted in each channel, with a bounded memory footprint.
+/// The channels are keyed by `channel_key`, like in the db.
+pub(crate) struct SeenUrls {
+    max_channels: usize,
+    urls: HashMap<String, VecDeque<Url>>,
+    /// tracked channels, from least to most recently updated
+    lru: VecDeque<String>,
+}
+
+impl Default for SeenUrls {
+    fn default() -> Self {
+        SeenUrls::new(MAX_CHANNELS)
+    }
+}
+
+impl SeenUrls {
+    pub(crate) fn new(max_channels: usize) -> Self {
+        SeenUrls {
+            max_channels,
+            urls: HashMap::new(),
+            lru: VecDeque::new(),
+        }
+    }
+
+    /// A url already stored for the channel becomes the most recent one,
+    /// instead of being stored twice
+    pub(crate) fn add(&mut self, channel: &str, urls: Vec<Url>) {
+        if urls.is_empty() {
+            return;
+        }
+
+        let e = self.urls.entry(channel.to_string()).or_default();
+        for url in urls {
+            log::info!("Adding {url} to chan {channel}");
+            e.retain(|u| u != &url);
+            e.push_back(url);
+            if e.len() > URLS_PER_CHANNEL {
+                e.pop_front();
+            }
+        }
+
+        self.lru.retain(|c| c != channel);
+        self.lru.push_back(channel.to_string());
+        while self.lru.len() > self.max_channels {
+            if let Some(evicted) = self.lru.pop_front() {
+                log::debug!("Forgetting urls for {evicted}");
+                self.urls.remove(&evicted);
+            }
+        }
+    }
+
+    /// The url at index `idx`, starting from the most recent one
+    pub(crate) fn get(&self, channel: &str, idx: usize) -> Option<&Url> {
+        self.urls
+            .get(channel)
+            .and_then(|urls| urls.len().checked_sub(1 + idx).and_then(|i| urls.get(i)))
+    }
+
+    /// All the urls of the channel, starting from the most recent one
+    pub(crate) fn list(&self, channel: &str) -> Vec<&Url> {
+        self.urls
+            .get(channel)
+            .map(|urls| urls.iter().rev().collect())
+            .unwrap_or_default()
+    }
+
+    /// The most recent url of the channel on `host` or one of its subdomains
+    pub(crate) fn find_by_host(&self, channel: &str, host: &str) -> Option<&Url> {
+        let host = host.to_lowercase();
+        let subdomain = format!(".{host}");
+        self.list(channel).into_iter().find(|url| {
+            url.host_str()
+                .map_or(false, |h| h == host || h.ends_with(&subdomain))
+        })
+    }
+
+    pub(crate) fn forget(&mut self, channel: &str) {
+        self.urls.remove(channel);
+        self.lru.retain(|c| c != channel);
+    }
+
+    pub(crate) fn channel_count(&self) -> usize {
+        self.urls.len()
+    }
+}
+
+#[derive(Insertable)]
+#[table_name = "seen_urls"]
+struct NewSeenUrl<'a> {
+    channel: &'a str,
+    url: &'a str,
+}
+
+/// Store the urls of a channel, only keeping the most recent ones in the db.
+/// Like in memory, reposted urls replace their previous occurrence.
+pub(crate) fn save(conn: &SqliteConnection, channel: &str, urls: &[Url]) -> anyhow::Result<()> {
+    let rows = urls
+        .iter()
+        .enumerate()
+        .filter(|(i, url)| !urls[i + 1..].contains(*url))
+        .map(|(_, url)| NewSeenUrl {
+            channel,
+            url: url.as_str(),
+        })
+        .collect::<Vec<_>>();
+    diesel::delete(
+        dsl::seen_urls
+            .filter(dsl::channel.eq(channel))
+            .filter(dsl::url.eq_any(rows.iter().map(|r| r.url).collect::<Vec<_>>())),
+    )
+    .execute(conn)
+    .with_context(|| format!("Cannot remove reposted urls for {channel}"))?;
+    diesel::insert_into(seen_urls::table)
+        .values(&rows)
+        .execute(conn)
+        .with_context(|| format!("Cannot save urls for {channel}"))?;
+
+    let old_ids = dsl::seen_urls
+        .filter(dsl::channel.eq(channel))
+        .select(dsl::id)
+        .order_by(dsl::id.desc())
+        .load::<i32>(conn)?
+        .into_iter()
+        .skip(URLS_PER_CHANNEL)
+        .collect::<Vec<_>>();
+    diesel::delete(dsl::seen_urls.filter(dsl::id.eq_any(old_ids)))
+        .execute(conn)
+        .with_context(|| format!("Cannot prune urls for {channel}"))?;
+    Ok(())
+}
+
+pub(crate) fn delete(conn: &SqliteConnection, channel: &str) -> anyhow::Result<()> {
+    diesel::delete(dsl::seen_urls.filter(dsl::channel.eq(channel)))
+        .execute(conn)
+        .with_context(|| format!("Cannot delete urls for {channel}"))?;
+    Ok(())
+}
+
+/// The urls stored in the db, with the same caps as when they were seen
+pub(crate) fn load(conn: &SqliteConnection) -> anyhow::Result<SeenUrls> {
+    let rows = dsl::seen_urls
+        .select((dsl::channel, dsl::url))
+        .order_by(dsl::id.asc())
+        .load::<(String, String)>(conn)
+        .context("Cannot load seen urls")?;
+
+    let mut seen = SeenUrls::default();
+    for (channel, raw_url) in rows {
+        match Url::parse(&raw_url) {
+            Ok(url) => seen.add(&channel, vec![url]),
+            Err(err) => log::warn!("Ignoring invalid stored url {raw_url}: {err}"),
+        }
+    }
+    Ok(seen)
+}
+
+/// The channel the bot is leaving, if the message is the bot parting
+/// or being kicked from a channel.
+pub(crate) fn left_channel<'a>(msg: &'a Message, own_nicks: &OwnNicks) -> Option<&'a str> {
+    match &msg.command {
+        Command::PART(chan, _)
+            if msg
+                .source_nickname()
+                .map_or(false, |nick| own_nicks.is_own_nick(nick)) =>
+        {
+            Some(chan)
+        }
+        Command::KICK(chan, nick, _) if own_nicks.is_own_nick(nick) => Some(chan),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn url(n: usize) -> Url {
+        Url::parse(&format!("https://coucou.com/{n}")).unwrap()
+    }
+
+    #[test]
+    fn test_keeps_recent_urls() {
+        let mut seen = SeenUrls::default();
+        seen.add("#chan", (0..15).map(url).collect());
+        assert_eq!(seen.get("#chan", 0), Some(&url(14)));
+        assert_eq!(seen.get("#chan", 9), Some(&url(5)));
+        assert_eq!(seen.get("#chan", 10), None);
+
+        let listed = seen.list("#chan");
+        assert_eq!(listed.len(), URLS_PER_CHANNEL);
+        assert_eq!(listed[0], &url(14), "same indexing as get");
+        assert_eq!(listed[9], &url(5));
+        assert!(seen.list("#other").is_empty());
+    }
+
+    #[test]
+    fn test_reposts_are_not_duplicated() {
+        let mut seen = SeenUrls::default();
+        seen.add("#chan", (0..10).map(url).collect());
+        seen.add("#chan", vec![url(3), url(3)]);
+        seen.add("#chan", vec![url(3)]);
+
+        let listed = seen.list("#chan");
+        assert_eq!(listed.len(), URLS_PER_CHANNEL, "nothing pushed out");
+        assert_eq!(listed[0], &url(3), "the repost is the most recent one");
+        assert_eq!(listed[1], &url(9));
+        assert_eq!(listed.iter().filter(|u| **u == &url(3)).count(), 1);
+        assert_eq!(seen.get("#chan", 9), Some(&url(0)));
+
+        let conn = SqliteConnection::establish(":memory:").unwrap();
+        crate::db::run_migrations(&conn).unwrap();
+        save(&conn, "#chan", &(0..10).map(url).collect::<Vec<_>>()).unwrap();
+        save(&conn, "#chan", &[url(3), url(11), url(3)]).unwrap();
+        let seen = load(&conn).unwrap();
+        assert_eq!(seen.get("#chan", 0), Some(&url(3)));
+        assert_eq!(seen.get("#chan", 1), Some(&url(11)));
+        assert_eq!(
+            seen.get("#chan", 9),
+            Some(&url(1)),
+            "the oldest one is pruned"
+        );
+    }
+
+    #[test]
+    fn test_find_by_host() {
+        let mut seen = SeenUrls::default();
+        let urls = [
+            "https://www.youtube.com/watch?v=1",
+            "https://github.com/CoucouInc",
+            "https://www.youtube.com/watch?v=2",
+            "https://notyoutube.com/",
+        ];
+        seen.add(
+            "#chan",
+            urls.iter().map(|u| Url::parse(u).unwrap()).collect(),
+        );
+
+        let find = |host| seen.find_by_host("#chan", host).map(|u| u.as_str());
+        assert_eq!(find("youtube.com"), Some(urls[2]), "most recent one");
+        assert_eq!(find("WWW.youtube.com"), Some(urls[2]));
+        assert_eq!(find("github.com"), Some(urls[1]));
+        assert_eq!(find("twitch.tv"), None);
+        assert_eq!(seen.find_by_host("#other", "github.com"), None);
+    }
+
+    #[test]
+    fn test_channel_count_is_bounded() {
+        let mut seen = SeenUrls::new(2);
+        seen.add("#a", vec![url(1)]);
+        seen.add("#b", vec![url(2)]);
+        seen.add("#a", vec![url(3)]);
+        seen.add("#c", vec![url(4)]);
+
+        assert_eq!(seen.channel_count(), 2);
+        assert_eq!(seen.get("#b", 0), None, "least recently updated is evicted");
+        assert_eq!(seen.get("#a", 0), Some(&url(3)));
+        assert_eq!(seen.get("#c", 0), Some(&url(4)));
+
+        seen.add("#d", vec![]);
+        assert_eq!(seen.channel_count(), 2, "no url, no tracking");
+    }
+
+    #[test]
+    fn test_persistence() {
+        let conn = SqliteConnection::establish(":memory:").unwrap();
+        crate::db::run_migrations(&conn).unwrap();
+
+        save(&conn, "#chan", &(0..8).map(url).collect::<Vec<_>>()).unwrap();
+        save(&conn, "#other", &[url(100)]).unwrap();
+        save(&conn, "#chan", &(8..15).map(url).collect::<Vec<_>>()).unwrap();
+
+        let count = |chan: &str| {
+            dsl::seen_urls
+                .filter(dsl::channel.eq(chan))
+                .count()
+                .get_result::<i64>(&conn)
+                .unwrap()
+        };
+        assert_eq!(
+            count("#chan"),
+            URLS_PER_CHANNEL as i64,
+            "old urls are pruned"
+        );
+
+        let seen = load(&conn).unwrap();
+        assert_eq!(seen.get("#chan", 0), Some(&url(14)));
+        assert_eq!(seen.get("#chan", 9), Some(&url(5)));
+        assert_eq!(seen.get("#chan", 10), None);
+        assert_eq!(seen.get("#other", 0), Some(&url(100)));
+
+        delete(&conn, "#chan").unwrap();
+        assert_eq!(count("#chan"), 0);
+        assert_eq!(load(&conn).unwrap().channel_count(), 1);
+    }
+
+    #[test]
+    fn test_leaving_frees_history() {
+        let mut seen = SeenUrls::default();
+        seen.add("#chan", vec![url(1)]);
+        seen.add("#other", vec![url(2)]);
+
+        let part: Message = ":golem!golem@host PART #chan :bye".parse().unwrap();
+        let kick: Message = ":op!op@host KICK #other golem :dehors".parse().unwrap();
+        let someone_else: Message = ":bob!bob@host PART #other".parse().unwrap();
+
+        let own_nicks = OwnNicks::new("golem");
+        assert_eq!(left_channel(&someone_else, &own_nicks), None);
+        for msg in [part, kick] {
+            let chan = left_channel(&msg, &own_nicks).expect("bot left the channel");
+            seen.forget(chan);
+        }
+
+        assert_eq!(seen.channel_count(), 0);
+        assert_eq!(seen.get("#chan", 0), None);
+    }
+
+    #[test]
+    fn test_leaving_with_alt_nick() {
+        let own_nicks = OwnNicks::new("golem");
+        own_nicks.set("irc.libera.chat", "golem_");
+        let part: Message = ":golem_!golem@host PART #chan :bye".parse().unwrap();
+        let kick: Message = ":op!op@host KICK #chan golem_ :dehors".parse().unwrap();
+        let other: Message = ":golem!golem@host PART #chan".parse().unwrap();
+
+        assert_eq!(left_channel(&part, &own_nicks), Some("#chan"));
+        assert_eq!(left_channel(&kick, &own_nicks), Some("#chan"));
+        assert_eq!(
+            left_channel(&other, &own_nicks),
+            None,
+            "someone else has the configured nick"
+        );
+    }
+}
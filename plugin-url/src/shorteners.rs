@@ -0,0 +1,74 @@
+use async_trait::async_trait;
+use plugin_core::Result;
+use url::Url;
+
+use crate::handlers::{TitleSniffer, UrlHandler};
+use crate::UrlTitle;
+
+/// Hosts whose pages are only a redirection to the actual url
+pub(crate) const DEFAULT_SHORTENERS: [&str; 8] = [
+    "bit.ly",
+    "buff.ly",
+    "goo.gl",
+    "is.gd",
+    "ow.ly",
+    "rb.gy",
+    "t.co",
+    "tinyurl.com",
+];
+
+/// Describe short urls by the page at the end of their redirections,
+/// noting which shortener was used
+pub(crate) struct ShortenerHandler {
+    hosts: Vec<String>,
+    sniffer: TitleSniffer,
+}
+
+#[async_trait]
+impl UrlHandler for ShortenerHandler {
+    fn name(&self) -> &'static str {
+        "shortener"
+    }
+
+    fn matches(&self, url: &Url) -> bool {
+        url.host_str()
+            .map_or(false, |host| self.hosts.iter().any(|h| h == host))
+    }
+
+    async fn describe(&self, url: &Url) -> Result<UrlTitle> {
+        match self.sniffer.describe(url).await? {
+            UrlTitle::Found(title) => Ok(UrlTitle::Found(format!(
+                "{title} (via {})",
+                url.host_str().unwrap_or_default()
+            ))),
+            missing => Ok(missing),
+        }
+    }
+}
+
+impl ShortenerHandler {
+    pub(crate) fn new(hosts: Vec<String>, sniffer: TitleSniffer) -> Self {
+        ShortenerHandler { hosts, sniffer }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_matches() {
+        let handler = ShortenerHandler::new(
+            DEFAULT_SHORTENERS.iter().map(|h| h.to_string()).collect(),
+            TitleSniffer {
+                max_title_length: crate::DEFAULT_MAX_TITLE_LENGTH,
+                max_page_size: 1024,
+                max_redirects: crate::guard::DEFAULT_MAX_REDIRECTS,
+            },
+        );
+        assert!(handler.matches(&Url::parse("https://bit.ly/3xyz").unwrap()));
+        assert!(handler.matches(&Url::parse("https://t.co/abcd").unwrap()));
+        assert!(!handler.matches(&Url::parse("https://www.t.co/abcd").unwrap()));
+        assert!(!handler.matches(&Url::parse("https://github.com/CoucouInc").unwrap()));
+    }
+}
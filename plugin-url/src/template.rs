@@ -0,0 +1,103 @@
+use std::collections::HashMap;
+use std::iter::Peekable;
+use std::str::Chars;
+
+/// A format string with `{name}` placeholders, and optional sections
+/// written `{?…}`, which are left out when one of their placeholders is
+/// empty, like `{? - {published}}`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Template {
+    raw: String,
+}
+
+impl Template {
+    pub(crate) fn new(raw: &str) -> Self {
+        Template {
+            raw: raw.to_string(),
+        }
+    }
+
+    /// The placeholders of the template which aren't in `known`
+    pub(crate) fn unknown_placeholders(&self, known: &[&str]) -> Vec<String> {
+        let mut unknown = vec![];
+        let mut chars = self.raw.chars().peekable();
+        while let Some(c) = chars.next() {
+            if c == '{' && chars.peek() != Some(&'?') {
+                let name = chars.by_ref().take_while(|c| *c != '}').collect::<String>();
+                if !known.contains(&name.as_str()) && !unknown.contains(&name) {
+                    unknown.push(name);
+                }
+            }
+        }
+        unknown
+    }
+
+    /// Unknown placeholders are rendered empty
+    pub(crate) fn render(&self, values: &HashMap<&str, String>) -> String {
+        render_section(&mut self.raw.chars().peekable(), values).0
+    }
+}
+
+/// Renders until the end of the current section, and whether all
+/// of its placeholders had a value
+fn render_section(chars: &mut Peekable<Chars>, values: &HashMap<&str, String>) -> (String, bool) {
+    let mut rendered = String::new();
+    let mut complete = true;
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'?') => {
+                chars.next();
+                let (section, section_complete) = render_section(chars, values);
+                if section_complete {
+                    rendered.push_str(&section);
+                }
+            }
+            '{' => {
+                let name = chars.by_ref().take_while(|c| *c != '}').collect::<String>();
+                match values.get(name.as_str()).filter(|v| !v.is_empty()) {
+                    Some(value) => rendered.push_str(value),
+                    None => complete = false,
+                }
+            }
+            // end of the optional section
+            '}' => break,
+            c => rendered.push(c),
+        }
+    }
+    (rendered, complete)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_render() {
+        let template = Template::new("{title} [{channel}{? - {published}}]{? ({views} views)}");
+        let mut values = HashMap::new();
+        values.insert("title", "Coucou".to_string());
+        values.insert("channel", "CoucouInc".to_string());
+        values.insert("published", "".to_string());
+        assert_eq!(template.render(&values), "Coucou [CoucouInc]");
+
+        values.insert("published", "2022-03-01".to_string());
+        values.insert("views", "1.2k".to_string());
+        assert_eq!(
+            template.render(&values),
+            "Coucou [CoucouInc - 2022-03-01] (1.2k views)"
+        );
+    }
+
+    #[test]
+    fn test_unknown_placeholders() {
+        let template = Template::new("{title} {? by {author}} {url} {author}");
+        assert_eq!(
+            template.unknown_placeholders(&["title", "url"]),
+            vec!["author".to_string()]
+        );
+        assert!(template
+            .unknown_placeholders(&["title", "url", "author"])
+            .is_empty());
+    }
+}
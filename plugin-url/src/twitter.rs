@@ -0,0 +1,130 @@
+use async_trait::async_trait;
+use plugin_core::Result;
+use url::Url;
+
+use crate::handlers::{TitleSniffer, UrlHandler};
+use crate::{guard, meta_content, read_head, shorten, text_with_charset, UrlTitle};
+
+/// The meta tags are in the <head>, but it comes with a lot of scripts
+const HEAD_SIZE: usize = 64 * 1024;
+
+/// Describe the posts on X (twitter) by their preview tags, their <title>
+/// is only the name of the site. The page title is used when the tags
+/// are missing.
+pub(crate) struct TwitterHandler {
+    fallback: TitleSniffer,
+}
+
+#[async_trait]
+impl UrlHandler for TwitterHandler {
+    fn name(&self) -> &'static str {
+        "twitter"
+    }
+
+    fn matches(&self, url: &Url) -> bool {
+        is_twitter_status_url(url)
+    }
+
+    async fn describe(&self, url: &Url) -> Result<UrlTitle> {
+        let mut resp = match guard::get(url, self.fallback.max_redirects).await {
+            Ok(resp) if resp.status() == reqwest::StatusCode::OK => resp,
+            _ => return self.fallback.describe(url).await,
+        };
+        let ct = resp.headers().get(reqwest::header::CONTENT_TYPE).cloned();
+        let head = read_head(&mut resp, HEAD_SIZE).await?;
+        let page = text_with_charset(&head, &ct)?;
+        match describe_post(&page, url, self.fallback.max_title_length) {
+            Some(title) => Ok(UrlTitle::Found(title)),
+            None => {
+                log::info!("No preview tags found for {url}");
+                self.fallback.describe(url).await
+            }
+        }
+    }
+}
+
+impl TwitterHandler {
+    pub(crate) fn new(fallback: TitleSniffer) -> Self {
+        TwitterHandler { fallback }
+    }
+}
+
+/// From og:title, usually the author, and og:description, the text of the post
+fn describe_post(page: &str, url: &Url, max_length: usize) -> Option<String> {
+    let document = scraper::Html::parse_document(page);
+    let title = meta_content(&document, r#"meta[property="og:title"]"#);
+    let description = meta_content(&document, r#"meta[property="og:description"]"#).map(|d| {
+        shorten(
+            &d.split_whitespace().collect::<Vec<_>>().join(" "),
+            max_length,
+        )
+    });
+    match (title, description) {
+        (Some(title), Some(description)) => Some(format!("{title}: {description} [{url}]")),
+        (Some(text), None) | (None, Some(text)) => Some(format!("{text} [{url}]")),
+        (None, None) => None,
+    }
+}
+
+/// Like https://x.com/someone/status/1594351283725926400
+fn is_twitter_status_url(url: &Url) -> bool {
+    let host = match url.host_str() {
+        Some(host) => host,
+        None => return false,
+    };
+    let known_host = ["twitter.com", "x.com"].iter().any(|site| {
+        host == *site || host == format!("www.{site}") || host == format!("mobile.{site}")
+    });
+    let is_status = url
+        .path_segments()
+        .map_or(false, |mut segments| segments.nth(1) == Some("status"));
+    known_host && is_status
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_is_twitter_status_url() {
+        let status = |url: &str| is_twitter_status_url(&Url::parse(url).unwrap());
+        assert!(status("https://x.com/someone/status/1594351283725926400"));
+        assert!(status(
+            "https://twitter.com/someone/status/1594351283725926400?s=20"
+        ));
+        assert!(status(
+            "https://mobile.twitter.com/someone/status/159435128"
+        ));
+        assert!(!status("https://x.com/someone"));
+        assert!(!status("https://notx.com/someone/status/159435128"));
+    }
+
+    #[test]
+    fn test_describe_post() {
+        let url = Url::parse("https://x.com/someone/status/123").unwrap();
+        let page = r#"<html><head><title>X</title>
+            <meta property="og:title" content="Someone (@someone) on X">
+            <meta property="og:description" content="coucou
+            le monde">
+            </head></html>"#;
+        assert_eq!(
+            describe_post(page, &url, 100),
+            Some(
+                "Someone (@someone) on X: coucou le monde [https://x.com/someone/status/123]"
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            describe_post(page, &url, 6),
+            Some(
+                "Someone (@someone) on X: coucou[…] [https://x.com/someone/status/123]".to_string()
+            )
+        );
+        assert_eq!(
+            describe_post("<title>X</title>", &url, 100),
+            None,
+            "falls back on the title"
+        );
+    }
+}
@@ -0,0 +1,737 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use google_youtube3::api::{
+    PlaylistListResponse, SearchListResponse, SearchResult, VideoListResponse,
+};
+use plugin_core::{Error, Result};
+use serde::de::DeserializeOwned;
+use url::Url;
+
+use crate::cache::TtlCache;
+use crate::handlers::UrlHandler;
+use crate::template::Template;
+use crate::UrlTitle;
+
+/// Reply when the youtube api answers without the fields we need
+const INCOMPLETE_YT_RESPONSE: &str = "réponse YouTube incomplète";
+
+/// How videos are described, see `VIDEO_PLACEHOLDERS`
+pub(crate) const DEFAULT_VIDEO_TEMPLATE: &str =
+    "{title} [{channel}{? - {published}}]{? ({details})}{? @ {start}}{? (playlist: {playlist})} [{url}]";
+
+/// `details` is the duration and the views, whichever are known
+pub(crate) const VIDEO_PLACEHOLDERS: [&str; 9] = [
+    "title",
+    "channel",
+    "published",
+    "duration",
+    "views",
+    "details",
+    "start",
+    "playlist",
+    "url",
+];
+
+/// Describe videos, channels and playlists with the youtube api
+pub(crate) struct YoutubeHandler {
+    client: reqwest::Client,
+    api_key: String,
+    video_template: Template,
+    /// api responses by request, so that reposted links don't use any quota
+    cache: TtlCache,
+}
+
+#[async_trait]
+impl UrlHandler for YoutubeHandler {
+    fn name(&self) -> &'static str {
+        "youtube"
+    }
+
+    fn matches(&self, url: &Url) -> bool {
+        is_yt_url(url)
+    }
+
+    async fn describe(&self, url: &Url) -> Result<UrlTitle> {
+        self.get_yt_url(url).await.map(UrlTitle::Found)
+    }
+}
+
+impl YoutubeHandler {
+    pub(crate) fn new(
+        client: reqwest::Client,
+        api_key: String,
+        video_template: Template,
+        cache: TtlCache,
+    ) -> Self {
+        YoutubeHandler {
+            client,
+            api_key,
+            video_template,
+            cache,
+        }
+    }
+
+    async fn get_yt_url(&self, url: &Url) -> Result<String> {
+        let yt_api_key = self.api_key.as_str();
+        let yt_id = match extract_yt_id(url) {
+            Some(x) => x,
+            None => {
+                return Ok(format!(
+                    "Ook Ook 🙈, pas possible de trouver quoi query pour {}",
+                    url
+                ))
+            }
+        };
+
+        log::debug!("fetching yt data for {yt_id:?}");
+        match yt_id {
+            YtId::Video(vid_id, start, playlist_id) => {
+                let vids: VideoListResponse = self
+                    .yt_api_call(
+                        yt_api_key,
+                        "videos",
+                        "snippet,contentDetails,statistics",
+                        &vid_id,
+                    )
+                    .await?;
+                match vids.items.unwrap_or_default().first() {
+                    Some(vid) => {
+                        let snip = match vid.snippet.as_ref() {
+                            Some(snip) => snip,
+                            None => return Ok(incomplete_yt_response(&vid_id)),
+                        };
+                        let duration = vid
+                            .content_details
+                            .as_ref()
+                            .and_then(|d| d.duration.as_deref())
+                            .and_then(parse_duration)
+                            .map(format_duration);
+                        let views = vid
+                            .statistics
+                            .as_ref()
+                            .and_then(|s| s.view_count.as_deref())
+                            .and_then(|v| v.parse().ok())
+                            .map(|v| format!("{} views", humanize_count(v)));
+                        let details = duration.iter().chain(&views).cloned().collect::<Vec<_>>();
+                        let playlist = match playlist_id {
+                            Some(playlist_id) => {
+                                self.playlist_title(yt_api_key, &playlist_id).await
+                            }
+                            None => None,
+                        };
+
+                        let mut values = HashMap::new();
+                        values.insert("title", snip.title.clone().unwrap_or_default());
+                        values.insert("channel", snip.channel_title.clone().unwrap_or_default());
+                        values.insert("published", snip.published_at.clone().unwrap_or_default());
+                        values.insert("duration", duration.unwrap_or_default());
+                        values.insert("views", views.unwrap_or_default());
+                        values.insert("details", details.join(", "));
+                        values.insert("start", start.map(format_duration).unwrap_or_default());
+                        values.insert("playlist", playlist.unwrap_or_default());
+                        values.insert("url", url.to_string());
+                        Ok(self.video_template.render(&values))
+                    }
+                    None => Ok(format!("Rien trouvé pour vidéo {vid_id}")),
+                }
+            }
+            YtId::Channel(chan_name) => {
+                let request = self
+                    .client
+                    .get("https://www.googleapis.com/youtube/v3/search")
+                    .query(&[("key", yt_api_key)])
+                    .query(&[("part", "snippet")])
+                    .query(&[("type", "channel")])
+                    .query(&[("q", chan_name)]);
+                let body = match self
+                    .fetch_cached(format!("channel/{chan_name}"), request)
+                    .await
+                {
+                    Ok(body) => body,
+                    Err(err) if err.status() == Some(reqwest::StatusCode::NOT_FOUND) => {
+                        return Ok(format!("Pas trouvé de chan pour {chan_name}"));
+                    }
+                    Err(err) => match err.status() {
+                        Some(status) => return Ok(format!("Ooops, status code: {status}")),
+                        None => {
+                            return Err(Error::Wrapped {
+                                source: Box::new(err),
+                                ctx: format!("Failed to fetch channel with id {chan_name}"),
+                            })
+                        }
+                    },
+                };
+
+                let results: SearchListResponse =
+                    serde_json::from_str(&body).map_err(|err| Error::Wrapped {
+                        source: Box::new(err),
+                        ctx: format!("Cannot parse response when fetching channel {chan_name}"),
+                    })?;
+
+                match results.items.unwrap_or_default().first() {
+                    Some(search_result) => Ok(format_channel(search_result, url)
+                        .unwrap_or_else(|| incomplete_yt_response(chan_name))),
+                    None => Ok(format!("Pas trouvé de chan pour {chan_name}")),
+                }
+            }
+            YtId::Playlist(playlist_id) => {
+                let playlists: PlaylistListResponse = self
+                    .yt_api_call(yt_api_key, "playlists", "snippet", &playlist_id)
+                    .await?;
+                match playlists.items.unwrap_or_default().first() {
+                    Some(playlist) => {
+                        let snip = match playlist.snippet.as_ref() {
+                            Some(snip) => snip,
+                            None => return Ok(incomplete_yt_response(&playlist_id)),
+                        };
+                        let title = snip.title.as_deref().unwrap_or("");
+                        Ok(format!("Playlist: {} [{}]", &title, &url))
+                    }
+                    None => Ok(format!("Pas de playlist trouvée pour {playlist_id}")),
+                }
+            }
+        }
+    }
+
+    /// Title of the playlist a video is watched from. It's only a detail
+    /// of the video, so failing to get it is logged and ignored.
+    async fn playlist_title(&self, yt_api_key: &str, playlist_id: &str) -> Option<String> {
+        let playlists: PlaylistListResponse = match self
+            .yt_api_call(yt_api_key, "playlists", "snippet", playlist_id)
+            .await
+        {
+            Ok(playlists) => playlists,
+            Err(err) => {
+                log::warn!("Cannot get the playlist {playlist_id}: {err}");
+                return None;
+            }
+        };
+        playlists
+            .items?
+            .into_iter()
+            .next()?
+            .snippet?
+            .title
+            .filter(|t| !t.is_empty())
+    }
+
+    async fn yt_api_call<T, Q>(
+        &self,
+        yt_api_key: &str,
+        resource: &str,
+        part: &str,
+        resource_id: Q,
+    ) -> Result<T>
+    where
+        T: DeserializeOwned,
+        Q: serde::Serialize + std::fmt::Display,
+    {
+        let mut url = Url::parse("https://www.googleapis.com/youtube/v3").unwrap();
+        url.path_segments_mut().unwrap().push(resource);
+
+        let request = self
+            .client
+            .get(url)
+            .query(&[("id", &resource_id)])
+            .query(&[("key", yt_api_key.to_owned())])
+            .query(&[("part", part)]);
+        let body = self
+            .fetch_cached(format!("{resource}/{part}/{resource_id}"), request)
+            .await
+            .map_err(|err| Error::Wrapped {
+                source: Box::new(err),
+                ctx: format!("Failed to fetch {resource} with id {resource_id}"),
+            })?;
+        serde_json::from_str(&body).map_err(|err| Error::Wrapped {
+            source: Box::new(err),
+            ctx: format!("Failed to parse {resource} with id {resource_id}"),
+        })
+    }
+
+    /// The body of a successful response, from the cache when the same
+    /// request was made recently. Failed requests aren't cached.
+    async fn fetch_cached(
+        &self,
+        cache_key: String,
+        request: reqwest::RequestBuilder,
+    ) -> reqwest::Result<String> {
+        if let Some(body) = self.cache.get(&cache_key) {
+            log::debug!("youtube api response for {cache_key} found in cache");
+            return Ok(body);
+        }
+        let body = request
+            .timeout(Duration::from_secs(10))
+            .send()
+            .await?
+            .error_for_status()?
+            .text()
+            .await?;
+        self.cache.insert(cache_key, body.clone());
+        Ok(body)
+    }
+}
+
+pub(crate) fn incomplete_yt_response(query: &str) -> String {
+    log::warn!("Incomplete youtube api response for {query}");
+    format!("{INCOMPLETE_YT_RESPONSE} pour {query}")
+}
+
+/// None if the search result lacks its snippet
+fn format_channel(search_result: &SearchResult, url: &Url) -> Option<String> {
+    let snip = search_result.snippet.as_ref()?;
+    let title = snip.channel_title.as_deref().unwrap_or("");
+    let description = snip.description.as_deref().unwrap_or("");
+    let published_at = snip
+        .published_at
+        .as_deref()
+        .map(|d| format!(" - {d}"))
+        .unwrap_or_else(|| "".to_string());
+    if description.is_empty() {
+        Some(format!("Channel: {}{} [{}]", title, published_at, url))
+    } else {
+        Some(format!(
+            "Channel: {}{} ({}) [{}]",
+            title, published_at, description, url
+        ))
+    }
+}
+
+/// None if the search result lacks the id or the snippet needed for its kind
+pub(crate) fn format_search_result(
+    search_result: &SearchResult,
+    search_term: &str,
+) -> Option<String> {
+    let id = search_result.id.as_ref()?;
+    let snippet = search_result.snippet.as_ref();
+    let channel_title = snippet
+        .and_then(|x| x.channel_title.as_deref())
+        .unwrap_or("no channel found");
+
+    match id.kind.as_deref()? {
+        "youtube#channel" => {
+            let channel_id = snippet?.channel_id.as_ref()?;
+            Some(format!(
+                "channel: [{channel_title}] https://www.youtube.com/channel/{channel_id}"
+            ))
+        }
+        "youtube#playlist" => {
+            let title = snippet?.title.as_ref()?;
+            let playlist_id = id.playlist_id.as_ref()?;
+            Some(format!(
+                "playlist: {title} https://www.youtube.com/playlist?list={playlist_id}"
+            ))
+        }
+        "youtube#video" => {
+            let title = snippet?.title.as_ref()?;
+            let vid_id = id.video_id.as_ref()?;
+            Some(format!(
+                "{title} [{channel_title}] https://www.youtube.com/watch?v={vid_id}"
+            ))
+        }
+        _ => Some(format!("Rien trouvé pour {search_term} /o\\")),
+    }
+}
+
+/// Number of seconds in an ISO 8601 duration, as returned by the youtube api,
+/// like PT1H2M3S or P1DT2H
+fn parse_duration(raw: &str) -> Option<u64> {
+    let mut secs = 0;
+    let mut number = String::new();
+    let mut in_time = false;
+    for c in raw.strip_prefix('P')?.chars() {
+        match c {
+            '0'..='9' => number.push(c),
+            'T' if number.is_empty() => in_time = true,
+            _ => {
+                let n: u64 = number.parse().ok()?;
+                number.clear();
+                secs += n * match (c, in_time) {
+                    ('W', false) => 7 * 24 * 60 * 60,
+                    ('D', false) => 24 * 60 * 60,
+                    ('H', true) => 60 * 60,
+                    ('M', true) => 60,
+                    ('S', true) => 1,
+                    _ => return None,
+                };
+            }
+        }
+    }
+    if number.is_empty() {
+        Some(secs)
+    } else {
+        None
+    }
+}
+
+/// H:MM:SS, or M:SS for videos shorter than an hour
+fn format_duration(secs: u64) -> String {
+    let (hours, minutes, seconds) = (secs / 3600, (secs % 3600) / 60, secs % 60);
+    if hours > 0 {
+        format!("{hours}:{minutes:02}:{seconds:02}")
+    } else {
+        format!("{minutes}:{seconds:02}")
+    }
+}
+
+/// 1234567 -> 1.2M
+fn humanize_count(n: u64) -> String {
+    match n {
+        0..=999 => n.to_string(),
+        1_000..=999_999 => format!("{:.1}k", n as f64 / 1e3),
+        1_000_000..=999_999_999 => format!("{:.1}M", n as f64 / 1e6),
+        _ => format!("{:.1}B", n as f64 / 1e9),
+    }
+}
+
+const YT_HOSTNAMES: [&str; 5] = [
+    "youtube.com",
+    "www.youtube.com",
+    "youtu.be",
+    "www.youtu.be",
+    "m.youtube.com",
+];
+
+fn is_yt_url(url: &Url) -> bool {
+    url.host()
+        .map(|h| match h {
+            url::Host::Domain(domain) => YT_HOSTNAMES.contains(&domain),
+            url::Host::Ipv4(_) | url::Host::Ipv6(_) => false,
+        })
+        .unwrap_or(false)
+}
+
+#[derive(PartialEq, Eq, Debug)]
+enum YtId<'url> {
+    /// video id, where to start watching in seconds,
+    /// and the playlist it's watched from
+    Video(Cow<'url, str>, Option<u64>, Option<Cow<'url, str>>),
+    Channel(&'url str),
+    Playlist(Cow<'url, str>),
+}
+
+fn extract_yt_id(url: &Url) -> Option<YtId<'_>> {
+    let mut segments = url.path_segments()?;
+    let first_segment = segments.next();
+    let second_segment = segments.next();
+    let start = url
+        .query_pairs()
+        .find(|(k, _)| k == "t" || k == "start")
+        .and_then(|(_, v)| parse_timestamp(&v));
+
+    let query_param = |name: &str| {
+        url.query_pairs()
+            .find_map(|(k, v)| if k == name { Some(v) } else { None })
+    };
+    let playlist = query_param("list");
+
+    if matches!(url.host(), Some(url::Host::Domain("youtu.be"))) {
+        return first_segment.map(|v| YtId::Video(Cow::Borrowed(v), start, playlist));
+    }
+
+    match first_segment {
+        Some("c") | Some("channel") | Some("user") => second_segment.map(YtId::Channel),
+        // the video wins over the playlist it's played from
+        Some("watch") => query_param("v").map(|v| YtId::Video(v, start, playlist)),
+        Some("shorts") => second_segment.map(|v| YtId::Video(Cow::Borrowed(v), start, None)),
+        Some("playlist") => playlist.map(YtId::Playlist),
+        _ => None,
+    }
+}
+
+/// The `t` parameter of youtube urls, either in seconds like 256,
+/// or with units like 1m30s
+fn parse_timestamp(raw: &str) -> Option<u64> {
+    if let Ok(secs) = raw.parse() {
+        return Some(secs);
+    }
+
+    let mut secs = 0;
+    let mut number = String::new();
+    for c in raw.chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+        let n: u64 = number.parse().ok()?;
+        number.clear();
+        secs += n * match c {
+            'h' => 60 * 60,
+            'm' => 60,
+            's' => 1,
+            _ => return None,
+        };
+    }
+    if number.is_empty() {
+        Some(secs)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_incomplete_yt_search_result() {
+        use google_youtube3::api::{ResourceId, SearchResultSnippet};
+
+        let video_id = ResourceId {
+            kind: Some("youtube#video".to_string()),
+            video_id: Some("dQw4w9WgXcQ".to_string()),
+            ..Default::default()
+        };
+        let snippet = SearchResultSnippet {
+            title: Some("Never gonna give you up".to_string()),
+            channel_title: Some("Rick Astley".to_string()),
+            ..Default::default()
+        };
+
+        let complete = SearchResult {
+            id: Some(video_id.clone()),
+            snippet: Some(snippet.clone()),
+            ..Default::default()
+        };
+        assert_eq!(
+            format_search_result(&complete, "rick"),
+            Some(
+                "Never gonna give you up [Rick Astley] https://www.youtube.com/watch?v=dQw4w9WgXcQ"
+                    .to_string()
+            )
+        );
+
+        let no_snippet = SearchResult {
+            id: Some(video_id.clone()),
+            ..Default::default()
+        };
+        let no_id = SearchResult {
+            snippet: Some(snippet),
+            ..Default::default()
+        };
+        let no_video_id = SearchResult {
+            id: Some(ResourceId {
+                video_id: None,
+                ..video_id
+            }),
+            ..complete.clone()
+        };
+        for incomplete in [&no_snippet, &no_id, &no_video_id] {
+            assert_eq!(format_search_result(incomplete, "rick"), None);
+        }
+
+        let url = Url::parse("https://youtube.com/c/RickAstleyYT").unwrap();
+        assert_eq!(format_channel(&no_snippet, &url), None);
+        assert_eq!(
+            incomplete_yt_response("rick"),
+            "réponse YouTube incomplète pour rick"
+        );
+    }
+
+    #[test]
+    fn test_default_video_template() {
+        let template = Template::new(DEFAULT_VIDEO_TEMPLATE);
+        assert!(template
+            .unknown_placeholders(&VIDEO_PLACEHOLDERS)
+            .is_empty());
+
+        let mut values = HashMap::new();
+        values.insert("title", "Never gonna give you up".to_string());
+        values.insert("channel", "Rick Astley".to_string());
+        values.insert("url", "https://youtu.be/dQw4w9WgXcQ".to_string());
+        assert_eq!(
+            template.render(&values),
+            "Never gonna give you up [Rick Astley] [https://youtu.be/dQw4w9WgXcQ]"
+        );
+        values.insert("published", "2009-10-25".to_string());
+        values.insert("details", "3:33, 1.5B views".to_string());
+        values.insert("start", "0:43".to_string());
+        values.insert("playlist", "80s".to_string());
+        assert_eq!(
+            template.render(&values),
+            "Never gonna give you up [Rick Astley - 2009-10-25] (3:33, 1.5B views) @ 0:43 (playlist: 80s) [https://youtu.be/dQw4w9WgXcQ]"
+        );
+    }
+
+    #[test]
+    fn test_parse_timestamp() {
+        assert_eq!(parse_timestamp("256"), Some(256));
+        assert_eq!(parse_timestamp("256s"), Some(256));
+        assert_eq!(parse_timestamp("1m30s"), Some(90));
+        assert_eq!(parse_timestamp("1h2m"), Some(3720));
+        assert_eq!(parse_timestamp("1x"), None);
+        assert_eq!(parse_timestamp("m"), None);
+        assert_eq!(format_duration(256), "4:16");
+    }
+
+    #[test]
+    fn test_video_details() {
+        assert_eq!(parse_duration("PT12M34S"), Some(12 * 60 + 34));
+        assert_eq!(parse_duration("PT1H2M3S"), Some(3723));
+        assert_eq!(parse_duration("PT45S"), Some(45));
+        assert_eq!(parse_duration("P1DT2H"), Some(26 * 3600));
+        assert_eq!(parse_duration("P0D"), Some(0), "live streams");
+        assert_eq!(parse_duration("PT12"), None);
+        assert_eq!(parse_duration("12M"), None);
+        assert_eq!(parse_duration("PT1D"), None, "days aren't a time unit");
+
+        assert_eq!(format_duration(12 * 60 + 34), "12:34");
+        assert_eq!(format_duration(3723), "1:02:03");
+        assert_eq!(format_duration(5), "0:05");
+
+        assert_eq!(humanize_count(999), "999");
+        assert_eq!(humanize_count(1_234), "1.2k");
+        assert_eq!(humanize_count(1_234_567), "1.2M");
+        assert_eq!(humanize_count(4_200_000_000), "4.2B");
+    }
+
+    #[test]
+    fn test_is_yt_url() {
+        assert!(!is_yt_url(
+            &Url::parse("https://github.com/CoucouInc/rustygolem").unwrap()
+        ));
+
+        assert!(is_yt_url(
+            &Url::parse("https://youtube.com/c/BosnianApeSociety").unwrap()
+        ));
+
+        assert!(is_yt_url(
+            &Url::parse("https://www.youtube.com/watch?v=0F5GQAnj0lo").unwrap()
+        ));
+
+        assert!(is_yt_url(
+            &Url::parse("https://youtu.be/haLBM94SENg?t=256").unwrap()
+        ));
+
+        assert!(is_yt_url(
+            &Url::parse("https://m.youtube.com/watch?v=haLBM94SENg").unwrap()
+        ));
+
+        assert!(is_yt_url(
+            &Url::parse(
+                "https://m.youtube.com/watch?list=PLJcTRymdlUQPwx8qU4ln83huPx-6Y3XxH&v=5MKjPYuD60I&feature=emb_imp_woyt"
+            )
+            .unwrap()
+        ));
+    }
+
+    #[test]
+    fn test_extract_yt_id() {
+        assert_eq!(
+            extract_yt_id(&Url::parse("https://github.com/CoucouInc/rustygolem").unwrap()),
+            None
+        );
+
+        assert_eq!(
+            extract_yt_id(&Url::parse("https://www.youtube.com/results?search_query=mj").unwrap()),
+            None
+        );
+
+        assert_eq!(
+            extract_yt_id(&Url::parse("https://youtu.be/6gwBOTggfRc").unwrap()),
+            Some(YtId::Video("6gwBOTggfRc".into(), None, None))
+        );
+
+        assert_eq!(
+            extract_yt_id(&Url::parse("https://www.youtube.com/watch?v=ZZ3F3zWiEmc").unwrap()),
+            Some(YtId::Video("ZZ3F3zWiEmc".into(), None, None))
+        );
+
+        assert_eq!(
+            extract_yt_id(&Url::parse("https://www.youtube.com/shorts/EU4p-OC4O3o").unwrap()),
+            Some(YtId::Video("EU4p-OC4O3o".into(), None, None))
+        );
+
+        assert_eq!(
+            extract_yt_id(&Url::parse("https://youtu.be/haLBM94SENg?t=256").unwrap()),
+            Some(YtId::Video("haLBM94SENg".into(), Some(256), None))
+        );
+
+        assert_eq!(
+            extract_yt_id(
+                &Url::parse("https://www.youtube.com/watch?v=ZZ3F3zWiEmc&t=1m30s").unwrap()
+            ),
+            Some(YtId::Video("ZZ3F3zWiEmc".into(), Some(90), None))
+        );
+
+        assert_eq!(
+            extract_yt_id(
+                &Url::parse("https://www.youtube.com/watch?v=ZZ3F3zWiEmc&t=nope").unwrap()
+            ),
+            Some(YtId::Video("ZZ3F3zWiEmc".into(), None, None)),
+            "invalid timestamps are ignored"
+        );
+
+        assert_eq!(
+            extract_yt_id(
+                &Url::parse("https://www.youtube.com/c/%E3%81%8B%E3%82%89%E3%82%81%E3%82%8B")
+                    .unwrap()
+            ),
+            // からめる
+            Some(YtId::Channel("%E3%81%8B%E3%82%89%E3%82%81%E3%82%8B"))
+        );
+
+        assert_eq!(
+            extract_yt_id(&Url::parse("https://www.youtube.com/c/inanutshell").unwrap()),
+            Some(YtId::Channel("inanutshell"))
+        );
+
+        assert_eq!(
+            extract_yt_id(&Url::parse("https://www.youtube.com/c/inanutshell/videos").unwrap()),
+            Some(YtId::Channel("inanutshell"))
+        );
+
+        assert_eq!(
+            extract_yt_id(
+                &Url::parse("https://www.youtube.com/channel/UCworsKCR-Sx6R6-BnIjS2MA").unwrap()
+            ),
+            Some(YtId::Channel("UCworsKCR-Sx6R6-BnIjS2MA"))
+        );
+
+        assert_eq!(
+            extract_yt_id(&Url::parse("https://youtube.com/c/BosnianApeSociety").unwrap()),
+            Some(YtId::Channel("BosnianApeSociety"))
+        );
+
+        assert_eq!(
+            extract_yt_id(
+                &Url::parse(
+                    "https://www.youtube.com/playlist?list=PLoBxKk9n0UWcv0HTYARFyCb0s9P21cDSd"
+                )
+                .unwrap()
+            ),
+            Some(YtId::Playlist("PLoBxKk9n0UWcv0HTYARFyCb0s9P21cDSd".into()))
+        );
+
+        assert_eq!(
+            extract_yt_id(
+                &Url::parse(
+                    "https://m.youtube.com/watch?list=PLJcTRymdlUQPwx8qU4ln83huPx-6Y3XxH&v=5MKjPYuD60I&feature=emb_imp_woyt"
+                )
+                .unwrap()
+            ),
+            Some(YtId::Video(
+                "5MKjPYuD60I".into(),
+                None,
+                Some("PLJcTRymdlUQPwx8qU4ln83huPx-6Y3XxH".into())
+            ))
+        );
+
+        assert_eq!(
+            extract_yt_id(&Url::parse("https://youtu.be/5MKjPYuD60I?list=PLJcTRymdl").unwrap()),
+            Some(YtId::Video(
+                "5MKjPYuD60I".into(),
+                None,
+                Some("PLJcTRymdl".into())
+            ))
+        );
+
+        assert_eq!(
+            extract_yt_id(&Url::parse("https://www.youtube.com/user/VieDeChouhartem").unwrap()),
+            Some(YtId::Channel("VieDeChouhartem"))
+        );
+    }
+}
@@ -0,0 +1,181 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use anyhow::Context;
+use google_youtube3::api::{SearchListResponse, SearchResult};
+use irc::proto::{Command, Message};
+use serde::Deserialize;
+use tokio::sync::mpsc;
+
+pub(crate) const DEFAULT_LIVE_INTERVAL: Duration = Duration::from_secs(10 * 60);
+/// Each check costs 100 units of the daily youtube quota, for every channel
+pub(crate) const MIN_LIVE_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct LiveSpec {
+    /// id of the youtube channel, the UC… part of youtube.com/channel/UC…
+    pub(crate) channel_id: String,
+    /// how the channel is called in the announcements
+    pub(crate) name: String,
+    /// Which channels to notify?
+    pub(crate) irc_channels: Vec<String>,
+}
+
+/// Polls youtube for the lives of the watched channels
+pub(crate) struct LiveWatcher {
+    client: reqwest::Client,
+    api_key: String,
+    streams: Vec<LiveSpec>,
+    interval: Duration,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+struct LiveVideo {
+    id: String,
+    title: String,
+}
+
+impl LiveWatcher {
+    pub(crate) fn new(
+        client: reqwest::Client,
+        api_key: String,
+        streams: Vec<LiveSpec>,
+        interval: Duration,
+    ) -> Self {
+        LiveWatcher {
+            client,
+            api_key,
+            streams,
+            interval: interval.max(MIN_LIVE_INTERVAL),
+        }
+    }
+
+    /// Only stops when the announcements cannot be sent anymore, fetch errors are logged.
+    /// The lives already going on at startup aren't announced, like for twitch.
+    pub(crate) async fn run(&self, bot_chan: mpsc::Sender<Message>) -> anyhow::Result<()> {
+        let mut announced = Announced::default();
+        let mut first_check = true;
+        loop {
+            for spec in &self.streams {
+                let live = match self.live_video(&spec.channel_id).await {
+                    Ok(live) => live,
+                    Err(err) => {
+                        log::warn!("Cannot check youtube lives of {}: {:#}", spec.name, err);
+                        continue;
+                    }
+                };
+                let msg = match announced.update(spec, live) {
+                    Some(_) if first_check => {
+                        log::info!("{} is already live on youtube", spec.name);
+                        continue;
+                    }
+                    Some(msg) => msg,
+                    None => continue,
+                };
+                log::info!("Youtube live: {msg}");
+                for channel in &spec.irc_channels {
+                    bot_chan
+                        .send(Command::PRIVMSG(channel.clone(), msg.clone()).into())
+                        .await
+                        .with_context(|| format!("can't send message to {}", channel))?;
+                }
+            }
+            first_check = false;
+            tokio::time::sleep(self.interval).await;
+        }
+    }
+
+    /// The live currently going on for the channel, if any
+    async fn live_video(&self, channel_id: &str) -> anyhow::Result<Option<LiveVideo>> {
+        let resp: SearchListResponse = self
+            .client
+            .get("https://www.googleapis.com/youtube/v3/search")
+            .query(&[("key", self.api_key.as_str())])
+            .query(&[("part", "snippet")])
+            .query(&[("channelId", channel_id)])
+            .query(&[("eventType", "live")])
+            .query(&[("type", "video")])
+            .timeout(Duration::from_secs(10))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        Ok(resp
+            .items
+            .unwrap_or_default()
+            .iter()
+            .find_map(live_video_of))
+    }
+}
+
+/// None if the search result lacks its id or title
+fn live_video_of(search_result: &SearchResult) -> Option<LiveVideo> {
+    Some(LiveVideo {
+        id: search_result.id.as_ref()?.video_id.clone()?,
+        title: search_result.snippet.as_ref()?.title.clone()?,
+    })
+}
+
+/// The lives already announced, by youtube channel id,
+/// so that a long live is only announced once
+#[derive(Default)]
+struct Announced(HashMap<String, String>);
+
+impl Announced {
+    /// The announcement to make when the channel started a new live
+    fn update(&mut self, spec: &LiveSpec, live: Option<LiveVideo>) -> Option<String> {
+        match live {
+            None => {
+                self.0.remove(&spec.channel_id);
+                None
+            }
+            Some(video) if self.0.get(&spec.channel_id) == Some(&video.id) => None,
+            Some(video) => {
+                self.0.insert(spec.channel_id.clone(), video.id.clone());
+                Some(format_live(&spec.name, &video))
+            }
+        }
+    }
+}
+
+fn format_live(name: &str, video: &LiveVideo) -> String {
+    let url = format!("https://www.youtube.com/watch?v={}", video.id);
+    match video.title.trim() {
+        "" => format!("{name} est maintenant live sur YouTube at {url}!"),
+        title => format!("{name} est maintenant live sur YouTube at {url}: {title}"),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_announce_once() {
+        let spec = LiveSpec {
+            channel_id: "UCcoucou".to_string(),
+            name: "CoucouInc".to_string(),
+            irc_channels: vec!["#arch-fr-free".to_string()],
+        };
+        let video = |id: &str| {
+            Some(LiveVideo {
+                id: id.to_string(),
+                title: "speedrun".to_string(),
+            })
+        };
+        let mut announced = Announced::default();
+        assert_eq!(
+            announced.update(&spec, video("abc")),
+            Some("CoucouInc est maintenant live sur YouTube at https://www.youtube.com/watch?v=abc: speedrun".to_string())
+        );
+        assert_eq!(announced.update(&spec, video("abc")), None, "same live");
+        assert!(announced.update(&spec, video("def")).is_some(), "new live");
+        assert_eq!(announced.update(&spec, None), None);
+        assert!(
+            announced.update(&spec, video("def")).is_some(),
+            "live again after going offline"
+        );
+    }
+}
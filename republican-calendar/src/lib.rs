@@ -2,7 +2,6 @@ use std::convert::TryFrom;
 
 use time::Date;
 
-#[allow(dead_code)] // never constructed because I'm using the mapping to u8
 #[derive(Eq, PartialEq, Debug, Clone, Copy)]
 #[repr(u8)]
 pub(crate) enum Month {
@@ -42,15 +41,39 @@ impl std::fmt::Display for Month {
     }
 }
 
+impl std::str::FromStr for Month {
+    type Err = &'static str;
+
+    /// The month names as displayed, ignoring the case
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        (0..=12)
+            .filter_map(|m| Month::try_from(m).ok())
+            .find(|m| m.to_string().to_lowercase() == s.to_lowercase())
+            .ok_or("Mois inconnu")
+    }
+}
+
 impl TryFrom<u8> for Month {
     type Error = &'static str;
 
     fn try_from(value: u8) -> Result<Self, Self::Error> {
-        if value <= 12 {
-            Ok(unsafe { std::mem::transmute(value) })
-        } else {
-            Err("Month cannot be strictly greater than 12")
-        }
+        let month = match value {
+            0 => Month::Vnd,
+            1 => Month::Bru,
+            2 => Month::Fri,
+            3 => Month::Niv,
+            4 => Month::Plu,
+            5 => Month::Vnt,
+            6 => Month::Ger,
+            7 => Month::Flo,
+            8 => Month::Pra,
+            9 => Month::Mes,
+            10 => Month::The,
+            11 => Month::Fru,
+            12 => Month::SC,
+            _ => return Err("Month cannot be strictly greater than 12"),
+        };
+        Ok(month)
     }
 }
 
@@ -80,10 +103,52 @@ impl std::fmt::Display for RepublicanDate {
 // but with less features (at least for now)
 
 impl RepublicanDate {
+    /// Build a date from its parts, the month being its french name like `Nivôse`
+    pub fn new(year: i32, month: &str, day: u8) -> Result<Self, &'static str> {
+        let month = month.parse()?;
+        if day == 0 || day > days_in_month(year, month) {
+            return Err("Ce jour n'existe pas dans ce mois");
+        }
+        Ok(RepublicanDate { year, month, day })
+    }
+
+    /// Sextile years have a 6th sans-culottide. The calendar was abandoned
+    /// before a rule was settled, so this is Romme's rule, the same
+    /// as the gregorian one.
+    pub fn is_sextile(year: i32) -> bool {
+        year % 4 == 0 && (year % 100 != 0 || year % 400 == 0)
+    }
+
+    /// Inverse of the conversion from a gregorian date
+    pub fn to_gregorian(&self) -> Result<Date, &'static str> {
+        let (french_era_end, fake_french_date, padding) = era_anchors()?;
+        if self.year < 20 {
+            return Err("Can only convert date from after the official end of the calendar");
+        }
+
+        let day_of_year = (self.month as i64) * 30 + self.day as i64 - 1;
+        let year_start = Date::from_calendar_date(self.year + padding, time::Month::January, 1)
+            .map_err(|e| e.name())?;
+        let french_date = year_start
+            .checked_add(time::Duration::days(day_of_year))
+            .ok_or("Date trop lointaine")?;
+        if french_date.year() != year_start.year() {
+            // only the leap years have a 6th sans-culottide
+            return Err("Ce jour n'existe pas cette année");
+        }
+
+        french_era_end
+            .checked_add(french_date - fake_french_date)
+            .ok_or("Date trop lointaine")
+    }
+
     fn from_yd(y: i32, day_of_year: i64) -> Result<Self, &'static str> {
         let raw_m = day_of_year / 30;
         let month = Month::try_from(raw_m as u8)?; // .context(format!("cannot find a month for {}", raw_m))?;
         let day = day_of_year - raw_m * 30 + 1; // 0 based
+        if day < 1 || day > days_in_month(y, month) as i64 {
+            return Err("Day out of range for this month");
+        }
         Ok(RepublicanDate {
             year: y,
             month,
@@ -489,20 +554,12 @@ impl TryFrom<Date> for RepublicanDate {
     type Error = &'static str;
 
     fn try_from(value: Date) -> Result<Self, Self::Error> {
-        let french_era_end =
-            Date::from_calendar_date(1811, time::Month::September, 23).map_err(|e| e.name())?;
+        let (french_era_end, fake_french_date, padding) = era_anchors()?;
         let duration_since_french_era_end = value - french_era_end;
         if duration_since_french_era_end.is_negative() {
             return Err("Can only convert date from after the official end of the calendar");
         }
 
-        // create a fake Date object so we can perform conversion on it
-        // and then extract the year and day of year. In the republican calendar
-        // the last year was 20, but at that time, there was no leap year yet, so
-        // artificially pad it.
-        let padding = 2000;
-        let fake_french_date = Date::from_calendar_date(20 + padding, time::Month::January, 1)
-            .map_err(|e| e.name())?;
         let french_date = fake_french_date + duration_since_french_era_end;
         let tmp_date =
             Date::from_calendar_date(french_date.year(), time::Month::January, 1).unwrap();
@@ -512,6 +569,30 @@ impl TryFrom<Date> for RepublicanDate {
     }
 }
 
+fn days_in_month(year: i32, month: Month) -> u8 {
+    match month {
+        Month::SC if RepublicanDate::is_sextile(year) => 6,
+        Month::SC => 5,
+        _ => 30,
+    }
+}
+
+/// The official end of the calendar, the fake gregorian date matching it,
+/// and the padding used to get this fake date.
+fn era_anchors() -> Result<(Date, Date, i32), &'static str> {
+    let french_era_end =
+        Date::from_calendar_date(1811, time::Month::September, 23).map_err(|e| e.name())?;
+
+    // create a fake Date object so we can perform conversion on it
+    // and then extract the year and day of year. In the republican calendar
+    // the last year was 20, but at that time, there was no leap year yet, so
+    // artificially pad it.
+    let padding = 2000;
+    let fake_french_date =
+        Date::from_calendar_date(20 + padding, time::Month::January, 1).map_err(|e| e.name())?;
+    Ok((french_era_end, fake_french_date, padding))
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -529,4 +610,98 @@ mod test {
             })
         );
     }
+
+    #[test]
+    fn test_month_try_from() {
+        use Month::*;
+        let expected = [
+            Vnd, Bru, Fri, Niv, Plu, Vnt, Ger, Flo, Pra, Mes, The, Fru, SC,
+        ];
+        for (value, month) in expected.into_iter().enumerate() {
+            assert_eq!(Month::try_from(value as u8), Ok(month));
+            assert_eq!(month as u8, value as u8, "discriminant matches for {month}");
+        }
+        assert!(Month::try_from(13).is_err());
+        assert!(Month::try_from(u8::MAX).is_err());
+    }
+
+    #[test]
+    fn test_to_gregorian() {
+        assert_eq!(
+            RepublicanDate::new(229, "Nivôse", 25)
+                .unwrap()
+                .to_gregorian(),
+            Ok(Date::from_calendar_date(2021, time::Month::January, 14).unwrap())
+        );
+        assert_eq!(
+            RepublicanDate::new(229, "nivôse", 25),
+            RepublicanDate::new(229, "Nivôse", 25),
+            "case insensitive month"
+        );
+        assert!(RepublicanDate::new(229, "Janvier", 25).is_err());
+        assert!(RepublicanDate::new(228, "Sans-Culottides", 7).is_err());
+        assert!(RepublicanDate::new(229, "Nivôse", 0).is_err());
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let start = Date::from_calendar_date(2020, time::Month::January, 1).unwrap();
+        for days in 0..(4 * 366) {
+            let date = start + time::Duration::days(days);
+            let rd = RepublicanDate::try_from(date).unwrap();
+            assert_eq!(rd.to_gregorian(), Ok(date), "round trip for {date} ({rd})");
+        }
+    }
+
+    #[test]
+    fn test_sextile_year() {
+        let date = |y, m, d| Date::from_calendar_date(y, m, d).unwrap();
+        assert!(RepublicanDate::is_sextile(228));
+        assert_eq!(
+            RepublicanDate::try_from(date(2020, time::Month::September, 21)),
+            Ok(RepublicanDate {
+                year: 228,
+                month: Month::SC,
+                day: 6
+            }),
+            "fête de la révolution in a sextile year"
+        );
+
+        assert!(!RepublicanDate::is_sextile(229));
+        assert_eq!(
+            RepublicanDate::try_from(date(2021, time::Month::September, 21)),
+            Ok(RepublicanDate {
+                year: 229,
+                month: Month::SC,
+                day: 5
+            })
+        );
+        assert_eq!(
+            RepublicanDate::try_from(date(2021, time::Month::September, 22)),
+            Ok(RepublicanDate {
+                year: 230,
+                month: Month::Vnd,
+                day: 1
+            }),
+            "no 6th sans-culottide in a normal year"
+        );
+        assert!(RepublicanDate::new(229, "Sans-Culottides", 6).is_err());
+        assert!(RepublicanDate::from_yd(229, 365).is_err());
+        assert!(!RepublicanDate::is_sextile(300));
+        assert!(RepublicanDate::is_sextile(400));
+    }
+
+    #[test]
+    fn test_sans_culottide_leap_year() {
+        // 6th sans-culottide only exists in leap years
+        let leap = (204..208)
+            .filter(|y| {
+                RepublicanDate::new(*y, "Sans-Culottides", 6)
+                    .unwrap()
+                    .to_gregorian()
+                    .is_ok()
+            })
+            .count();
+        assert_eq!(leap, 1);
+    }
 }
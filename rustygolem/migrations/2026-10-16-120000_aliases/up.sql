@@ -0,0 +1,5 @@
+-- Your SQL goes here
+CREATE TABLE IF NOT EXISTS aliases (
+  name TEXT PRIMARY KEY NOT NULL,
+  expansion TEXT NOT NULL
+)
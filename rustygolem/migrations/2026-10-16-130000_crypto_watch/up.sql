@@ -0,0 +1,8 @@
+-- Your SQL goes here
+CREATE TABLE IF NOT EXISTS crypto_watch (
+  channel TEXT NOT NULL,
+  coin TEXT NOT NULL,
+  interval_secs BIGINT NOT NULL,
+  next_fire TIMESTAMP NOT NULL,
+  PRIMARY KEY (channel, coin)
+)
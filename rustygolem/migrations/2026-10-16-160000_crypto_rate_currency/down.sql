@@ -0,0 +1,13 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE crypto_rate RENAME TO crypto_rate_tmp;
+CREATE TABLE crypto_rate (
+  date DATETIME NOT NULL,
+  coin TEXT CHECK(coin in ("BTC", "ETH", "DOGE", "XRP", "ALGO")) NOT NULL,
+  rate REAL NOT NULL,
+  PRIMARY KEY(date, coin)
+);
+
+INSERT INTO crypto_rate
+  SELECT date, coin, rate FROM crypto_rate_tmp
+  WHERE currency = 'EUR' AND coin in ("BTC", "ETH", "DOGE", "XRP", "ALGO");
+DROP TABLE crypto_rate_tmp;
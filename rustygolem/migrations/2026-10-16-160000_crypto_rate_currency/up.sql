@@ -0,0 +1,13 @@
+-- Your SQL goes here
+-- The coins now come from the config, so no more check on them
+ALTER TABLE crypto_rate RENAME TO crypto_rate_tmp;
+CREATE TABLE crypto_rate (
+  date DATETIME NOT NULL,
+  coin TEXT NOT NULL,
+  rate REAL NOT NULL,
+  currency TEXT NOT NULL DEFAULT 'EUR',
+  PRIMARY KEY(date, coin, currency)
+);
+
+INSERT INTO crypto_rate (date, coin, rate) SELECT date, coin, rate FROM crypto_rate_tmp;
+DROP TABLE crypto_rate_tmp;
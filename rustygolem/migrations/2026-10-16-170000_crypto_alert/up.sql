@@ -0,0 +1,9 @@
+-- Your SQL goes here
+CREATE TABLE IF NOT EXISTS crypto_alert (
+  channel TEXT NOT NULL,
+  nick TEXT NOT NULL,
+  coin TEXT NOT NULL,
+  direction TEXT CHECK(direction in ("above", "below")) NOT NULL,
+  threshold REAL NOT NULL,
+  PRIMARY KEY (channel, nick, coin, direction, threshold)
+)
@@ -0,0 +1,8 @@
+-- Your SQL goes here
+CREATE TABLE IF NOT EXISTS reminders (
+  id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+  nick TEXT NOT NULL,
+  channel TEXT NOT NULL,
+  fire_at TIMESTAMP NOT NULL,
+  message TEXT NOT NULL
+)
@@ -0,0 +1,8 @@
+-- Your SQL goes here
+CREATE TABLE IF NOT EXISTS seen (
+  channel TEXT NOT NULL,
+  nick TEXT NOT NULL,
+  last_seen TIMESTAMP NOT NULL,
+  message TEXT NOT NULL,
+  PRIMARY KEY (channel, nick)
+)
@@ -0,0 +1,7 @@
+-- Your SQL goes here
+CREATE TABLE IF NOT EXISTS karma (
+  channel TEXT NOT NULL,
+  thing TEXT NOT NULL,
+  score INTEGER NOT NULL,
+  PRIMARY KEY (channel, thing)
+)
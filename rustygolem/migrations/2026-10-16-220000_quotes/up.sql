@@ -0,0 +1,9 @@
+-- Your SQL goes here
+CREATE TABLE IF NOT EXISTS quotes (
+  channel TEXT NOT NULL,
+  id INTEGER NOT NULL,
+  added_by TEXT NOT NULL,
+  text TEXT NOT NULL,
+  added_at TIMESTAMP NOT NULL,
+  PRIMARY KEY (channel, id)
+)
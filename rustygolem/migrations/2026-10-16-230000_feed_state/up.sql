@@ -0,0 +1,5 @@
+-- Your SQL goes here
+CREATE TABLE IF NOT EXISTS feed_state (
+  feed TEXT PRIMARY KEY NOT NULL,
+  last_entry_id TEXT NOT NULL
+)
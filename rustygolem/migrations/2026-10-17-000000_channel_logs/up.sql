@@ -0,0 +1,10 @@
+-- Your SQL goes here
+CREATE TABLE IF NOT EXISTS channel_logs (
+  id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+  logged_at TIMESTAMP NOT NULL,
+  channel TEXT NOT NULL,
+  nick TEXT NOT NULL,
+  text TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS channel_logs_nick ON channel_logs (channel, lower(nick))
@@ -0,0 +1,5 @@
+-- Your SQL goes here
+CREATE TABLE IF NOT EXISTS lastfm_users (
+  irc_nick TEXT NOT NULL PRIMARY KEY,
+  lastfm_user TEXT NOT NULL
+)
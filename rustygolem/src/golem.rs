@@ -1,9 +1,24 @@
+use crate::db;
 use crate::plugins;
+use crate::utils::admin;
+use crate::utils::caps::{self, CapSummary};
+use crate::utils::help;
+use crate::utils::messages;
+use crate::utils::plugin_toggle::{self, DisabledPlugins};
+use crate::utils::rejoin::RejoinPolicy;
+use crate::utils::throttle::{DuplicateGuard, RateLimiter};
 use anyhow::{Context, Result};
 use axum::Router;
 use futures::prelude::*;
 use irc::client::ClientStream;
 use irc::proto::{CapSubCommand, Command, Message, Response};
+use plugin_core::config::{ConfigError, ConfigSection};
+use plugin_core::metrics::BotMetrics;
+use plugin_core::utils::backoff::Backoff;
+use plugin_core::utils::network;
+use plugin_core::utils::nicks::OwnNicks;
+use plugin_core::utils::owners::Owners;
+use plugin_core::utils::parser;
 use plugin_core::{Initialised, Plugin};
 use serde::Deserialize;
 use std::path::Path;
@@ -13,94 +28,303 @@ use std::time::Duration;
 use tokio::sync::{mpsc, oneshot, Mutex as AsyncMutex};
 use tokio::time::timeout;
 
-#[derive(Debug, Deserialize)]
+const DEFAULT_DUPLICATE_MESSAGE_WINDOW_MS: u64 = 5000;
+const DEFAULT_SEND_RATE_PER_SECOND: f64 = 1.0;
+const DEFAULT_SEND_BURST: u32 = 5;
+const DEFAULT_REJOIN_DELAY: Duration = Duration::from_secs(10);
+/// kicks in a row after which the bot stays out of the channel
+const MAX_REJOIN_ATTEMPTS: u32 = 3;
+const RECONNECT_MIN_DELAY: Duration = Duration::from_secs(1);
+const RECONNECT_MAX_DELAY: Duration = Duration::from_secs(5 * 60);
+/// how long to wait for the server, then nickserv, when taking back the nick
+const REGAIN_NICK_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Deserialize)]
 struct GolemConfig {
     blacklisted_users: Vec<String>,
     plugins: Vec<String>,
     sasl_password: Option<String>,
     server_bind_address: String,
     server_bind_port: u16,
+    /// don't send the same message twice in a row to a target within this window.
+    /// 0 disables the check.
+    duplicate_message_window_ms: Option<u64>,
+    /// IRCv3 capabilities to request, when supported by the server
+    capabilities: Option<Vec<String>>,
+    /// messages sent to the server per second, once the burst is exhausted.
+    /// 0 disables the limit.
+    send_rate_per_second: Option<f64>,
+    /// messages which can be sent at once
+    send_burst: Option<u32>,
+    /// rejoin the channels the bot gets kicked from, true by default
+    auto_rejoin: Option<bool>,
+    /// wait before rejoining, doubled for each kick in a row
+    rejoin_delay_secs: Option<u64>,
+    /// channels where the bot stays out once kicked
+    no_rejoin_channels: Option<Vec<String>>,
+    /// what commands start with, `&` and `λ` by default
+    command_prefixes: Option<Vec<String>>,
+    /// sqlite database of the plugins, `rustygolem.sqlite` by default
+    db_path: Option<String>,
+    /// other networks to connect to, on top of the one given on the command line
+    extra_servers: Option<Vec<ServerSpec>>,
+}
+
+/// Each server gets its own connection, the plugins are shared and keep
+/// the state of each server apart
+#[derive(Debug, Clone, Deserialize)]
+struct ServerSpec {
+    server: String,
+    port: u16,
+    use_tls: bool,
+    nickname: String,
+    channels: Vec<String>,
+    /// server password, sent with PASS
+    password: Option<String>,
+    sasl_password: Option<String>,
+    /// the same plugins as the main server when None
+    plugins: Option<Vec<String>>,
+}
+
+impl ServerSpec {
+    /// The settings which aren't specific to a server come from the main one
+    fn irc_config(&self, main: &irc::client::data::Config) -> irc::client::data::Config {
+        irc::client::data::Config {
+            nickname: Some(self.nickname.clone()),
+            alt_nicks: vec![format!("{}_", self.nickname)],
+            server: Some(self.server.clone()),
+            port: Some(self.port),
+            use_tls: Some(self.use_tls),
+            channels: self.channels.clone(),
+            password: self.password.clone(),
+            ..main.clone()
+        }
+    }
+}
+
+impl ConfigSection for GolemConfig {
+    const SECTION: Option<&'static str> = None;
+    const SCHEMA: &'static str = "{ blacklisted_users : List Text, plugins : List Text, \
+        sasl_password : Optional Text, server_bind_address : Text, server_bind_port : Natural, \
+        duplicate_message_window_ms : Optional Natural, capabilities : Optional (List Text), \
+        send_rate_per_second : Optional Double, send_burst : Optional Natural, \
+        auto_rejoin : Optional Bool, rejoin_delay_secs : Optional Natural, \
+        no_rejoin_channels : Optional (List Text), command_prefixes : Optional (List Text), \
+        db_path : Optional Text, extra_servers : Optional (List { server : Text, port : Natural, \
+        use_tls : Bool, nickname : Text, channels : List Text, password : Optional Text, \
+        sasl_password : Optional Text, plugins : Optional (List Text) }) }";
 }
 
 impl GolemConfig {
-    pub fn from_path<P>(config_path: P) -> std::result::Result<GolemConfig, serde_dhall::Error>
+    pub fn from_path<P>(config_path: P) -> std::result::Result<GolemConfig, ConfigError>
     where
         P: AsRef<Path>,
     {
-        serde_dhall::from_file(config_path).parse::<GolemConfig>()
+        plugin_core::config::load(config_path)
     }
 }
 
 pub struct Golem {
+    /// kept to rebuild the client when the connection drops
+    irc_config: irc::client::data::Config,
+    /// what the messages of this server are tagged with for the plugins,
+    /// None for the main server
+    network: Option<String>,
     irc_client: Arc<Mutex<irc::client::Client>>,
     message_stream: AsyncMutex<ClientStream>,
     sasl_password: Option<String>,
+    /// the nick after a change made once connected, like taking back the
+    /// configured nick. The irc client only knows about its alt nicks.
+    changed_nick: Mutex<Option<String>>,
+    /// shared with the plugins, which only know the configured nick otherwise
+    own_nicks: OwnNicks,
+    capabilities: Vec<String>,
     blacklisted_users: Vec<String>,
-    plugins: Vec<Box<dyn Plugin>>,
+    /// allowed to use λadmin, shared with the plugins. When a server tags
+    /// messages with the account of the sender, owners must be logged in there.
+    owners: Owners,
+    /// the plugins of this server, their instances are shared by the golems
+    /// of all the servers
+    plugins: Vec<Arc<dyn Plugin>>,
+    /// the plugins whose run loop is driven by this golem: all of them for
+    /// the main server and none for the others, so that the background
+    /// tasks only run once
+    running_plugins: Vec<Arc<dyn Plugin>>,
+    /// the golems of all the servers, this one first, where the messages
+    /// of the run loops are routed. Empty for the extra servers.
+    peers: Vec<Peer>,
+    /// messages of the run loops to send to this server
+    outbox: mpsc::Sender<(&'static str, Message)>,
+    inbox: AsyncMutex<mpsc::Receiver<(&'static str, Message)>>,
     /// bind the local server on this address
     address: std::net::SocketAddr,
     /// axum router so that plugins can define their own routes and state
     /// if required. For example for webhooks
     router: Option<Router<()>>,
+    /// last lines of each channel, shared with the plugins
+    history: plugin_core::MessageHistory,
+    outbound_guard: DuplicateGuard,
+    /// shared by everything sending to the server
+    rate_limiter: RateLimiter,
+    /// None when the bot shouldn't rejoin after a kick
+    rejoin: Option<RejoinPolicy>,
+    /// command prefix shown by λhelp
+    help_prefix: String,
+    metrics: BotMetrics,
+    /// toggled by the owners with λplugin
+    disabled_plugins: DisabledPlugins,
 }
 
 impl Golem {
-    #[allow(dead_code)]
-    pub async fn new_from_config(
+    /// One golem for the given server, and one for each of the extra servers
+    /// of the config. The plugins are only initialised once, their run loops
+    /// and webhooks are driven by the first golem. The messages of the run
+    /// loops are sent to the server they are tagged with, or else to the
+    /// server which joined their channel.
+    pub async fn new_all_from_config(
         irc_config: irc::client::data::Config,
         golem_config_path: String,
-    ) -> Result<Self> {
-        let mut irc_client = irc::client::Client::from_config(irc_config).await?;
+    ) -> Result<Vec<Self>> {
         let conf = GolemConfig::from_path(&golem_config_path)
             .with_context(|| format!("Cannot parse golem config at {golem_config_path}"))?;
         log::debug!("Loaded config: {conf:?}");
+        let extra_servers = conf.extra_servers.clone().unwrap_or_default();
+        let mut names = conf.plugins.clone();
+        for name in extra_servers
+            .iter()
+            .flat_map(|s| s.plugins.iter().flatten())
+        {
+            if !names.contains(name) {
+                names.push(name.clone());
+            }
+        }
+        let shared = SharedPlugins::init(
+            &irc_config,
+            golem_config_path,
+            &conf,
+            &names,
+            BotMetrics::default(),
+        )
+        .await?;
 
-        let core_config = plugin_core::Config {
-            config_path: golem_config_path,
-        };
-        let core_config = Arc::new(core_config);
+        let mut golems = vec![
+            Self::new(
+                irc_config.clone(),
+                None,
+                conf.clone(),
+                &shared,
+                &conf.plugins,
+            )
+            .await?,
+        ];
+        for spec in extra_servers {
+            let server_conf = GolemConfig {
+                sasl_password: spec.sasl_password.clone(),
+                ..conf.clone()
+            };
+            let names = spec.plugins.clone().unwrap_or_else(|| conf.plugins.clone());
+            let network = Some(spec.server.clone());
+            let golem = Self::new(
+                spec.irc_config(&irc_config),
+                network,
+                server_conf,
+                &shared,
+                &names,
+            )
+            .await
+            .with_context(|| format!("Cannot set up golem for {}", spec.server))?;
+            golems.push(golem);
+        }
 
-        let inits = stream::iter(conf.plugins)
-            .map(|name| {
-                let core_config = Arc::clone(&core_config);
-                async move { init_plugin(&core_config, &name).await }
+        let peers = golems
+            .iter()
+            .map(|g| Peer {
+                network: g.network.clone(),
+                irc_client: Arc::clone(&g.irc_client),
+                outbox: g.outbox.clone(),
             })
-            .buffer_unordered(10)
-            .collect::<Vec<_>>()
-            .await
-            .into_iter()
-            .collect::<Result<Vec<_>>>()?;
+            .collect();
+        golems[0].peers = peers;
+        golems[0].drive_plugins(shared);
+        Ok(golems)
+    }
 
-        let mut router: Option<Router<()>> = None;
-        let mut plugins = Vec::with_capacity(inits.len());
-        for init in inits {
-            if let Some(r) = init.router {
-                match router {
-                    Some(x) => {
-                        log::info!("Mounting a router from plugin {}", init.plugin.get_name());
-                        router = Some(x.merge(r))
-                    }
-                    None => router = Some(r),
-                }
-            }
-            plugins.push(init.plugin);
-        }
+    /// The golem of a server, with the given plugins among the shared ones
+    async fn new(
+        irc_config: irc::client::data::Config,
+        network: Option<String>,
+        conf: GolemConfig,
+        shared: &SharedPlugins,
+        plugin_names: &[String],
+    ) -> Result<Self> {
+        let owners = shared.owners.clone();
+        let own_nicks = shared.own_nicks.clone();
+        let mut irc_client = irc::client::Client::from_config(irc_config.clone()).await?;
+        let plugins = shared.select(plugin_names);
+        let (outbox, inbox) = mpsc::channel(10);
 
         let addr = std::net::IpAddr::from_str(&conf.server_bind_address)?;
         let address = std::net::SocketAddr::from((addr, conf.server_bind_port));
         let message_stream = irc_client.stream()?;
+        let rejoin = if conf.auto_rejoin.unwrap_or(true) {
+            Some(RejoinPolicy::new(
+                conf.rejoin_delay_secs
+                    .map(Duration::from_secs)
+                    .unwrap_or(DEFAULT_REJOIN_DELAY),
+                MAX_REJOIN_ATTEMPTS,
+                conf.no_rejoin_channels.unwrap_or_default(),
+            ))
+        } else {
+            None
+        };
+        let duplicate_window = Duration::from_millis(
+            conf.duplicate_message_window_ms
+                .unwrap_or(DEFAULT_DUPLICATE_MESSAGE_WINDOW_MS),
+        );
 
         Ok(Self {
+            irc_config,
+            network,
             irc_client: Arc::new(Mutex::new(irc_client)),
             message_stream: AsyncMutex::new(message_stream),
             sasl_password: conf.sasl_password,
+            changed_nick: Mutex::new(None),
+            own_nicks,
+            capabilities: conf.capabilities.unwrap_or_else(|| {
+                caps::DEFAULT_CAPABILITIES
+                    .iter()
+                    .map(|c| c.to_string())
+                    .collect()
+            }),
             blacklisted_users: conf.blacklisted_users,
+            owners,
             plugins,
+            running_plugins: vec![],
+            peers: vec![],
+            outbox,
+            inbox: AsyncMutex::new(inbox),
             address,
-            router,
+            router: None,
+            history: shared.history.clone(),
+            outbound_guard: DuplicateGuard::new(duplicate_window),
+            rate_limiter: RateLimiter::new(
+                conf.send_rate_per_second
+                    .unwrap_or(DEFAULT_SEND_RATE_PER_SECOND),
+                conf.send_burst.unwrap_or(DEFAULT_SEND_BURST),
+            ),
+            rejoin,
+            help_prefix: shared.help_prefix.clone(),
+            metrics: shared.metrics.clone(),
+            disabled_plugins: shared.disabled_plugins.clone(),
         })
     }
 
+    /// Run the loops of all the plugins and serve their webhooks
+    fn drive_plugins(&mut self, shared: SharedPlugins) {
+        self.running_plugins = shared.plugins.into_iter().map(|(_, p)| p).collect();
+        self.router = shared.router;
+    }
+
     pub async fn run(&mut self) -> Result<()> {
         self.authenticate_and_identify()
             .await
@@ -119,17 +343,186 @@ impl Golem {
     }
 
     async fn authenticate_and_identify(&self) -> Result<()> {
+        let summary = self.negotiate_capabilities().await?;
+        if summary.is_acked("account-tag") {
+            self.owners.require_account(self.network.as_deref());
+        }
+
         match self.sasl_password {
             None => {
                 log::info!("No SASL_PASSWORD env var found, not authenticating anything.");
-                self.irc_client.lock().unwrap().identify()?;
-                Ok(())
             }
-            Some(ref password) => {
+            Some(ref password) if summary.is_acked("sasl") => {
                 self.sasl_auth(password).await?;
-                Ok(())
+            }
+            Some(_) => anyhow::bail!("SASL password given but the server didn't ack sasl"),
+        }
+
+        self.irc_client
+            .lock()
+            .unwrap()
+            .send(Command::CAP(None, CapSubCommand::END, None, None))?;
+        log::info!("Handshake finished, ready to work");
+
+        if self.sasl_password.is_some() {
+            if let Err(err) = self.regain_nick().await {
+                log::warn!("Cannot take back the nick: {err:#}");
+            }
+        }
+        Ok(())
+    }
+
+    /// When the configured nick was taken and an alt nick is used instead,
+    /// ask nickserv to free it. Only works once authenticated, and
+    /// gives up after a while instead of blocking the startup.
+    async fn regain_nick(&self) -> Result<()> {
+        timeout(
+            REGAIN_NICK_TIMEOUT,
+            self.wait_for_message(|msg| {
+                matches!(msg.command, Command::Response(Response::RPL_WELCOME, _))
+            }),
+        )
+        .await
+        .context("Timeout waiting for the registration")??;
+
+        let nick = match &self.irc_config.nickname {
+            Some(nick) => nick.clone(),
+            None => return Ok(()),
+        };
+        let own_nick = self.own_nick();
+        if own_nick.eq_ignore_ascii_case(&nick) {
+            return Ok(());
+        }
+
+        log::info!("{nick} is taken, using {own_nick}, asking nickserv to regain it");
+        self.irc_client.lock().unwrap().send(Command::PRIVMSG(
+            "NickServ".to_string(),
+            format!("REGAIN {nick}"),
+        ))?;
+        let regained = timeout(
+            REGAIN_NICK_TIMEOUT,
+            self.wait_for_message(|msg| nick_change(msg, &own_nick) == Some(nick.as_str())),
+        )
+        .await;
+        match regained {
+            Ok(msg) => {
+                msg?;
+                log::info!("Regained the nick {nick}");
+                *self.changed_nick.lock().unwrap() = Some(nick);
+            }
+            Err(_) => {
+                // some services only know about GHOST, the change
+                // of nick is noticed later, among the other messages
+                log::warn!("No answer to REGAIN {nick}, trying GHOST");
+                let client = self.irc_client.lock().unwrap();
+                client.send(Command::PRIVMSG(
+                    "NickServ".to_string(),
+                    format!("GHOST {nick}"),
+                ))?;
+                client.send(Command::NICK(nick))?;
             }
         }
+        Ok(())
+    }
+
+    fn own_nick(&self) -> String {
+        match &*self.changed_nick.lock().unwrap() {
+            Some(nick) => nick.clone(),
+            None => self
+                .irc_client
+                .lock()
+                .unwrap()
+                .current_nickname()
+                .to_string(),
+        }
+    }
+
+    /// Ask the server which capabilities it supports, and request all the
+    /// configured ones it knows about in a single CAP REQ.
+    /// The client.identify() provided by the irc library starts by sending
+    /// a CAP END before sending NICK and USER messages, which ends the negotiation
+    /// too early, so manually send the stuff.
+    async fn negotiate_capabilities(&self) -> Result<CapSummary> {
+        let duration = Duration::from_secs(10);
+        {
+            let client = self.irc_client.lock().unwrap();
+            let nick = client.current_nickname();
+            client.send(Command::CAP(
+                None,
+                CapSubCommand::LS,
+                Some("302".to_string()),
+                None,
+            ))?;
+            let password = self.irc_config.password();
+            if !password.is_empty() {
+                client.send(Command::PASS(password.to_string()))?;
+            }
+            client.send(Command::NICK(nick.to_string()))?;
+            // the last argument is sent as a trailing one by the irc library
+            client.send(Command::USER(
+                self.irc_config.username().to_string(),
+                "0".to_string(),
+                self.irc_config.real_name().to_string(),
+            ))?;
+        }
+
+        let mut available = Vec::new();
+        loop {
+            let msg = timeout(
+                duration,
+                self.wait_for_message(|msg| caps::ls_reply(msg).is_some()),
+            )
+            .await
+            .context("Timeout waiting for CAP LS")??;
+            match caps::ls_reply(&msg) {
+                Some((ls_caps, true)) => available.extend(ls_caps),
+                Some((ls_caps, false)) => {
+                    available.extend(ls_caps);
+                    break;
+                }
+                None => unreachable!("waited for a CAP LS reply"),
+            }
+        }
+
+        let wanted = self
+            .capabilities
+            .iter()
+            .filter(|c| self.sasl_password.is_some() || c.as_str() != "sasl")
+            .cloned()
+            .collect::<Vec<_>>();
+        let requested = caps::to_request(&wanted, &available);
+        let unsupported = wanted
+            .iter()
+            .filter(|c| !requested.contains(c))
+            .collect::<Vec<_>>();
+
+        let mut summary = CapSummary::default();
+        if !requested.is_empty() {
+            self.irc_client
+                .lock()
+                .unwrap()
+                .send(caps::cap_req(&requested))?;
+            let reply = timeout(
+                duration,
+                self.wait_for_message(|msg| {
+                    matches!(
+                        msg.command,
+                        Command::CAP(_, CapSubCommand::ACK | CapSubCommand::NAK, _, _)
+                    )
+                }),
+            )
+            .await
+            .context("Timeout waiting for CAP ACK/NAK")??;
+            summary.record(&reply);
+        }
+
+        log::info!(
+            "Capabilities acked: {:?}, naked: {:?}, not supported by the server: {:?}",
+            summary.acked,
+            summary.naked,
+            unsupported
+        );
+        Ok(summary)
     }
 
     // SASL PLAIN authentication
@@ -139,30 +532,7 @@ impl Golem {
         let nick = client.current_nickname();
         log::info!("Authenticating with SASL for {nick}");
 
-        client.send_cap_req(&[irc::proto::Capability::Sasl])?;
-        // the call client.identify() provided by the irc library starts
-        // by sending a CAP END before sending NICK and USER messages.
-        // but as far as I can tell, this is incorrect for SASL, so manually send
-        // the stuff
-        client.send(Command::NICK(nick.to_string()))?;
-        client.send(Command::USER(
-            nick.to_string(),
-            "0".to_string(),
-            format!(":{nick}"),
-        ))?;
-
         let duration = Duration::from_secs(10);
-        timeout(
-            duration,
-            self.wait_for_message(|msg| match &msg.command {
-                Command::CAP(_, CapSubCommand::ACK, Some(opt), _) if opt == "sasl" => true,
-                _ => false,
-            }),
-        )
-        .await
-        .context("Timeout waiting for CAP ACK sasl")??;
-
-        log::info!("GOT ACK for SASL !");
         client.send_sasl_plain()?;
 
         timeout(
@@ -193,10 +563,6 @@ impl Golem {
             anyhow::bail!("SASL auth failed {resp:?}");
         }
         log::info!("SASL authenticated");
-
-        client.send(Command::CAP(None, CapSubCommand::END, None, None))?;
-        log::info!("Handshake finished, ready to work");
-
         Ok(())
     }
 
@@ -215,9 +581,81 @@ impl Golem {
         anyhow::bail!("Waited for message failed");
     }
 
+    /// Consume the incoming messages, reconnecting when the connection drops.
+    /// Only returns on plugin errors.
     async fn recv_irc_messages(&self) -> Result<()> {
+        let mut backoff = Backoff::new(RECONNECT_MIN_DELAY, RECONNECT_MAX_DELAY);
+        loop {
+            self.consume_irc_messages().await?;
+
+            let mut attempt = 1;
+            loop {
+                let delay = backoff.next_delay();
+                log::info!("Reconnecting in {delay:?}, attempt {attempt}");
+                tokio::time::sleep(delay).await;
+                match self.reconnect().await {
+                    Ok(()) => break,
+                    Err(err) => log::error!("Reconnection attempt {attempt} failed: {err:?}"),
+                }
+                attempt += 1;
+            }
+            log::info!("Reconnected after {attempt} attempt(s)");
+            backoff.reset();
+        }
+    }
+
+    /// Rebuild the client from the config, and go through the handshake again.
+    /// The client joins the configured channels by itself once identified.
+    async fn reconnect(&self) -> Result<()> {
+        let mut irc_client = irc::client::Client::from_config(self.irc_config.clone()).await?;
+        let message_stream = irc_client.stream()?;
+        *self.irc_client.lock().unwrap() = irc_client;
+        *self.message_stream.lock().await = message_stream;
+        *self.changed_nick.lock().unwrap() = None;
+        self.authenticate_and_identify()
+            .await
+            .context("Problem while authenticating")
+    }
+
+    /// Returns once the connection to the server is lost.
+    /// Errors come from the plugins.
+    async fn consume_irc_messages(&self) -> Result<()> {
         let mut message_stream = self.message_stream.lock().await;
-        while let Some(irc_message) = message_stream.next().await.transpose()? {
+        loop {
+            let irc_message = match message_stream.next().await {
+                Some(Ok(msg)) => msg,
+                Some(Err(err)) => {
+                    log::error!("IRC connection error: {err:?}");
+                    return Ok(());
+                }
+                None => {
+                    log::error!("IRC receiving stream exited");
+                    return Ok(());
+                }
+            };
+            let own_nick = self.own_nick();
+            if let Some(new_nick) = nick_change(&irc_message, &own_nick) {
+                log::info!("Nick changed from {own_nick} to {new_nick}");
+                *self.changed_nick.lock().unwrap() = Some(new_nick.to_string());
+            }
+            self.own_nicks.set(
+                self.irc_config.server.as_deref().unwrap_or_default(),
+                &self.own_nick(),
+            );
+            // plugins shouldn't react to what the bot said itself
+            if caps::is_echo(&irc_message, &own_nick) {
+                continue;
+            }
+            self.metrics.messages_received.inc();
+
+            if let Command::KICK(channel, nick, _) = &irc_message.command {
+                if nick == &own_nick {
+                    self.on_kicked(channel);
+                }
+            }
+
+            let irc_message = network::with_network(irc_message, self.network.as_deref());
+            let irc_message = self.rewrite_message(irc_message);
             let messages = self
                 .plugins_in_messages(&irc_message)
                 .await
@@ -226,56 +664,165 @@ impl Golem {
             for message in messages.into_iter().flatten() {
                 self.outbound_message(&message).await?;
             }
+            for reply in self.help_replies(&irc_message) {
+                self.outbound_message(&("help", reply)).await?;
+            }
+            if let Some(command) = self.admin_command(&irc_message) {
+                self.outbound_message(&("admin", command)).await?;
+            }
+            if let Some(reply) = self.plugin_command_reply(&irc_message) {
+                self.outbound_message(&("plugins", reply)).await?;
+            }
+
+            // recorded after the plugins ran, so that they see the previous line
+            self.history.record(&irc_message, &own_nick);
         }
-        Err(anyhow!("IRC receiving stream exited"))
     }
 
-    async fn plugins_in_messages(
-        &self,
-        msg: &Message,
-    ) -> Result<Vec<Option<(&'static str, Message)>>> {
-        let mut results = Vec::with_capacity(self.plugins.len());
+    /// Rejoin the channel later, without blocking the incoming messages
+    fn on_kicked(&self, channel: &str) {
+        let delay = match &self.rejoin {
+            None => return,
+            Some(policy) => policy.on_kick(channel, std::time::Instant::now()),
+        };
+        let delay = match delay {
+            Some(delay) => delay,
+            None => {
+                log::warn!("Kicked from {channel}, staying out");
+                return;
+            }
+        };
 
-        let (txs, rxs): (Vec<_>, Vec<_>) = self.plugins.iter().map(|_| oneshot::channel()).unzip();
+        log::warn!("Kicked from {channel}, rejoining in {delay:?}");
+        let client = Arc::clone(&self.irc_client);
+        let channel = channel.to_string();
+        tokio::spawn(async move {
+            tokio::time::sleep(delay).await;
+            let result = client.lock().unwrap().send_join(&channel);
+            if let Err(err) = result {
+                log::error!("Cannot rejoin {channel}: {err:?}");
+            }
+        });
+    }
 
-        futures::stream::iter(self.plugins.iter().zip(txs))
-            .map(Ok)
-            .try_for_each_concurrent(5, |(plugin, tx)| async move {
-                if let Some(source) = msg.source_nickname() {
-                    if plugin.ignore_blacklisted_users()
-                        && self.blacklisted_users.contains(&source.to_string())
-                    {
-                        log::debug!("Message from blacklisted user: {}, discarding", source);
-                        if tx.send(None).is_err() {
-                            return Err(anyhow!("cannot send plugin message !"));
-                        };
-                        return Ok::<(), anyhow::Error>(());
-                    }
-                }
+    /// λhelp lists the commands of all the plugins, so it's handled here
+    fn help_replies(&self, msg: &Message) -> Vec<Message> {
+        let (text, response_target) = match (&msg.command, msg.response_target()) {
+            (Command::PRIVMSG(_, text), Some(target)) => (text, target),
+            _ => return vec![],
+        };
+        if let Some(source) = msg.source_nickname() {
+            if self.blacklisted_users.iter().any(|u| u == source) {
+                return vec![];
+            }
+        }
+        let (cmd, mb_target) = match help::parse_command(text) {
+            Some(x) => x,
+            None => return vec![],
+        };
 
-                let mb_msg = plugin.in_message(msg).await.with_context(|| {
-                    format!("in_message error from plugin {}", plugin.get_name())
-                })?;
-                let msg = mb_msg.map(|m| (plugin.get_name(), m));
-                if tx.send(msg).is_err() {
-                    return Err(anyhow!("cannot send plugin message !"));
-                }
-                Ok::<(), anyhow::Error>(())
+        let commands = self
+            .plugins
+            .iter()
+            .filter(|p| !self.disabled_plugins.contains(p.get_name()))
+            .flat_map(|p| p.commands())
+            .collect::<Vec<_>>();
+        help::help_lines(&commands, cmd, &self.help_prefix)
+            .into_iter()
+            .enumerate()
+            .map(|(i, line)| {
+                let line = if i == 0 {
+                    messages::with_target(&line, &mb_target)
+                } else {
+                    line
+                };
+                Command::PRIVMSG(response_target.to_string(), line).into()
             })
-            .await?;
+            .collect()
+    }
+
+    /// λadmin commands, silently ignored when they don't come from an owner
+    fn admin_command(&self, msg: &Message) -> Option<Message> {
+        let text = match &msg.command {
+            Command::PRIVMSG(_, text) => text,
+            _ => return None,
+        };
+        let cmd = admin::parse_command(text)?;
+        if !self.owners.is_owner(msg) {
+            log::warn!(
+                "Ignoring admin command from non owner {:?}: {text}",
+                msg.source_nickname()
+            );
+            return None;
+        }
+        log::info!("Admin command from {:?}: {cmd:?}", msg.source_nickname());
+        Some(cmd.to_command().into())
+    }
 
-        for rx in rxs {
-            let rx: oneshot::Receiver<Option<(&'static str, Message)>> = rx;
-            results.push(rx.await?);
+    /// λplugins lists the loaded plugins, and the owners can toggle them
+    /// with λplugin, other people are silently ignored for that one
+    fn plugin_command_reply(&self, msg: &Message) -> Option<Message> {
+        let (text, response_target) = match (&msg.command, msg.response_target()) {
+            (Command::PRIVMSG(_, text), Some(target)) => (text, target),
+            _ => return None,
+        };
+        let source = msg.source_nickname()?;
+        if self.blacklisted_users.iter().any(|u| u == source) {
+            return None;
+        }
+        let cmd = plugin_toggle::parse_command(text)?;
+        if cmd.needs_owner() && !self.owners.is_owner(msg) {
+            log::warn!("Ignoring plugin command from non owner {source}: {text}");
+            return None;
         }
 
-        Ok(results)
+        let loaded = self
+            .plugins
+            .iter()
+            .map(|p| p.get_name())
+            .collect::<Vec<_>>();
+        let reply = plugin_toggle::handle_command(&cmd, &loaded, &self.disabled_plugins);
+        log::info!("Plugin command from {source}: {cmd:?}, {reply}");
+        Some(Command::PRIVMSG(response_target.to_string(), reply).into())
+    }
+
+    /// Let each plugin rewrite the incoming message in turn
+    fn rewrite_message(&self, msg: Message) -> Message {
+        self.plugins
+            .iter()
+            .filter(|plugin| !self.disabled_plugins.contains(plugin.get_name()))
+            .fold(msg, |msg, plugin| match plugin.rewrite_message(&msg) {
+                Some(rewritten) => {
+                    log::debug!(
+                        "Message rewritten by plugin {}: {:?} -> {:?}",
+                        plugin.get_name(),
+                        msg,
+                        rewritten
+                    );
+                    rewritten
+                }
+                None => msg,
+            })
+    }
+
+    async fn plugins_in_messages(
+        &self,
+        msg: &Message,
+    ) -> Result<Vec<Option<(&'static str, Message)>>> {
+        plugins_in_messages(
+            &self.plugins,
+            &self.blacklisted_users,
+            &self.disabled_plugins,
+            &self.metrics,
+            msg,
+        )
+        .await
     }
 
+    /// Drive the run loops of the plugins, when this golem is the one doing it,
+    /// and send the messages of the run loops routed to this server
     async fn run_plugins(&self) -> Result<()> {
-        let (tx, mut rx) = mpsc::channel(10);
-        let runs = self.plugins.iter().map(|p| {
-            let tx = tx.clone();
+        let runs = self.running_plugins.iter().map(|p| {
             // The logic here is a bit meh.
             // need to create an intermediate channel to add the plugin name
             // to the message. Would be nice to be able to map over a channel
@@ -291,7 +838,7 @@ impl Golem {
                     },
                     async {
                         while let Some(plugin_message) = plug_rx.recv().await {
-                            tx.send((name, plugin_message))
+                            self.route((name, plugin_message))
                                 .await
                                 .with_context(|| format!("Plugin {}.run() failed", p.get_name()))?;
                         }
@@ -303,7 +850,16 @@ impl Golem {
             }
         });
         let process = async move {
-            while let Some(msg) = rx.recv().await {
+            let mut inbox = self.inbox.lock().await;
+            while let Some(msg) = inbox.recv().await {
+                if self.disabled_plugins.contains(msg.0) {
+                    log::debug!(
+                        "Dropping message from disabled plugin {}: {:?}",
+                        msg.0,
+                        msg.1
+                    );
+                    continue;
+                }
                 self.outbound_message(&msg).await?;
             }
             Ok::<(), anyhow::Error>(())
@@ -312,12 +868,52 @@ impl Golem {
         Ok(())
     }
 
+    /// Hand a message of a run loop to the golem of its server
+    async fn route(&self, message: (&'static str, Message)) -> Result<()> {
+        let peer = match network::network(&message.1) {
+            Some(network) => self
+                .peers
+                .iter()
+                .find(|peer| peer.network.as_deref() == Some(network)),
+            None => {
+                let joined = self
+                    .peers
+                    .iter()
+                    .map(|peer| {
+                        let client = peer.irc_client.lock().expect("lock golem irc client");
+                        client.list_channels().unwrap_or_default()
+                    })
+                    .collect::<Vec<_>>();
+                self.peers.get(route_index(&message.1, &joined))
+            }
+        };
+        let outbox = match peer {
+            Some(peer) => &peer.outbox,
+            None => &self.outbox,
+        };
+        outbox
+            .send(message)
+            .await
+            .context("Cannot hand the message to its server")
+    }
+
     async fn outbound_message(&self, message: &(&'static str, Message)) -> Result<()> {
+        if !self.outbound_guard.allow(&message.1) {
+            log::warn!(
+                "Suppressing duplicate message from plugin {}: {:?}",
+                message.0,
+                message.1
+            );
+            return Ok(());
+        }
+
+        // the plugins see the messages of this server like the incoming ones
+        let tagged = network::with_network(message.1.clone(), self.network.as_deref());
         // TODO don't crash if a plugin returns an error
         futures::stream::iter(self.plugins.iter())
             .map(Ok)
             .try_for_each_concurrent(5, |plugin| {
-                let (orig_name, msg) = &message;
+                let (orig_name, msg) = (&message.0, &tagged);
                 async move {
                     if &plugin.get_name() != orig_name {
                         plugin.out_message(msg).await?;
@@ -326,9 +922,16 @@ impl Golem {
                 }
             })
             .await?;
-        let client = self.irc_client.lock().expect("lock golem irc client");
-        // TODO this is blocking
-        client.send(message.1.clone())?;
+        // messages sent while reconnecting are lost, but the plugins keep running
+        let untagged = network::without_network(message.1.clone());
+        for msg in messages::split_long_privmsg(&untagged) {
+            self.rate_limiter.acquire().await;
+            let client = self.irc_client.lock().expect("lock golem irc client");
+            // TODO this is blocking
+            if let Err(err) = client.send(msg) {
+                log::error!("Cannot send message {:?}: {err:?}", message.1);
+            }
+        }
         Ok(())
     }
 
@@ -346,6 +949,217 @@ impl Golem {
     }
 }
 
+/// Where the golems send the messages of the plugins run loops
+#[derive(Clone)]
+struct Peer {
+    /// the tag of the messages for this server
+    network: Option<String>,
+    irc_client: Arc<Mutex<irc::client::Client>>,
+    outbox: mpsc::Sender<(&'static str, Message)>,
+}
+
+/// The plugins, initialised once for all the servers, and what they
+/// share with the golems
+struct SharedPlugins {
+    /// by name in the config
+    plugins: Vec<(String, Arc<dyn Plugin>)>,
+    router: Option<Router<()>>,
+    history: plugin_core::MessageHistory,
+    owners: Owners,
+    own_nicks: OwnNicks,
+    help_prefix: String,
+    metrics: BotMetrics,
+    /// a plugin toggled with λplugin is toggled on every server
+    disabled_plugins: DisabledPlugins,
+}
+
+impl SharedPlugins {
+    async fn init(
+        irc_config: &irc::client::data::Config,
+        golem_config_path: String,
+        conf: &GolemConfig,
+        names: &[String],
+        metrics: BotMetrics,
+    ) -> Result<Self> {
+        let history = plugin_core::MessageHistory::default();
+        let owners = Owners::new(irc_config.owners.clone());
+        let nickname = irc_config.nickname.clone().unwrap_or_default();
+        let own_nicks = OwnNicks::new(&nickname);
+        parser::set_command_prefixes(conf.command_prefixes.as_deref().unwrap_or_default());
+        plugin_core::store::set_db_path(conf.db_path.as_deref().unwrap_or_default());
+        // once for all the plugins, they would lock each other otherwise
+        db::with_connection(|conn| {
+            plugin_core::store::run_migrations(conn)?;
+            db::run_migrations(conn)?;
+            plugin_url::run_migrations(conn)?;
+            plugin_twitch::run_migrations(conn)
+        })
+        .await
+        .context("Cannot run migrations")?;
+        let help_prefix = parser::command_prefixes()
+            .first()
+            .cloned()
+            .unwrap_or_default();
+        let http_client = reqwest::ClientBuilder::new()
+            .user_agent(plugin_core::USER_AGENT)
+            .build()
+            .context("Cannot build http client")?;
+        let core_config = plugin_core::Config {
+            config_path: golem_config_path,
+            nickname,
+            own_nicks: own_nicks.clone(),
+            owners: owners.clone(),
+            blacklisted_users: conf.blacklisted_users.clone(),
+            history: history.clone(),
+            http_client,
+            metrics: metrics.clone(),
+        };
+        let core_config = Arc::new(core_config);
+
+        let inits = stream::iter(names.to_vec())
+            .map(|name| {
+                let core_config = Arc::clone(&core_config);
+                async move {
+                    let init = init_plugin(&core_config, &name).await?;
+                    Ok::<_, anyhow::Error>((name, init))
+                }
+            })
+            .buffer_unordered(10)
+            .collect::<Vec<_>>()
+            .await
+            .into_iter()
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut router: Option<Router<()>> = None;
+        let mut plugins = Vec::with_capacity(inits.len());
+        for (name, init) in inits {
+            if let Some(r) = init.router {
+                match router {
+                    Some(x) => {
+                        log::info!("Mounting a router from plugin {}", init.plugin.get_name());
+                        router = Some(x.merge(r))
+                    }
+                    None => router = Some(r),
+                }
+            }
+            plugins.push((name, Arc::from(init.plugin)));
+        }
+
+        Ok(SharedPlugins {
+            plugins,
+            router,
+            history,
+            owners,
+            own_nicks,
+            help_prefix,
+            metrics,
+            disabled_plugins: DisabledPlugins::default(),
+        })
+    }
+
+    /// The instances of the given plugins, in the order of the names
+    fn select(&self, names: &[String]) -> Vec<Arc<dyn Plugin>> {
+        names
+            .iter()
+            .filter_map(|name| {
+                self.plugins
+                    .iter()
+                    .find(|(n, _)| n == name)
+                    .map(|(_, p)| Arc::clone(p))
+            })
+            .collect()
+    }
+}
+
+/// Which golem should send a message of the plugins run loops: the first
+/// one which joined the channel it's for, the first one otherwise,
+/// like for the messages to a nick
+fn route_index(msg: &Message, joined: &[Vec<String>]) -> usize {
+    let target = match &msg.command {
+        Command::PRIVMSG(target, _) | Command::NOTICE(target, _) => target,
+        _ => return 0,
+    };
+    joined
+        .iter()
+        .position(|channels| channels.iter().any(|c| c.eq_ignore_ascii_case(target)))
+        .unwrap_or(0)
+}
+
+/// The replies of each plugin to the message, in the order of the plugins.
+/// A plugin failing is logged and counted as no reply, so that the other
+/// plugins still get to answer. Disabled plugins don't reply.
+async fn plugins_in_messages(
+    plugins: &[Arc<dyn Plugin>],
+    blacklisted_users: &[String],
+    disabled_plugins: &DisabledPlugins,
+    metrics: &BotMetrics,
+    msg: &Message,
+) -> Result<Vec<Option<(&'static str, Message)>>> {
+    let mut results = Vec::with_capacity(plugins.len());
+
+    let (txs, rxs): (Vec<_>, Vec<_>) = plugins.iter().map(|_| oneshot::channel()).unzip();
+
+    futures::stream::iter(plugins.iter().zip(txs))
+        .map(Ok)
+        .try_for_each_concurrent(5, |(plugin, tx)| async move {
+            if disabled_plugins.contains(plugin.get_name()) {
+                if tx.send(None).is_err() {
+                    return Err(anyhow!("cannot send plugin message !"));
+                };
+                return Ok::<(), anyhow::Error>(());
+            }
+            if let Some(source) = msg.source_nickname() {
+                if plugin.ignore_blacklisted_users()
+                    && blacklisted_users.contains(&source.to_string())
+                {
+                    log::debug!("Message from blacklisted user: {}, discarding", source);
+                    if tx.send(None).is_err() {
+                        return Err(anyhow!("cannot send plugin message !"));
+                    };
+                    return Ok::<(), anyhow::Error>(());
+                }
+            }
+
+            let mb_msg = match plugin.in_message(msg).await {
+                Ok(mb_msg) => mb_msg,
+                Err(err) => {
+                    log::error!(
+                        "in_message error from plugin {}: {err:?}",
+                        plugin.get_name()
+                    );
+                    let errors = &metrics.plugin_errors;
+                    errors.with_label_values(&[plugin.get_name()]).inc();
+                    None
+                }
+            };
+            if mb_msg.is_some() {
+                let handled = &metrics.commands_handled;
+                handled.with_label_values(&[plugin.get_name()]).inc();
+            }
+            let msg = mb_msg.map(|m| (plugin.get_name(), m));
+            if tx.send(msg).is_err() {
+                return Err(anyhow!("cannot send plugin message !"));
+            }
+            Ok::<(), anyhow::Error>(())
+        })
+        .await?;
+
+    for rx in rxs {
+        let rx: oneshot::Receiver<Option<(&'static str, Message)>> = rx;
+        results.push(rx.await?);
+    }
+
+    Ok(results)
+}
+
+/// The new nick, when the message is the server confirming a change of `own_nick`
+fn nick_change<'msg>(msg: &'msg Message, own_nick: &str) -> Option<&'msg str> {
+    match &msg.command {
+        Command::NICK(new_nick) if msg.source_nickname() == Some(own_nick) => Some(new_nick),
+        _ => None,
+    }
+}
+
 // The function https://docs.rs/irc/latest/irc/client/prelude/enum.Response.html#method.is_error
 // is broken, and consider anything with a code above 400 to be an error
 // which doesn't account for SASL successes 900, 901, 902 and 903
@@ -358,16 +1172,158 @@ async fn init_plugin(config: &plugin_core::Config, name: &str) -> Result<Initial
     // TODO: generate a macro which automatically match the name
     // with the correct module based on the exports of crate::plugins
     let plugin = match name {
+        "alias" => plugins::Alias::init(&config).await,
+        "calc" => plugins::Calc::init(&config).await,
         "crypto" => plugins::Crypto::init(&config).await,
         "ctcp" => plugins::Ctcp::init(&config).await,
         "echo" => plugins::Echo::init(&config).await,
+        "feed" => plugins::Feed::init(&config).await,
         "joke" => plugins::Joke::init(&config).await,
+        "karma" => plugins::Karma::init(&config).await,
+        "lastfm" => plugins::LastFm::init(&config).await,
+        "logs" => plugins::Logs::init(&config).await,
+        "metrics" => plugins::Metrics::init(&config).await,
+        "quote" => plugins::Quote::init(&config).await,
+        "remind" => plugins::Remind::init(&config).await,
         "republican_calendar" => plugins::RepublicanCalendar::init(&config).await,
+        "roll" => plugins::Roll::init(&config).await,
+        "search" => plugins::Search::init(&config).await,
+        "seen" => plugins::Seen::init(&config).await,
+        "tell" => plugins::Tell::init(&config).await,
+        "topic" => plugins::Topic::init(&config).await,
+        "translate" => plugins::Translate::init(&config).await,
         "twitch" => plugin_twitch::Twitch::init(&config).await,
+        "urbain" => plugins::Urbain::init(&config).await,
         "url" => plugin_url::UrlPlugin::init(&config).await,
+        "weather" => plugins::Weather::init(&config).await,
+        "welcome" => plugins::Welcome::init(&config).await,
         _ => return Err(anyhow!("Unknown plugin name: {}", name)),
     };
     let plugin = plugin.with_context(|| format!("Cannot initalize plugin {}", name))?;
     log::info!("Plugin initialized: {}", name);
     Ok(plugin)
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use plugin_core::test_util::{privmsg, reply_text};
+    use pretty_assertions::assert_eq;
+
+    struct Failing {}
+
+    #[async_trait::async_trait]
+    impl Plugin for Failing {
+        async fn init(_config: &plugin_core::Config) -> plugin_core::Result<Initialised> {
+            Ok(Initialised::from(Failing {}))
+        }
+
+        fn get_name(&self) -> &'static str {
+            "failing"
+        }
+
+        async fn in_message(&self, _msg: &Message) -> plugin_core::Result<Option<Message>> {
+            Err(plugin_core::Error::Synthetic("boom".to_string()))
+        }
+    }
+
+    struct Replying {}
+
+    #[async_trait::async_trait]
+    impl Plugin for Replying {
+        async fn init(_config: &plugin_core::Config) -> plugin_core::Result<Initialised> {
+            Ok(Initialised::from(Replying {}))
+        }
+
+        fn get_name(&self) -> &'static str {
+            "replying"
+        }
+
+        async fn in_message(&self, _msg: &Message) -> plugin_core::Result<Option<Message>> {
+            Ok(Some(privmsg("golem", "#coucou", "still here")))
+        }
+    }
+
+    #[test]
+    async fn test_failing_plugin() {
+        let plugins: Vec<Arc<dyn Plugin>> = vec![
+            Arc::new(Failing {}),
+            Arc::new(Replying {}),
+            Arc::new(Failing {}),
+        ];
+        let metrics = BotMetrics::default();
+        let replies = plugins_in_messages(
+            &plugins,
+            &[],
+            &DisabledPlugins::default(),
+            &metrics,
+            &privmsg("charlie", "#coucou", "coucou"),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(replies.len(), 3);
+        assert_eq!(replies[0], None);
+        assert_eq!(replies[2], None);
+        let (name, reply) = replies[1].clone().expect("a reply from the other plugin");
+        assert_eq!(name, "replying");
+        assert_eq!(reply_text(Some(reply)), "still here");
+        assert_eq!(
+            metrics.plugin_errors.with_label_values(&["failing"]).get(),
+            2
+        );
+    }
+
+    #[test]
+    async fn test_disabled_plugin() {
+        let plugins: Vec<Arc<dyn Plugin>> = vec![Arc::new(Replying {})];
+        let disabled = DisabledPlugins::default();
+        disabled.disable("replying");
+        let replies = plugins_in_messages(
+            &plugins,
+            &[],
+            &disabled,
+            &BotMetrics::default(),
+            &privmsg("charlie", "#coucou", "coucou"),
+        )
+        .await
+        .unwrap();
+        assert_eq!(replies, vec![None]);
+    }
+
+    #[test]
+    async fn test_route_index() {
+        let joined = vec![
+            vec!["#coucou".to_string()],
+            vec!["#rust".to_string(), "#Coucou".to_string()],
+            vec!["#haskell".to_string()],
+        ];
+        let route = |target: &str| {
+            let msg: Message = Command::PRIVMSG(target.to_string(), "coucou".to_string()).into();
+            route_index(&msg, &joined)
+        };
+        assert_eq!(route("#rust"), 1);
+        assert_eq!(route("#haskell"), 2);
+        assert_eq!(route("#coucou"), 0, "the first server in the channel");
+        assert_eq!(route("#elsewhere"), 0, "not joined");
+        assert_eq!(route("charlie"), 0, "a nick");
+        assert_eq!(
+            route_index(
+                &Command::JOIN("#rust".to_string(), None, None).into(),
+                &joined
+            ),
+            0
+        );
+    }
+
+    #[test]
+    async fn test_nick_change() {
+        let msg: Message = ":golem_!g@host NICK golem".parse().unwrap();
+        assert_eq!(nick_change(&msg, "golem_"), Some("golem"));
+        assert_eq!(nick_change(&msg, "charlie"), None, "someone else");
+        assert_eq!(
+            nick_change(&privmsg("golem_", "#coucou", "NICK golem"), "golem_"),
+            None
+        );
+    }
+}
@@ -15,6 +15,7 @@ use anyhow::{Context, Result};
 use log::info;
 use structopt::StructOpt;
 
+mod db;
 mod golem;
 mod plugins;
 mod schema;
@@ -66,11 +67,14 @@ async fn main() -> Result<()> {
         ..Config::default()
     };
 
-    golem::Golem::new_from_config(config, opt.config)
-        .await?
-        .run()
-        .await
-        .context("Plugin golem crashed")?;
+    let golems = golem::Golem::new_all_from_config(config, opt.config).await?;
+    futures::future::try_join_all(
+        golems
+            .into_iter()
+            .map(|mut golem| async move { golem.run().await }),
+    )
+    .await
+    .context("Plugin golem crashed")?;
 
     Err(anyhow!("Golem exited!"))
 }
@@ -0,0 +1,359 @@
+use std::collections::HashMap;
+use std::result::Result as StdResult;
+use std::sync::Mutex;
+
+use crate::db;
+use crate::schema::aliases::{self, dsl};
+use crate::utils::parser::{command_prefix, word};
+use anyhow::Context;
+use async_trait::async_trait;
+use diesel::prelude::*;
+use irc::proto::{Command, Message};
+use nom::branch::alt;
+use nom::bytes::complete::tag;
+use nom::character::complete::{char, multispace0, multispace1};
+use nom::combinator::{all_consuming, map, opt, rest, verify};
+use nom::sequence::{preceded, terminated, tuple};
+use nom::{Finish, IResult};
+use plugin_core::utils::owners::Owners;
+use plugin_core::utils::parser;
+use plugin_core::{CommandHelp, Initialised, Plugin, Result};
+
+pub struct Alias {
+    owners: Owners,
+    /// alias name -> expansion, without the command prefix
+    aliases: Mutex<HashMap<String, String>>,
+    /// command prefix shown in the replies
+    prefix: String,
+}
+
+#[derive(Debug, Queryable, Insertable)]
+#[table_name = "aliases"]
+struct AliasRow {
+    name: String,
+    expansion: String,
+}
+
+#[async_trait]
+impl Plugin for Alias {
+    async fn init(config: &plugin_core::Config) -> Result<Initialised> {
+        let rows = db::with_connection(|conn| {
+            dsl::aliases
+                .load::<AliasRow>(conn)
+                .context("Cannot load aliases")
+        })
+        .await?;
+
+        Ok(Initialised::from(Alias {
+            owners: config.owners.clone(),
+            aliases: Mutex::new(rows.into_iter().map(|r| (r.name, r.expansion)).collect()),
+            prefix: parser::command_prefixes()
+                .first()
+                .cloned()
+                .unwrap_or_default(),
+        }))
+    }
+
+    fn get_name(&self) -> &'static str {
+        "alias"
+    }
+
+    fn commands(&self) -> Vec<CommandHelp> {
+        vec![
+            CommandHelp::new("alias", "[list]", "liste les alias"),
+            CommandHelp::new(
+                "alias",
+                "add <nom> = <commande>",
+                "λnom devient un raccourci pour λcommande",
+            ),
+            CommandHelp::new("alias", "remove <nom>", "supprime un alias"),
+        ]
+    }
+
+    fn rewrite_message(&self, msg: &Message) -> Option<Message> {
+        let (target, text) = match &msg.command {
+            Command::PRIVMSG(target, text) => (target, text),
+            _ => return None,
+        };
+
+        let aliases = self.aliases.lock().expect("alias lock");
+        match expand(&aliases, text) {
+            Ok(Some(expanded)) => Some(Message {
+                command: Command::PRIVMSG(target.to_string(), expanded),
+                ..msg.clone()
+            }),
+            Ok(None) => None,
+            Err(name) => {
+                log::warn!("Alias loop through {name} when expanding {text}");
+                None
+            }
+        }
+    }
+
+    async fn in_message(&self, msg: &Message) -> Result<Option<Message>> {
+        self.in_msg(msg).await
+    }
+}
+
+impl Alias {
+    async fn in_msg(&self, msg: &Message) -> Result<Option<Message>> {
+        let privmsg = match &msg.command {
+            Command::PRIVMSG(_source, privmsg) => privmsg,
+            _ => return Ok(None),
+        };
+        let (cmd, channel) = match (parse_command(privmsg), msg.response_target()) {
+            (Some(cmd), Some(channel)) => (cmd, channel.to_string()),
+            _ => return Ok(None),
+        };
+
+        let reply = match cmd {
+            AliasCmd::List => self.list(),
+            AliasCmd::Add(..) | AliasCmd::Remove(_) if !self.owners.is_owner(msg) => {
+                "Seuls mes patrons peuvent gérer les alias.".to_string()
+            }
+            AliasCmd::Add(name, expansion) => self.add(name, expansion).await?,
+            AliasCmd::Remove(name) => self.remove(name).await?,
+        };
+        Ok(Some(Command::PRIVMSG(channel, reply).into()))
+    }
+
+    fn list(&self) -> String {
+        let aliases = self.aliases.lock().expect("alias lock");
+        if aliases.is_empty() {
+            return "Aucun alias défini.".to_string();
+        }
+        let mut aliases = aliases
+            .iter()
+            .map(|(name, expansion)| format!("{p}{name} → {p}{expansion}", p = self.prefix))
+            .collect::<Vec<_>>();
+        aliases.sort();
+        aliases.join(", ")
+    }
+
+    async fn add(&self, name: &str, expansion: &str) -> anyhow::Result<String> {
+        {
+            let mut aliases = self.aliases.lock().expect("alias lock").clone();
+            aliases.insert(name.to_string(), expansion.to_string());
+            let p = &self.prefix;
+            if let Err(looping) = expand(&aliases, &format!("{p}{name}")) {
+                return Ok(format!(
+                    "Impossible, {p}{name} bouclerait sur {p}{looping}."
+                ));
+            }
+        }
+
+        let row = AliasRow {
+            name: name.to_string(),
+            expansion: expansion.to_string(),
+        };
+        db::with_connection(move |conn| {
+            diesel::replace_into(aliases::table)
+                .values(&row)
+                .execute(conn)
+                .with_context(|| format!("Cannot save alias {:?}", row))
+        })
+        .await?;
+
+        self.aliases
+            .lock()
+            .expect("alias lock")
+            .insert(name.to_string(), expansion.to_string());
+        let p = &self.prefix;
+        Ok(format!(
+            "{p}{name} est maintenant un alias pour {p}{expansion}"
+        ))
+    }
+
+    async fn remove(&self, name: &str) -> anyhow::Result<String> {
+        let to_delete = name.to_string();
+        let deleted = db::with_connection(move |conn| {
+            diesel::delete(dsl::aliases.filter(dsl::name.eq(to_delete.as_str())))
+                .execute(conn)
+                .with_context(|| format!("Cannot delete alias {to_delete}"))
+        })
+        .await?;
+
+        self.aliases.lock().expect("alias lock").remove(name);
+        let p = &self.prefix;
+        if deleted > 0 {
+            Ok(format!("Alias {p}{name} supprimé."))
+        } else {
+            Ok(format!("Pas d'alias {p}{name}."))
+        }
+    }
+}
+
+/// Expand `text` if it's a command whose name is an alias. The expansion is itself
+/// expanded if it starts with another alias.
+/// Returns Err(name) if the expansion loops back to the alias `name`.
+fn expand(aliases: &HashMap<String, String>, text: &str) -> StdResult<Option<String>, String> {
+    let (args, prefix) = match command_prefix(text) {
+        Ok(x) => x,
+        Err(_) => return Ok(None),
+    };
+    let (mut name, args) = split_command(args);
+    if !aliases.contains_key(name) {
+        return Ok(None);
+    }
+
+    let mut args = args.to_string();
+    let mut seen = Vec::new();
+    while let Some(expansion) = aliases.get(name) {
+        if seen.contains(&name) {
+            return Err(name.to_string());
+        }
+        seen.push(name);
+        let (next, expansion_args) = split_command(expansion);
+        args = format!("{expansion_args}{args}");
+        name = next;
+    }
+    Ok(Some(format!("{prefix}{name}{args}")))
+}
+
+/// split a command into its name and the rest, including the leading whitespace
+fn split_command(input: &str) -> (&str, &str) {
+    let idx = input.find(char::is_whitespace).unwrap_or(input.len());
+    input.split_at(idx)
+}
+
+#[derive(Debug, PartialEq)]
+enum AliasCmd<'input> {
+    List,
+    Add(&'input str, &'input str),
+    Remove(&'input str),
+}
+
+fn parse_command(input: &str) -> Option<AliasCmd> {
+    all_consuming(terminated(parse_alias, multispace0))(input)
+        .finish()
+        .map(|x| x.1)
+        .ok()
+}
+
+fn parse_alias(input: &str) -> IResult<&str, AliasCmd> {
+    let add = map(
+        tuple((
+            tag("add"),
+            multispace1,
+            word,
+            multispace0,
+            char('='),
+            multispace0,
+            opt(command_prefix),
+            verify(rest, |s: &str| !s.trim().is_empty()),
+        )),
+        |(_, _, name, _, _, _, _, expansion)| AliasCmd::Add(name, expansion.trim_end()),
+    );
+    let remove = map(tuple((tag("remove"), multispace1, word)), |(_, _, name)| {
+        AliasCmd::Remove(name)
+    });
+
+    preceded(
+        tuple((command_prefix, tag("alias"))),
+        alt((
+            preceded(multispace1, alt((add, remove))),
+            map(opt(preceded(multispace1, tag("list"))), |_| AliasCmd::List),
+        )),
+    )(input)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use plugin_core::test_util::{privmsg, reply_text};
+    use pretty_assertions::assert_eq;
+
+    fn aliases(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(n, e)| (n.to_string(), e.to_string()))
+            .collect()
+    }
+
+    #[test]
+    async fn test_parse_command() {
+        assert_eq!(parse_command("λalias"), Some(AliasCmd::List));
+        assert_eq!(parse_command("λalias list"), Some(AliasCmd::List));
+        assert_eq!(
+            parse_command("λalias add btc = crypto btc"),
+            Some(AliasCmd::Add("btc", "crypto btc"))
+        );
+        assert_eq!(
+            parse_command("λalias add btc=λcrypto btc "),
+            Some(AliasCmd::Add("btc", "crypto btc")),
+            "prefix in the expansion is optional"
+        );
+        assert_eq!(parse_command("λalias add btc ="), None, "need an expansion");
+        assert_eq!(
+            parse_command("λalias remove btc"),
+            Some(AliasCmd::Remove("btc"))
+        );
+    }
+
+    #[test]
+    async fn test_expand() {
+        let aliases = aliases(&[("btc", "crypto btc"), ("b", "btc")]);
+        assert_eq!(
+            expand(&aliases, "λbtc"),
+            Ok(Some("λcrypto btc".to_string()))
+        );
+        assert_eq!(
+            expand(&aliases, "&btc > charlie"),
+            Ok(Some("&crypto btc > charlie".to_string())),
+            "keeps the prefix and the arguments"
+        );
+        assert_eq!(
+            expand(&aliases, "λb ath"),
+            Ok(Some("λcrypto btc ath".to_string())),
+            "alias of an alias"
+        );
+        assert_eq!(expand(&aliases, "λbtcx"), Ok(None));
+        assert_eq!(expand(&aliases, "btc"), Ok(None), "not a command");
+    }
+
+    #[test]
+    async fn test_expand_loop() {
+        let aliases = aliases(&[("a", "b x"), ("b", "c y"), ("c", "a z")]);
+        assert!(expand(&aliases, "λa").is_err(), "indirect loop");
+
+        let aliases = self::aliases(&[("crypto", "crypto btc")]);
+        assert_eq!(
+            expand(&aliases, "λcrypto"),
+            Err("crypto".to_string()),
+            "alias to itself"
+        );
+    }
+
+    #[test]
+    async fn test_owner_only() {
+        let plugin = Alias {
+            owners: Owners::new(vec!["charlie".to_string()]),
+            aliases: Mutex::new(aliases(&[("btc", "crypto btc")])),
+            prefix: "λ".to_string(),
+        };
+        let refused = "Seuls mes patrons peuvent gérer les alias.";
+        let reply = |from: &str, text: &str| {
+            let msg = privmsg(from, "#chan", text);
+            let plugin = &plugin;
+            async move { reply_text(plugin.in_msg(&msg).await.unwrap()) }
+        };
+
+        assert_eq!(reply("bob", "λalias add eth = crypto eth").await, refused);
+        assert_eq!(reply("bob", "λalias remove btc").await, refused);
+        assert_eq!(
+            plugin.aliases.lock().unwrap().clone(),
+            aliases(&[("btc", "crypto btc")]),
+            "aliases unchanged"
+        );
+        assert_eq!(
+            reply("charlie", "λalias add crypto = crypto btc").await,
+            "Impossible, λcrypto bouclerait sur λcrypto.",
+            "owner cannot add a looping alias"
+        );
+        assert_eq!(
+            reply("bob", "λalias list").await,
+            "λbtc → λcrypto btc",
+            "anyone can list the aliases"
+        );
+    }
+}
@@ -0,0 +1,265 @@
+use crate::utils::parser::{self, command_prefix};
+use async_trait::async_trait;
+use irc::proto::{Command, Message};
+use nom::branch::alt;
+use nom::bytes::complete::tag;
+use nom::character::complete::{char, digit0, digit1, multispace0, multispace1, one_of};
+use nom::combinator::{all_consuming, map, map_res, opt, recognize};
+use nom::multi::many0;
+use nom::sequence::{delimited, pair, preceded, terminated, tuple};
+use nom::{Finish, IResult};
+use plugin_core::{CommandHelp, Initialised, Plugin, Result};
+
+/// Bigger exponents are refused, the result would overflow anyway
+const MAX_EXPONENT: f64 = 1000.0;
+
+pub struct Calc {}
+
+#[derive(Debug, PartialEq, Clone)]
+enum Expr {
+    Number(f64),
+    Neg(Box<Expr>),
+    /// operator among `+-*/%^`, and both operands
+    BinOp(char, Box<Expr>, Box<Expr>),
+}
+
+#[async_trait]
+impl Plugin for Calc {
+    async fn init(_config: &plugin_core::Config) -> Result<Initialised> {
+        Ok(Initialised::from(Calc {}))
+    }
+
+    fn get_name(&self) -> &'static str {
+        "calc"
+    }
+
+    fn commands(&self) -> Vec<CommandHelp> {
+        vec![CommandHelp::new(
+            "calc",
+            "<expression, comme 2 + 3 * (4 - 1)>",
+            "calcule avec + - * / % ^ et des parenthèses",
+        )]
+    }
+
+    async fn in_message(&self, msg: &Message) -> Result<Option<Message>> {
+        Ok(in_msg(msg))
+    }
+}
+
+fn in_msg(msg: &Message) -> Option<Message> {
+    let privmsg = match &msg.command {
+        Command::PRIVMSG(_source, privmsg) => privmsg,
+        _ => return None,
+    };
+    let (expr, mb_target) = parse_command(privmsg)?;
+    let response_target = msg.response_target()?;
+
+    let reply = match evaluate(&expr) {
+        Ok(result) => format_number(result),
+        Err(err) => err,
+    };
+    let reply = crate::utils::messages::with_target(&reply, &mb_target);
+    Some(Command::PRIVMSG(response_target.to_string(), reply).into())
+}
+
+/// The error is the reply explaining why there is no result
+fn evaluate(expr: &Expr) -> std::result::Result<f64, String> {
+    let result = match expr {
+        Expr::Number(n) => *n,
+        Expr::Neg(e) => -evaluate(e)?,
+        Expr::BinOp(op, a, b) => {
+            let (a, b) = (evaluate(a)?, evaluate(b)?);
+            match op {
+                '+' => a + b,
+                '-' => a - b,
+                '*' => a * b,
+                '/' | '%' if b == 0.0 => return Err("Division par zéro".to_string()),
+                '/' => a / b,
+                '%' => a % b,
+                '^' if b.abs() > MAX_EXPONENT => {
+                    return Err(format!("Pas d'exposant au-delà de {}", MAX_EXPONENT))
+                }
+                '^' => a.powf(b),
+                _ => unreachable!("unknown operator {}", op),
+            }
+        }
+    };
+    if result.is_finite() {
+        Ok(result)
+    } else if result.is_nan() {
+        Err("Ce n'est pas un nombre réel".to_string())
+    } else {
+        Err("Le résultat est trop grand".to_string())
+    }
+}
+
+/// Integers without decimals, and at most 10 decimals otherwise,
+/// so that 0.1 + 0.2 is 0.3
+fn format_number(n: f64) -> String {
+    if n.abs() >= 1e15 {
+        return format!("{:e}", n);
+    }
+    let formatted = format!("{:.10}", n);
+    let formatted = formatted.trim_end_matches('0').trim_end_matches('.');
+    match formatted {
+        "-0" => "0".to_string(),
+        f => f.to_string(),
+    }
+}
+
+/// `λcalc 2 + 3 * (4 - 1) [> target]`
+fn parse_command(input: &str) -> Option<(Expr, Option<&str>)> {
+    let cmd = preceded(
+        tuple((command_prefix, tag("calc"), multispace1)),
+        parser::with_target(expr),
+    );
+    all_consuming(terminated(cmd, multispace0))(input)
+        .finish()
+        .map(|x| x.1)
+        .ok()
+}
+
+/// Left associative operations, from the operands parsed by `operand`
+fn left_assoc<'a>(
+    operators: &'static str,
+    operand: fn(&'a str) -> IResult<&'a str, Expr>,
+    input: &'a str,
+) -> IResult<&'a str, Expr> {
+    map(
+        pair(
+            operand,
+            many0(pair(
+                delimited(multispace0, one_of(operators), multispace0),
+                operand,
+            )),
+        ),
+        |(first, rest)| {
+            rest.into_iter().fold(first, |acc, (op, e)| {
+                Expr::BinOp(op, Box::new(acc), Box::new(e))
+            })
+        },
+    )(input)
+}
+
+/// `a + b - c`
+fn expr(input: &str) -> IResult<&str, Expr> {
+    left_assoc("+-", product, input)
+}
+
+/// `a * b / c % d`
+fn product(input: &str) -> IResult<&str, Expr> {
+    left_assoc("*/%", signed, input)
+}
+
+/// `-a`, which applies after the powers: -2^2 is -4
+fn signed(input: &str) -> IResult<&str, Expr> {
+    alt((
+        map(preceded(pair(char('-'), multispace0), signed), |e| {
+            Expr::Neg(Box::new(e))
+        }),
+        power,
+    ))(input)
+}
+
+/// `a ^ b`, right associative: 2^3^2 is 2^9
+fn power(input: &str) -> IResult<&str, Expr> {
+    map(
+        pair(
+            atom,
+            opt(preceded(
+                delimited(multispace0, char('^'), multispace0),
+                signed,
+            )),
+        ),
+        |(base, exponent)| match exponent {
+            Some(exponent) => Expr::BinOp('^', Box::new(base), Box::new(exponent)),
+            None => base,
+        },
+    )(input)
+}
+
+/// a number, or an expression in parentheses
+fn atom(input: &str) -> IResult<&str, Expr> {
+    alt((
+        map(
+            map_res(
+                recognize(pair(digit1, opt(pair(char('.'), digit0)))),
+                |n: &str| n.parse::<f64>(),
+            ),
+            Expr::Number,
+        ),
+        delimited(
+            pair(char('('), multispace0),
+            expr,
+            pair(multispace0, char(')')),
+        ),
+    ))(input)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn calc(input: &str) -> Option<std::result::Result<String, String>> {
+        let (expr, _) = parse_command(&format!("λcalc {}", input))?;
+        Some(evaluate(&expr).map(format_number))
+    }
+
+    #[test]
+    async fn test_parse_command() {
+        assert_eq!(
+            parse_command("λcalc 1 + 2 > charlie"),
+            Some((
+                Expr::BinOp(
+                    '+',
+                    Box::new(Expr::Number(1.0)),
+                    Box::new(Expr::Number(2.0))
+                ),
+                Some("charlie")
+            ))
+        );
+        assert_eq!(parse_command("λcalc"), None);
+        assert_eq!(parse_command("λcalc 2 +"), None);
+        assert_eq!(parse_command("λcalc (2 + 3"), None);
+        assert_eq!(parse_command("λcalc inf"), None);
+    }
+
+    #[test]
+    async fn test_precedence() {
+        assert_eq!(calc("2 + 3 * (4 - 1)"), Some(Ok("11".to_string())));
+        assert_eq!(calc("10 - 4 - 3"), Some(Ok("3".to_string())));
+        assert_eq!(calc("2^3^2"), Some(Ok("512".to_string())));
+        assert_eq!(calc("-2^2"), Some(Ok("-4".to_string())));
+        assert_eq!(calc("2^-1"), Some(Ok("0.5".to_string())));
+        assert_eq!(calc("7 % 3 * 2"), Some(Ok("2".to_string())));
+        assert_eq!(calc("-(1.5 + 1)"), Some(Ok("-2.5".to_string())));
+        assert_eq!(calc("0.1 + 0.2"), Some(Ok("0.3".to_string())));
+        assert_eq!(calc("1 / 3"), Some(Ok("0.3333333333".to_string())));
+    }
+
+    #[test]
+    async fn test_errors() {
+        assert_eq!(
+            calc("1 / (2 - 2)"),
+            Some(Err("Division par zéro".to_string()))
+        );
+        assert_eq!(calc("5 % 0"), Some(Err("Division par zéro".to_string())));
+        assert_eq!(
+            calc("2 ^ 100000"),
+            Some(Err("Pas d'exposant au-delà de 1000".to_string()))
+        );
+        assert_eq!(
+            calc("10 ^ 1000"),
+            Some(Err("Le résultat est trop grand".to_string()))
+        );
+        assert_eq!(
+            calc("(-8) ^ 0.5"),
+            Some(Err("Ce n'est pas un nombre réel".to_string()))
+        );
+        assert_eq!(
+            calc("2 ^ 64"),
+            Some(Ok("1.8446744073709552e19".to_string()))
+        );
+    }
+}
@@ -0,0 +1,229 @@
+use anyhow::Context;
+use diesel::prelude::*;
+use nom::character::complete::{multispace0, one_of};
+use nom::combinator::{map, verify};
+use nom::number::complete::float;
+use nom::sequence::separated_pair;
+use nom::IResult;
+
+use crate::schema::crypto_alert::{self, dsl};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) enum Direction {
+    Above,
+    Below,
+}
+
+impl Direction {
+    /// stored in the db
+    fn as_str(&self) -> &'static str {
+        match self {
+            Direction::Above => "above",
+            Direction::Below => "below",
+        }
+    }
+
+    fn symbol(&self) -> char {
+        match self {
+            Direction::Above => '>',
+            Direction::Below => '<',
+        }
+    }
+}
+
+/// Someone waiting for a coin to cross a threshold, in euros
+#[derive(Debug, Clone, PartialEq, Queryable, Insertable)]
+#[table_name = "crypto_alert"]
+pub(super) struct Alert {
+    /// where to ping `nick`, can be the nick itself for a private message.
+    /// See `channel_key`.
+    pub(super) channel: String,
+    pub(super) nick: String,
+    /// symbol of the coin
+    pub(super) coin: String,
+    /// `above` or `below`
+    direction: String,
+    pub(super) threshold: f32,
+}
+
+impl Alert {
+    pub(super) fn new(
+        channel: &str,
+        nick: &str,
+        coin: &str,
+        direction: Direction,
+        threshold: f32,
+    ) -> Self {
+        Alert {
+            channel: channel.to_string(),
+            nick: nick.to_string(),
+            coin: coin.to_string(),
+            direction: direction.as_str().to_string(),
+            threshold,
+        }
+    }
+
+    fn direction(&self) -> Direction {
+        if self.direction == Direction::Below.as_str() {
+            Direction::Below
+        } else {
+            Direction::Above
+        }
+    }
+
+    /// Whether `rate` is on the other side of the threshold
+    pub(super) fn is_crossed(&self, rate: f32) -> bool {
+        match self.direction() {
+            Direction::Above => rate >= self.threshold,
+            Direction::Below => rate <= self.threshold,
+        }
+    }
+}
+
+/// `bitcoin > 50000`, with the display name of the coin
+pub(super) fn format_alert(coin_name: &str, alert: &Alert) -> String {
+    format!(
+        "{} {} {}",
+        coin_name,
+        alert.direction().symbol(),
+        alert.threshold
+    )
+}
+
+pub(super) fn register(conn: &SqliteConnection, alert: &Alert) -> anyhow::Result<()> {
+    diesel::replace_into(crypto_alert::table)
+        .values(alert)
+        .execute(conn)
+        .with_context(|| format!("Cannot save alert {:?}", alert))?;
+    Ok(())
+}
+
+/// Alerts set up by `nick` in `channel`
+pub(super) fn for_user(
+    conn: &SqliteConnection,
+    channel: &str,
+    nick: &str,
+) -> anyhow::Result<Vec<Alert>> {
+    dsl::crypto_alert
+        .filter(dsl::channel.eq(channel))
+        .filter(dsl::nick.eq(nick))
+        .order_by((dsl::coin, dsl::threshold))
+        .load::<Alert>(conn)
+        .with_context(|| format!("Cannot load alerts for {} in {}", nick, channel))
+}
+
+/// Remove all the alerts of `nick` in `channel`, returns how many were removed
+pub(super) fn clear(conn: &SqliteConnection, channel: &str, nick: &str) -> anyhow::Result<usize> {
+    diesel::delete(
+        dsl::crypto_alert
+            .filter(dsl::channel.eq(channel))
+            .filter(dsl::nick.eq(nick)),
+    )
+    .execute(conn)
+    .with_context(|| format!("Cannot delete alerts for {} in {}", nick, channel))
+}
+
+/// Remove and return the alerts crossed by the current `rate` of `coin`
+pub(super) fn take_triggered(
+    conn: &SqliteConnection,
+    coin: &str,
+    rate: f32,
+) -> anyhow::Result<Vec<Alert>> {
+    let triggered = dsl::crypto_alert
+        .filter(dsl::coin.eq(coin))
+        .load::<Alert>(conn)
+        .with_context(|| format!("Cannot load alerts for {}", coin))?
+        .into_iter()
+        .filter(|a| a.is_crossed(rate))
+        .collect::<Vec<_>>();
+
+    for alert in &triggered {
+        diesel::delete(
+            dsl::crypto_alert
+                .filter(dsl::channel.eq(alert.channel.as_str()))
+                .filter(dsl::nick.eq(alert.nick.as_str()))
+                .filter(dsl::coin.eq(alert.coin.as_str()))
+                .filter(dsl::direction.eq(alert.direction.as_str()))
+                .filter(dsl::threshold.eq(alert.threshold)),
+        )
+        .execute(conn)
+        .with_context(|| format!("Cannot delete alert {:?}", alert))?;
+    }
+    Ok(triggered)
+}
+
+/// `> 50000` or `< 1000`
+pub(super) fn threshold(input: &str) -> IResult<&str, (Direction, f32)> {
+    separated_pair(
+        map(one_of("<>"), |c| {
+            if c == '<' {
+                Direction::Below
+            } else {
+                Direction::Above
+            }
+        }),
+        multispace0,
+        verify(float, |t: &f32| *t > 0.0),
+    )(input)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::db;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    async fn test_threshold() {
+        assert_eq!(threshold("> 50000"), Ok(("", (Direction::Above, 50000.0))));
+        assert_eq!(threshold("<0.5"), Ok(("", (Direction::Below, 0.5))));
+        assert!(threshold("= 12").is_err(), "needs a direction");
+        assert!(threshold("> -3").is_err(), "must be positive");
+    }
+
+    #[test]
+    async fn test_is_crossed() {
+        let above = Alert::new("#chan", "charlie", "BTC", Direction::Above, 50000.0);
+        assert!(!above.is_crossed(49999.0));
+        assert!(above.is_crossed(50000.0));
+        let below = Alert::new("#chan", "charlie", "BTC", Direction::Below, 30000.0);
+        assert!(!below.is_crossed(30001.0));
+        assert!(below.is_crossed(29000.0));
+        assert_eq!(format_alert("bitcoin", &below), "bitcoin < 30000");
+    }
+
+    #[test]
+    async fn test_alert_store() {
+        let conn = SqliteConnection::establish(":memory:").unwrap();
+        db::run_migrations(&conn).unwrap();
+
+        let high = Alert::new("#chan", "charlie", "BTC", Direction::Above, 50000.0);
+        let low = Alert::new("#chan", "charlie", "BTC", Direction::Below, 30000.0);
+        let eth = Alert::new("#chan", "charlie", "ETH", Direction::Above, 4000.0);
+        let other = Alert::new("#chan", "bob", "BTC", Direction::Above, 45000.0);
+        for a in [&high, &low, &eth, &other] {
+            register(&conn, a).unwrap();
+        }
+        register(&conn, &high).unwrap();
+
+        assert_eq!(
+            for_user(&conn, "#chan", "charlie").unwrap(),
+            vec![low.clone(), high.clone(), eth.clone()],
+            "registering twice doesn't duplicate the alert"
+        );
+
+        assert_eq!(
+            take_triggered(&conn, "BTC", 48000.0).unwrap(),
+            vec![other.clone()]
+        );
+        assert_eq!(
+            take_triggered(&conn, "BTC", 48000.0).unwrap(),
+            vec![],
+            "alerts only fire once"
+        );
+        assert_eq!(for_user(&conn, "#chan", "bob").unwrap(), vec![]);
+
+        assert_eq!(clear(&conn, "#chan", "charlie").unwrap(), 3);
+        assert_eq!(for_user(&conn, "#chan", "charlie").unwrap(), vec![]);
+    }
+}
@@ -0,0 +1,38 @@
+/// From the lowest to the highest value
+const BLOCKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// One block per value, scaled between the min and the max of the values.
+/// Flat series are drawn at mid height.
+pub(super) fn sparkline(values: &[f32]) -> String {
+    let min = values.iter().copied().fold(f32::INFINITY, f32::min);
+    let max = values.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+    let range = max - min;
+    values
+        .iter()
+        .map(|v| {
+            if range > 0.0 {
+                let idx = ((v - min) / range * (BLOCKS.len() - 1) as f32).round() as usize;
+                BLOCKS[idx.min(BLOCKS.len() - 1)]
+            } else {
+                BLOCKS[BLOCKS.len() / 2]
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    async fn test_sparkline() {
+        assert_eq!(
+            sparkline(&[1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0]),
+            "▁▂▃▄▅▆▇█"
+        );
+        assert_eq!(sparkline(&[30000.0, 35000.0, 31000.0]), "▁█▂");
+        assert_eq!(sparkline(&[2.0, 2.0]), "▅▅", "flat");
+        assert_eq!(sparkline(&[]), "");
+    }
+}
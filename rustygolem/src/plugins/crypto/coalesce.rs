@@ -0,0 +1,130 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Coalesce identical requests made in quick succession, so that only the
+/// first one actually runs. A key stays taken while the first request is in
+/// flight, and for `window` after it completed. A zero window disables
+/// coalescing, even for the requests in flight.
+pub(crate) struct Coalescer<K> {
+    window: Duration,
+    // None means a request is currently in flight for this key
+    slots: Arc<Mutex<HashMap<K, Option<Instant>>>>,
+}
+
+pub(crate) struct CoalesceGuard<K: Eq + Hash> {
+    key: Option<K>,
+    slots: Arc<Mutex<HashMap<K, Option<Instant>>>>,
+}
+
+impl<K: Eq + Hash + Clone> Coalescer<K> {
+    pub(crate) fn new(window: Duration) -> Self {
+        Coalescer {
+            window,
+            slots: Default::default(),
+        }
+    }
+
+    /// Returns None if an identical request is in flight or completed
+    /// less than `window` ago. Otherwise returns a guard which marks the
+    /// request as completed when dropped.
+    pub(crate) fn try_start(&self, key: K) -> Option<CoalesceGuard<K>> {
+        if self.window.is_zero() {
+            return Some(CoalesceGuard {
+                key: None,
+                slots: Arc::clone(&self.slots),
+            });
+        }
+        let now = Instant::now();
+        let mut slots = self.slots.lock().expect("coalescer lock");
+        slots.retain(|_, done_at| match done_at {
+            None => true,
+            Some(t) => now.duration_since(*t) < self.window,
+        });
+
+        if slots.contains_key(&key) {
+            return None;
+        }
+        slots.insert(key.clone(), None);
+        Some(CoalesceGuard {
+            key: Some(key),
+            slots: Arc::clone(&self.slots),
+        })
+    }
+}
+
+impl<K: Eq + Hash> Drop for CoalesceGuard<K> {
+    fn drop(&mut self) {
+        if let Some(key) = self.key.take() {
+            self.slots
+                .lock()
+                .expect("coalescer lock")
+                .insert(key, Some(Instant::now()));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    async fn test_coalesce_concurrent_requests() {
+        let coalescer = Coalescer::new(Duration::from_secs(1));
+        let fetch_count = AtomicUsize::new(0);
+
+        let request = |key: (&'static str, &'static str)| {
+            let coalescer = &coalescer;
+            let fetch_count = &fetch_count;
+            async move {
+                let _guard = coalescer.try_start(key)?;
+                tokio::time::sleep(Duration::from_millis(10)).await;
+                fetch_count.fetch_add(1, Ordering::SeqCst);
+                Some("1 bitcoin vaut beaucoup trop")
+            }
+        };
+
+        let (first, second) = join!(request(("btc", "#chan")), request(("btc", "#chan")));
+        assert_eq!(
+            (first.is_some(), second.is_some()),
+            (true, false),
+            "only the first request gets a reply"
+        );
+        assert_eq!(fetch_count.load(Ordering::SeqCst), 1, "a single fetch");
+
+        assert!(
+            request(("btc", "#chan")).await.is_none(),
+            "still coalesced right after completion"
+        );
+
+        assert!(
+            request(("btc", "#other_chan")).await.is_some(),
+            "requests are coalesced per channel"
+        );
+        assert!(
+            request(("eth", "#chan")).await.is_some(),
+            "requests are coalesced per coin"
+        );
+    }
+
+    #[test]
+    async fn test_coalesce_window_expires() {
+        let coalescer = Coalescer::new(Duration::from_millis(10));
+        drop(coalescer.try_start("btc"));
+        assert!(coalescer.try_start("btc").is_none());
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(
+            coalescer.try_start("btc").is_some(),
+            "can run again once the window expired"
+        );
+    }
+
+    #[test]
+    async fn test_coalesce_disabled() {
+        let coalescer = Coalescer::new(Duration::ZERO);
+        let _in_flight = coalescer.try_start("btc");
+        assert!(coalescer.try_start("btc").is_some());
+    }
+}
@@ -0,0 +1,91 @@
+use serde::Deserialize;
+
+/// A coin the plugin knows about, as listed in the config
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub(super) struct CryptoCoin {
+    /// stored in the db, and what users type (case insensitive)
+    pub(super) symbol: String,
+    /// shown in the replies
+    pub(super) name: String,
+    /// id of the coin for the CoinGecko API
+    pub(super) id: String,
+    /// other symbols users can type for this coin
+    #[serde(default)]
+    pub(super) aliases: Vec<String>,
+}
+
+impl CryptoCoin {
+    fn new(symbol: &str, name: &str, id: &str, aliases: &[&str]) -> Self {
+        CryptoCoin {
+            symbol: symbol.to_string(),
+            name: name.to_string(),
+            id: id.to_string(),
+            aliases: aliases.iter().map(|a| a.to_string()).collect(),
+        }
+    }
+
+    fn matches(&self, symbol: &str) -> bool {
+        self.symbol.eq_ignore_ascii_case(symbol)
+            || self.aliases.iter().any(|a| a.eq_ignore_ascii_case(symbol))
+    }
+}
+
+impl std::fmt::Display for CryptoCoin {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.name)
+    }
+}
+
+/// The coins supported when the config doesn't list any
+pub(super) fn default_coins() -> Vec<CryptoCoin> {
+    vec![
+        CryptoCoin::new("BTC", "bitcoin", "bitcoin", &["xbt"]),
+        CryptoCoin::new("ETH", "ethereum", "ethereum", &[]),
+        CryptoCoin::new("DOGE", "dogecoin", "dogecoin", &[]),
+        CryptoCoin::new("XRP", "ripple", "ripple", &[]),
+        CryptoCoin::new("ALGO", "algorand", "algorand", &[]),
+    ]
+}
+
+/// Find the coin for a symbol typed by a user, or stored in the db
+pub(super) fn find<'a>(coins: &'a [CryptoCoin], symbol: &str) -> Option<&'a CryptoCoin> {
+    coins.iter().find(|c| c.matches(symbol))
+}
+
+pub(super) fn unknown_coin_message(coins: &[CryptoCoin], symbol: &str) -> String {
+    let mut symbols = coins
+        .iter()
+        .map(|c| c.symbol.to_lowercase())
+        .collect::<Vec<_>>();
+    let known = match symbols.pop() {
+        None => "rien du tout".to_string(),
+        Some(last) if symbols.is_empty() => last,
+        Some(last) => format!("{} et {}", symbols.join(", "), last),
+    };
+    format!(
+        "Dénomination inconnue: {}. Ici on ne deal qu'avec des monnais vaguement respectueuses comme {}.",
+        symbol, known
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    async fn test_find() {
+        let coins = default_coins();
+        assert_eq!(find(&coins, "btc").map(|c| c.symbol.as_str()), Some("BTC"));
+        assert_eq!(find(&coins, "XBT").map(|c| c.symbol.as_str()), Some("BTC"));
+        assert_eq!(find(&coins, "wut"), None);
+    }
+
+    #[test]
+    async fn test_unknown_coin_message() {
+        assert_eq!(
+            unknown_coin_message(&default_coins(), "wut"),
+            "Dénomination inconnue: wut. Ici on ne deal qu'avec des monnais vaguement respectueuses comme btc, eth, doge, xrp et algo."
+        );
+    }
+}
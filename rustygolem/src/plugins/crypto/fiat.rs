@@ -0,0 +1,86 @@
+use std::str::FromStr;
+
+/// The currencies rates can be quoted in
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub(super) enum Fiat {
+    #[default]
+    Eur,
+    Usd,
+    Gbp,
+    Chf,
+}
+
+const ALL: [Fiat; 4] = [Fiat::Eur, Fiat::Usd, Fiat::Gbp, Fiat::Chf];
+
+impl Fiat {
+    /// stored in the db, and what users type (case insensitive)
+    pub(super) fn code(&self) -> &'static str {
+        match self {
+            Fiat::Eur => "EUR",
+            Fiat::Usd => "USD",
+            Fiat::Gbp => "GBP",
+            Fiat::Chf => "CHF",
+        }
+    }
+
+    /// currency id for the CoinGecko API
+    pub(super) fn api_id(&self) -> String {
+        self.code().to_lowercase()
+    }
+}
+
+impl FromStr for Fiat {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        ALL.iter()
+            .find(|f| f.code().eq_ignore_ascii_case(s))
+            .copied()
+            .ok_or(())
+    }
+}
+
+impl std::fmt::Display for Fiat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Fiat::Eur => "euros",
+            Fiat::Usd => "dollars",
+            Fiat::Gbp => "livres sterling",
+            Fiat::Chf => "francs suisses",
+        };
+        f.write_str(name)
+    }
+}
+
+pub(super) fn unknown_fiat_message(symbol: &str) -> String {
+    let known = ALL
+        .iter()
+        .map(|f| f.code().to_lowercase())
+        .collect::<Vec<_>>();
+    format!(
+        "Devise inconnue: {}. Les cours sont donnés en {}.",
+        symbol,
+        known.join(", ")
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    async fn test_from_str() {
+        assert_eq!("usd".parse(), Ok(Fiat::Usd));
+        assert_eq!("GBP".parse(), Ok(Fiat::Gbp));
+        assert_eq!("doge".parse::<Fiat>(), Err(()));
+    }
+
+    #[test]
+    async fn test_unknown_fiat_message() {
+        assert_eq!(
+            unknown_fiat_message("yen"),
+            "Devise inconnue: yen. Les cours sont donnés en eur, usd, gbp, chf."
+        );
+    }
+}
@@ -1,4 +1,9 @@
+mod alert;
+mod chart;
+mod coalesce;
+mod coin;
+mod fiat;
 mod plugin;
-mod db;
+mod watch;
 
 pub use plugin::Crypto;
@@ -1,232 +1,472 @@
 use anyhow::Context;
 use async_trait::async_trait;
 use chrono::Utc;
-use diesel::serialize::ToSql;
-use diesel::{backend::Backend, prelude::*, sql_types};
-use diesel::{deserialize::FromSql, sql_types::Text};
+use diesel::prelude::*;
 use nom::branch::alt;
 use nom::bytes::complete::tag;
 use nom::character::complete::{multispace0, multispace1};
-use nom::combinator::{all_consuming, map};
+use nom::combinator::{all_consuming, map, opt};
 use nom::sequence::{preceded, terminated, tuple};
 use nom::{Finish, IResult};
 use republican_calendar::RepublicanDate;
 use reqwest::Client;
 use serde::Deserialize;
+use std::collections::HashMap;
 use std::result::Result as StdResult;
 use std::time::Duration;
 use tokio::sync::mpsc;
-use tokio::task;
 
-use super::db;
+use super::alert::{self, Alert, Direction};
+use super::chart;
+use super::coalesce::Coalescer;
+use super::coin::{self, CryptoCoin};
+use super::fiat::{self, Fiat};
+use super::watch::{self, Watch};
+use crate::db;
 use crate::schema::crypto_rate::{self, dsl};
 use crate::utils::parser::{self, command_prefix};
 use irc::proto::{Command, Message};
-use plugin_core::{Error, Initialised, Plugin, Result};
+use plugin_core::config::ConfigSection;
+use plugin_core::utils::network::{channel_key, privmsg_to};
+use plugin_core::utils::owners::Owners;
+use plugin_core::{CommandHelp, Error, Initialised, Plugin, Result};
 
-pub struct Crypto {}
+const DEFAULT_COALESCE_WINDOW: Duration = Duration::from_secs(1);
+const DEFAULT_RATE_TTL: Duration = Duration::from_secs(5 * 60);
+const DEFAULT_MONITOR_INTERVAL: Duration = Duration::from_secs(60 * 60);
+/// All the rates can't be fetched more often than that, to spare the CoinGecko API
+const MIN_MONITOR_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+#[derive(Debug, Default, Deserialize)]
+pub(super) struct CryptoConfig {
+    /// identical requests made in the same channel within this window
+    /// share a single fetch and a single reply. 0 disables coalescing.
+    coalesce_window_ms: Option<u64>,
+    /// stored rates younger than this are used instead of fetching a
+    /// live one. 0 always fetches.
+    rate_ttl_secs: Option<u64>,
+    /// channels where anyone can set up periodic rate postings,
+    /// elsewhere only the owners can
+    watch_channels: Option<Vec<String>>,
+    /// supported coins, btc, eth, doge, xrp and algo by default
+    coins: Option<Vec<CryptoCoin>>,
+    /// how often all the rates are fetched and stored, every hour by default
+    monitor_interval_secs: Option<u64>,
+}
+
+impl ConfigSection for CryptoConfig {
+    const SECTION: Option<&'static str> = Some("crypto");
+    const SCHEMA: &'static str =
+        "{ coalesce_window_ms : Optional Natural, rate_ttl_secs : Optional Natural, \
+        watch_channels : Optional (List Text), \
+        coins : Optional (List { symbol : Text, name : Text, id : Text, aliases : List Text }), \
+        monitor_interval_secs : Optional Natural }";
+}
+
+pub struct Crypto {
+    /// keyed by (command, channel key)
+    coalescer: Coalescer<(String, String)>,
+    owners: Owners,
+    watch_channels: Vec<String>,
+    coins: Vec<CryptoCoin>,
+    rate_ttl: Duration,
+    monitor_interval: Duration,
+    client: Client,
+}
 
 #[async_trait]
 impl Plugin for Crypto {
-    async fn init(_config: &plugin_core::Config) -> Result<Initialised> {
-        let _db_conn: Result<_> = tokio::task::spawn_blocking(|| {
-            let conn = db::establish_connection()?;
-            db::run_migrations(&conn)?;
-            Ok(conn)
-        })
-        .await
-        .map_err(|e| {
-            let e: anyhow::Error = e.into();
-            e
-        })?;
+    async fn init(config: &plugin_core::Config) -> Result<Initialised> {
+        let crypto_config: CryptoConfig =
+            plugin_core::config::load_or_default(&config.config_path)?;
+
+        let coalesce_window = crypto_config
+            .coalesce_window_ms
+            .map(Duration::from_millis)
+            .unwrap_or(DEFAULT_COALESCE_WINDOW);
+        let monitor_interval = crypto_config
+            .monitor_interval_secs
+            .map(Duration::from_secs)
+            .unwrap_or(DEFAULT_MONITOR_INTERVAL);
+        if monitor_interval < MIN_MONITOR_INTERVAL {
+            log::warn!(
+                "crypto monitor_interval_secs is too short, using {:?} instead",
+                MIN_MONITOR_INTERVAL
+            );
+        }
 
-        Ok(Initialised::from(Crypto {}))
+        Ok(Initialised::from(Crypto {
+            coalescer: Coalescer::new(coalesce_window),
+            owners: config.owners.clone(),
+            watch_channels: crypto_config.watch_channels.unwrap_or_default(),
+            coins: crypto_config.coins.unwrap_or_else(coin::default_coins),
+            rate_ttl: crypto_config
+                .rate_ttl_secs
+                .map(Duration::from_secs)
+                .unwrap_or(DEFAULT_RATE_TTL),
+            monitor_interval: monitor_interval.max(MIN_MONITOR_INTERVAL),
+            client: config.http_client.clone(),
+        }))
     }
 
     fn get_name(&self) -> &'static str {
         "crypto"
     }
 
+    fn commands(&self) -> Vec<CommandHelp> {
+        vec![
+            CommandHelp::new(
+                "crypto",
+                "<coin> [devise]",
+                "cours actuel et variations sur 1 jour, 1 semaine et 1 mois",
+            ),
+            CommandHelp::new(
+                "crypto",
+                "compare <coin> <coin>",
+                "rapport entre deux cours",
+            ),
+            CommandHelp::new("crypto", "<coin> ath", "plus haut cours enregistré"),
+            CommandHelp::new("crypto", "<coin> chart", "graphe des derniers cours"),
+            CommandHelp::new(
+                "crypto",
+                "movers",
+                "les coins qui ont le plus monté et baissé sur 24h",
+            ),
+            CommandHelp::new(
+                "crypto",
+                "watch <coin> every <n>[mhd]",
+                "poste le cours régulièrement dans le channel",
+            ),
+            CommandHelp::new("crypto", "unwatch <coin>", "arrête de poster le cours"),
+            CommandHelp::new(
+                "crypto",
+                "alert <coin> <|> <seuil>",
+                "prévient quand le cours passe le seuil, en euros",
+            ),
+            CommandHelp::new("crypto", "alerts [clear]", "liste ou supprime tes alertes"),
+        ]
+    }
+
     async fn in_message(&self, msg: &Message) -> Result<Option<Message>> {
-        in_msg(msg).await
+        self.in_msg(msg).await
     }
 
-    async fn run(&self, _bot_chan: mpsc::Sender<Message>) -> Result<()> {
-        monitor_crypto_coins().await?;
+    async fn run(&self, bot_chan: mpsc::Sender<Message>) -> Result<()> {
+        try_join!(
+            monitor_crypto_coins(
+                &self.client,
+                bot_chan.clone(),
+                &self.coins,
+                self.monitor_interval
+            ),
+            post_watched_rates(&self.client, bot_chan, &self.coins, self.rate_ttl)
+        )?;
         Err(Error::Synthetic(
             "crypto coin monitoring job stopped".to_string(),
         ))
     }
 }
 
-async fn in_msg(msg: &Message) -> Result<Option<Message>> {
-    let response_target = match msg.response_target() {
-        None => return Ok(None),
-        Some(target) => target.to_string(),
-    };
+impl Crypto {
+    fn can_watch(&self, msg: &Message, channel: &str) -> bool {
+        self.owners.is_owner(msg) || self.watch_channels.iter().any(|c| c == channel)
+    }
 
-    if let Command::PRIVMSG(_source, message) = &msg.command {
-        let (mb_coin, mb_target) = match parse_command(message) {
-            Ok(x) => x,
-            Err(_) => return Ok(None),
+    async fn in_msg(&self, msg: &Message) -> Result<Option<Message>> {
+        let response_target = match msg.response_target() {
+            None => return Ok(None),
+            Some(target) => target.to_string(),
         };
-        let msg = match mb_coin {
-            Ok(coin) => get_rate_and_history(coin).await?,
-            Err(x) => {
-                format!("Dénomination inconnue: {}. Ici on ne deal qu'avec des monnais vaguement respectueuses comme btc (aka xbt), eth, doge, xrp et algo.", x)
+
+        if let Command::PRIVMSG(_source, message) = &msg.command {
+            let (cmd, mb_target) = match parse_command(message) {
+                Ok(x) => x,
+                Err(_) => return Ok(None),
+            };
+            let nick = match msg.source_nickname() {
+                Some(nick) => nick,
+                None => return Ok(None),
+            };
+            let channel = channel_key(msg, &response_target);
+
+            // several people asking for the same thing at the same time
+            // only get one answer, the ones addressed to someone else with
+            // `> nick` are answered separately
+            let key = if cmd.is_personal() {
+                format!("{:?} {:?} {}", cmd, mb_target, nick)
+            } else {
+                format!("{:?} {:?}", cmd, mb_target)
+            };
+            let _guard = match self.coalescer.try_start((key, channel.clone())) {
+                Some(guard) => guard,
+                None => {
+                    log::debug!("Coalescing crypto request {:?} in {}", cmd, response_target);
+                    return Ok(None);
+                }
+            };
+
+            let msg = match cmd.resolve(&self.coins) {
+                Ok(CryptoCmd::Rate(coin, fiat)) => {
+                    get_rate_and_history(&self.client, coin.clone(), fiat, self.rate_ttl).await?
+                }
+                Ok(CryptoCmd::Compare(coin_a, coin_b)) => {
+                    compare_rates(&self.client, coin_a.clone(), coin_b.clone(), self.rate_ttl)
+                        .await?
+                }
+                Ok(CryptoCmd::Ath(coin)) => {
+                    get_all_time_high(&self.client, coin.clone(), self.rate_ttl).await?
+                }
+                Ok(CryptoCmd::Chart(coin)) => get_chart(coin.clone()).await?,
+                Ok(CryptoCmd::Movers) => get_movers(self.coins.clone()).await?,
+                Ok(CryptoCmd::Watch(..) | CryptoCmd::Unwatch(_))
+                    if !self.can_watch(msg, &response_target) =>
+                {
+                    "Seuls mes patrons peuvent programmer des cours ici.".to_string()
+                }
+                Ok(CryptoCmd::Watch(coin, interval)) => add_watch(&channel, coin, interval).await?,
+                Ok(CryptoCmd::Unwatch(coin)) => remove_watch(&channel, coin).await?,
+                Ok(CryptoCmd::Alert(coin, direction, threshold)) => {
+                    let a = Alert::new(&channel, nick, &coin.symbol, direction, threshold);
+                    add_alert(a, coin).await?
+                }
+                Ok(CryptoCmd::Alerts) => list_alerts(&channel, nick, &self.coins).await?,
+                Ok(CryptoCmd::ClearAlerts) => clear_alerts(&channel, nick).await?,
+                Err(Unknown::Coin(x)) => coin::unknown_coin_message(&self.coins, x),
+                Err(Unknown::Fiat(x)) => fiat::unknown_fiat_message(x),
+            };
+            let full_msg = crate::utils::messages::with_target(&msg, &mb_target);
+            let irc_message = Command::PRIVMSG(response_target, full_msg).into();
+            return Ok(Some(irc_message));
+        }
+        Ok(None)
+    }
+}
+
+/// Parsed with the coins and currency as typed by the user, and then
+/// resolved against the configured coins and the supported currencies.
+#[derive(Debug, PartialEq)]
+enum CryptoCmd<C, F> {
+    /// current rate and history for one coin, in the given currency
+    Rate(C, F),
+    /// ratio and daily variations between two coins
+    Compare(C, C),
+    /// highest stored rate for one coin, compared to the current one
+    Ath(C),
+    /// sparkline of the last stored rates for one coin
+    Chart(C),
+    /// the coins which moved the most over the last day
+    Movers,
+    /// post the rate for one coin periodically in the channel
+    Watch(C, Duration),
+    /// stop the periodic posting
+    Unwatch(C),
+    /// ping the user once the coin crosses the threshold, in euros
+    Alert(C, Direction, f32),
+    /// list the alerts of the user
+    Alerts,
+    /// remove all the alerts of the user
+    ClearAlerts,
+}
+
+impl<C, F> CryptoCmd<C, F> {
+    /// Whether the answer depends on who is asking
+    fn is_personal(&self) -> bool {
+        matches!(
+            self,
+            CryptoCmd::Alert(..) | CryptoCmd::Alerts | CryptoCmd::ClearAlerts
+        )
+    }
+}
+
+/// A symbol which doesn't match any supported coin or currency
+#[derive(Debug, PartialEq)]
+enum Unknown<'input> {
+    Coin(&'input str),
+    Fiat(&'input str),
+}
+
+impl<'input> CryptoCmd<&'input str, Option<&'input str>> {
+    /// Returns the first unknown symbol if any
+    fn resolve(
+        self,
+        coins: &[CryptoCoin],
+    ) -> StdResult<CryptoCmd<&CryptoCoin, Fiat>, Unknown<'input>> {
+        let find = |symbol| coin::find(coins, symbol).ok_or(Unknown::Coin(symbol));
+        Ok(match self {
+            CryptoCmd::Rate(c, f) => {
+                let coin = find(c)?;
+                let fiat = match f {
+                    None => Fiat::default(),
+                    Some(f) => f.parse().map_err(|_| Unknown::Fiat(f))?,
+                };
+                CryptoCmd::Rate(coin, fiat)
             }
-        };
-        let full_msg = crate::utils::messages::with_target(&msg, &mb_target);
-        let irc_message = Command::PRIVMSG(response_target, full_msg).into();
-        return Ok(Some(irc_message));
+            CryptoCmd::Compare(a, b) => CryptoCmd::Compare(find(a)?, find(b)?),
+            CryptoCmd::Ath(c) => CryptoCmd::Ath(find(c)?),
+            CryptoCmd::Chart(c) => CryptoCmd::Chart(find(c)?),
+            CryptoCmd::Movers => CryptoCmd::Movers,
+            CryptoCmd::Watch(c, interval) => CryptoCmd::Watch(find(c)?, interval),
+            CryptoCmd::Unwatch(c) => CryptoCmd::Unwatch(find(c)?),
+            CryptoCmd::Alert(c, direction, threshold) => {
+                CryptoCmd::Alert(find(c)?, direction, threshold)
+            }
+            CryptoCmd::Alerts => CryptoCmd::Alerts,
+            CryptoCmd::ClearAlerts => CryptoCmd::ClearAlerts,
+        })
     }
-    Ok(None)
 }
 
-fn parse_command(input: &str) -> StdResult<(StdResult<CryptoCoin, &str>, Option<&str>), String> {
+fn parse_command(input: &str) -> StdResult<(CryptoCmd<&str, Option<&str>>, Option<&str>), String> {
     all_consuming(terminated(parse_crypto, multispace0))(input)
         .finish()
         .map(|x| x.1)
         .map_err(|e| format!("{:?}", e))
 }
 
-fn parse_crypto(input: &str) -> IResult<&str, (StdResult<CryptoCoin, &str>, Option<&str>)> {
+fn parse_crypto(input: &str) -> IResult<&str, (CryptoCmd<&str, Option<&str>>, Option<&str>)> {
     preceded(
         command_prefix,
         map(
-            parser::with_target(tuple((tag("crypto"), multispace1, crypto_cmd))),
+            parser::with_target(tuple((
+                tag("crypto"),
+                multispace1,
+                alt((
+                    compare_cmd,
+                    watch_cmd,
+                    unwatch_cmd,
+                    alert_cmd,
+                    alerts_cmd,
+                    movers_cmd,
+                    ath_cmd,
+                    chart_cmd,
+                    rate_cmd,
+                )),
+            ))),
             |((_, _, c), t)| (c, t),
         ),
     )(input)
 }
 
-fn crypto_cmd(input: &str) -> IResult<&str, StdResult<CryptoCoin, &str>> {
-    alt((
-        map(tag("xbt"), |_| Ok(CryptoCoin::Bitcoin)),
-        map(tag("btc"), |_| Ok(CryptoCoin::Bitcoin)),
-        map(tag("eth"), |_| Ok(CryptoCoin::Ethereum)),
-        map(tag("doge"), |_| Ok(CryptoCoin::Doge)),
-        map(tag("xrp"), |_| Ok(CryptoCoin::Ripple)),
-        map(tag("algo"), |_| Ok(CryptoCoin::Algorand)),
-        map(parser::word, Err),
-    ))(input)
-}
-
-#[derive(Debug, FromSqlRow, AsExpression, PartialEq, Clone, Copy)]
-#[sql_type = "Text"]
-enum CryptoCoin {
-    Bitcoin,
-    Ethereum,
-    Doge,
-    Ripple,
-    Algorand,
-}
-
-impl<DB> FromSql<sql_types::Text, DB> for CryptoCoin
-where
-    DB: Backend,
-    String: FromSql<sql_types::Text, DB>,
-{
-    fn from_sql(bytes: Option<&DB::RawValue>) -> diesel::deserialize::Result<Self> {
-        match &(String::from_sql(bytes)?)[..] {
-            "BTC" => Ok(CryptoCoin::Bitcoin),
-            "ETH" => Ok(CryptoCoin::Ethereum),
-            "DOGE" => Ok(CryptoCoin::Doge),
-            "XRP" => Ok(CryptoCoin::Ripple),
-            "ALGO" => Ok(CryptoCoin::Algorand),
-            x => Err(format!("Unknown denomination: {}", x).into()),
-        }
-    }
+fn compare_cmd(input: &str) -> IResult<&str, CryptoCmd<&str, Option<&str>>> {
+    map(
+        tuple((
+            tag("compare"),
+            multispace1,
+            crypto_cmd,
+            multispace1,
+            crypto_cmd,
+        )),
+        |(_, _, a, _, b)| CryptoCmd::Compare(a, b),
+    )(input)
 }
 
-impl<DB> ToSql<sql_types::Text, DB> for CryptoCoin
-where
-    DB: Backend,
-{
-    fn to_sql<W: std::io::Write>(
-        &self,
-        out: &mut diesel::serialize::Output<W, DB>,
-    ) -> diesel::serialize::Result {
-        let tag = match self {
-            CryptoCoin::Bitcoin => "BTC",
-            CryptoCoin::Ethereum => "ETH",
-            CryptoCoin::Doge => "DOGE",
-            CryptoCoin::Ripple => "XRP",
-            CryptoCoin::Algorand => "ALGO",
-        };
-        ToSql::<sql_types::Text, DB>::to_sql(tag, out)
-    }
+fn rate_cmd(input: &str) -> IResult<&str, CryptoCmd<&str, Option<&str>>> {
+    map(
+        tuple((crypto_cmd, opt(preceded(multispace1, parser::word)))),
+        |(coin, fiat)| CryptoCmd::Rate(coin, fiat),
+    )(input)
 }
 
-impl std::fmt::Display for CryptoCoin {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            CryptoCoin::Bitcoin => f.write_str("bitcoin"),
-            CryptoCoin::Ethereum => f.write_str("ethereum"),
-            CryptoCoin::Doge => f.write_str("dogecoin"),
-            CryptoCoin::Ripple => f.write_str("ripple"),
-            CryptoCoin::Algorand => f.write_str("algorand"),
-        }
-    }
+fn ath_cmd(input: &str) -> IResult<&str, CryptoCmd<&str, Option<&str>>> {
+    map(
+        tuple((crypto_cmd, multispace1, tag("ath"))),
+        |(coin, _, _)| CryptoCmd::Ath(coin),
+    )(input)
 }
 
-// a bit tedious to map a rust struct from json
-// which doesn't immediately reflect the structure.
-// So use tmp structs and the serde_derive feature
-#[derive(Debug, Deserialize, PartialEq)]
-struct CryptowatchResponse {
-    result: CryptowatchResponseResult,
-    allowance: CryptowatchResponseAllowance,
+fn chart_cmd(input: &str) -> IResult<&str, CryptoCmd<&str, Option<&str>>> {
+    map(
+        tuple((crypto_cmd, multispace1, tag("chart"))),
+        |(coin, _, _)| CryptoCmd::Chart(coin),
+    )(input)
 }
 
-#[derive(Debug, Deserialize, PartialEq)]
-struct CryptowatchResponseResult {
-    price: f32,
+fn watch_cmd(input: &str) -> IResult<&str, CryptoCmd<&str, Option<&str>>> {
+    map(
+        tuple((
+            tag("watch"),
+            multispace1,
+            crypto_cmd,
+            multispace1,
+            watch::recurrence,
+        )),
+        |(_, _, coin, _, interval)| CryptoCmd::Watch(coin, interval),
+    )(input)
 }
 
-#[derive(Debug, Deserialize, PartialEq)]
-struct CryptowatchResponseAllowance {
-    cost: f32,
-    remaining: f32,
+fn unwatch_cmd(input: &str) -> IResult<&str, CryptoCmd<&str, Option<&str>>> {
+    map(
+        tuple((tag("unwatch"), multispace1, crypto_cmd)),
+        |(_, _, coin)| CryptoCmd::Unwatch(coin),
+    )(input)
+}
+
+fn alert_cmd(input: &str) -> IResult<&str, CryptoCmd<&str, Option<&str>>> {
+    map(
+        tuple((
+            tag("alert"),
+            multispace1,
+            crypto_cmd,
+            multispace1,
+            alert::threshold,
+        )),
+        |(_, _, coin, _, (direction, threshold))| CryptoCmd::Alert(coin, direction, threshold),
+    )(input)
+}
+
+fn alerts_cmd(input: &str) -> IResult<&str, CryptoCmd<&str, Option<&str>>> {
+    map(
+        tuple((tag("alerts"), opt(preceded(multispace1, tag("clear"))))),
+        |(_, clear)| match clear {
+            Some(_) => CryptoCmd::ClearAlerts,
+            None => CryptoCmd::Alerts,
+        },
+    )(input)
+}
+
+fn movers_cmd(input: &str) -> IResult<&str, CryptoCmd<&str, Option<&str>>> {
+    map(tag("movers"), |_| CryptoCmd::Movers)(input)
 }
 
+/// symbol of a coin, known or not
+fn crypto_cmd(input: &str) -> IResult<&str, &str> {
+    parser::word(input)
+}
+
+// a bit tedious to map a rust struct from json
+// which doesn't immediately reflect the structure.
+// So use tmp structs and the serde_derive feature
+// The response is keyed by coin id, then by currency:
+// {"bitcoin":{"eur":30250.14}}
+type CoinGeckoResponse = HashMap<String, HashMap<String, f32>>;
+
 impl CryptoCoin {
-    async fn get_rate_in_euro(&self, http_client: &Client) -> anyhow::Result<f32> {
-        let symbol = match &self {
-            CryptoCoin::Bitcoin => "btc",
-            CryptoCoin::Ethereum => "eth",
-            CryptoCoin::Doge => "doge",
-            CryptoCoin::Ripple => "xrp",
-            CryptoCoin::Algorand => "algo",
-        };
-        let exchange = match &self {
-            CryptoCoin::Bitcoin => "bitstamp",
-            CryptoCoin::Ethereum => "bitstamp",
-            CryptoCoin::Doge => "bittrex",
-            CryptoCoin::Ripple => "bittrex",
-            CryptoCoin::Algorand => "coinbase-pro",
-        };
+    async fn get_rate_in(&self, http_client: &Client, fiat: Fiat) -> anyhow::Result<f32> {
+        let id = self.id.as_str();
+        let currency = fiat.api_id();
         let url = format!(
-            "https://api.cryptowat.ch/markets/{}/{}eur/price",
-            exchange, symbol
+            "https://api.coingecko.com/api/v3/simple/price?ids={}&vs_currencies={}",
+            id, currency
         );
 
         let json_resp = http_client
             .get(&url)
             .send()
             .await?
-            .json::<CryptowatchResponse>()
+            .json::<CoinGeckoResponse>()
             .await
             .context(format!("Error while fetching response from {}", url))?;
 
-        log::info!(
-            "Got price for {} at {} cost {} remaining allowance {}",
-            &self,
-            json_resp.result.price,
-            json_resp.allowance.cost,
-            json_resp.allowance.remaining
-        );
-        Ok(json_resp.result.price)
+        let price = json_resp
+            .get(id)
+            .and_then(|p| p.get(&currency))
+            .copied()
+            .with_context(|| {
+                format!("No {} price for {} in response from {}", currency, id, url)
+            })?;
+
+        log::info!("Got price for {} at {} {}", &self, price, fiat.code());
+        Ok(price)
     }
 }
 
@@ -234,115 +474,457 @@ impl CryptoCoin {
 #[table_name = "crypto_rate"]
 struct CryptoCoinRate {
     date: chrono::NaiveDateTime,
-    coin: CryptoCoin,
+    /// symbol of the coin
+    coin: String,
     rate: f32,
+    /// code of the fiat currency the rate is in
+    currency: String,
 }
 
-/// fetch, and save all crypto rates every minute
-async fn monitor_crypto_coins() -> anyhow::Result<()> {
+/// fetch, and save all crypto rates every `interval`, then fire the alerts
+/// crossed by the new rates
+async fn monitor_crypto_coins(
+    client: &Client,
+    bot_chan: mpsc::Sender<Message>,
+    coins: &[CryptoCoin],
+    interval: Duration,
+) -> anyhow::Result<()> {
     loop {
-        get_and_save_all_rates().await?;
-        tokio::time::sleep(Duration::from_secs(60 * 60)).await;
+        let rows = get_and_save_all_rates(client, coins).await?;
+        fire_alerts(&bot_chan, coins, rows).await?;
+        tokio::time::sleep(interval).await;
     }
 }
 
-async fn get_and_save_all_rates() -> anyhow::Result<()> {
-    let client = reqwest::Client::new();
-    let (btc_rate, eth_rate, doge_rate, ripple_rate, algo_rate) = try_join!(
-        CryptoCoin::Bitcoin.get_rate_in_euro(&client),
-        CryptoCoin::Ethereum.get_rate_in_euro(&client),
-        CryptoCoin::Doge.get_rate_in_euro(&client),
-        CryptoCoin::Ripple.get_rate_in_euro(&client),
-        CryptoCoin::Algorand.get_rate_in_euro(&client),
-    )?;
+async fn fire_alerts(
+    bot_chan: &mpsc::Sender<Message>,
+    coins: &[CryptoCoin],
+    rates: Vec<CryptoCoinRate>,
+) -> anyhow::Result<()> {
+    let triggered = db::with_connection(move |conn| {
+        let mut triggered = vec![];
+        for r in rates {
+            for a in alert::take_triggered(conn, &r.coin, r.rate)? {
+                triggered.push((a, r.rate));
+            }
+        }
+        Ok::<_, anyhow::Error>(triggered)
+    })
+    .await?;
 
-    let btc_row = CryptoCoinRate {
-        date: chrono::Utc::now().naive_utc(),
-        coin: CryptoCoin::Bitcoin,
-        rate: btc_rate,
-    };
+    for (a, rate) in triggered {
+        let name = coin::find(coins, &a.coin)
+            .map(|c| c.name.as_str())
+            .unwrap_or(a.coin.as_str());
+        let msg = format_triggered(name, &a, rate);
+        bot_chan
+            .send(privmsg_to(&a.channel, msg))
+            .await
+            .with_context(|| format!("can't send message to {}", &a.channel))?;
+    }
+    Ok(())
+}
 
-    let eth_row = CryptoCoinRate {
-        date: chrono::Utc::now().naive_utc(),
-        coin: CryptoCoin::Ethereum,
-        rate: eth_rate,
-    };
+fn format_triggered(coin_name: &str, a: &Alert, rate: f32) -> String {
+    format!(
+        "{}: 1 {} vaut {} euros, alerte {} déclenchée !",
+        a.nick,
+        coin_name,
+        rate,
+        alert::format_alert(coin_name, a)
+    )
+}
 
-    let doge_row = CryptoCoinRate {
-        date: chrono::Utc::now().naive_utc(),
-        coin: CryptoCoin::Doge,
-        rate: doge_rate,
-    };
+/// post the rates for the watches which are due, checking every minute
+async fn post_watched_rates(
+    client: &Client,
+    bot_chan: mpsc::Sender<Message>,
+    coins: &[CryptoCoin],
+    rate_ttl: Duration,
+) -> anyhow::Result<()> {
+    loop {
+        let now = Utc::now().naive_utc();
+        let due = db::with_connection(move |conn| watch::due(conn, now)).await?;
 
-    let ripple_row = CryptoCoinRate {
-        date: chrono::Utc::now().naive_utc(),
-        coin: CryptoCoin::Ripple,
-        rate: ripple_rate,
-    };
+        for w in due {
+            let posted = match coin::find(coins, &w.coin) {
+                Some(c) => get_rate_and_history(client, c.clone(), Fiat::default(), rate_ttl).await,
+                None => Err(anyhow!("{} isn't configured anymore", w.coin)),
+            };
+            match posted {
+                Ok(msg) => {
+                    bot_chan
+                        .send(privmsg_to(&w.channel, msg))
+                        .await
+                        .with_context(|| format!("can't send message to {}", &w.channel))?;
+                }
+                Err(err) => log::error!("Cannot post watched rate {:?}: {:?}", w, err),
+            }
+            db::with_connection(move |conn| watch::reschedule(conn, &w, now)).await?;
+        }
 
-    let algo_row = CryptoCoinRate {
-        date: chrono::Utc::now().naive_utc(),
-        coin: CryptoCoin::Algorand,
-        rate: algo_rate,
+        tokio::time::sleep(Duration::from_secs(60)).await;
+    }
+}
+
+async fn add_watch(channel: &str, coin: &CryptoCoin, interval: Duration) -> anyhow::Result<String> {
+    if interval < watch::MIN_INTERVAL {
+        return Ok(format!(
+            "Pas plus d'un cours toutes les {}, faut pas abuser.",
+            watch::format_interval(watch::MIN_INTERVAL)
+        ));
+    }
+    if interval > watch::MAX_INTERVAL {
+        return Ok(format!(
+            "Pas moins d'un cours tous les {}, sinon autant regarder soi-même.",
+            watch::format_interval(watch::MAX_INTERVAL)
+        ));
+    }
+
+    let w = match Watch::new(channel, &coin.symbol, interval, Utc::now().naive_utc()) {
+        Some(w) => w,
+        None => return Ok("C'est un peu loin, ça.".to_string()),
     };
+    db::with_connection(move |conn| watch::register(conn, &w)).await?;
+    Ok(format!(
+        "Le cours de {} sera posté ici toutes les {}",
+        coin,
+        watch::format_interval(interval)
+    ))
+}
+
+async fn remove_watch(channel: &str, coin: &CryptoCoin) -> anyhow::Result<String> {
+    let chan = channel.to_string();
+    let symbol = coin.symbol.clone();
+    let removed = db::with_connection(move |conn| watch::unregister(conn, &chan, &symbol)).await?;
+    Ok(if removed {
+        format!("Plus de cours de {} ici.", coin)
+    } else {
+        format!("Le cours de {} n'est pas suivi ici.", coin)
+    })
+}
+
+/// Saves the alert, returns the confirmation for the user
+async fn add_alert(a: Alert, coin: &CryptoCoin) -> anyhow::Result<String> {
+    let msg = format!(
+        "{}: je te préviens quand {} euros",
+        a.nick,
+        alert::format_alert(&coin.name, &a)
+    );
+    db::with_connection(move |conn| alert::register(conn, &a)).await?;
+    Ok(msg)
+}
 
-    task::spawn_blocking(move || {
-        let conn = db::establish_connection()?;
-        let vals = vec![&btc_row, &eth_row, &doge_row, &ripple_row, &algo_row];
+async fn list_alerts(channel: &str, nick: &str, coins: &[CryptoCoin]) -> anyhow::Result<String> {
+    let (chan, n) = (channel.to_string(), nick.to_string());
+    let alerts = db::with_connection(move |conn| alert::for_user(conn, &chan, &n)).await?;
+    Ok(format_alerts(nick, coins, &alerts))
+}
+
+fn format_alerts(nick: &str, coins: &[CryptoCoin], alerts: &[Alert]) -> String {
+    if alerts.is_empty() {
+        return format!("{}: pas d'alerte pour toi ici.", nick);
+    }
+    let alerts = alerts
+        .iter()
+        .map(|a| {
+            let name = coin::find(coins, &a.coin)
+                .map(|c| c.name.as_str())
+                .unwrap_or(a.coin.as_str());
+            alert::format_alert(name, a)
+        })
+        .collect::<Vec<_>>();
+    format!("{}: tes alertes, en euros: {}", nick, alerts.join(", "))
+}
+
+async fn clear_alerts(channel: &str, nick: &str) -> anyhow::Result<String> {
+    let (chan, n) = (channel.to_string(), nick.to_string());
+    let removed = db::with_connection(move |conn| alert::clear(conn, &chan, &n)).await?;
+    Ok(match removed {
+        0 => format!("{}: tu n'avais pas d'alerte ici.", nick),
+        1 => format!("{}: alerte supprimée.", nick),
+        n => format!("{}: {} alertes supprimées.", nick, n),
+    })
+}
+
+async fn get_and_save_all_rates(
+    client: &Client,
+    coins: &[CryptoCoin],
+) -> anyhow::Result<Vec<CryptoCoinRate>> {
+    let rates =
+        futures::future::try_join_all(coins.iter().map(|c| c.get_rate_in(client, Fiat::Eur)))
+            .await?;
+
+    let date = chrono::Utc::now().naive_utc();
+    let rows = coins
+        .iter()
+        .zip(rates)
+        .map(|(coin, rate)| CryptoCoinRate {
+            date,
+            coin: coin.symbol.clone(),
+            rate,
+            currency: Fiat::Eur.code().to_string(),
+        })
+        .collect::<Vec<_>>();
+
+    db::with_connection(move |conn| {
         diesel::insert_into(crypto_rate::table)
-            .values(vals.clone())
-            .execute(&conn)
-            .with_context(|| format!("Cannot insert {:?} into db", vals))
+            .values(&rows)
+            .execute(conn)
+            .with_context(|| format!("Cannot insert {:?} into db", rows))?;
+        Ok::<_, anyhow::Error>(rows)
     })
-    .await??;
+    .await?;
     log::info!("Successfully updated DB for crypto rates");
 
-    Ok(())
+    Ok(rows)
 }
 
-async fn get_rate_and_history(coin: CryptoCoin) -> anyhow::Result<String> {
-    let client = reqwest::Client::new();
-    let rate = coin.get_rate_in_euro(&client).await?;
+/// Current rate for the given coin. Uses the latest stored rate if it's
+/// younger than `ttl`, otherwise fetches the rate and stores it in the DB
+async fn get_rate(
+    client: &Client,
+    coin: &CryptoCoin,
+    fiat: Fiat,
+    ttl: Duration,
+) -> anyhow::Result<f32> {
+    let since = Utc::now().naive_utc() - chrono::Duration::from_std(ttl)?;
+    let c = coin.clone();
+    let cached = db::with_connection(move |conn| latest_rate(conn, &c, fiat, since)).await?;
+    if let Some(row) = cached {
+        log::debug!("Using stored rate {:?}", row);
+        return Ok(row.rate);
+    }
+
+    let rate = coin.get_rate_in(client, fiat).await?;
     let row = CryptoCoinRate {
         date: chrono::Utc::now().naive_utc(),
-        coin,
+        coin: coin.symbol.clone(),
         rate,
+        currency: fiat.code().to_string(),
     };
-    task::spawn_blocking(move || {
-        let conn = db::establish_connection()?;
+    db::with_connection(move |conn| {
         diesel::insert_into(crypto_rate::table)
             .values(&row)
-            .execute(&conn)
-            .with_context(|| format!("Cannot insert {:?} into db", row))?;
-
-        let now = Utc::now();
-        let past_day = dsl::crypto_rate
-            .filter(dsl::date.le((now - chrono::Duration::days(1)).naive_utc()))
-            .filter(dsl::coin.eq(coin))
-            .order_by(dsl::date.desc())
-            .limit(1)
-            .load::<CryptoCoinRate>(&conn)?
-            .into_iter()
-            .next();
-
-        let past_week = dsl::crypto_rate
-            .filter(dsl::date.le((now - chrono::Duration::days(7)).naive_utc()))
-            .filter(dsl::coin.eq(coin))
-            .order_by(dsl::date.desc())
-            .limit(1)
-            .load::<CryptoCoinRate>(&conn)?
-            .into_iter()
-            .next();
-
-        let past_month = dsl::crypto_rate
-            // not quite 1 month, but 🤷
-            .filter(dsl::date.le((now - chrono::Duration::days(30)).naive_utc()))
-            .filter(dsl::coin.eq(coin))
-            .order_by(dsl::date.desc())
-            .limit(1)
-            .load::<CryptoCoinRate>(&conn)?
-            .into_iter()
-            .next();
+            .execute(conn)
+            .with_context(|| format!("Cannot insert {:?} into db", row))
+    })
+    .await?;
+    Ok(rate)
+}
+
+/// Most recent stored rate for the given coin and currency,
+/// if it was stored after `since`
+fn latest_rate(
+    conn: &SqliteConnection,
+    coin: &CryptoCoin,
+    fiat: Fiat,
+    since: chrono::NaiveDateTime,
+) -> anyhow::Result<Option<CryptoCoinRate>> {
+    let rate = dsl::crypto_rate
+        .filter(dsl::date.ge(since))
+        .filter(dsl::coin.eq(coin.symbol.as_str()))
+        .filter(dsl::currency.eq(fiat.code()))
+        .order_by(dsl::date.desc())
+        .limit(1)
+        .load::<CryptoCoinRate>(conn)?
+        .into_iter()
+        .next();
+    Ok(rate)
+}
+
+/// Most recent stored rate for the given coin and currency
+/// which is at least `days` old
+fn rate_days_ago(
+    conn: &SqliteConnection,
+    coin: &CryptoCoin,
+    fiat: Fiat,
+    days: i64,
+) -> anyhow::Result<Option<CryptoCoinRate>> {
+    let now = Utc::now();
+    let rate = dsl::crypto_rate
+        .filter(dsl::date.le((now - chrono::Duration::days(days)).naive_utc()))
+        .filter(dsl::coin.eq(coin.symbol.as_str()))
+        .filter(dsl::currency.eq(fiat.code()))
+        .order_by(dsl::date.desc())
+        .limit(1)
+        .load::<CryptoCoinRate>(conn)?
+        .into_iter()
+        .next();
+    Ok(rate)
+}
+
+/// Highest stored rate in euros for the given coin
+fn all_time_high(
+    conn: &SqliteConnection,
+    coin: &CryptoCoin,
+) -> anyhow::Result<Option<CryptoCoinRate>> {
+    let rate = dsl::crypto_rate
+        .filter(dsl::coin.eq(coin.symbol.as_str()))
+        .filter(dsl::currency.eq(Fiat::Eur.code()))
+        .order_by(dsl::rate.desc())
+        .limit(1)
+        .load::<CryptoCoinRate>(conn)?
+        .into_iter()
+        .next();
+    Ok(rate)
+}
+
+async fn get_all_time_high(
+    client: &Client,
+    coin: CryptoCoin,
+    rate_ttl: Duration,
+) -> anyhow::Result<String> {
+    let rate = get_rate(client, &coin, Fiat::Eur, rate_ttl).await?;
+    let c = coin.clone();
+    let ath = db::with_connection(move |conn| all_time_high(conn, &c)).await?;
+
+    Ok(match ath {
+        None => format!("Pas encore de cours enregistré pour {}", coin),
+        Some(ath) => format_ath(&coin, rate, &ath),
+    })
+}
+
+fn format_ath(coin: &CryptoCoin, rate: f32, ath: &CryptoCoinRate) -> String {
+    let date = ath.date.format("%Y-%m-%d");
+    if rate >= ath.rate {
+        format!(
+            "1 {} vaut {} euros, c'est l'ATH ! To the moon 🚀",
+            coin, rate
+        )
+    } else {
+        format!(
+            "1 {} vaut {} euros, −{:.02}% sous l'ATH de {} le {}",
+            coin,
+            rate,
+            below_ath(rate, ath.rate),
+            ath.rate,
+            date
+        )
+    }
+}
+
+/// How far below the all time high the current rate is, in percent
+fn below_ath(rate: f32, ath: f32) -> f32 {
+    ((ath - rate) * 100.0) / ath
+}
+
+/// Stored rates drawn by λcrypto chart, a day with the default monitor interval
+const CHART_SAMPLES: i64 = 24;
+
+/// The last `n` stored rates in euros for the given coin, oldest first
+fn recent_rates(conn: &SqliteConnection, coin: &CryptoCoin, n: i64) -> anyhow::Result<Vec<f32>> {
+    let mut rates = dsl::crypto_rate
+        .select(dsl::rate)
+        .filter(dsl::coin.eq(coin.symbol.as_str()))
+        .filter(dsl::currency.eq(Fiat::Eur.code()))
+        .order_by(dsl::date.desc())
+        .limit(n)
+        .load::<f32>(conn)?;
+    rates.reverse();
+    Ok(rates)
+}
+
+async fn get_chart(coin: CryptoCoin) -> anyhow::Result<String> {
+    let c = coin.clone();
+    let rates = db::with_connection(move |conn| recent_rates(conn, &c, CHART_SAMPLES)).await?;
+    Ok(format_chart(&coin, &rates))
+}
+
+fn format_chart(coin: &CryptoCoin, rates: &[f32]) -> String {
+    if rates.len() < 2 {
+        return format!("Pas assez de cours enregistrés pour {}", coin);
+    }
+    let min = rates.iter().copied().fold(f32::INFINITY, f32::min);
+    let max = rates.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+    format!(
+        "{} {} (de {} à {} euros, sur les {} derniers cours)",
+        coin,
+        chart::sparkline(rates),
+        min,
+        max,
+        rates.len()
+    )
+}
+
+/// Shown for each direction in λcrypto movers
+const MAX_MOVERS: usize = 3;
+
+async fn get_movers(coins: Vec<CryptoCoin>) -> anyhow::Result<String> {
+    let movers = db::with_connection(move |conn| daily_variations(conn, &coins)).await?;
+    Ok(format_movers(&movers))
+}
+
+/// Variation in euros over the last day of the coins, from the stored rates.
+/// Coins without a rate in the last day, or without one a day older, are skipped.
+fn daily_variations(
+    conn: &SqliteConnection,
+    coins: &[CryptoCoin],
+) -> anyhow::Result<Vec<(CryptoCoin, RateVariation)>> {
+    let since = (Utc::now() - chrono::Duration::days(1)).naive_utc();
+    let mut variations = vec![];
+    for coin in coins {
+        let current = latest_rate(conn, coin, Fiat::Eur, since)?;
+        let past = rate_days_ago(conn, coin, Fiat::Eur, 1)?;
+        match (current, past) {
+            (Some(current), Some(past)) => variations.push((
+                coin.clone(),
+                RateVariation::between(past.rate, current.rate),
+            )),
+            _ => log::debug!("Not enough stored rates for {} to compute its movers", coin),
+        }
+    }
+    Ok(variations)
+}
+
+fn format_movers(variations: &[(CryptoCoin, RateVariation)]) -> String {
+    if variations.is_empty() {
+        return "Pas assez de cours enregistrés sur les dernières 24h".to_string();
+    }
+    let mut variations = variations.iter().collect::<Vec<_>>();
+    variations.sort_by(|(_, a), (_, b)| b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal));
+    let list = |movers: Vec<&&(CryptoCoin, RateVariation)>| {
+        movers
+            .iter()
+            .map(|(coin, var)| format!("{} {:.02}", coin, var))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    let ups = variations
+        .iter()
+        .take_while(|(_, var)| var.0 > 0.0)
+        .take(MAX_MOVERS)
+        .collect::<Vec<_>>();
+    let downs = variations
+        .iter()
+        .rev()
+        .take_while(|(_, var)| var.0 < 0.0)
+        .take(MAX_MOVERS)
+        .collect::<Vec<_>>();
+    let sections = [("en hausse", ups), ("en baisse", downs)]
+        .into_iter()
+        .filter(|(_, movers)| !movers.is_empty())
+        .map(|(direction, movers)| format!("{}: {}", direction, list(movers)))
+        .collect::<Vec<_>>();
+    if sections.is_empty() {
+        "Rien n'a bougé sur les dernières 24h".to_string()
+    } else {
+        format!("Sur 24h, {}", sections.join(" − "))
+    }
+}
+
+async fn get_rate_and_history(
+    client: &Client,
+    coin: CryptoCoin,
+    fiat: Fiat,
+    rate_ttl: Duration,
+) -> anyhow::Result<String> {
+    let rate = get_rate(client, &coin, fiat, rate_ttl).await?;
+    db::with_connection(move |conn| {
+        let past_day = rate_days_ago(conn, &coin, fiat, 1)?;
+        let past_week = rate_days_ago(conn, &coin, fiat, 7)?;
+        // not quite 1 month, but 🤷
+        let past_month = rate_days_ago(conn, &coin, fiat, 30)?;
 
         log::debug!(
             "current rate: {}, past day: {:?}, past week: {:?}, past month: {:?}",
@@ -356,7 +938,7 @@ async fn get_rate_and_history(coin: CryptoCoin) -> anyhow::Result<String> {
             .into_iter()
             .filter_map(|(mb_r, suffix)| {
                 mb_r.map(|r| {
-                    let var = RateVariation(((rate - r.rate) * 100.0) / r.rate);
+                    let var = RateVariation::between(r.rate, rate);
                     format!("{:.02} {}", var, suffix)
                 })
             })
@@ -372,20 +954,93 @@ async fn get_rate_and_history(coin: CryptoCoin) -> anyhow::Result<String> {
         let rep_date = RepublicanDate::try_from(now.date()).map_err(|e| anyhow!(e))?;
 
         let result = format!(
-            "1 {} vaut {} euros grâce au pouvoir de la spéculation et {} ! {}",
+            "1 {} vaut {} {} grâce au pouvoir de la spéculation et {} ! {}",
             coin,
             rate,
+            fiat,
             rep_date.day_symbol(),
             variations,
         );
 
         Ok(result)
     })
-    .await?
+    .await
+}
+
+async fn compare_rates(
+    client: &Client,
+    coin_a: CryptoCoin,
+    coin_b: CryptoCoin,
+    rate_ttl: Duration,
+) -> anyhow::Result<String> {
+    let (rate_a, rate_b) = join!(
+        get_rate(client, &coin_a, Fiat::Eur, rate_ttl),
+        get_rate(client, &coin_b, Fiat::Eur, rate_ttl)
+    );
+
+    let (rate_a, rate_b) = match (rate_a, rate_b) {
+        (Ok(a), Ok(b)) => (a, b),
+        (Err(err), Ok(_)) => {
+            log::error!("Cannot fetch rate for {}: {:?}", coin_a, err);
+            return Ok(format!("Impossible de récupérer le cours de {}", coin_a));
+        }
+        (Ok(_), Err(err)) => {
+            log::error!("Cannot fetch rate for {}: {:?}", coin_b, err);
+            return Ok(format!("Impossible de récupérer le cours de {}", coin_b));
+        }
+        (Err(err_a), Err(err_b)) => {
+            log::error!("Cannot fetch rate for {}: {:?}", coin_a, err_a);
+            log::error!("Cannot fetch rate for {}: {:?}", coin_b, err_b);
+            return Ok(format!(
+                "Impossible de récupérer le cours de {} et de {}",
+                coin_a, coin_b
+            ));
+        }
+    };
+
+    let (a, b) = (coin_a.clone(), coin_b.clone());
+    let (past_a, past_b) = db::with_connection(move |conn| {
+        let past_a = rate_days_ago(conn, &a, Fiat::Eur, 1)?;
+        let past_b = rate_days_ago(conn, &b, Fiat::Eur, 1)?;
+        Ok::<_, anyhow::Error>((past_a.map(|r| r.rate), past_b.map(|r| r.rate)))
+    })
+    .await?;
+
+    Ok(format_comparison(
+        (&coin_a, rate_a, past_a),
+        (&coin_b, rate_b, past_b),
+    ))
+}
+
+/// Each argument is (coin, current rate, rate 24h ago)
+fn format_comparison(
+    (coin_a, rate_a, past_a): (&CryptoCoin, f32, Option<f32>),
+    (coin_b, rate_b, past_b): (&CryptoCoin, f32, Option<f32>),
+) -> String {
+    let variation = |coin: &CryptoCoin, rate: f32, past: Option<f32>| match past {
+        Some(past) => format!("{} {:.02}", coin, RateVariation::between(past, rate)),
+        None => format!("{} ?", coin),
+    };
+
+    format!(
+        "1 {} = {:.04} {}; {} {} (1D)",
+        coin_a,
+        rate_a / rate_b,
+        coin_b,
+        variation(coin_a, rate_a, past_a),
+        variation(coin_b, rate_b, past_b),
+    )
 }
 
 struct RateVariation(f32);
 
+impl RateVariation {
+    /// variation in percent from `past` to `current`
+    fn between(past: f32, current: f32) -> Self {
+        RateVariation(((current - past) * 100.0) / past)
+    }
+}
+
 impl std::fmt::Display for RateVariation {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let r = self.0;
@@ -407,22 +1062,26 @@ mod test {
     use super::*;
     use pretty_assertions::assert_eq;
 
+    fn find(symbol: &str) -> CryptoCoin {
+        coin::find(&coin::default_coins(), symbol).unwrap().clone()
+    }
+
     #[test]
     async fn price_from_json() {
-        let json = r#"{"result":{"price":30250.14},"allowance":{"cost":0.005,"remaining":9.98,"upgrade":"For unlimited API access, create an account at https://cryptowat.ch"}}"#;
-        let expected = CryptowatchResponse {
-            result: CryptowatchResponseResult { price: 30250.14 },
-            allowance: CryptowatchResponseAllowance {
-                cost: 0.005,
-                remaining: 9.98,
-            },
-        };
-
+        let json = r#"{"bitcoin":{"usd":35120.5}}"#;
+        let resp: CoinGeckoResponse = serde_json::from_str(json).unwrap();
         assert_eq!(
-            serde_json::from_str(json).map_err(|e| format!("{:?}", e)),
-            // CryptowatchPrice::from_str(json).map_err(|e| format!("{:?}", e)),
-            Ok(expected)
-        )
+            resp.get(find("btc").id.as_str())
+                .and_then(|p| p.get(&Fiat::Usd.api_id())),
+            Some(&35120.5)
+        );
+        assert_eq!(
+            serde_json::from_str::<CoinGeckoResponse>("{}")
+                .unwrap()
+                .get("bitcoin"),
+            None,
+            "unknown ids are simply missing from the response"
+        );
     }
 
     #[test]
@@ -434,14 +1093,421 @@ mod test {
 
         assert_eq!(
             parse_command("λcrypto xbt"),
-            Ok((Ok(CryptoCoin::Bitcoin), None)),
+            Ok((CryptoCmd::Rate("xbt", None), None)),
             "can parse bitcoin"
         );
 
+        assert_eq!(
+            parse_command("λcrypto btc usd > charlie"),
+            Ok((CryptoCmd::Rate("btc", Some("usd")), Some("charlie"))),
+            "with a currency"
+        );
+
         assert_eq!(
             parse_command("λcrypto wut"),
-            Ok((Err("wut"), None)),
-            "inner error on unknown coin"
+            Ok((CryptoCmd::Rate("wut", None), None)),
+            "unknown coins are rejected later"
+        );
+    }
+
+    #[test]
+    async fn test_resolve() {
+        let coins = coin::default_coins();
+        let btc = find("btc");
+        assert_eq!(
+            CryptoCmd::Rate("xbt", None).resolve(&coins),
+            Ok(CryptoCmd::Rate(&btc, Fiat::Eur)),
+            "euros by default"
+        );
+        assert_eq!(
+            CryptoCmd::Rate("btc", Some("GBP")).resolve(&coins),
+            Ok(CryptoCmd::Rate(&btc, Fiat::Gbp))
+        );
+        assert_eq!(
+            CryptoCmd::Rate("wut", None).resolve(&coins),
+            Err(Unknown::Coin("wut"))
+        );
+        assert_eq!(
+            CryptoCmd::Rate("btc", Some("yen")).resolve(&coins),
+            Err(Unknown::Fiat("yen"))
+        );
+        assert_eq!(
+            CryptoCmd::Compare("doge", "wut").resolve(&coins),
+            Err(Unknown::Coin("wut")),
+            "error on the unknown coin"
+        );
+    }
+
+    #[test]
+    async fn test_coins_from_config() {
+        let dhall = r#"
+            { crypto =
+                { coins = Some
+                    [ { symbol = "BTC", name = "bitcoin", id = "bitcoin", aliases = ["xbt"] }
+                    , { symbol = "SOL", name = "solana", id = "solana", aliases = [] : List Text }
+                    ]
+                }
+            }
+        "#;
+        let coins = plugin_core::config::from_str::<CryptoConfig>(dhall)
+            .unwrap()
+            .coins
+            .unwrap();
+        assert_eq!(
+            CryptoCmd::Rate("sol", None).resolve(&coins),
+            Ok(CryptoCmd::Rate(&coins[1], Fiat::Eur)),
+            "new coins without recompiling"
+        );
+        assert_eq!(
+            CryptoCmd::Rate("eth", None).resolve(&coins),
+            Err(Unknown::Coin("eth"))
+        );
+    }
+
+    #[test]
+    async fn test_crypto_compare() {
+        assert_eq!(
+            parse_command("λcrypto compare btc eth"),
+            Ok((CryptoCmd::Compare("btc", "eth"), None)),
+            "can parse two coins"
+        );
+
+        assert_eq!(
+            parse_command("λcrypto compare doge wut > charlie"),
+            Ok((CryptoCmd::Compare("doge", "wut"), Some("charlie"))),
+            "unknown coin, with target"
+        );
+
+        assert!(
+            parse_command("λcrypto compare btc").is_err(),
+            "needs two coins to compare"
+        );
+    }
+
+    #[test]
+    async fn test_crypto_ath() {
+        assert_eq!(
+            parse_command("λcrypto btc ath > charlie"),
+            Ok((CryptoCmd::Ath("btc"), Some("charlie"))),
+        );
+
+        assert_eq!(
+            parse_command("λcrypto wut ath"),
+            Ok((CryptoCmd::Ath("wut"), None)),
+            "unknown coin"
+        );
+    }
+
+    #[test]
+    async fn test_crypto_chart() {
+        assert_eq!(
+            parse_command("λcrypto btc chart > charlie"),
+            Ok((CryptoCmd::Chart("btc"), Some("charlie"))),
+        );
+        assert_eq!(
+            parse_command("λcrypto btc eur"),
+            Ok((CryptoCmd::Rate("btc", Some("eur")), None)),
+            "still a currency"
+        );
+    }
+
+    #[test]
+    async fn test_chart() {
+        let conn = SqliteConnection::establish(":memory:").unwrap();
+        db::run_migrations(&conn).unwrap();
+
+        let row = |hour: u32, rate: f32, fiat: Fiat| CryptoCoinRate {
+            date: chrono::NaiveDate::from_ymd(2021, 11, 10).and_hms(hour, 0, 0),
+            coin: "BTC".to_string(),
+            rate,
+            currency: fiat.code().to_string(),
+        };
+        diesel::insert_into(crypto_rate::table)
+            .values(&vec![
+                row(1, 29000.0, Fiat::Eur),
+                row(2, 30000.0, Fiat::Eur),
+                row(3, 35000.0, Fiat::Eur),
+                row(4, 40000.0, Fiat::Usd),
+                row(5, 31000.0, Fiat::Eur),
+            ])
+            .execute(&conn)
+            .unwrap();
+
+        let rates = recent_rates(&conn, &find("btc"), 3).unwrap();
+        assert_eq!(rates, vec![30000.0, 35000.0, 31000.0], "oldest first");
+        assert_eq!(
+            format_chart(&find("btc"), &rates),
+            "bitcoin ▁█▂ (de 30000 à 35000 euros, sur les 3 derniers cours)"
+        );
+        assert_eq!(
+            format_chart(&find("eth"), &recent_rates(&conn, &find("eth"), 3).unwrap()),
+            "Pas assez de cours enregistrés pour ethereum"
+        );
+    }
+
+    #[test]
+    async fn test_crypto_movers() {
+        assert_eq!(
+            parse_command("λcrypto movers > charlie"),
+            Ok((CryptoCmd::Movers, Some("charlie"))),
+        );
+    }
+
+    #[test]
+    async fn test_daily_variations() {
+        let conn = SqliteConnection::establish(":memory:").unwrap();
+        db::run_migrations(&conn).unwrap();
+
+        let row = |hours: i64, coin: &str, rate: f32| CryptoCoinRate {
+            date: (Utc::now() - chrono::Duration::hours(hours)).naive_utc(),
+            coin: coin.to_string(),
+            rate,
+            currency: Fiat::Eur.code().to_string(),
+        };
+        diesel::insert_into(crypto_rate::table)
+            .values(&vec![
+                row(1, "BTC", 33000.0),
+                row(25, "BTC", 30000.0),
+                row(2, "ETH", 1500.0),
+                row(26, "ETH", 2000.0),
+                // only a recent rate
+                row(1, "DOGE", 0.1),
+                // only an old rate
+                row(30, "XRP", 0.5),
+            ])
+            .execute(&conn)
+            .unwrap();
+
+        let variations = daily_variations(&conn, &coin::default_coins()).unwrap();
+        assert_eq!(
+            variations
+                .iter()
+                .map(|(c, v)| (c.symbol.as_str(), v.0))
+                .collect::<Vec<_>>(),
+            vec![("BTC", 10.0), ("ETH", -25.0)],
+            "coins without enough history are skipped"
+        );
+    }
+
+    #[test]
+    async fn test_format_movers() {
+        let var = |symbol: &str, v: f32| (find(symbol), RateVariation(v));
+        assert_eq!(
+            format_movers(&[
+                var("btc", 2.0),
+                var("eth", -25.0),
+                var("doge", 10.0),
+                var("xrp", 0.0),
+            ]),
+            "Sur 24h, en hausse: dogecoin ↗10.00%, bitcoin ↗2.00% − en baisse: ethereum ↘25.00%"
+        );
+        assert_eq!(
+            format_movers(&[var("xrp", 0.0)]),
+            "Rien n'a bougé sur les dernières 24h"
+        );
+        assert_eq!(
+            format_movers(&[]),
+            "Pas assez de cours enregistrés sur les dernières 24h"
+        );
+    }
+
+    #[test]
+    async fn test_crypto_watch() {
+        assert_eq!(
+            parse_command("λcrypto watch btc every 1h"),
+            Ok((CryptoCmd::Watch("btc", Duration::from_secs(60 * 60)), None)),
+        );
+        assert_eq!(
+            parse_command("λcrypto unwatch eth"),
+            Ok((CryptoCmd::Unwatch("eth"), None)),
+        );
+        assert!(
+            parse_command("λcrypto watch btc").is_err(),
+            "needs a recurrence"
+        );
+
+        let (cmd, _) = parse_command("λcrypto watch btc every 100000000d").unwrap();
+        let interval = match cmd {
+            CryptoCmd::Watch(_, interval) => interval,
+            cmd => panic!("not a watch: {:?}", cmd),
+        };
+        assert_eq!(
+            add_watch("#chan", &find("btc"), interval).await.unwrap(),
+            "Pas moins d'un cours tous les 365j, sinon autant regarder soi-même."
+        );
+    }
+
+    #[test]
+    async fn test_crypto_alert() {
+        assert_eq!(
+            parse_command("λcrypto alert btc > 50000"),
+            Ok((CryptoCmd::Alert("btc", Direction::Above, 50000.0), None)),
+        );
+        assert_eq!(
+            parse_command("λcrypto alert eth < 1000.5 > charlie"),
+            Ok((
+                CryptoCmd::Alert("eth", Direction::Below, 1000.5),
+                Some("charlie")
+            )),
+        );
+        assert!(
+            parse_command("λcrypto alert btc").is_err(),
+            "needs a threshold"
+        );
+        assert_eq!(
+            parse_command("λcrypto alerts"),
+            Ok((CryptoCmd::Alerts, None))
+        );
+        assert_eq!(
+            parse_command("λcrypto alerts clear"),
+            Ok((CryptoCmd::ClearAlerts, None))
+        );
+    }
+
+    #[test]
+    async fn test_format_alerts() {
+        let coins = coin::default_coins();
+        let a = Alert::new("#chan", "charlie", "BTC", Direction::Above, 50000.0);
+        assert_eq!(
+            format_alerts("charlie", &coins, &[]),
+            "charlie: pas d'alerte pour toi ici."
+        );
+        assert_eq!(
+            format_alerts("charlie", &coins, &[a.clone()]),
+            "charlie: tes alertes, en euros: bitcoin > 50000"
+        );
+        assert_eq!(
+            format_triggered("bitcoin", &a, 50100.0),
+            "charlie: 1 bitcoin vaut 50100 euros, alerte bitcoin > 50000 déclenchée !"
+        );
+    }
+
+    #[test]
+    async fn test_all_time_high_query() {
+        let conn = SqliteConnection::establish(":memory:").unwrap();
+        db::run_migrations(&conn).unwrap();
+
+        assert!(
+            all_time_high(&conn, &find("btc")).unwrap().is_none(),
+            "no data yet"
+        );
+
+        let row = |day: u32, coin: &str, rate: f32, fiat: Fiat| CryptoCoinRate {
+            date: chrono::NaiveDate::from_ymd(2021, 11, day).and_hms(12, 0, 0),
+            coin: coin.to_string(),
+            rate,
+            currency: fiat.code().to_string(),
+        };
+        diesel::insert_into(crypto_rate::table)
+            .values(&vec![
+                row(9, "BTC", 60000.0, Fiat::Eur),
+                row(10, "BTC", 69000.0, Fiat::Eur),
+                row(10, "BTC", 80000.0, Fiat::Usd),
+                row(11, "BTC", 65000.0, Fiat::Eur),
+                row(12, "ETH", 100000.0, Fiat::Eur),
+            ])
+            .execute(&conn)
+            .unwrap();
+
+        let ath = all_time_high(&conn, &find("btc")).unwrap().unwrap();
+        assert_eq!(
+            (ath.date.to_string(), ath.rate),
+            ("2021-11-10 12:00:00".to_string(), 69000.0)
+        );
+    }
+
+    #[test]
+    async fn test_latest_rate() {
+        let conn = SqliteConnection::establish(":memory:").unwrap();
+        db::run_migrations(&conn).unwrap();
+
+        let now = Utc::now().naive_utc();
+        let row = |mins: i64, rate: f32, fiat: Fiat| CryptoCoinRate {
+            date: now - chrono::Duration::minutes(mins),
+            coin: "BTC".to_string(),
+            rate,
+            currency: fiat.code().to_string(),
+        };
+        diesel::insert_into(crypto_rate::table)
+            .values(&vec![
+                row(2, 30000.0, Fiat::Eur),
+                row(10, 29000.0, Fiat::Eur),
+                row(1, 35000.0, Fiat::Usd),
+            ])
+            .execute(&conn)
+            .unwrap();
+
+        let latest = |mins: i64, fiat| {
+            latest_rate(
+                &conn,
+                &find("btc"),
+                fiat,
+                now - chrono::Duration::minutes(mins),
+            )
+            .unwrap()
+            .map(|r| r.rate)
+        };
+        assert_eq!(latest(5, Fiat::Eur), Some(30000.0), "most recent one");
+        assert_eq!(latest(1, Fiat::Eur), None, "too old");
+        assert_eq!(latest(5, Fiat::Gbp), None, "other currency");
+    }
+
+    #[test]
+    async fn test_rate_days_ago_same_currency() {
+        let conn = SqliteConnection::establish(":memory:").unwrap();
+        db::run_migrations(&conn).unwrap();
+
+        let row = |days: i64, rate: f32, fiat: Fiat| CryptoCoinRate {
+            date: (Utc::now() - chrono::Duration::days(days)).naive_utc(),
+            coin: "BTC".to_string(),
+            rate,
+            currency: fiat.code().to_string(),
+        };
+        diesel::insert_into(crypto_rate::table)
+            .values(&vec![
+                row(2, 30000.0, Fiat::Eur),
+                row(3, 35000.0, Fiat::Usd),
+            ])
+            .execute(&conn)
+            .unwrap();
+
+        let rate = |fiat| {
+            rate_days_ago(&conn, &find("btc"), fiat, 1)
+                .unwrap()
+                .map(|r| r.rate)
+        };
+        assert_eq!(rate(Fiat::Eur), Some(30000.0));
+        assert_eq!(rate(Fiat::Usd), Some(35000.0));
+        assert_eq!(rate(Fiat::Gbp), None);
+    }
+
+    #[test]
+    async fn test_format_ath() {
+        let ath = CryptoCoinRate {
+            date: chrono::NaiveDate::from_ymd(2021, 11, 10).and_hms(12, 0, 0),
+            coin: "BTC".to_string(),
+            rate: 69000.0,
+            currency: "EUR".to_string(),
+        };
+        assert_eq!(below_ath(51750.0, 69000.0), 25.0);
+        assert_eq!(
+            format_ath(&find("btc"), 51750.0, &ath),
+            "1 bitcoin vaut 51750 euros, −25.00% sous l'ATH de 69000 le 2021-11-10"
+        );
+        assert_eq!(
+            format_ath(&find("btc"), 70000.0, &ath),
+            "1 bitcoin vaut 70000 euros, c'est l'ATH ! To the moon 🚀"
+        );
+    }
+
+    #[test]
+    async fn test_format_comparison() {
+        assert_eq!(
+            format_comparison(
+                (&find("btc"), 30.0, Some(25.0)),
+                (&find("eth"), 2.0, Some(4.0)),
+            ),
+            "1 bitcoin = 15.0000 ethereum; bitcoin ↗20.00% ethereum ↘50.00% (1D)"
         );
     }
 }
@@ -0,0 +1,248 @@
+use std::time::Duration;
+
+use anyhow::Context;
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use nom::bytes::complete::tag;
+use nom::character::complete::{multispace1, one_of, u64 as integer};
+use nom::combinator::map_opt;
+use nom::sequence::tuple;
+use nom::IResult;
+
+use crate::schema::crypto_watch::{self, dsl};
+
+/// Shortest interval allowed between two postings of the same rate
+pub(super) const MIN_INTERVAL: Duration = Duration::from_secs(15 * 60);
+/// Longest one, far enough for anyone and far from overflowing the dates
+pub(super) const MAX_INTERVAL: Duration = Duration::from_secs(365 * 24 * 60 * 60);
+
+/// A rate posted periodically to a channel
+#[derive(Debug, Clone, PartialEq, Queryable, Insertable)]
+#[table_name = "crypto_watch"]
+pub(super) struct Watch {
+    /// see `channel_key`
+    pub(super) channel: String,
+    /// symbol of the coin
+    pub(super) coin: String,
+    pub(super) interval_secs: i64,
+    pub(super) next_fire: NaiveDateTime,
+}
+
+impl Watch {
+    /// None when the first firing is too far to be represented
+    pub(super) fn new(
+        channel: &str,
+        coin: &str,
+        interval: Duration,
+        now: NaiveDateTime,
+    ) -> Option<Self> {
+        let next_fire = chrono::Duration::from_std(interval)
+            .ok()
+            .and_then(|d| now.checked_add_signed(d))?;
+        Some(Watch {
+            channel: channel.to_string(),
+            coin: coin.to_string(),
+            interval_secs: i64::try_from(interval.as_secs()).ok()?,
+            next_fire,
+        })
+    }
+}
+
+/// Register a watch, replacing any existing one for the same coin and channel
+pub(super) fn register(conn: &SqliteConnection, watch: &Watch) -> anyhow::Result<()> {
+    diesel::replace_into(crypto_watch::table)
+        .values(watch)
+        .execute(conn)
+        .with_context(|| format!("Cannot save watch {:?}", watch))?;
+    Ok(())
+}
+
+/// Returns false if there was nothing to remove
+pub(super) fn unregister(
+    conn: &SqliteConnection,
+    channel: &str,
+    coin: &str,
+) -> anyhow::Result<bool> {
+    let deleted = diesel::delete(
+        dsl::crypto_watch
+            .filter(dsl::channel.eq(channel))
+            .filter(dsl::coin.eq(coin)),
+    )
+    .execute(conn)
+    .with_context(|| format!("Cannot delete watch for {} in {}", coin, channel))?;
+    Ok(deleted > 0)
+}
+
+/// Watches which should fire at `now`
+pub(super) fn due(conn: &SqliteConnection, now: NaiveDateTime) -> anyhow::Result<Vec<Watch>> {
+    dsl::crypto_watch
+        .filter(dsl::next_fire.le(now))
+        .load::<Watch>(conn)
+        .context("Cannot load due watches")
+}
+
+/// Move the watch to its next firing time once it fired at `now`
+pub(super) fn reschedule(
+    conn: &SqliteConnection,
+    watch: &Watch,
+    now: NaiveDateTime,
+) -> anyhow::Result<()> {
+    let next = next_fire(
+        watch.next_fire,
+        chrono::Duration::seconds(watch.interval_secs),
+        now,
+    );
+    diesel::update(
+        dsl::crypto_watch
+            .filter(dsl::channel.eq(watch.channel.as_str()))
+            .filter(dsl::coin.eq(watch.coin.as_str())),
+    )
+    .set(dsl::next_fire.eq(next))
+    .execute(conn)
+    .with_context(|| format!("Cannot reschedule watch {:?}", watch))?;
+    Ok(())
+}
+
+/// First firing time after `now`, on the schedule of `previous`.
+/// Firings missed while the bot was down are skipped instead of
+/// being posted in a burst.
+pub(super) fn next_fire(
+    previous: NaiveDateTime,
+    interval: chrono::Duration,
+    now: NaiveDateTime,
+) -> NaiveDateTime {
+    if previous > now {
+        return previous;
+    }
+    let missed = (now - previous).num_seconds() / interval.num_seconds() + 1;
+    previous + chrono::Duration::seconds(missed * interval.num_seconds())
+}
+
+/// `every 30m`, `every 2h` or `every 1d`
+pub(super) fn recurrence(input: &str) -> IResult<&str, Duration> {
+    map_opt(
+        tuple((tag("every"), multispace1, integer, one_of("mhd"))),
+        |(_, _, n, unit)| {
+            let secs = match unit {
+                'm' => 60,
+                'h' => 60 * 60,
+                _ => 24 * 60 * 60,
+            };
+            n.checked_mul(secs).map(Duration::from_secs)
+        },
+    )(input)
+}
+
+/// Human readable interval, in the largest unit that divides it
+pub(super) fn format_interval(interval: Duration) -> String {
+    let secs = interval.as_secs();
+    if secs % (24 * 60 * 60) == 0 {
+        format!("{}j", secs / (24 * 60 * 60))
+    } else if secs % (60 * 60) == 0 {
+        format!("{}h", secs / (60 * 60))
+    } else {
+        format!("{}min", secs / 60)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::db;
+    use pretty_assertions::assert_eq;
+
+    fn at(hour: u32, min: u32) -> NaiveDateTime {
+        chrono::NaiveDate::from_ymd(2021, 11, 10).and_hms(hour, min, 0)
+    }
+
+    #[test]
+    async fn test_recurrence() {
+        assert_eq!(
+            recurrence("every 30m"),
+            Ok(("", Duration::from_secs(30 * 60)))
+        );
+        assert_eq!(
+            recurrence("every 2h"),
+            Ok(("", Duration::from_secs(2 * 60 * 60)))
+        );
+        assert_eq!(
+            recurrence("every 1d"),
+            Ok(("", Duration::from_secs(24 * 60 * 60)))
+        );
+        assert!(recurrence("every 2y").is_err(), "unknown unit");
+        assert!(recurrence("every h").is_err(), "needs a count");
+        assert_eq!(format_interval(Duration::from_secs(2 * 60 * 60)), "2h");
+        assert_eq!(format_interval(Duration::from_secs(90 * 60)), "90min");
+    }
+
+    #[test]
+    async fn test_next_fire() {
+        let hour = chrono::Duration::hours(1);
+        assert_eq!(
+            next_fire(at(12, 0), hour, at(11, 0)),
+            at(12, 0),
+            "not due yet"
+        );
+        assert_eq!(
+            next_fire(at(12, 0), hour, at(12, 0)),
+            at(13, 0),
+            "fires on time"
+        );
+        assert_eq!(
+            next_fire(at(12, 0), hour, at(12, 1)),
+            at(13, 0),
+            "keeps the schedule when firing late"
+        );
+        assert_eq!(
+            next_fire(at(12, 0), hour, at(15, 30)),
+            at(16, 0),
+            "skips the missed firings"
+        );
+    }
+
+    #[test]
+    async fn test_registration_store() {
+        let conn = SqliteConnection::establish(":memory:").unwrap();
+        db::run_migrations(&conn).unwrap();
+        let hour = Duration::from_secs(60 * 60);
+
+        let btc = Watch::new("#chan", "BTC", hour, at(12, 0)).unwrap();
+        let eth = Watch::new("#chan", "ETH", 2 * hour, at(12, 0)).unwrap();
+        register(&conn, &btc).unwrap();
+        register(&conn, &eth).unwrap();
+        assert_eq!(btc.next_fire, at(13, 0));
+        assert_eq!(
+            Watch::new("#chan", "BTC", Duration::from_secs(u64::MAX), at(12, 0)),
+            None,
+            "overflow"
+        );
+
+        assert_eq!(due(&conn, at(12, 30)).unwrap(), vec![]);
+        assert_eq!(due(&conn, at(13, 0)).unwrap(), vec![btc.clone()]);
+
+        reschedule(&conn, &btc, at(13, 0)).unwrap();
+        assert_eq!(
+            due(&conn, at(14, 0)).unwrap(),
+            vec![
+                Watch {
+                    next_fire: at(14, 0),
+                    ..btc.clone()
+                },
+                eth.clone()
+            ]
+        );
+
+        let replaced = Watch::new("#chan", "BTC", 3 * hour, at(12, 0)).unwrap();
+        register(&conn, &replaced).unwrap();
+        assert_eq!(
+            due(&conn, at(14, 0)).unwrap(),
+            vec![eth.clone()],
+            "registering again replaces the watch"
+        );
+
+        assert!(unregister(&conn, "#chan", "ETH").unwrap());
+        assert!(!unregister(&conn, "#chan", "ETH").unwrap());
+        assert!(!unregister(&conn, "#other", "BTC").unwrap());
+        assert_eq!(due(&conn, at(23, 0)).unwrap(), vec![replaced]);
+    }
+}
@@ -27,12 +27,30 @@ impl Plugin for Ctcp {
     async fn in_message(&self, msg: &Message) -> Result<Option<Message>> {
         in_msg(msg).await
     }
+
+    /// `/me` messages are seen by the other plugins as normal messages,
+    /// without the CTCP wrapper, so that karma or seen work with them.
+    fn rewrite_message(&self, msg: &Message) -> Option<Message> {
+        let (target, text) = match &msg.command {
+            Command::PRIVMSG(target, text) => (target, text),
+            _ => return None,
+        };
+        match parse_command(text)? {
+            CtcpCmd::ACTION(action) => Some(Message {
+                tags: msg.tags.clone(),
+                prefix: msg.prefix.clone(),
+                command: Command::PRIVMSG(target.clone(), action.to_string()),
+            }),
+            _ => None,
+        }
+    }
 }
 
 async fn in_msg(msg: &Message) -> Result<Option<Message>> {
-    let response_target = match msg.response_target() {
+    // replies go to whoever asked, even for a query sent to a channel
+    let response_target = match msg.source_nickname() {
         None => return Ok(None),
-        Some(target) => target.to_string(),
+        Some(nick) => nick.to_string(),
     };
 
     if let Command::PRIVMSG(_source, message) = &msg.command {
@@ -42,7 +60,7 @@ async fn in_msg(msg: &Message) -> Result<Option<Message>> {
             None => return Ok(None),
         };
         let msg = match command {
-            CtcpCmd::VERSION => "rustygolem".to_string(),
+            CtcpCmd::VERSION => "VERSION rustygolem".to_string(),
             CtcpCmd::TIME => {
                 let now = time::OffsetDateTime::now_utc();
                 let fmt = time::macros::format_description!("[hour]:[minute]:[second]");
@@ -56,9 +74,11 @@ async fn in_msg(msg: &Message) -> Result<Option<Message>> {
                     .unwrap_or_else(|| "".to_string());
                 format!("PING{}", arg)
             }
+            // nothing to reply, they are rewritten as normal messages
+            CtcpCmd::ACTION(_) => return Ok(None),
         };
 
-        let irc_msg = Command::PRIVMSG(response_target, msg).into();
+        let irc_msg = Command::NOTICE(response_target, format!("\u{001}{}\u{001}", msg)).into();
         return Ok(Some(irc_msg));
     }
 
@@ -70,6 +90,8 @@ enum CtcpCmd<'input> {
     VERSION,
     TIME,
     PING(Option<&'input str>),
+    /// `/me <text>`
+    ACTION(&'input str),
 }
 
 fn parse_command(input: &str) -> Option<CtcpCmd<'_>> {
@@ -101,30 +123,71 @@ fn ctcp_cmd(input: &str) -> IResult<&str, CtcpCmd> {
             ),
             |(_, arg)| CtcpCmd::PING(arg),
         ),
+        map(
+            pair(
+                tag("ACTION"),
+                opt(preceded(multispace1, recognize(is_not("\x01")))),
+            ),
+            |(_, text)| CtcpCmd::ACTION(text.unwrap_or_default()),
+        ),
     ))(input)
 }
 
-// // ctcp feature is disabled so we can override the TIME to reply with
-// // the republican calendar (crucial feature right there).
-// fn handle_ctcp(client: &Arc<Mutex<Client>>, target: String, ctcp: CTCP) -> Result<()> {
-//     let msg = match ctcp {
-//         CTCP::VERSION => "VERSION rustygolem".to_string(),
-//         CTCP::TIME => {
-//             let now = Utc::now();
-//             let fmt = StrftimeItems::new("%H:%M:%S");
-//             let rd = RepublicanDate::try_from(now.naive_utc().date())?;
-//             format!("TIME {} UTC - {}", now.format_with_items(fmt), rd)
-//         }
-//         CTCP::PING(opt_arg) => {
-//             let arg = opt_arg
-//                 .map(|c| format!(" {}", c))
-//                 .unwrap_or_else(|| "".to_string());
-//             format!("PING{}", arg)
-//         }
-//     };
-//     {
-//         let client = client.lock().unwrap();
-//         client.send(Command::NOTICE(target, format!("\u{001}{}\u{001}", msg)))?;
-//     }
-//     Ok(())
-// }
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    async fn test_parse_command() {
+        assert_eq!(parse_command("\x01VERSION\x01"), Some(CtcpCmd::VERSION));
+        assert_eq!(
+            parse_command("\x01PING 1234\x01"),
+            Some(CtcpCmd::PING(Some("1234")))
+        );
+        assert_eq!(
+            parse_command("\x01ACTION waves at charlie\x01"),
+            Some(CtcpCmd::ACTION("waves at charlie"))
+        );
+        assert_eq!(parse_command("\x01ACTION\x01"), Some(CtcpCmd::ACTION("")));
+        assert_eq!(parse_command("ACTION waves"), None);
+    }
+
+    #[test]
+    async fn test_rewrite_action() {
+        let plugin = Ctcp {};
+        let action =
+            plugin_core::test_util::privmsg("charlie", "#chan", "\x01ACTION likes rust++\x01");
+        assert_eq!(
+            plugin.rewrite_message(&action).map(|m| m.command),
+            Some(Command::PRIVMSG(
+                "#chan".to_string(),
+                "likes rust++".to_string()
+            ))
+        );
+        let version = plugin_core::test_util::privmsg("charlie", "#chan", "\x01VERSION\x01");
+        assert_eq!(plugin.rewrite_message(&version), None);
+    }
+
+    #[test]
+    async fn test_reply_with_notice() {
+        let query = plugin_core::test_util::privmsg("charlie", "#chan", "\x01PING 1234\x01");
+        assert_eq!(
+            in_msg(&query).await.unwrap().map(|m| m.command),
+            Some(Command::NOTICE(
+                "charlie".to_string(),
+                "\x01PING 1234\x01".to_string()
+            )),
+            "to the nick who asked, not the channel"
+        );
+
+        let version = plugin_core::test_util::privmsg("charlie", "golem", "\x01VERSION\x01");
+        assert_eq!(
+            in_msg(&version).await.unwrap().map(|m| m.command),
+            Some(Command::NOTICE(
+                "charlie".to_string(),
+                "\x01VERSION rustygolem\x01".to_string()
+            ))
+        );
+    }
+}
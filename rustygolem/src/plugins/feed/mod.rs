@@ -0,0 +1,5 @@
+mod parse;
+mod plugin;
+mod store;
+
+pub use plugin::Feed;
@@ -0,0 +1,141 @@
+//! The entries of RSS and Atom feeds.
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+
+/// An item of a RSS feed, or an entry of an Atom feed
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct Entry {
+    /// guid or id, generated from the link and the title when the feed has none
+    pub(super) id: String,
+    pub(super) title: String,
+    pub(super) link: String,
+    /// published or updated, when the feed has it
+    pub(super) date: Option<DateTime<Utc>>,
+}
+
+/// The entries, newest first. Feeds don't always list them in that order,
+/// so they are sorted by date, and kept in document order when the feed
+/// has no dates. Entries without a link are skipped.
+pub(super) fn entries(xml: &[u8]) -> anyhow::Result<Vec<Entry>> {
+    let feed = feed_rs::parser::parse(xml).context("invalid feed")?;
+    let mut entries = feed
+        .entries
+        .into_iter()
+        .filter_map(|entry| {
+            let link = entry
+                .links
+                .into_iter()
+                .find(|link| matches!(link.rel.as_deref(), None | Some("alternate")))?;
+            let title = entry
+                .title
+                .map(|title| {
+                    title
+                        .content
+                        .split_whitespace()
+                        .collect::<Vec<_>>()
+                        .join(" ")
+                })
+                .unwrap_or_default();
+            Some(Entry {
+                id: entry.id,
+                title,
+                link: link.href,
+                date: entry.published.or(entry.updated),
+            })
+        })
+        .collect::<Vec<_>>();
+    // the sort is stable, and entries without a date go last
+    entries.sort_by(|a, b| b.date.cmp(&a.date));
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn titles_and_links(entries: &[Entry]) -> Vec<(&str, &str)> {
+        entries
+            .iter()
+            .map(|e| (e.title.as_str(), e.link.as_str()))
+            .collect()
+    }
+
+    #[test]
+    async fn test_rss_entries() {
+        let rss = r#"<?xml version="1.0"?>
+            <rss version="2.0"><channel>
+              <title>Le blog</title>
+              <link>https://blog.example/</link>
+              <item>
+                <title>Rust &amp; IRC</title>
+                <link>https://blog.example/2</link>
+                <guid isPermaLink="false">post-2</guid>
+              </item>
+              <item>
+                <title><![CDATA[Premier <b>post</b>]]></title>
+                <link>https://blog.example/1</link>
+              </item>
+              <item><title>Sans lien</title></item>
+            </channel></rss>"#;
+        let entries = entries(rss.as_bytes()).unwrap();
+        assert_eq!(
+            titles_and_links(&entries),
+            vec![
+                ("Rust & IRC", "https://blog.example/2"),
+                ("Premier <b>post</b>", "https://blog.example/1"),
+            ]
+        );
+        assert_eq!(entries[0].id, "post-2");
+        assert!(!entries[1].id.is_empty(), "generated id");
+    }
+
+    #[test]
+    async fn test_atom_entries() {
+        let atom = r#"<feed xmlns="http://www.w3.org/2005/Atom">
+              <title>Releases</title>
+              <link href="https://example.org/releases"/>
+              <entry>
+                <id>tag:example.org,2024:v1.2</id>
+                <title type="text">v1.2
+                  released</title>
+                <link rel="enclosure" href="https://example.org/v1.2.tar.gz"/>
+                <link rel="alternate" href="https://example.org/v1.2?a=1&amp;b=2"/>
+              </entry>
+            </feed>"#;
+        let entries = entries(atom.as_bytes()).unwrap();
+        assert_eq!(
+            titles_and_links(&entries),
+            vec![("v1.2 released", "https://example.org/v1.2?a=1&b=2")]
+        );
+        assert_eq!(entries[0].id, "tag:example.org,2024:v1.2");
+    }
+
+    #[test]
+    async fn test_newest_first() {
+        let rss = r#"<rss version="2.0"><channel>
+              <item>
+                <title>old</title>
+                <link>https://blog.example/1</link>
+                <pubDate>Mon, 01 Jan 2024 10:00:00 GMT</pubDate>
+              </item>
+              <item>
+                <title>new</title>
+                <link>https://blog.example/2</link>
+                <pubDate>Tue, 02 Jan 2024 10:00:00 GMT</pubDate>
+              </item>
+              <item>
+                <title>undated</title>
+                <link>https://blog.example/3</link>
+              </item>
+            </channel></rss>"#;
+        let entries = entries(rss.as_bytes()).unwrap();
+        let titles = entries.iter().map(|e| e.title.as_str()).collect::<Vec<_>>();
+        assert_eq!(titles, vec!["new", "old", "undated"]);
+    }
+
+    #[test]
+    async fn test_invalid_feed() {
+        assert!(entries(b"<html>not a feed</html>").is_err());
+    }
+}
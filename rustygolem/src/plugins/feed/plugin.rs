@@ -0,0 +1,171 @@
+use std::time::Duration;
+
+use anyhow::Context;
+use async_trait::async_trait;
+use irc::proto::{Command, Message};
+use plugin_core::config::ConfigSection;
+use plugin_core::{Error, Initialised, Plugin, Result};
+use serde::Deserialize;
+use tokio::sync::mpsc;
+
+use super::parse::{self, Entry};
+use super::store;
+use crate::db;
+
+const DEFAULT_FEED_INTERVAL: Duration = Duration::from_secs(15 * 60);
+/// Feeds can't be polled more often than that
+const MIN_FEED_INTERVAL: Duration = Duration::from_secs(5 * 60);
+const FETCH_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Deserialize)]
+struct FeedSpec {
+    /// url of the RSS or Atom feed
+    url: String,
+    /// where the new entries are posted
+    channels: Vec<String>,
+    interval_secs: Option<u64>,
+}
+
+#[derive(Default, Deserialize)]
+struct FeedConfig {
+    feeds: Vec<FeedSpec>,
+}
+
+impl ConfigSection for FeedConfig {
+    const SECTION: Option<&'static str> = Some("feeds");
+    const SCHEMA: &'static str = "{ feeds : List { url : Text, channels : List Text, \
+        interval_secs : Optional Natural } }";
+}
+
+pub struct Feed {
+    feeds: Vec<FeedSpec>,
+    client: reqwest::Client,
+}
+
+#[async_trait]
+impl Plugin for Feed {
+    async fn init(config: &plugin_core::Config) -> Result<Initialised> {
+        let feed_config: FeedConfig = plugin_core::config::load_or_default(&config.config_path)?;
+
+        Ok(Initialised::from(Feed {
+            feeds: feed_config.feeds,
+            client: config.http_client.clone(),
+        }))
+    }
+
+    fn get_name(&self) -> &'static str {
+        "feed"
+    }
+
+    async fn run(&self, bot_chan: mpsc::Sender<Message>) -> Result<()> {
+        if self.feeds.is_empty() {
+            return Ok(());
+        }
+        futures::future::try_join_all(
+            self.feeds
+                .iter()
+                .map(|feed| watch_feed(&self.client, feed, bot_chan.clone())),
+        )
+        .await?;
+        Err(Error::Synthetic("feed watcher stopped".to_string()))
+    }
+}
+
+/// Only stops when the entries cannot be sent anymore, fetch errors are logged
+async fn watch_feed(
+    client: &reqwest::Client,
+    feed: &FeedSpec,
+    bot_chan: mpsc::Sender<Message>,
+) -> anyhow::Result<()> {
+    let interval = feed
+        .interval_secs
+        .map(Duration::from_secs)
+        .unwrap_or(DEFAULT_FEED_INTERVAL)
+        .max(MIN_FEED_INTERVAL);
+    loop {
+        match poll_feed(client, &feed.url).await {
+            Ok(new) => {
+                for entry in new {
+                    let msg = format_entry(&entry, plugin_url::DEFAULT_MAX_TITLE_LENGTH);
+                    for channel in &feed.channels {
+                        bot_chan
+                            .send(Command::PRIVMSG(channel.clone(), msg.clone()).into())
+                            .await
+                            .with_context(|| format!("can't send message to {}", channel))?;
+                    }
+                }
+            }
+            Err(err) => log::warn!("Cannot check feed {}: {:#}", feed.url, err),
+        }
+        tokio::time::sleep(interval).await;
+    }
+}
+
+/// Fetch the feed and return the entries not posted yet, oldest first
+async fn poll_feed(client: &reqwest::Client, url: &str) -> anyhow::Result<Vec<Entry>> {
+    let xml = client
+        .get(url)
+        .timeout(FETCH_TIMEOUT)
+        .send()
+        .await
+        .context("cannot fetch feed")?
+        .error_for_status()
+        .context("feed error")?
+        .bytes()
+        .await
+        .context("cannot read feed")?;
+    // newest first, by date
+    let entries = parse::entries(&xml)?;
+
+    let url = url.to_string();
+    db::with_connection(move |conn| {
+        let last = store::last_entry_id(conn, &url)?;
+        let new = store::new_entries(&entries, last.as_deref())
+            .into_iter()
+            .cloned()
+            .collect::<Vec<_>>();
+        if let Some(newest) = entries.first() {
+            store::set_last_entry_id(conn, &url, &newest.id)?;
+        }
+        Ok(new)
+    })
+    .await
+}
+
+/// Like the titles of the url plugin, `title [link]`
+fn format_entry(entry: &Entry, max_title_length: usize) -> String {
+    let title = if entry.title.is_empty() {
+        "Sans titre"
+    } else {
+        &entry.title
+    };
+    format!(
+        "{} [{}]",
+        plugin_url::shorten(title, max_title_length),
+        entry.link
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    async fn test_format_entry() {
+        let entry = Entry {
+            id: "1".to_string(),
+            title: "Ça sent le sapin".to_string(),
+            link: "https://example.org/1".to_string(),
+            date: None,
+        };
+        assert_eq!(
+            format_entry(&entry, 100),
+            "Ça sent le sapin [https://example.org/1]"
+        );
+        assert_eq!(
+            format_entry(&entry, 7),
+            "Ça sent[…] [https://example.org/1]"
+        );
+    }
+}
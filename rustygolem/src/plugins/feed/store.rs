@@ -0,0 +1,108 @@
+use anyhow::Context;
+use diesel::prelude::*;
+
+use super::parse::Entry;
+use crate::schema::feed_state::{self, dsl};
+
+/// Don't flood the channels when the last seen entry isn't in the feed anymore
+pub(super) const MAX_NEW_ENTRIES: usize = 3;
+
+#[derive(Debug, PartialEq, Queryable, Insertable)]
+#[table_name = "feed_state"]
+struct FeedState {
+    /// url of the feed
+    feed: String,
+    last_entry_id: String,
+}
+
+/// Id of the newest entry posted for that feed
+pub(super) fn last_entry_id(conn: &SqliteConnection, feed: &str) -> anyhow::Result<Option<String>> {
+    dsl::feed_state
+        .filter(dsl::feed.eq(feed))
+        .select(dsl::last_entry_id)
+        .first::<String>(conn)
+        .optional()
+        .with_context(|| format!("Cannot load the last entry of {}", feed))
+}
+
+pub(super) fn set_last_entry_id(
+    conn: &SqliteConnection,
+    feed: &str,
+    last_entry_id: &str,
+) -> anyhow::Result<()> {
+    let state = FeedState {
+        feed: feed.to_string(),
+        last_entry_id: last_entry_id.to_string(),
+    };
+    diesel::replace_into(feed_state::table)
+        .values(&state)
+        .execute(conn)
+        .with_context(|| format!("Cannot save feed state {:?}", state))?;
+    Ok(())
+}
+
+/// The entries newer than the last one seen, oldest first.
+/// Nothing is new the first time a feed is seen, so that adding a feed
+/// doesn't post its whole history.
+pub(super) fn new_entries<'a>(entries: &'a [Entry], last_entry_id: Option<&str>) -> Vec<&'a Entry> {
+    let last_entry_id = match last_entry_id {
+        Some(id) => id,
+        None => return vec![],
+    };
+    let mut new = Vec::<&Entry>::new();
+    for entry in entries.iter().take_while(|e| e.id != last_entry_id) {
+        // feeds sometimes list the same entry twice
+        if !new.iter().any(|e| e.id == entry.id || e.link == entry.link) {
+            new.push(entry);
+        }
+    }
+    new.truncate(MAX_NEW_ENTRIES);
+    new.reverse();
+    new
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::db;
+    use pretty_assertions::assert_eq;
+
+    fn entry(id: &str) -> Entry {
+        Entry {
+            id: id.to_string(),
+            title: format!("title {}", id),
+            link: format!("https://example.org/{}", id),
+            date: None,
+        }
+    }
+
+    #[test]
+    async fn test_new_entries() {
+        let entries = ["5", "4", "3", "2", "1"].map(entry);
+        let ids = |new: Vec<&Entry>| new.into_iter().map(|e| e.id.clone()).collect::<Vec<_>>();
+
+        assert_eq!(ids(new_entries(&entries, None)), Vec::<String>::new());
+        assert_eq!(ids(new_entries(&entries, Some("5"))), Vec::<String>::new());
+        assert_eq!(ids(new_entries(&entries, Some("3"))), vec!["4", "5"]);
+        assert_eq!(
+            ids(new_entries(&entries, Some("gone"))),
+            vec!["3", "4", "5"],
+            "at most MAX_NEW_ENTRIES"
+        );
+
+        let duplicated = [entry("2"), entry("2"), entry("1")];
+        assert_eq!(ids(new_entries(&duplicated, Some("1"))), vec!["2"]);
+    }
+
+    #[test]
+    async fn test_feed_state_store() {
+        let conn = SqliteConnection::establish(":memory:").unwrap();
+        db::run_migrations(&conn).unwrap();
+
+        let feed = "https://example.org/feed.xml";
+        assert_eq!(last_entry_id(&conn, feed).unwrap(), None);
+        set_last_entry_id(&conn, feed, "1").unwrap();
+        set_last_entry_id(&conn, feed, "2").unwrap();
+        assert_eq!(last_entry_id(&conn, feed).unwrap(), Some("2".to_string()));
+    }
+}
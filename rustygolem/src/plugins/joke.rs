@@ -1,76 +1,296 @@
-use crate::utils::parser;
+use crate::utils::parser::{self, command_prefix};
+use anyhow::Context;
 use async_trait::async_trait;
 use irc::proto::{Command, Message};
-use plugin_core::{Initialised, Plugin, Result};
+use nom::bytes::complete::tag;
+use nom::character::complete::{multispace0, multispace1};
+use nom::combinator::{all_consuming, map, opt};
+use nom::sequence::{pair, preceded, terminated, tuple};
+use nom::Finish;
+use plugin_core::{CommandHelp, Error, Initialised, Plugin, Result};
+use serde::Deserialize;
+use tokio::sync::{mpsc, Mutex};
 
-pub struct Joke {}
+/// Setup and punchline, more than that is squashed in the last line
+const MAX_JOKE_LINES: usize = 2;
+
+pub struct Joke {
+    client: reqwest::Client,
+    // a joke can span several messages, which in_message cannot return,
+    // so they are all sent through run() to keep them in order
+    lines_tx: mpsc::Sender<Message>,
+    lines_rx: Mutex<mpsc::Receiver<Message>>,
+}
 
 #[async_trait]
 impl Plugin for Joke {
-    async fn init(_config: &plugin_core::Config) -> Result<Initialised> {
-        Ok(Initialised::from(Joke {}))
+    async fn init(config: &plugin_core::Config) -> Result<Initialised> {
+        let (lines_tx, lines_rx) = mpsc::channel(10);
+        Ok(Initialised::from(Joke {
+            client: config.http_client.clone(),
+            lines_tx,
+            lines_rx: Mutex::new(lines_rx),
+        }))
     }
 
     fn get_name(&self) -> &'static str {
         "joke"
     }
 
-    async fn in_message(&self, msg: &Message) -> Result<Option<Message>> {
-        in_msg(msg).await
+    fn commands(&self) -> Vec<CommandHelp> {
+        vec![CommandHelp::new(
+            "joke",
+            "[dad|chuck|geek] [slug]",
+            "une blague, pour détendre l'atmosphère",
+        )]
     }
-}
 
-async fn in_msg(msg: &Message) -> Result<Option<Message>> {
-    let response_target = match msg.response_target() {
-        None => return Ok(None),
-        Some(target) => target,
-    };
+    async fn in_message(&self, msg: &Message) -> Result<Option<Message>> {
+        self.in_msg(msg).await
+    }
 
-    if let Command::PRIVMSG(_source, privmsg) = &msg.command {
-        if let Some(mb_target) = parser::single_command("joke", privmsg) {
-            let msg = handle_command(mb_target)
+    async fn run(&self, bot_chan: mpsc::Sender<Message>) -> Result<()> {
+        // hold that lock forever
+        let mut lines_rx = self.lines_rx.lock().await;
+        while let Some(msg) = lines_rx.recv().await {
+            bot_chan
+                .send(msg)
                 .await
-                .unwrap_or_else(|| "Error handling joke".to_string());
+                .map_err(|err| Error::Synthetic(format!("cannot send joke: {err}")))?;
+        }
+        Ok(())
+    }
+}
 
-            return Ok(Some(
-                Command::PRIVMSG(response_target.to_string(), msg).into(),
-            ));
+impl Joke {
+    async fn in_msg(&self, msg: &Message) -> Result<Option<Message>> {
+        let response_target = match msg.response_target() {
+            None => return Ok(None),
+            Some(target) => target,
+        };
+
+        if let Command::PRIVMSG(_source, privmsg) = &msg.command {
+            if let Some((cmd, mb_target)) = parse_command(privmsg) {
+                for line in handle_command(&self.client, cmd, mb_target).await {
+                    let msg = Command::PRIVMSG(response_target.to_string(), line).into();
+                    self.lines_tx
+                        .send(msg)
+                        .await
+                        .map_err(|err| Error::Synthetic(format!("cannot queue joke: {err}")))?;
+                }
+            }
         }
+        Ok(None)
     }
-    Ok(None)
 }
 
-async fn handle_command(mb_target: Option<&str>) -> Option<String> {
-    let client = reqwest::ClientBuilder::new()
-        .user_agent("rustygolem: https://github.com/CoucouInc/rustygolem")
-        .build()
-        .unwrap();
-
-    let req = client
-        .get("https://icanhazdadjoke.com")
-        .header("Accept", "text/plain");
-    let resp = match req.send().await {
-        Ok(r) => r,
-        Err(err) => {
-            return Some(format!(
-                "Error while querying icanhazdadjoke API: {:?}",
-                err
-            ))
+#[derive(Debug, PartialEq)]
+struct JokeCmd<'input> {
+    /// where to get the joke from, dad jokes by default
+    source: Option<&'input str>,
+    /// a specific joke, only for dad jokes
+    slug: Option<&'input str>,
+}
+
+/// `λjoke [source [slug]] [> target]`
+fn parse_command(input: &str) -> Option<(JokeCmd, Option<&str>)> {
+    let cmd = preceded(
+        tuple((command_prefix, tag("joke"))),
+        parser::with_target(map(
+            opt(pair(
+                preceded(multispace1, parser::word),
+                opt(preceded(multispace1, parser::word)),
+            )),
+            |words| JokeCmd {
+                source: words.map(|(source, _)| source),
+                slug: words.and_then(|(_, slug)| slug),
+            },
+        )),
+    );
+
+    all_consuming(terminated(cmd, multispace0))(input)
+        .finish()
+        .map(|x| x.1)
+        .ok()
+}
+
+const SOURCES: &str = "dad, chuck, geek";
+
+#[derive(Debug, Deserialize, PartialEq)]
+struct JokeResponse {
+    joke: String,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+struct ChuckResponse {
+    value: String,
+}
+
+async fn handle_command(
+    client: &reqwest::Client,
+    cmd: JokeCmd<'_>,
+    mb_target: Option<&str>,
+) -> Vec<String> {
+    let source = cmd.source.unwrap_or("dad");
+    let joke = match (source, cmd.slug) {
+        ("dad", slug) => fetch_dad_joke(client, slug).await,
+        ("chuck", None) => fetch_chuck_joke(client).await,
+        ("geek", None) => fetch_geek_joke(client).await,
+        ("chuck" | "geek", Some(_)) => {
+            return vec!["Seules les blagues dad ont un identifiant".to_string()]
+        }
+        (other, _) => {
+            return vec![format!(
+                "Connais pas les blagues {other}, essaye parmi: {SOURCES}"
+            )]
         }
     };
 
-    let joke = match resp.text().await {
-        Ok(t) => t,
-        Err(err) => {
-            return Some(format!(
-                "Error while getting the response from icanhazdadjoke: {:?}",
-                err
-            ))
-        }
+    let joke = match joke {
+        Ok(joke) => joke,
+        Err(err) => return vec![format!("Error while getting a {source} joke: {err:#}")],
     };
 
-    // https://github.com/CoucouInc/rustygolem/issues/9
-    let joke = joke.lines().collect::<Vec<_>>().join(" − ");
+    let mut lines = joke_lines(&joke);
+    if let Some(first) = lines.first_mut() {
+        *first = crate::utils::messages::with_target(first, &mb_target);
+    }
+    lines
+}
 
-    Some(crate::utils::messages::with_target(&joke, &mb_target))
+async fn fetch_dad_joke(client: &reqwest::Client, mb_slug: Option<&str>) -> anyhow::Result<String> {
+    let url = match mb_slug {
+        Some(slug) => format!("https://icanhazdadjoke.com/j/{slug}"),
+        None => "https://icanhazdadjoke.com".to_string(),
+    };
+    let resp = client
+        .get(url)
+        .header("Accept", "application/json")
+        .send()
+        .await
+        .context("cannot query icanhazdadjoke API")?;
+
+    if let Some(slug) = mb_slug {
+        if resp.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(format!(
+                "Pas de blague {slug}, elle était sûrement trop drôle"
+            ));
+        }
+    }
+
+    let joke = resp
+        .json::<JokeResponse>()
+        .await
+        .context("unexpected response from icanhazdadjoke")?;
+    Ok(joke.joke)
+}
+
+async fn fetch_chuck_joke(client: &reqwest::Client) -> anyhow::Result<String> {
+    let joke = client
+        .get("https://api.chucknorris.io/jokes/random")
+        .send()
+        .await
+        .context("cannot query chucknorris.io API")?
+        .json::<ChuckResponse>()
+        .await
+        .context("unexpected response from chucknorris.io")?;
+    Ok(joke.value)
+}
+
+async fn fetch_geek_joke(client: &reqwest::Client) -> anyhow::Result<String> {
+    let joke = client
+        .get("https://geek-jokes.sameerkumar.website/api?format=json")
+        .send()
+        .await
+        .context("cannot query geek-jokes API")?
+        .json::<JokeResponse>()
+        .await
+        .context("unexpected response from geek-jokes")?;
+    Ok(joke.joke)
+}
+
+/// One irc message per line of the joke, at most MAX_JOKE_LINES.
+/// See https://github.com/CoucouInc/rustygolem/issues/9
+fn joke_lines(joke: &str) -> Vec<String> {
+    let lines = joke
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+        .collect::<Vec<_>>();
+    if lines.len() <= MAX_JOKE_LINES {
+        return lines.into_iter().map(String::from).collect();
+    }
+    let (head, tail) = lines.split_at(MAX_JOKE_LINES - 1);
+    head.iter()
+        .map(|l| l.to_string())
+        .chain(std::iter::once(tail.join(" − ")))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn cmd<'a>(source: Option<&'a str>, slug: Option<&'a str>) -> JokeCmd<'a> {
+        JokeCmd { source, slug }
+    }
+
+    #[test]
+    async fn test_parse_command() {
+        assert_eq!(parse_command("λjoke"), Some((cmd(None, None), None)));
+        assert_eq!(
+            parse_command("λjoke chuck"),
+            Some((cmd(Some("chuck"), None), None))
+        );
+        assert_eq!(
+            parse_command("λjoke dad B5hNeNJYgFd"),
+            Some((cmd(Some("dad"), Some("B5hNeNJYgFd")), None))
+        );
+        assert_eq!(
+            parse_command("&joke dad B5hNeNJYgFd > charlie"),
+            Some((cmd(Some("dad"), Some("B5hNeNJYgFd")), Some("charlie")))
+        );
+        assert_eq!(
+            parse_command("λjoke > charlie"),
+            Some((cmd(None, None), Some("charlie")))
+        );
+        assert_eq!(parse_command("λjokes"), None);
+        assert_eq!(parse_command("λjoke dad B5hNeNJYgFd more"), None);
+    }
+
+    #[test]
+    async fn test_unknown_source() {
+        assert_eq!(
+            handle_command(&reqwest::Client::new(), cmd(Some("toto"), None), None).await,
+            vec!["Connais pas les blagues toto, essaye parmi: dad, chuck, geek"]
+        );
+    }
+
+    #[test]
+    async fn test_chuck_response() {
+        let json = r#"{"categories":[],"icon_url":"https://api.chucknorris.io/img/avatar/chuck-norris.png","id":"abc","value":"Chuck Norris can divide by zero."}"#;
+        let resp: ChuckResponse = serde_json::from_str(json).unwrap();
+        assert_eq!(resp.value, "Chuck Norris can divide by zero.");
+    }
+
+    #[test]
+    async fn test_multiline_joke() {
+        let json = r#"{"id":"GlGBIY0wAAd","joke":"How much does a hipster weigh?\r\nAn instagram.","status":200}"#;
+        let resp: JokeResponse = serde_json::from_str(json).unwrap();
+        assert_eq!(
+            joke_lines(&resp.joke),
+            vec!["How much does a hipster weigh?", "An instagram."]
+        );
+        assert_eq!(joke_lines("no punchline"), vec!["no punchline"]);
+    }
+
+    #[test]
+    async fn test_long_joke() {
+        assert_eq!(
+            joke_lines("setup\n\nmore setup\npunchline\n"),
+            vec!["setup", "more setup − punchline"],
+            "capped to avoid flooding the channel"
+        );
+    }
 }
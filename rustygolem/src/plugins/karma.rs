@@ -0,0 +1,265 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::db;
+use crate::schema::karma::{self, dsl};
+use crate::utils::parser::command_prefix;
+use anyhow::Context;
+use async_trait::async_trait;
+use diesel::prelude::*;
+use irc::proto::{Command, Message};
+use nom::branch::alt;
+use nom::bytes::complete::{tag, take_while1};
+use nom::character::complete::{char, multispace0, multispace1};
+use nom::combinator::{all_consuming, map, recognize, value};
+use nom::multi::separated_list1;
+use nom::sequence::{pair, tuple};
+use nom::{Finish, IResult};
+use plugin_core::utils::network::channel_key;
+use plugin_core::{CommandHelp, Initialised, Plugin, Result};
+
+/// how long someone has to wait before changing the karma of the same thing again
+const KARMA_COOLDOWN: Duration = Duration::from_secs(60);
+
+pub struct Karma {
+    /// (channel key, giver, thing) -> last time the giver changed that karma
+    last_change: Mutex<HashMap<(String, String, String), Instant>>,
+}
+
+#[derive(Debug, PartialEq, Queryable, Insertable)]
+#[table_name = "karma"]
+struct KarmaRow {
+    /// see `channel_key`
+    channel: String,
+    /// lowercased
+    thing: String,
+    score: i32,
+}
+
+#[async_trait]
+impl Plugin for Karma {
+    async fn init(_config: &plugin_core::Config) -> Result<Initialised> {
+        Ok(Initialised::from(Karma {
+            last_change: Mutex::new(HashMap::new()),
+        }))
+    }
+
+    fn get_name(&self) -> &'static str {
+        "karma"
+    }
+
+    fn commands(&self) -> Vec<CommandHelp> {
+        vec![CommandHelp::new(
+            "karma",
+            "<truc>",
+            "le karma de truc, qui change avec truc++ ou truc--",
+        )]
+    }
+
+    async fn in_message(&self, msg: &Message) -> Result<Option<Message>> {
+        self.in_msg(msg).await
+    }
+}
+
+impl Karma {
+    async fn in_msg(&self, msg: &Message) -> Result<Option<Message>> {
+        let (target, text) = match &msg.command {
+            Command::PRIVMSG(target, text) => (target, text),
+            _ => return Ok(None),
+        };
+        if !target.starts_with('#') {
+            return Ok(None);
+        }
+        let channel = channel_key(msg, target);
+
+        if let Some(thing) = parse_command(text) {
+            let thing = thing.to_string();
+            let reply_thing = thing.clone();
+            let score = db::with_connection(move |conn| get_score(conn, &channel, &thing))
+                .await
+                .context("Cannot get karma")?;
+            let reply = format!("{} a un karma de {}", reply_thing, score);
+            return Ok(Some(Command::PRIVMSG(target.clone(), reply).into()));
+        }
+
+        // commands can legitimately contain `--`, only look at plain messages
+        if command_prefix(text).is_ok() {
+            return Ok(None);
+        }
+        let giver = match msg.source_nickname() {
+            Some(giver) => giver,
+            None => return Ok(None),
+        };
+        let changes = karma_changes(text)
+            .into_iter()
+            .filter(|(thing, _)| !thing.eq_ignore_ascii_case(giver))
+            .filter(|(thing, _)| self.allow_change(&channel, giver, thing, Instant::now()))
+            .map(|(thing, delta)| (thing.to_string(), delta))
+            .collect::<Vec<_>>();
+        if changes.is_empty() {
+            return Ok(None);
+        }
+
+        db::with_connection(move |conn| {
+            for (thing, delta) in changes {
+                add_karma(conn, &channel, &thing, delta)?;
+            }
+            Ok::<_, anyhow::Error>(())
+        })
+        .await
+        .context("Cannot update karma")?;
+        Ok(None)
+    }
+
+    /// Whether `giver` can change the karma of `thing` at `now`,
+    /// and if so, starts the cooldown.
+    fn allow_change(&self, channel: &str, giver: &str, thing: &str, now: Instant) -> bool {
+        let mut last_change = self.last_change.lock().expect("karma cooldown lock");
+        last_change.retain(|_, at| now.saturating_duration_since(*at) < KARMA_COOLDOWN);
+        let key = (
+            channel.to_string(),
+            giver.to_lowercase(),
+            thing.to_lowercase(),
+        );
+        if last_change.contains_key(&key) {
+            return false;
+        }
+        last_change.insert(key, now);
+        true
+    }
+}
+
+fn get_score(conn: &SqliteConnection, channel: &str, thing: &str) -> anyhow::Result<i32> {
+    let score = dsl::karma
+        .filter(dsl::channel.eq(channel))
+        .filter(dsl::thing.eq(thing.to_lowercase()))
+        .select(dsl::score)
+        .first::<i32>(conn)
+        .optional()
+        .with_context(|| format!("Cannot load karma for {} in {}", thing, channel))?;
+    Ok(score.unwrap_or(0))
+}
+
+fn add_karma(
+    conn: &SqliteConnection,
+    channel: &str,
+    thing: &str,
+    delta: i32,
+) -> anyhow::Result<()> {
+    conn.transaction::<_, anyhow::Error, _>(|| {
+        let row = KarmaRow {
+            channel: channel.to_string(),
+            thing: thing.to_lowercase(),
+            score: get_score(conn, channel, thing)? + delta,
+        };
+        diesel::replace_into(karma::table)
+            .values(&row)
+            .execute(conn)
+            .with_context(|| format!("Cannot save karma {:?}", row))?;
+        Ok(())
+    })
+}
+
+/// The `thing++` and `thing--` in a message, each thing at most once
+fn karma_changes(text: &str) -> Vec<(&str, i32)> {
+    let mut changes: Vec<(&str, i32)> = vec![];
+    for (thing, delta) in text.split_whitespace().filter_map(karma_token) {
+        if !changes.iter().any(|(t, _)| t.eq_ignore_ascii_case(thing)) {
+            changes.push((thing, delta));
+        }
+    }
+    changes
+}
+
+/// A whole word like `charlie++` or `rust-lang--`, so `http://a--b.com`
+/// or `a--b` don't count
+fn karma_token(word: &str) -> Option<(&str, i32)> {
+    all_consuming(pair(thing, karma_delta))(word)
+        .finish()
+        .map(|x| x.1)
+        .ok()
+}
+
+/// Nick-like chars, with single dashes in the middle
+fn thing(input: &str) -> IResult<&str, &str> {
+    let part = take_while1(|c: char| c.is_alphanumeric() || "_[]{}|^`\\.".contains(c));
+    recognize(separated_list1(char('-'), part))(input)
+}
+
+fn karma_delta(input: &str) -> IResult<&str, i32> {
+    alt((value(1, tag("++")), value(-1, tag("--"))))(input)
+}
+
+/// `λkarma <thing>`
+fn parse_command(input: &str) -> Option<&str> {
+    all_consuming(map(
+        tuple((
+            command_prefix,
+            tag("karma"),
+            multispace1,
+            thing,
+            multispace0,
+        )),
+        |(_, _, _, thing, _)| thing,
+    ))(input)
+    .finish()
+    .map(|x| x.1)
+    .ok()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    async fn test_karma_changes() {
+        assert_eq!(
+            karma_changes("charlie++ merci, et rust-lang-- aussi"),
+            vec![("charlie", 1), ("rust-lang", -1)]
+        );
+        assert_eq!(karma_changes("c++ c'est bien"), vec![("c", 1)]);
+        assert_eq!(
+            karma_changes("charlie++ Charlie++ charlie--"),
+            vec![("charlie", 1)],
+            "one change per thing and message"
+        );
+        assert_eq!(karma_changes("http://a--b.com"), vec![]);
+        assert_eq!(karma_changes("a--b"), vec![]);
+        assert_eq!(karma_changes("++ -- +++"), vec![]);
+    }
+
+    #[test]
+    async fn test_parse_command() {
+        assert_eq!(parse_command("λkarma charlie"), Some("charlie"));
+        assert_eq!(parse_command("λkarma rust-lang "), Some("rust-lang"));
+        assert_eq!(parse_command("λkarma"), None);
+    }
+
+    #[test]
+    async fn test_cooldown() {
+        let plugin = Karma {
+            last_change: Mutex::new(HashMap::new()),
+        };
+        let now = Instant::now();
+        assert!(plugin.allow_change("#chan", "bob", "charlie", now));
+        assert!(!plugin.allow_change("#chan", "Bob", "CHARLIE", now + Duration::from_secs(10)));
+        assert!(plugin.allow_change("#chan", "bob", "alice", now));
+        assert!(plugin.allow_change("#other", "bob", "charlie", now));
+        assert!(plugin.allow_change("#chan", "bob", "charlie", now + KARMA_COOLDOWN));
+    }
+
+    #[test]
+    async fn test_karma_store() {
+        let conn = SqliteConnection::establish(":memory:").unwrap();
+        db::run_migrations(&conn).unwrap();
+
+        assert_eq!(get_score(&conn, "#chan", "charlie").unwrap(), 0);
+        add_karma(&conn, "#chan", "charlie", 1).unwrap();
+        add_karma(&conn, "#chan", "Charlie", 1).unwrap();
+        add_karma(&conn, "#other", "charlie", -1).unwrap();
+        assert_eq!(get_score(&conn, "#chan", "CHARLIE").unwrap(), 2);
+        assert_eq!(get_score(&conn, "#other", "charlie").unwrap(), -1);
+    }
+}
@@ -0,0 +1,352 @@
+use std::time::Duration;
+
+use crate::db;
+use crate::schema::lastfm_users::{self, dsl};
+use crate::utils::parser::command_prefix;
+use anyhow::Context;
+use async_trait::async_trait;
+use diesel::prelude::*;
+use irc::proto::{Command, Message};
+use nom::branch::alt;
+use nom::bytes::complete::{tag, take_while1};
+use nom::character::complete::{multispace0, multispace1};
+use nom::combinator::{all_consuming, map, opt};
+use nom::sequence::{preceded, terminated, tuple};
+use nom::{Finish, IResult};
+use plugin_core::config::ConfigSection;
+use plugin_core::utils::parser::with_target;
+use plugin_core::{CommandHelp, Initialised, Plugin, Result};
+use serde::Deserialize;
+
+const FETCH_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Deserialize)]
+struct LastFmConfig {
+    lastfm_api_key: Option<String>,
+}
+
+impl ConfigSection for LastFmConfig {
+    const SECTION: Option<&'static str> = None;
+    const SCHEMA: &'static str = "{ lastfm_api_key : Optional Text }";
+}
+
+pub struct LastFm {
+    client: reqwest::Client,
+    api_key: Option<String>,
+}
+
+#[derive(Debug, PartialEq)]
+enum NpCmd<'input> {
+    /// track of the given last.fm user, or of the one bound to the nick
+    Show(Option<&'input str>),
+    /// bind the nick to a last.fm user
+    Set(&'input str),
+}
+
+#[derive(Debug, Insertable)]
+#[table_name = "lastfm_users"]
+struct LastFmUser<'a> {
+    /// lowercased
+    irc_nick: &'a str,
+    lastfm_user: &'a str,
+}
+
+#[async_trait]
+impl Plugin for LastFm {
+    async fn init(config: &plugin_core::Config) -> Result<Initialised> {
+        let lastfm_config: LastFmConfig = plugin_core::config::load(&config.config_path)?;
+        if lastfm_config.lastfm_api_key.is_none() {
+            log::warn!("Last.fm plugin is missing lastfm_api_key.");
+        }
+        Ok(Initialised::from(LastFm {
+            client: config.http_client.clone(),
+            api_key: lastfm_config.lastfm_api_key,
+        }))
+    }
+
+    fn get_name(&self) -> &'static str {
+        "lastfm"
+    }
+
+    fn commands(&self) -> Vec<CommandHelp> {
+        vec![
+            CommandHelp::new(
+                "np",
+                "[utilisateur last.fm]",
+                "ce que tu écoutes, ou as écouté en dernier",
+            ),
+            CommandHelp::new(
+                "np",
+                "set <utilisateur last.fm>",
+                "ton compte last.fm, pour λnp sans argument",
+            ),
+        ]
+    }
+
+    async fn in_message(&self, msg: &Message) -> Result<Option<Message>> {
+        self.in_msg(msg).await
+    }
+}
+
+impl LastFm {
+    async fn in_msg(&self, msg: &Message) -> Result<Option<Message>> {
+        let privmsg = match &msg.command {
+            Command::PRIVMSG(_source, privmsg) => privmsg,
+            _ => return Ok(None),
+        };
+        let (response_target, nick, (cmd, mb_target)) = match (
+            msg.response_target(),
+            msg.source_nickname(),
+            parse_command(privmsg),
+        ) {
+            (Some(response_target), Some(nick), Some(cmd)) => (response_target, nick, cmd),
+            _ => return Ok(None),
+        };
+
+        let reply = match cmd {
+            NpCmd::Set(user) => {
+                let (n, u) = (nick.to_lowercase(), user.to_string());
+                db::with_connection(move |conn| bind_user(conn, &n, &u))
+                    .await
+                    .context("Cannot save last.fm user")?;
+                format!("{nick}: tu es {user} sur last.fm")
+            }
+            NpCmd::Show(Some(user)) => self.now_playing(user).await,
+            NpCmd::Show(None) => {
+                let n = nick.to_lowercase();
+                let bound = db::with_connection(move |conn| bound_user(conn, &n))
+                    .await
+                    .context("Cannot load last.fm user")?;
+                match bound {
+                    Some(user) => self.now_playing(&user).await,
+                    None => format!(
+                        "{nick}: je ne connais pas ton compte last.fm, dis le moi avec λnp set <utilisateur>"
+                    ),
+                }
+            }
+        };
+        let reply = crate::utils::messages::with_target(&reply, &mb_target);
+        Ok(Some(
+            Command::PRIVMSG(response_target.to_string(), reply).into(),
+        ))
+    }
+
+    async fn now_playing(&self, user: &str) -> String {
+        let api_key = match &self.api_key {
+            Some(key) => key,
+            None => return "Pas de clé d'api last.fm configurée".to_string(),
+        };
+        match recent_track(&self.client, api_key, user).await {
+            Ok(track) => format_track(user, track.as_ref()),
+            Err(err) => {
+                log::warn!("Cannot get the recent tracks of {user}: {err:#}");
+                format!("Pas moyen de savoir ce qu'écoute {user}: {err:#}")
+            }
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum RecentTracksResponse {
+    Ok { recenttracks: RecentTracks },
+    Error { message: String },
+}
+
+#[derive(Debug, Deserialize)]
+struct RecentTracks {
+    #[serde(default)]
+    track: Vec<Track>,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+struct Track {
+    name: String,
+    artist: Text,
+    album: Option<Text>,
+    #[serde(rename = "@attr")]
+    attr: Option<TrackAttr>,
+    /// absent for the track being played
+    date: Option<Text>,
+}
+
+/// last.fm puts the values of some fields in `#text`
+#[derive(Debug, Deserialize, PartialEq)]
+struct Text {
+    #[serde(rename = "#text")]
+    text: String,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+struct TrackAttr {
+    nowplaying: Option<String>,
+}
+
+impl Track {
+    fn is_playing(&self) -> bool {
+        self.attr
+            .as_ref()
+            .and_then(|a| a.nowplaying.as_deref())
+            .map_or(false, |playing| playing == "true")
+    }
+}
+
+/// The track being played, or the last one played. None if the user
+/// never listened to anything.
+async fn recent_track(
+    client: &reqwest::Client,
+    api_key: &str,
+    user: &str,
+) -> anyhow::Result<Option<Track>> {
+    let resp: RecentTracksResponse = client
+        .get("https://ws.audioscrobbler.com/2.0/")
+        .query(&[
+            ("method", "user.getrecenttracks"),
+            ("user", user),
+            ("api_key", api_key),
+            ("format", "json"),
+            ("limit", "1"),
+        ])
+        .timeout(FETCH_TIMEOUT)
+        .send()
+        .await?
+        .json()
+        .await
+        .context("Cannot parse the last.fm response")?;
+    match resp {
+        RecentTracksResponse::Ok { recenttracks } => Ok(recenttracks.track.into_iter().next()),
+        RecentTracksResponse::Error { message } => Err(anyhow!(message)),
+    }
+}
+
+fn format_track(user: &str, track: Option<&Track>) -> String {
+    let track = match track {
+        Some(track) => track,
+        None => return format!("{user} n'a rien écouté récemment"),
+    };
+    let album = track
+        .album
+        .as_ref()
+        .map(|a| a.text.as_str())
+        .filter(|a| !a.is_empty())
+        .map(|a| format!(" ({a})"))
+        .unwrap_or_default();
+    let song = format!("{} - {}{}", track.artist.text, track.name, album);
+    match &track.date {
+        _ if track.is_playing() => format!("{user} écoute {song}"),
+        Some(date) => format!("{user} a écouté {song}, le {}", date.text),
+        None => format!("{user} a écouté {song}"),
+    }
+}
+
+fn bind_user(conn: &SqliteConnection, irc_nick: &str, lastfm_user: &str) -> anyhow::Result<()> {
+    diesel::replace_into(lastfm_users::table)
+        .values(&LastFmUser {
+            irc_nick,
+            lastfm_user,
+        })
+        .execute(conn)
+        .with_context(|| format!("Cannot bind {irc_nick} to {lastfm_user}"))?;
+    Ok(())
+}
+
+fn bound_user(conn: &SqliteConnection, irc_nick: &str) -> anyhow::Result<Option<String>> {
+    dsl::lastfm_users
+        .filter(dsl::irc_nick.eq(irc_nick))
+        .select(dsl::lastfm_user)
+        .first::<String>(conn)
+        .optional()
+        .with_context(|| format!("Cannot load the last.fm user of {irc_nick}"))
+}
+
+/// `λnp [user]` or `λnp set <user>`, with an optional target
+fn parse_command(input: &str) -> Option<(NpCmd, Option<&str>)> {
+    let set = map(
+        preceded(tuple((tag("set"), multispace1)), lastfm_user),
+        NpCmd::Set,
+    );
+    let show = map(opt(preceded(multispace1, lastfm_user)), NpCmd::Show);
+    let cmd = preceded(
+        tuple((command_prefix, tag("np"))),
+        with_target(alt((preceded(multispace1, set), show))),
+    );
+    all_consuming(terminated(cmd, multispace0))(input)
+        .finish()
+        .map(|x| x.1)
+        .ok()
+}
+
+/// last.fm usernames are made of letters, digits, `_` and `-`
+fn lastfm_user(input: &str) -> IResult<&str, &str> {
+    take_while1(|c: char| c.is_ascii_alphanumeric() || c == '_' || c == '-')(input)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    async fn test_parse_command() {
+        assert_eq!(parse_command("λnp"), Some((NpCmd::Show(None), None)));
+        assert_eq!(
+            parse_command("λnp rj_music > charlie"),
+            Some((NpCmd::Show(Some("rj_music")), Some("charlie")))
+        );
+        assert_eq!(
+            parse_command("λnp set rj-music "),
+            Some((NpCmd::Set("rj-music"), None))
+        );
+        assert_eq!(
+            parse_command("λnp set"),
+            Some((NpCmd::Show(Some("set")), None)),
+            "someone called set"
+        );
+        assert_eq!(parse_command("λnpfoo"), None);
+        assert_eq!(parse_command("λnp a b"), None);
+    }
+
+    #[test]
+    async fn test_format_track() {
+        let json = r##"{"recenttracks":{"track":[{"artist":{"mbid":"","#text":"Daft Punk"},"album":{"mbid":"","#text":"Discovery"},"name":"One More Time","@attr":{"nowplaying":"true"}}],"@attr":{"user":"rj"}}}"##;
+        let playing = match serde_json::from_str(json).unwrap() {
+            RecentTracksResponse::Ok { recenttracks } => recenttracks.track,
+            resp => panic!("not a list of tracks: {:?}", resp),
+        };
+        assert_eq!(
+            format_track("rj", playing.first()),
+            "rj écoute Daft Punk - One More Time (Discovery)"
+        );
+
+        let json = r##"{"recenttracks":{"track":[{"artist":{"#text":"Daft Punk"},"album":{"#text":""},"name":"Aerodynamic","date":{"uts":"1640995200","#text":"01 Jan 2022, 00:00"}}]}}"##;
+        let played = match serde_json::from_str(json).unwrap() {
+            RecentTracksResponse::Ok { recenttracks } => recenttracks.track,
+            resp => panic!("not a list of tracks: {:?}", resp),
+        };
+        assert_eq!(
+            format_track("rj", played.first()),
+            "rj a écouté Daft Punk - Aerodynamic, le 01 Jan 2022, 00:00"
+        );
+        assert_eq!(format_track("rj", None), "rj n'a rien écouté récemment");
+
+        let json = r#"{"message":"User not found","error":6}"#;
+        assert!(matches!(
+            serde_json::from_str(json).unwrap(),
+            RecentTracksResponse::Error { message } if message == "User not found"
+        ));
+    }
+
+    #[test]
+    async fn test_bound_user() {
+        let conn = SqliteConnection::establish(":memory:").unwrap();
+        db::run_migrations(&conn).unwrap();
+
+        assert_eq!(bound_user(&conn, "charlie").unwrap(), None);
+        bind_user(&conn, "charlie", "rj").unwrap();
+        bind_user(&conn, "charlie", "rj_music").unwrap();
+        assert_eq!(
+            bound_user(&conn, "charlie").unwrap(),
+            Some("rj_music".to_string())
+        );
+    }
+}
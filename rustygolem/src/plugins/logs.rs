@@ -0,0 +1,270 @@
+use crate::db;
+use crate::schema::channel_logs::{self, dsl};
+use crate::utils::messages::format_ago;
+use crate::utils::parser::command_prefix;
+use anyhow::Context;
+use async_trait::async_trait;
+use chrono::{NaiveDateTime, Utc};
+use diesel::prelude::*;
+use diesel::sql_types::Text;
+use irc::proto::{Command, Message};
+use nom::bytes::complete::{tag, take_while1};
+use nom::character::complete::{multispace0, multispace1};
+use nom::combinator::all_consuming;
+use nom::sequence::{delimited, tuple};
+use nom::Finish;
+use plugin_core::utils::network::channel_key;
+use plugin_core::{CommandHelp, Initialised, Plugin, Result};
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::sync::{mpsc, Mutex};
+
+/// Lines waiting to be written, the new ones are dropped past that
+const LOG_QUEUE_SIZE: usize = 1000;
+/// Lines written in a single transaction
+const MAX_BATCH_SIZE: usize = 100;
+
+sql_function!(fn lower(x: Text) -> Text);
+
+/// Logs every line said in the channels, including by blacklisted users,
+/// which are only prevented from using λlogs.
+/// The lines are written by `run`, so that a busy channel or a slow disk
+/// doesn't hold the other plugins.
+pub struct Logs {
+    blacklisted_users: Vec<String>,
+    lines_tx: mpsc::Sender<LogLine>,
+    lines_rx: Mutex<mpsc::Receiver<LogLine>>,
+}
+
+#[derive(Debug, PartialEq, Insertable)]
+#[table_name = "channel_logs"]
+struct LogLine {
+    logged_at: NaiveDateTime,
+    /// see `channel_key`
+    channel: String,
+    nick: String,
+    text: String,
+}
+
+impl LogLine {
+    fn new(logged_at: NaiveDateTime, channel: &str, nick: &str, text: &str) -> Self {
+        LogLine {
+            logged_at,
+            channel: channel.to_string(),
+            nick: nick.to_string(),
+            text: text.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Queryable)]
+struct LastLine {
+    logged_at: NaiveDateTime,
+    nick: String,
+    text: String,
+}
+
+#[async_trait]
+impl Plugin for Logs {
+    async fn init(config: &plugin_core::Config) -> Result<Initialised> {
+        let (lines_tx, lines_rx) = mpsc::channel(LOG_QUEUE_SIZE);
+        Ok(Initialised::from(Logs {
+            blacklisted_users: config.blacklisted_users.clone(),
+            lines_tx,
+            lines_rx: Mutex::new(lines_rx),
+        }))
+    }
+
+    fn get_name(&self) -> &'static str {
+        "logs"
+    }
+
+    fn commands(&self) -> Vec<CommandHelp> {
+        vec![CommandHelp::new(
+            "logs",
+            "<nick>",
+            "combien de messages nick a écrit dans le channel, et le dernier",
+        )]
+    }
+
+    async fn in_message(&self, msg: &Message) -> Result<Option<Message>> {
+        self.in_msg(msg).await
+    }
+
+    fn ignore_blacklisted_users(&self) -> bool {
+        false
+    }
+
+    async fn run(&self, _bot_chan: mpsc::Sender<Message>) -> Result<()> {
+        // hold that lock forever
+        let mut lines_rx = self.lines_rx.lock().await;
+        while let Some(line) = lines_rx.recv().await {
+            let mut batch = vec![line];
+            while batch.len() < MAX_BATCH_SIZE {
+                match lines_rx.try_recv() {
+                    Ok(line) => batch.push(line),
+                    Err(_) => break,
+                }
+            }
+            let saved = db::with_connection(move |conn| insert(conn, &batch)).await;
+            if let Err(err) = saved {
+                log::error!("Cannot save channel logs: {:#}", err);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Logs {
+    async fn in_msg(&self, msg: &Message) -> Result<Option<Message>> {
+        let (target, text) = match &msg.command {
+            Command::PRIVMSG(target, text) => (target, text),
+            _ => return Ok(None),
+        };
+        if !target.starts_with('#') {
+            return Ok(None);
+        }
+        let source = match msg.source_nickname() {
+            Some(source) => source,
+            None => return Ok(None),
+        };
+
+        let now = Utc::now().naive_utc();
+        let channel = channel_key(msg, target);
+        self.enqueue(LogLine::new(now, &channel, source, text));
+
+        if self.blacklisted_users.iter().any(|u| u == source) {
+            return Ok(None);
+        }
+        let nick = match parse_command(text) {
+            Some(nick) => nick,
+            None => return Ok(None),
+        };
+        let n = nick.to_string();
+        let stats = db::with_connection(move |conn| nick_stats(conn, &channel, &n))
+            .await
+            .context("Cannot look up logs")?;
+
+        let reply = format_stats(nick, stats, now);
+        Ok(Some(Command::PRIVMSG(target.clone(), reply).into()))
+    }
+
+    /// Never waits, the line is dropped when the queue is full
+    fn enqueue(&self, line: LogLine) {
+        match self.lines_tx.try_send(line) {
+            Ok(()) => (),
+            Err(TrySendError::Full(line)) => {
+                log::warn!("Channel log queue is full, dropping {:?}", line)
+            }
+            Err(TrySendError::Closed(line)) => {
+                log::error!("Channel log queue is closed, dropping {:?}", line)
+            }
+        }
+    }
+}
+
+fn insert(conn: &SqliteConnection, lines: &[LogLine]) -> anyhow::Result<()> {
+    conn.transaction::<_, diesel::result::Error, _>(|| {
+        for line in lines {
+            diesel::insert_into(channel_logs::table)
+                .values(line)
+                .execute(conn)?;
+        }
+        Ok(())
+    })
+    .with_context(|| format!("Cannot insert {} log lines", lines.len()))
+}
+
+/// How many lines `nick` wrote in `channel`, and the last one
+fn nick_stats(
+    conn: &SqliteConnection,
+    channel: &str,
+    nick: &str,
+) -> anyhow::Result<Option<(i64, LastLine)>> {
+    let lines = || {
+        dsl::channel_logs
+            .filter(dsl::channel.eq(channel))
+            .filter(lower(dsl::nick).eq(nick.to_lowercase()))
+    };
+    let count = lines()
+        .count()
+        .get_result::<i64>(conn)
+        .with_context(|| format!("Cannot count the lines of {} in {}", nick, channel))?;
+    let last = lines()
+        .order_by(dsl::id.desc())
+        .select((dsl::logged_at, dsl::nick, dsl::text))
+        .first::<LastLine>(conn)
+        .optional()
+        .with_context(|| format!("Cannot load the last line of {} in {}", nick, channel))?;
+    Ok(last.map(|last| (count, last)))
+}
+
+fn format_stats(nick: &str, stats: Option<(i64, LastLine)>, now: NaiveDateTime) -> String {
+    match stats {
+        None => format!("Pas de message de {} ici", nick),
+        Some((count, last)) => format!(
+            "{} a écrit {} message{} ici, le dernier il y a {}: {}",
+            last.nick,
+            count,
+            if count > 1 { "s" } else { "" },
+            format_ago(now - last.logged_at),
+            last.text
+        ),
+    }
+}
+
+/// `λlogs <nick>`
+fn parse_command(input: &str) -> Option<&str> {
+    all_consuming(delimited(
+        tuple((command_prefix, tag("logs"), multispace1)),
+        take_while1(|c: char| !c.is_whitespace()),
+        multispace0,
+    ))(input)
+    .finish()
+    .map(|x| x.1)
+    .ok()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn at(hour: u32) -> NaiveDateTime {
+        chrono::NaiveDate::from_ymd(2024, 1, 1).and_hms(hour, 0, 0)
+    }
+
+    #[test]
+    async fn test_parse_command() {
+        assert_eq!(parse_command("λlogs charlie "), Some("charlie"));
+        assert_eq!(parse_command("λlogs"), None);
+        assert_eq!(parse_command("λlogs charlie alice"), None);
+    }
+
+    #[test]
+    async fn test_nick_stats() {
+        let conn = SqliteConnection::establish(":memory:").unwrap();
+        db::run_migrations(&conn).unwrap();
+
+        insert(
+            &conn,
+            &[
+                LogLine::new(at(9), "#chan", "Charlie", "coucou"),
+                LogLine::new(at(10), "#chan", "alice", "salut"),
+                LogLine::new(at(11), "#chan", "Charlie", "ça va ?"),
+                LogLine::new(at(12), "#other", "Charlie", "ailleurs"),
+            ],
+        )
+        .unwrap();
+
+        let stats = nick_stats(&conn, "#chan", "charlie").unwrap();
+        assert_eq!(
+            format_stats("charlie", stats, at(14)),
+            "Charlie a écrit 2 messages ici, le dernier il y a 3h: ça va ?"
+        );
+        let stats = nick_stats(&conn, "#chan", "bob").unwrap();
+        assert_eq!(
+            format_stats("bob", stats, at(14)),
+            "Pas de message de bob ici"
+        );
+    }
+}
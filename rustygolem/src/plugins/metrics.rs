@@ -0,0 +1,44 @@
+use async_trait::async_trait;
+use axum::extract::State;
+use axum::http::{header, StatusCode};
+use axum::response::IntoResponse;
+use axum::{routing, Router};
+use plugin_core::metrics::{BotMetrics, CONTENT_TYPE};
+use plugin_core::{Initialised, Plugin, Result};
+
+/// Serves the counters of the bot on `/metrics`, for prometheus to scrape
+pub struct Metrics {}
+
+#[async_trait]
+impl Plugin for Metrics {
+    async fn init(config: &plugin_core::Config) -> Result<Initialised> {
+        Ok(Initialised {
+            plugin: Box::new(Metrics {}),
+            router: Some(router(config.metrics.clone())),
+        })
+    }
+
+    fn get_name(&self) -> &'static str {
+        "metrics"
+    }
+}
+
+fn router(metrics: BotMetrics) -> Router<()> {
+    Router::new()
+        .route("/metrics", routing::get(render))
+        .with_state(metrics)
+}
+
+async fn render(State(metrics): State<BotMetrics>) -> impl IntoResponse {
+    match metrics.render() {
+        Ok(body) => (StatusCode::OK, [(header::CONTENT_TYPE, CONTENT_TYPE)], body),
+        Err(err) => {
+            log::error!("Cannot render metrics: {err}");
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                [(header::CONTENT_TYPE, "text/plain")],
+                "Cannot render metrics".to_string(),
+            )
+        }
+    }
+}
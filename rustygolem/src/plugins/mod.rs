@@ -1,11 +1,47 @@
+mod alias;
+mod calc;
 mod crypto;
 mod ctcp;
 mod echo;
+mod feed;
 mod joke;
+mod karma;
+mod lastfm;
+mod logs;
+mod metrics;
+mod quote;
+mod remind;
 mod republican_calendar;
+mod roll;
+mod search;
+mod seen;
+mod tell;
+mod topic;
+mod translate;
+mod urbain;
+mod weather;
+mod welcome;
 
+pub use alias::Alias;
+pub use calc::Calc;
 pub use crypto::Crypto;
 pub use ctcp::Ctcp;
 pub use echo::Echo;
+pub use feed::Feed;
 pub use joke::Joke;
+pub use karma::Karma;
+pub use lastfm::LastFm;
+pub use logs::Logs;
+pub use metrics::Metrics;
+pub use quote::Quote;
+pub use remind::Remind;
 pub use self::republican_calendar::RepublicanCalendar;
+pub use roll::Roll;
+pub use search::Search;
+pub use seen::Seen;
+pub use tell::Tell;
+pub use topic::Topic;
+pub use translate::Translate;
+pub use urbain::Urbain;
+pub use weather::Weather;
+pub use welcome::Welcome;
@@ -0,0 +1,263 @@
+use crate::db;
+use crate::schema::quotes::{self, dsl};
+use crate::utils::parser::command_prefix;
+use anyhow::Context;
+use async_trait::async_trait;
+use chrono::{NaiveDateTime, Utc};
+use diesel::dsl::max;
+use diesel::prelude::*;
+use irc::proto::{Command, Message};
+use nom::branch::alt;
+use nom::bytes::complete::tag;
+use nom::character::complete::{multispace0, multispace1, u32 as integer};
+use nom::combinator::{all_consuming, map, rest, value, verify};
+use nom::sequence::{preceded, terminated, tuple};
+use nom::{Finish, IResult};
+use plugin_core::utils::network::channel_key;
+use plugin_core::{CommandHelp, Initialised, Plugin, Result};
+
+no_arg_sql_function!(
+    random,
+    diesel::sql_types::Integer,
+    "sqlite RANDOM(), to pick a random row"
+);
+
+pub struct Quote {}
+
+#[derive(Debug, PartialEq, Queryable, Insertable)]
+#[table_name = "quotes"]
+struct QuoteRow {
+    /// see `channel_key`
+    channel: String,
+    /// starts at 1 in each channel
+    id: i32,
+    added_by: String,
+    text: String,
+    added_at: NaiveDateTime,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+enum QuoteCmd<'input> {
+    Add(&'input str),
+    Random,
+    Get(i32),
+}
+
+#[async_trait]
+impl Plugin for Quote {
+    async fn init(_config: &plugin_core::Config) -> Result<Initialised> {
+        Ok(Initialised::from(Quote {}))
+    }
+
+    fn get_name(&self) -> &'static str {
+        "quote"
+    }
+
+    fn commands(&self) -> Vec<CommandHelp> {
+        vec![
+            CommandHelp::new("quote", "", "une citation du channel au hasard"),
+            CommandHelp::new("quote", "<numéro>", "la citation avec ce numéro"),
+            CommandHelp::new("quote", "add <texte>", "ajoute une citation"),
+        ]
+    }
+
+    async fn in_message(&self, msg: &Message) -> Result<Option<Message>> {
+        self.in_msg(msg).await
+    }
+}
+
+impl Quote {
+    async fn in_msg(&self, msg: &Message) -> Result<Option<Message>> {
+        let (target, text) = match &msg.command {
+            Command::PRIVMSG(target, text) => (target, text),
+            _ => return Ok(None),
+        };
+        if !target.starts_with('#') {
+            return Ok(None);
+        }
+        let (cmd, source) = match (parse_command(text), msg.source_nickname()) {
+            (Some(cmd), Some(source)) => (cmd, source.to_string()),
+            _ => return Ok(None),
+        };
+
+        let channel = channel_key(msg, target);
+        let reply = match cmd {
+            QuoteCmd::Add(quote) => {
+                let quote = quote.to_string();
+                let id = db::with_connection(move |conn| {
+                    add(conn, &channel, &source, &quote, Utc::now().naive_utc())
+                })
+                .await
+                .context("Cannot add quote")?;
+                format!("Citation #{} ajoutée", id)
+            }
+            QuoteCmd::Random => {
+                let found = db::with_connection(move |conn| {
+                    Ok::<_, anyhow::Error>((random_quote(conn, &channel)?, count(conn, &channel)?))
+                })
+                .await
+                .context("Cannot get a random quote")?;
+                match found {
+                    (Some(quote), total) => format!("{} (#{} sur {})", quote.text, quote.id, total),
+                    (None, _) => "Pas encore de citation ici".to_string(),
+                }
+            }
+            QuoteCmd::Get(id) => {
+                let found = db::with_connection(move |conn| {
+                    Ok::<_, anyhow::Error>((get(conn, &channel, id)?, count(conn, &channel)?))
+                })
+                .await
+                .context("Cannot get quote")?;
+                match found {
+                    (Some(quote), _) => format!(
+                        "{} (#{}, ajoutée par {} le {})",
+                        quote.text,
+                        quote.id,
+                        quote.added_by,
+                        quote.added_at.format("%Y-%m-%d")
+                    ),
+                    (None, 0) => "Pas encore de citation ici".to_string(),
+                    (None, total) => {
+                        format!("Pas de citation #{}, elles vont de #1 à #{}", id, total)
+                    }
+                }
+            }
+        };
+
+        Ok(Some(Command::PRIVMSG(target.clone(), reply).into()))
+    }
+}
+
+/// Returns the id of the new quote
+fn add(
+    conn: &SqliteConnection,
+    channel: &str,
+    added_by: &str,
+    text: &str,
+    added_at: NaiveDateTime,
+) -> anyhow::Result<i32> {
+    conn.transaction::<_, anyhow::Error, _>(|| {
+        let last_id = dsl::quotes
+            .filter(dsl::channel.eq(channel))
+            .select(max(dsl::id))
+            .first::<Option<i32>>(conn)
+            .with_context(|| format!("Cannot get the last quote id in {}", channel))?;
+        let row = QuoteRow {
+            channel: channel.to_string(),
+            id: last_id.unwrap_or(0) + 1,
+            added_by: added_by.to_string(),
+            text: text.to_string(),
+            added_at,
+        };
+        diesel::insert_into(quotes::table)
+            .values(&row)
+            .execute(conn)
+            .with_context(|| format!("Cannot save quote {:?}", row))?;
+        Ok(row.id)
+    })
+}
+
+fn get(conn: &SqliteConnection, channel: &str, id: i32) -> anyhow::Result<Option<QuoteRow>> {
+    dsl::quotes
+        .filter(dsl::channel.eq(channel))
+        .filter(dsl::id.eq(id))
+        .first::<QuoteRow>(conn)
+        .optional()
+        .with_context(|| format!("Cannot load quote #{} in {}", id, channel))
+}
+
+fn random_quote(conn: &SqliteConnection, channel: &str) -> anyhow::Result<Option<QuoteRow>> {
+    dsl::quotes
+        .filter(dsl::channel.eq(channel))
+        .order(random)
+        .first::<QuoteRow>(conn)
+        .optional()
+        .with_context(|| format!("Cannot load a random quote in {}", channel))
+}
+
+fn count(conn: &SqliteConnection, channel: &str) -> anyhow::Result<i64> {
+    dsl::quotes
+        .filter(dsl::channel.eq(channel))
+        .count()
+        .get_result::<i64>(conn)
+        .with_context(|| format!("Cannot count quotes in {}", channel))
+}
+
+/// `λquote`, `λquote <id>` or `λquote add <text>`
+fn parse_command(input: &str) -> Option<QuoteCmd> {
+    all_consuming(preceded(
+        tuple((command_prefix, tag("quote"))),
+        terminated(
+            alt((add_cmd, get_cmd, value(QuoteCmd::Random, multispace0))),
+            multispace0,
+        ),
+    ))(input)
+    .finish()
+    .map(|x| x.1)
+    .ok()
+}
+
+fn add_cmd(input: &str) -> IResult<&str, QuoteCmd> {
+    map(
+        preceded(
+            tuple((multispace1, tag("add"), multispace1)),
+            verify(rest, |s: &str| !s.trim().is_empty()),
+        ),
+        |quote: &str| QuoteCmd::Add(quote.trim_end()),
+    )(input)
+}
+
+fn get_cmd(input: &str) -> IResult<&str, QuoteCmd> {
+    map(
+        preceded(multispace1, verify(integer, |id| *id <= i32::MAX as u32)),
+        |id| QuoteCmd::Get(id as i32),
+    )(input)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    async fn test_parse_command() {
+        assert_eq!(parse_command("λquote"), Some(QuoteCmd::Random));
+        assert_eq!(parse_command("λquote "), Some(QuoteCmd::Random));
+        assert_eq!(parse_command("λquote 7"), Some(QuoteCmd::Get(7)));
+        assert_eq!(
+            parse_command("λquote add <charlie> ça marche chez moi "),
+            Some(QuoteCmd::Add("<charlie> ça marche chez moi"))
+        );
+        assert_eq!(parse_command("λquote add"), None, "needs a quote");
+        assert_eq!(parse_command("λquote foo"), None);
+        assert_eq!(parse_command("λquotes"), None);
+    }
+
+    #[test]
+    async fn test_quote_store() {
+        let conn = SqliteConnection::establish(":memory:").unwrap();
+        db::run_migrations(&conn).unwrap();
+        let now = chrono::NaiveDate::from_ymd(2024, 1, 1).and_hms(9, 0, 0);
+
+        assert_eq!(random_quote(&conn, "#chan").unwrap(), None);
+        assert_eq!(add(&conn, "#chan", "bob", "premier", now).unwrap(), 1);
+        assert_eq!(add(&conn, "#chan", "bob", "deuxième", now).unwrap(), 2);
+        assert_eq!(
+            add(&conn, "#other", "bob", "ailleurs", now).unwrap(),
+            1,
+            "ids are per channel"
+        );
+
+        assert_eq!(count(&conn, "#chan").unwrap(), 2);
+        assert_eq!(
+            get(&conn, "#chan", 2).unwrap().map(|q| q.text),
+            Some("deuxième".to_string())
+        );
+        assert_eq!(get(&conn, "#chan", 3).unwrap(), None);
+        assert_eq!(
+            random_quote(&conn, "#other").unwrap().map(|q| q.text),
+            Some("ailleurs".to_string()),
+            "random quotes only come from the channel"
+        );
+    }
+}
@@ -0,0 +1,377 @@
+use std::result::Result as StdResult;
+use std::time::Duration;
+
+use crate::db;
+use crate::schema::reminders::{self, dsl};
+use crate::utils::parser::command_prefix;
+use anyhow::Context;
+use async_trait::async_trait;
+use chrono::{NaiveDateTime, Utc};
+use diesel::prelude::*;
+use irc::proto::{Command, Message};
+use nom::branch::alt;
+use nom::bytes::complete::{tag, take_while1};
+use nom::character::complete::{multispace0, multispace1, one_of, u64 as integer};
+use nom::combinator::{all_consuming, map, map_opt, opt, rest, verify};
+use nom::sequence::{pair, preceded, terminated, tuple};
+use nom::{Finish, IResult};
+use plugin_core::utils::network::{channel_key, privmsg_to};
+use plugin_core::{CommandHelp, Error, Initialised, Plugin, Result};
+use tokio::sync::mpsc;
+
+/// how often the table is checked for due reminders
+const POLL_INTERVAL: Duration = Duration::from_secs(30);
+
+pub struct Remind {}
+
+#[derive(Debug, PartialEq, Queryable)]
+struct Reminder {
+    id: i32,
+    nick: String,
+    /// where to post the reminder, can be the nick itself for a private
+    /// message. See `channel_key`.
+    channel: String,
+    fire_at: NaiveDateTime,
+    message: String,
+}
+
+#[derive(Debug, Insertable)]
+#[table_name = "reminders"]
+struct NewReminder {
+    nick: String,
+    channel: String,
+    fire_at: NaiveDateTime,
+    message: String,
+}
+
+#[async_trait]
+impl Plugin for Remind {
+    async fn init(_config: &plugin_core::Config) -> Result<Initialised> {
+        Ok(Initialised::from(Remind {}))
+    }
+
+    fn get_name(&self) -> &'static str {
+        "remind"
+    }
+
+    fn commands(&self) -> Vec<CommandHelp> {
+        vec![
+            CommandHelp::new(
+                "remind",
+                "<nick|me> in <n>[mhd] [to] <message>",
+                "rappelle le message dans le channel plus tard",
+            ),
+            CommandHelp::new(
+                "remind",
+                "<nick|me> at <aaaa-mm-jjThh:mm> <message>",
+                "rappelle le message à une date donnée, en UTC",
+            ),
+        ]
+    }
+
+    async fn in_message(&self, msg: &Message) -> Result<Option<Message>> {
+        self.in_msg(msg).await
+    }
+
+    async fn run(&self, bot_chan: mpsc::Sender<Message>) -> Result<()> {
+        post_due_reminders(bot_chan).await?;
+        Err(Error::Synthetic("reminder job stopped".to_string()))
+    }
+}
+
+impl Remind {
+    async fn in_msg(&self, msg: &Message) -> Result<Option<Message>> {
+        let privmsg = match &msg.command {
+            Command::PRIVMSG(_source, privmsg) => privmsg,
+            _ => return Ok(None),
+        };
+        let (cmd, channel, source) = match (
+            parse_command(privmsg),
+            msg.response_target(),
+            msg.source_nickname(),
+        ) {
+            (Some(cmd), Some(channel), Some(source)) => (cmd, channel.to_string(), source),
+            _ => return Ok(None),
+        };
+
+        let nick = if cmd.who == "me" { source } else { cmd.who };
+        let fire_at = match cmd.when.fire_at(Utc::now().naive_utc()) {
+            Ok(fire_at) => fire_at,
+            Err(err) => return Ok(Some(Command::PRIVMSG(channel, err).into())),
+        };
+
+        let reply = format!(
+            "Ok, rappel pour {} le {} UTC",
+            nick,
+            fire_at.format("%Y-%m-%d à %H:%M")
+        );
+        let reminder = NewReminder {
+            nick: nick.to_string(),
+            channel: channel_key(msg, &channel),
+            fire_at,
+            message: cmd.message.to_string(),
+        };
+        db::with_connection(move |conn| add(conn, &reminder))
+            .await
+            .context("Cannot save reminder")?;
+
+        Ok(Some(Command::PRIVMSG(channel, reply).into()))
+    }
+}
+
+/// Only stops when the reminders cannot be sent anymore, database errors
+/// are logged and the reminders are tried again at the next poll
+async fn post_due_reminders(bot_chan: mpsc::Sender<Message>) -> anyhow::Result<()> {
+    loop {
+        let now = Utc::now().naive_utc();
+        match db::with_connection(move |conn| due(conn, now)).await {
+            Ok(due) => {
+                for r in due {
+                    // removed first, so that it isn't posted twice if that fails
+                    let id = r.id;
+                    if let Err(err) = db::with_connection(move |conn| remove(conn, id)).await {
+                        log::warn!("Cannot remove reminder {}: {:#}", id, err);
+                        continue;
+                    }
+                    let msg = format!("{}: rappel: {}", r.nick, r.message);
+                    bot_chan
+                        .send(privmsg_to(&r.channel, msg))
+                        .await
+                        .with_context(|| format!("can't send message to {}", &r.channel))?;
+                }
+            }
+            Err(err) => log::warn!("Cannot load the due reminders: {:#}", err),
+        }
+
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+}
+
+fn add(conn: &SqliteConnection, reminder: &NewReminder) -> anyhow::Result<()> {
+    diesel::insert_into(reminders::table)
+        .values(reminder)
+        .execute(conn)
+        .with_context(|| format!("Cannot save reminder {:?}", reminder))?;
+    Ok(())
+}
+
+/// Reminders which should fire at `now`, oldest first
+fn due(conn: &SqliteConnection, now: NaiveDateTime) -> anyhow::Result<Vec<Reminder>> {
+    dsl::reminders
+        .filter(dsl::fire_at.le(now))
+        .order_by(dsl::fire_at)
+        .load::<Reminder>(conn)
+        .context("Cannot load due reminders")
+}
+
+fn remove(conn: &SqliteConnection, id: i32) -> anyhow::Result<()> {
+    diesel::delete(dsl::reminders.filter(dsl::id.eq(id)))
+        .execute(conn)
+        .with_context(|| format!("Cannot delete reminder {}", id))?;
+    Ok(())
+}
+
+#[derive(Debug, PartialEq)]
+struct RemindCmd<'input> {
+    /// nick to remind, `me` for the sender
+    who: &'input str,
+    when: When<'input>,
+    message: &'input str,
+}
+
+#[derive(Debug, PartialEq)]
+enum When<'input> {
+    /// `in 30m`
+    In(Duration),
+    /// `at 2024-01-01T09:00`, not validated yet
+    At(&'input str),
+}
+
+impl<'input> When<'input> {
+    /// Returns the error to reply when the time is invalid or in the past
+    fn fire_at(&self, now: NaiveDateTime) -> StdResult<NaiveDateTime, String> {
+        let fire_at = match self {
+            When::In(delay) => chrono::Duration::from_std(*delay)
+                .ok()
+                .and_then(|d| now.checked_add_signed(d))
+                .ok_or_else(|| "C'est un peu loin, ça.".to_string())?,
+            When::At(raw) => parse_datetime(raw).ok_or_else(|| {
+                format!(
+                    "Date invalide: {}, le format est AAAA-MM-JJTHH:MM, en UTC",
+                    raw
+                )
+            })?,
+        };
+        if fire_at <= now {
+            return Err("C'est déjà passé.".to_string());
+        }
+        Ok(fire_at)
+    }
+}
+
+/// RFC3339, or without the offset for UTC, with or without the seconds
+fn parse_datetime(raw: &str) -> Option<NaiveDateTime> {
+    chrono::DateTime::parse_from_rfc3339(raw)
+        .map(|dt| dt.naive_utc())
+        .or_else(|_| NaiveDateTime::parse_from_str(raw, "%Y-%m-%dT%H:%M:%S"))
+        .or_else(|_| NaiveDateTime::parse_from_str(raw, "%Y-%m-%dT%H:%M"))
+        .ok()
+}
+
+/// `λremind <who> in <delay> [to] <message>` or `λremind <who> at <time> <message>`
+fn parse_command(input: &str) -> Option<RemindCmd> {
+    all_consuming(terminated(parse_remind, multispace0))(input)
+        .finish()
+        .map(|x| x.1)
+        .ok()
+}
+
+fn parse_remind(input: &str) -> IResult<&str, RemindCmd> {
+    let who = take_while1(|c: char| !c.is_whitespace());
+    let message = verify(rest, |s: &str| !s.trim().is_empty());
+    map(
+        preceded(
+            tuple((command_prefix, tag("remind"), multispace1)),
+            tuple((
+                who,
+                multispace1,
+                alt((when_in, when_at)),
+                multispace1,
+                opt(pair(tag("to"), multispace1)),
+                message,
+            )),
+        ),
+        |(who, _, when, _, _, message)| RemindCmd {
+            who,
+            when,
+            message: message.trim_end(),
+        },
+    )(input)
+}
+
+/// `in 30m`, `in 2h` or `in 1d`
+fn when_in(input: &str) -> IResult<&str, When> {
+    map_opt(
+        tuple((tag("in"), multispace1, integer, one_of("mhd"))),
+        |(_, _, n, unit)| {
+            let secs = match unit {
+                'm' => 60,
+                'h' => 60 * 60,
+                _ => 24 * 60 * 60,
+            };
+            n.checked_mul(secs)
+                .map(|s| When::In(Duration::from_secs(s)))
+        },
+    )(input)
+}
+
+fn when_at(input: &str) -> IResult<&str, When> {
+    map(
+        preceded(
+            pair(tag("at"), multispace1),
+            take_while1(|c: char| !c.is_whitespace()),
+        ),
+        When::At,
+    )(input)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn at(day: u32, hour: u32, min: u32) -> NaiveDateTime {
+        chrono::NaiveDate::from_ymd(2024, 1, day).and_hms(hour, min, 0)
+    }
+
+    #[test]
+    async fn test_parse_command() {
+        assert_eq!(
+            parse_command("λremind me in 30m to water plants"),
+            Some(RemindCmd {
+                who: "me",
+                when: When::In(Duration::from_secs(30 * 60)),
+                message: "water plants",
+            })
+        );
+        assert_eq!(
+            parse_command("λremind charlie at 2024-01-01T09:00 happy new year"),
+            Some(RemindCmd {
+                who: "charlie",
+                when: When::At("2024-01-01T09:00"),
+                message: "happy new year",
+            })
+        );
+        assert_eq!(
+            parse_command("λremind me in 2h tomorrow is another day"),
+            Some(RemindCmd {
+                who: "me",
+                when: When::In(Duration::from_secs(2 * 60 * 60)),
+                message: "tomorrow is another day",
+            }),
+            "only a standalone `to` is dropped"
+        );
+        assert_eq!(parse_command("λremind me in 30m"), None, "needs a message");
+        assert_eq!(
+            parse_command("λremind me in 30y to wait"),
+            None,
+            "unknown unit"
+        );
+    }
+
+    #[test]
+    async fn test_fire_at() {
+        let now = at(1, 9, 0);
+        assert_eq!(
+            When::In(Duration::from_secs(60 * 60)).fire_at(now),
+            Ok(at(1, 10, 0))
+        );
+        assert_eq!(When::At("2024-01-02T09:30").fire_at(now), Ok(at(2, 9, 30)));
+        assert_eq!(
+            When::At("2024-01-02T10:30:00+01:00").fire_at(now),
+            Ok(at(2, 9, 30)),
+            "converted to UTC"
+        );
+        assert_eq!(
+            When::At("2023-12-31T23:00").fire_at(now),
+            Err("C'est déjà passé.".to_string())
+        );
+        assert_eq!(
+            When::At("demain").fire_at(now),
+            Err("Date invalide: demain, le format est AAAA-MM-JJTHH:MM, en UTC".to_string())
+        );
+    }
+
+    #[test]
+    async fn test_reminder_store() {
+        let conn = SqliteConnection::establish(":memory:").unwrap();
+        db::run_migrations(&conn).unwrap();
+
+        let new = |nick: &str, fire_at, message: &str| NewReminder {
+            nick: nick.to_string(),
+            channel: "#chan".to_string(),
+            fire_at,
+            message: message.to_string(),
+        };
+        add(&conn, &new("charlie", at(2, 9, 0), "plus tard")).unwrap();
+        add(&conn, &new("bob", at(1, 10, 0), "arroser les plantes")).unwrap();
+
+        assert_eq!(due(&conn, at(1, 9, 0)).unwrap(), vec![]);
+        let due_now = due(&conn, at(2, 9, 0)).unwrap();
+        assert_eq!(
+            due_now.iter().map(|r| r.nick.as_str()).collect::<Vec<_>>(),
+            vec!["bob", "charlie"],
+            "oldest first"
+        );
+
+        remove(&conn, due_now[0].id).unwrap();
+        assert_eq!(
+            due(&conn, at(2, 9, 0))
+                .unwrap()
+                .into_iter()
+                .map(|r| r.message)
+                .collect::<Vec<_>>(),
+            vec!["plus tard"]
+        );
+    }
+}
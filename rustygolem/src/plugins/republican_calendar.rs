@@ -1,8 +1,14 @@
-use crate::utils::parser;
+use crate::utils::parser::{self, command_prefix};
 use anyhow::Context;
 use async_trait::async_trait;
 use irc::proto::{Command, Message};
-use plugin_core::{Initialised, Plugin, Result};
+use nom::branch::alt;
+use nom::bytes::complete::{tag, take_while1};
+use nom::character::complete::{digit1, multispace0, multispace1};
+use nom::combinator::{all_consuming, map, map_res, opt, verify};
+use nom::sequence::{preceded, terminated, tuple};
+use nom::{Finish, IResult};
+use plugin_core::{CommandHelp, Initialised, Plugin, Result};
 
 pub struct RepublicanCalendar {}
 
@@ -16,6 +22,26 @@ impl Plugin for RepublicanCalendar {
         "date"
     }
 
+    fn commands(&self) -> Vec<CommandHelp> {
+        vec![
+            CommandHelp::new(
+                "date",
+                "[saveur]",
+                "la date du jour dans le calendrier républicain",
+            ),
+            CommandHelp::new(
+                "date",
+                "<aaaa-mm-jj>",
+                "convertit une date grégorienne en date républicaine",
+            ),
+            CommandHelp::new(
+                "date",
+                "<jour> <mois> <année>",
+                "convertit une date républicaine en date grégorienne",
+            ),
+        ]
+    }
+
     async fn in_message(&self, msg: &Message) -> Result<Option<Message>> {
         in_msg(msg).await
     }
@@ -28,8 +54,16 @@ async fn in_msg(msg: &Message) -> Result<Option<Message>> {
     };
 
     if let Command::PRIVMSG(_source, privmsg) = &msg.command {
-        if let Some(mb_target) = parser::single_command("date", privmsg) {
-            let msg = handle_command(mb_target).context("republican calendar")?;
+        if let Some((cmd, mb_target)) = parse_command(privmsg) {
+            let msg = match cmd {
+                DateCmd::Today { flavour: true } => handle_flavour_command(mb_target),
+                DateCmd::Today { flavour: false } => handle_command(mb_target),
+                DateCmd::Convert { day, month, year } => {
+                    handle_convert_command(day, month, year, mb_target)
+                }
+                DateCmd::Gregorian(raw) => handle_gregorian_command(raw, mb_target),
+            }
+            .context("republican calendar")?;
 
             return Ok(Some(
                 Command::PRIVMSG(response_target.to_string(), msg).into(),
@@ -39,6 +73,65 @@ async fn in_msg(msg: &Message) -> Result<Option<Message>> {
     Ok(None)
 }
 
+#[derive(Debug, PartialEq)]
+enum DateCmd<'input> {
+    /// today's date, with the flavour line or not
+    Today { flavour: bool },
+    /// a republican date to convert to the gregorian calendar
+    Convert {
+        day: u8,
+        month: &'input str,
+        year: i32,
+    },
+    /// a gregorian date like 2021-01-14, not validated yet
+    Gregorian(&'input str),
+}
+
+/// `λdate [saveur] [> target]`, `λdate <yyyy-mm-dd> [> target]`
+/// or `λdate <day> <month> <year> [> target]`
+fn parse_command(input: &str) -> Option<(DateCmd, Option<&str>)> {
+    let cmd = preceded(
+        command_prefix,
+        parser::with_target(preceded(
+            tag("date"),
+            alt((parse_convert, parse_gregorian, parse_today)),
+        )),
+    );
+
+    all_consuming(terminated(cmd, multispace0))(input)
+        .finish()
+        .map(|x| x.1)
+        .ok()
+}
+
+fn parse_today(input: &str) -> IResult<&str, DateCmd> {
+    map(opt(preceded(multispace1, tag("saveur"))), |f| {
+        DateCmd::Today {
+            flavour: f.is_some(),
+        }
+    })(input)
+}
+
+fn parse_gregorian(input: &str) -> IResult<&str, DateCmd> {
+    let date = verify(
+        take_while1(|c: char| c.is_ascii_digit() || c == '-'),
+        |s: &str| s.contains('-'),
+    );
+    map(preceded(multispace1, date), DateCmd::Gregorian)(input)
+}
+
+fn parse_convert(input: &str) -> IResult<&str, DateCmd> {
+    let month = take_while1(|c: char| c.is_alphabetic() || c == '-');
+    map(
+        tuple((
+            preceded(multispace1, map_res(digit1, str::parse)),
+            preceded(multispace1, month),
+            preceded(multispace1, map_res(digit1, str::parse)),
+        )),
+        |(day, month, year)| DateCmd::Convert { day, month, year },
+    )(input)
+}
+
 pub(crate) fn handle_command(mb_target: Option<&str>) -> Option<String> {
     let now = time::OffsetDateTime::now_utc().date();
     let msg = match republican_calendar::RepublicanDate::try_from(now) {
@@ -50,3 +143,193 @@ pub(crate) fn handle_command(mb_target: Option<&str>) -> Option<String> {
     };
     Some(msg)
 }
+
+fn handle_flavour_command(mb_target: Option<&str>) -> Option<String> {
+    let now = time::OffsetDateTime::now_utc().date();
+    let msg = match republican_calendar::RepublicanDate::try_from(now) {
+        Ok(rd) => crate::utils::messages::with_target(
+            &add_flavour(
+                format!("Nous sommes aujourd'hui le {}", rd),
+                rd.day_symbol(),
+            ),
+            &mb_target,
+        ),
+        Err(err) => err.to_string(),
+    };
+    Some(msg)
+}
+
+fn handle_gregorian_command(raw: &str, mb_target: Option<&str>) -> Option<String> {
+    let format = time::macros::format_description!("[year]-[month]-[day]");
+    let msg = match time::Date::parse(raw, &format) {
+        Ok(date) => match republican_calendar::RepublicanDate::try_from(date) {
+            Ok(rd) => crate::utils::messages::with_target(
+                &format!("Le {raw} correspond au {rd}"),
+                &mb_target,
+            ),
+            Err(err) => err.to_string(),
+        },
+        Err(_) => format!("Date invalide: {raw}, le format est AAAA-MM-JJ"),
+    };
+    Some(msg)
+}
+
+fn handle_convert_command(
+    day: u8,
+    month: &str,
+    year: i32,
+    mb_target: Option<&str>,
+) -> Option<String> {
+    let msg = match republican_calendar::RepublicanDate::new(year, month, day)
+        .and_then(|rd| rd.to_gregorian())
+    {
+        Ok(date) => crate::utils::messages::with_target(
+            &format!(
+                "Le {day} {month} {year} correspond au {:02}/{:02}/{}",
+                date.day(),
+                date.month() as u8,
+                date.year(),
+            ),
+            &mb_target,
+        ),
+        Err(err) => err.to_string(),
+    };
+    Some(msg)
+}
+
+/// Append the flavour line for the day's symbol, if there is one
+fn add_flavour(reply: String, day_symbol: &str) -> String {
+    match flavour(day_symbol) {
+        Some(line) => format!("{reply} − {day_symbol} → {line}"),
+        None => reply,
+    }
+}
+
+/// A short comment about some of the symbols of the days
+fn flavour(day_symbol: &str) -> Option<&'static str> {
+    let line = match day_symbol {
+        "du raisin" => "c'est la saison des vendanges",
+        "de la châtaigne" => "sortez les poêles trouées",
+        "du cheval" => "en selle !",
+        "de la pomme de terre" => "Parmentier serait fier",
+        "du potiron" | "de la citrouille" => "soupe ce soir",
+        "du pressoir" => "le vin nouveau arrive",
+        "de la cuve" => "que la fermentation commence",
+        "du tournesol" => "toujours tourné vers le soleil",
+        "de l'âne" => "pas de quoi braire",
+        _ => return None,
+    };
+    Some(line)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    async fn test_parse_command() {
+        let today = DateCmd::Today { flavour: false };
+        let flavour = DateCmd::Today { flavour: true };
+        assert_eq!(parse_command("λdate"), Some((today, None)));
+        assert_eq!(parse_command("λdate saveur"), Some((flavour, None)));
+        assert_eq!(
+            parse_command("&date saveur > charlie"),
+            Some((DateCmd::Today { flavour: true }, Some("charlie")))
+        );
+        assert_eq!(
+            parse_command("λdate > charlie"),
+            Some((DateCmd::Today { flavour: false }, Some("charlie")))
+        );
+        assert_eq!(parse_command("λdatesaveur"), None);
+    }
+
+    #[test]
+    async fn test_parse_convert_command() {
+        assert_eq!(
+            parse_command("λdate 25 Nivôse 229"),
+            Some((
+                DateCmd::Convert {
+                    day: 25,
+                    month: "Nivôse",
+                    year: 229
+                },
+                None
+            ))
+        );
+        assert_eq!(
+            parse_command("λdate 3 Sans-Culottides 230 > charlie"),
+            Some((
+                DateCmd::Convert {
+                    day: 3,
+                    month: "Sans-Culottides",
+                    year: 230
+                },
+                Some("charlie")
+            ))
+        );
+        assert_eq!(parse_command("λdate 25 Nivôse"), None, "need a year");
+    }
+
+    #[test]
+    async fn test_parse_gregorian_command() {
+        assert_eq!(
+            parse_command("λdate 2021-01-14"),
+            Some((DateCmd::Gregorian("2021-01-14"), None))
+        );
+        assert_eq!(
+            parse_command("λdate 2021-13-45 > charlie"),
+            Some((DateCmd::Gregorian("2021-13-45"), Some("charlie"))),
+            "validated later to give a proper error"
+        );
+        assert_eq!(parse_command("λdate 2021"), None);
+    }
+
+    #[test]
+    async fn test_gregorian() {
+        assert_eq!(
+            handle_gregorian_command("2021-01-14", Some("charlie")),
+            Some(
+                "charlie: Le 2021-01-14 correspond au 25 Nivôse 229 − jour du chat − et c'est un Quintidi"
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            handle_gregorian_command("2021-13-45", None),
+            Some("Date invalide: 2021-13-45, le format est AAAA-MM-JJ".to_string())
+        );
+        assert_eq!(
+            handle_gregorian_command("1789-07-14", None),
+            Some("Can only convert date from after the official end of the calendar".to_string())
+        );
+    }
+
+    #[test]
+    async fn test_convert() {
+        assert_eq!(
+            handle_convert_command(25, "Nivôse", 229, None),
+            Some("Le 25 Nivôse 229 correspond au 14/01/2021".to_string())
+        );
+        assert_eq!(
+            handle_convert_command(25, "Décembre", 229, None),
+            Some("Mois inconnu".to_string())
+        );
+    }
+
+    #[test]
+    async fn test_symbol_with_flavour() {
+        assert_eq!(
+            add_flavour("Nous sommes aujourd'hui le …".to_string(), "du raisin"),
+            "Nous sommes aujourd'hui le … − du raisin → c'est la saison des vendanges"
+        );
+    }
+
+    #[test]
+    async fn test_symbol_without_flavour() {
+        assert_eq!(
+            add_flavour("Nous sommes aujourd'hui le …".to_string(), "du safran"),
+            "Nous sommes aujourd'hui le …",
+            "base reply unchanged"
+        );
+    }
+}
@@ -0,0 +1,279 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::utils::parser::{self, command_prefix};
+use async_trait::async_trait;
+use irc::proto::{Command, Message};
+use nom::branch::alt;
+use nom::bytes::complete::tag;
+use nom::character::complete::{char, multispace0, multispace1, one_of, u32 as integer};
+use nom::combinator::{all_consuming, map, opt};
+use nom::multi::many0;
+use nom::sequence::{delimited, pair, preceded, separated_pair, terminated, tuple};
+use nom::{Finish, IResult};
+use plugin_core::{CommandHelp, Initialised, Plugin, Result};
+
+/// for all the terms of a roll
+const MAX_DICE: u32 = 100;
+const MAX_SIDES: u32 = 1000;
+const MAX_MODIFIER: u32 = 1_000_000;
+
+pub struct Roll {}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Term {
+    /// `2d6`
+    Dice { count: u32, sides: u32 },
+    /// `3`
+    Constant(u32),
+}
+
+/// A term with its sign, `-2` is `(-1, Constant(2))`
+type SignedTerm = (i64, Term);
+
+#[async_trait]
+impl Plugin for Roll {
+    async fn init(_config: &plugin_core::Config) -> Result<Initialised> {
+        Ok(Initialised::from(Roll {}))
+    }
+
+    fn get_name(&self) -> &'static str {
+        "roll"
+    }
+
+    fn commands(&self) -> Vec<CommandHelp> {
+        vec![CommandHelp::new(
+            "roll",
+            "<dés, comme 2d6+3 ou d20 + 1d4>",
+            "lance les dés",
+        )]
+    }
+
+    async fn in_message(&self, msg: &Message) -> Result<Option<Message>> {
+        Ok(in_msg(msg))
+    }
+}
+
+fn in_msg(msg: &Message) -> Option<Message> {
+    let privmsg = match &msg.command {
+        Command::PRIVMSG(_source, privmsg) => privmsg,
+        _ => return None,
+    };
+    let (terms, mb_target) = parse_command(privmsg)?;
+    let response_target = msg.response_target()?;
+    let mb_target = mb_target.or_else(|| msg.source_nickname());
+
+    let reply = match validate(&terms) {
+        Err(err) => err,
+        Ok(()) => {
+            let mut rng = SplitMix64::from_time();
+            let (total, rolls) = evaluate(&terms, |sides| rng.roll(sides));
+            format_roll(total, &rolls)
+        }
+    };
+    let reply = crate::utils::messages::with_target(&reply, &mb_target);
+    Some(Command::PRIVMSG(response_target.to_string(), reply).into())
+}
+
+/// The error to reply when the roll goes over the limits
+fn validate(terms: &[SignedTerm]) -> std::result::Result<(), String> {
+    let mut dice = 0;
+    for (_, term) in terms {
+        match *term {
+            Term::Dice { count, sides } => {
+                if sides == 0 || sides > MAX_SIDES {
+                    return Err(format!("Les dés ont entre 1 et {} faces", MAX_SIDES));
+                }
+                dice = dice.saturating_add(count);
+            }
+            Term::Constant(k) if k > MAX_MODIFIER => {
+                return Err(format!("Pas de bonus au-delà de {}", MAX_MODIFIER));
+            }
+            Term::Constant(_) => {}
+        }
+        if dice > MAX_DICE {
+            return Err(format!("Pas plus de {} dés à la fois", MAX_DICE));
+        }
+    }
+    if dice == 0 {
+        return Err("Il faut au moins un dé".to_string());
+    }
+    Ok(())
+}
+
+/// Returns the total and, for each group of dice, its notation and the dice rolled.
+/// `roll` gives a value between 1 and the number of sides.
+fn evaluate<F>(terms: &[SignedTerm], mut roll: F) -> (i64, Vec<(String, Vec<u32>)>)
+where
+    F: FnMut(u32) -> u32,
+{
+    let mut total = 0;
+    let mut rolls = vec![];
+    for (sign, term) in terms {
+        match *term {
+            Term::Dice { count, sides } => {
+                let dice = (0..count).map(|_| roll(sides)).collect::<Vec<_>>();
+                total += sign * dice.iter().map(|&d| d as i64).sum::<i64>();
+                rolls.push((format!("{}d{}", count, sides), dice));
+            }
+            Term::Constant(k) => total += sign * k as i64,
+        }
+    }
+    (total, rolls)
+}
+
+/// `12 (2d6: 4, 5)`, or `15 (2d6: 4, 5; 1d4: 3)` with several groups of dice
+fn format_roll(total: i64, rolls: &[(String, Vec<u32>)]) -> String {
+    let details = rolls
+        .iter()
+        .map(|(notation, dice)| {
+            let dice = dice.iter().map(|d| d.to_string()).collect::<Vec<_>>();
+            format!("{}: {}", notation, dice.join(", "))
+        })
+        .collect::<Vec<_>>();
+    format!("{} ({})", total, details.join("; "))
+}
+
+/// Good enough to roll dice, and doesn't need a dependency.
+/// https://prng.di.unimi.it/splitmix64.c
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn from_time() -> Self {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or_default();
+        SplitMix64(nanos)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    /// between 1 and `sides`
+    fn roll(&mut self, sides: u32) -> u32 {
+        1 + (self.next_u64() % sides as u64) as u32
+    }
+}
+
+/// `λroll 2d6+3 [> target]`
+fn parse_command(input: &str) -> Option<(Vec<SignedTerm>, Option<&str>)> {
+    let cmd = preceded(
+        tuple((command_prefix, tag("roll"), multispace1)),
+        parser::with_target(dice_expr),
+    );
+    all_consuming(terminated(cmd, multispace0))(input)
+        .finish()
+        .map(|x| x.1)
+        .ok()
+}
+
+/// `2d6 + d4 - 1`, the first term can't be negative
+fn dice_expr(input: &str) -> IResult<&str, Vec<SignedTerm>> {
+    let sign = map(one_of("+-"), |c| if c == '-' { -1 } else { 1 });
+    map(
+        pair(
+            preceded(opt(char('+')), term),
+            many0(pair(delimited(multispace0, sign, multispace0), term)),
+        ),
+        |(first, mut rest)| {
+            rest.insert(0, (1, first));
+            rest
+        },
+    )(input)
+}
+
+fn term(input: &str) -> IResult<&str, Term> {
+    alt((
+        map(
+            separated_pair(opt(integer), one_of("dD"), integer),
+            |(count, sides)| Term::Dice {
+                count: count.unwrap_or(1),
+                sides,
+            },
+        ),
+        map(integer, Term::Constant),
+    ))(input)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn dice(count: u32, sides: u32) -> Term {
+        Term::Dice { count, sides }
+    }
+
+    #[test]
+    async fn test_parse_command() {
+        assert_eq!(
+            parse_command("λroll 2d6+3"),
+            Some((vec![(1, dice(2, 6)), (1, Term::Constant(3))], None))
+        );
+        assert_eq!(
+            parse_command("λroll d20 + 1D4 - 2 > charlie"),
+            Some((
+                vec![(1, dice(1, 20)), (1, dice(1, 4)), (-1, Term::Constant(2))],
+                Some("charlie")
+            ))
+        );
+        assert_eq!(parse_command("λroll"), None);
+        assert_eq!(parse_command("λroll 2d"), None);
+        assert_eq!(parse_command("λroll 2d6 *3"), None);
+        assert_eq!(parse_command("λroll -1d6"), None);
+    }
+
+    #[test]
+    async fn test_validate() {
+        assert_eq!(validate(&[(1, dice(100, 1000))]), Ok(()));
+        assert_eq!(
+            validate(&[(1, dice(60, 6)), (1, dice(50, 6))]),
+            Err("Pas plus de 100 dés à la fois".to_string()),
+            "the limit is for all the terms"
+        );
+        assert_eq!(
+            validate(&[(1, dice(1, 1001))]),
+            Err("Les dés ont entre 1 et 1000 faces".to_string())
+        );
+        assert_eq!(
+            validate(&[(1, dice(1, 0))]),
+            Err("Les dés ont entre 1 et 1000 faces".to_string())
+        );
+        assert_eq!(
+            validate(&[(1, Term::Constant(3))]),
+            Err("Il faut au moins un dé".to_string())
+        );
+    }
+
+    #[test]
+    async fn test_evaluate() {
+        let terms = [(1, dice(2, 6)), (-1, dice(1, 4)), (1, Term::Constant(3))];
+        let mut values = vec![4, 5, 2].into_iter();
+        let (total, rolls) = evaluate(&terms, |_| values.next().unwrap());
+        assert_eq!(total, 4 + 5 - 2 + 3);
+        assert_eq!(
+            rolls,
+            vec![
+                ("2d6".to_string(), vec![4, 5]),
+                ("1d4".to_string(), vec![2])
+            ]
+        );
+        assert_eq!(format_roll(total, &rolls), "10 (2d6: 4, 5; 1d4: 2)");
+    }
+
+    #[test]
+    async fn test_rng_range() {
+        let mut rng = SplitMix64(42);
+        for sides in [1, 6, 20, 1000] {
+            for _ in 0..100 {
+                let d = rng.roll(sides);
+                assert!((1..=sides).contains(&d), "{} not in 1..={}", d, sides);
+            }
+        }
+    }
+}
@@ -0,0 +1,178 @@
+use crate::utils::parser::{command_prefix, target};
+use async_trait::async_trait;
+use irc::proto::{Command, Message};
+use nom::branch::alt;
+use nom::bytes::complete::{tag, take_till1};
+use nom::character::complete::{multispace0, multispace1};
+use nom::combinator::{all_consuming, map, opt};
+use nom::sequence::{preceded, terminated, tuple};
+use nom::{Finish, IResult};
+use plugin_core::config::ConfigSection;
+use plugin_core::{CommandHelp, Error, Initialised, Plugin, Result};
+use reqwest::Url;
+use serde::Deserialize;
+
+const DEFAULT_SEARCH_ENGINE_URL: &str = "https://duckduckgo.com/";
+const DEFAULT_LMGTFY_URL: &str = "https://letmegooglethat.com/";
+
+#[derive(Deserialize)]
+struct SearchConfig {
+    /// base url of the search engine, the query is passed as the `q` parameter
+    search_engine_url: Option<String>,
+    /// same as above, for λlmgtfy
+    lmgtfy_url: Option<String>,
+}
+
+impl ConfigSection for SearchConfig {
+    const SECTION: Option<&'static str> = None;
+    const SCHEMA: &'static str =
+        "{ search_engine_url : Optional Text, lmgtfy_url : Optional Text }";
+}
+
+pub struct Search {
+    search_engine_url: String,
+    lmgtfy_url: String,
+}
+
+#[async_trait]
+impl Plugin for Search {
+    async fn init(config: &plugin_core::Config) -> Result<Initialised> {
+        let search_config: SearchConfig = plugin_core::config::load(&config.config_path)?;
+
+        let search_engine_url = search_config
+            .search_engine_url
+            .unwrap_or_else(|| DEFAULT_SEARCH_ENGINE_URL.to_string());
+        let lmgtfy_url = search_config
+            .lmgtfy_url
+            .unwrap_or_else(|| DEFAULT_LMGTFY_URL.to_string());
+
+        // fail early on invalid urls
+        for base in [&search_engine_url, &lmgtfy_url] {
+            Url::parse(base).map_err(|err| Error::Wrapped {
+                source: Box::new(err),
+                ctx: format!("Invalid search url {base}"),
+            })?;
+        }
+
+        Ok(Initialised::from(Search {
+            search_engine_url,
+            lmgtfy_url,
+        }))
+    }
+
+    fn get_name(&self) -> &'static str {
+        "search"
+    }
+
+    fn commands(&self) -> Vec<CommandHelp> {
+        vec![
+            CommandHelp::new("g", "<recherche>", "lien vers une recherche"),
+            CommandHelp::new("lmgtfy", "<recherche>", "laisse moi chercher ça pour toi"),
+        ]
+    }
+
+    async fn in_message(&self, msg: &Message) -> Result<Option<Message>> {
+        self.in_msg(msg)
+    }
+}
+
+impl Search {
+    fn in_msg(&self, msg: &Message) -> Result<Option<Message>> {
+        let response_target = match msg.response_target() {
+            None => return Ok(None),
+            Some(target) => target,
+        };
+
+        if let Command::PRIVMSG(_source, privmsg) = &msg.command {
+            if let Some((engine, query, mb_target)) = parse_command(privmsg) {
+                let base = match engine {
+                    Engine::Search => &self.search_engine_url,
+                    Engine::Lmgtfy => &self.lmgtfy_url,
+                };
+                let url = search_url(base, query)?;
+                let msg = crate::utils::messages::with_target(url.as_str(), &mb_target);
+                return Ok(Some(
+                    Command::PRIVMSG(response_target.to_string(), msg).into(),
+                ));
+            }
+        }
+        Ok(None)
+    }
+}
+
+fn search_url(base: &str, query: &str) -> Result<Url> {
+    Url::parse_with_params(base, &[("q", query)]).map_err(|err| Error::Wrapped {
+        source: Box::new(err),
+        ctx: format!("Cannot build search url from {base}"),
+    })
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Engine {
+    Search,
+    Lmgtfy,
+}
+
+/// returns Option<(engine, query, optional_target_nick)>
+fn parse_command(input: &str) -> Option<(Engine, &str, Option<&str>)> {
+    all_consuming(terminated(parse_search, multispace0))(input)
+        .finish()
+        .map(|x| x.1)
+        .ok()
+}
+
+fn parse_search(input: &str) -> IResult<&str, (Engine, &str, Option<&str>)> {
+    preceded(
+        command_prefix,
+        map(
+            tuple((
+                alt((
+                    map(tag("lmgtfy"), |_| Engine::Lmgtfy),
+                    map(tag("g"), |_| Engine::Search),
+                )),
+                multispace1,
+                take_till1(|c| c == '>'),
+                opt(target),
+            )),
+            |(engine, _, query, mb_target)| (engine, query.trim(), mb_target),
+        ),
+    )(input)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    async fn test_parse_command() {
+        assert_eq!(
+            parse_command("λg rust nom parser"),
+            Some((Engine::Search, "rust nom parser", None))
+        );
+        assert_eq!(
+            parse_command("λlmgtfy how to irc > charlie"),
+            Some((Engine::Lmgtfy, "how to irc", Some("charlie")))
+        );
+        assert_eq!(parse_command("λg"), None, "need a query");
+        assert_eq!(parse_command("λg > charlie"), None, "need a query");
+        assert_eq!(parse_command("λgoogle coucou"), None);
+    }
+
+    #[test]
+    async fn test_search_url_encoding() {
+        assert_eq!(
+            search_url(DEFAULT_SEARCH_ENGINE_URL, "rust nom")
+                .unwrap()
+                .as_str(),
+            "https://duckduckgo.com/?q=rust+nom"
+        );
+
+        assert_eq!(
+            search_url(DEFAULT_LMGTFY_URL, "c'est quoi & pourquoi? 100%#é")
+                .unwrap()
+                .as_str(),
+            "https://letmegooglethat.com/?q=c%27est+quoi+%26+pourquoi%3F+100%25%23%C3%A9"
+        );
+    }
+}
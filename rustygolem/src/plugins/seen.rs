@@ -0,0 +1,208 @@
+use crate::db;
+use crate::schema::seen::{self, dsl};
+use crate::utils::messages::format_ago;
+use crate::utils::parser::command_prefix;
+use anyhow::Context;
+use async_trait::async_trait;
+use chrono::{NaiveDateTime, Utc};
+use diesel::prelude::*;
+use irc::proto::{Command, Message};
+use nom::bytes::complete::{tag, take_while1};
+use nom::character::complete::{multispace0, multispace1};
+use nom::combinator::all_consuming;
+use nom::sequence::{delimited, tuple};
+use nom::Finish;
+use plugin_core::utils::network::channel_key;
+use plugin_core::{CommandHelp, Initialised, Plugin, Result};
+
+/// longer messages are truncated before being stored
+const MAX_MESSAGE_CHARS: usize = 200;
+
+/// Records when each nick last spoke in a channel.
+/// Blacklisted users (other bots) are ignored like everywhere else,
+/// so they are never seen.
+pub struct Seen {}
+
+#[derive(Debug, PartialEq, Queryable, Insertable)]
+#[table_name = "seen"]
+struct LastSeen {
+    /// see `channel_key`
+    channel: String,
+    /// lowercased, nicks are case insensitive
+    nick: String,
+    last_seen: NaiveDateTime,
+    message: String,
+}
+
+impl LastSeen {
+    fn new(channel: &str, nick: &str, last_seen: NaiveDateTime, message: &str) -> Self {
+        let message = if message.chars().count() > MAX_MESSAGE_CHARS {
+            let mut truncated = message
+                .chars()
+                .take(MAX_MESSAGE_CHARS - 1)
+                .collect::<String>();
+            truncated.push('…');
+            truncated
+        } else {
+            message.to_string()
+        };
+        LastSeen {
+            channel: channel.to_string(),
+            nick: nick.to_lowercase(),
+            last_seen,
+            message,
+        }
+    }
+}
+
+#[async_trait]
+impl Plugin for Seen {
+    async fn init(_config: &plugin_core::Config) -> Result<Initialised> {
+        Ok(Initialised::from(Seen {}))
+    }
+
+    fn get_name(&self) -> &'static str {
+        "seen"
+    }
+
+    fn commands(&self) -> Vec<CommandHelp> {
+        vec![CommandHelp::new(
+            "seen",
+            "<nick>",
+            "quand nick a parlé pour la dernière fois dans le channel",
+        )]
+    }
+
+    async fn in_message(&self, msg: &Message) -> Result<Option<Message>> {
+        self.in_msg(msg).await
+    }
+}
+
+impl Seen {
+    async fn in_msg(&self, msg: &Message) -> Result<Option<Message>> {
+        let (target, text) = match &msg.command {
+            Command::PRIVMSG(target, text) => (target, text),
+            _ => return Ok(None),
+        };
+        // private messages to the bot stay private
+        if !target.starts_with('#') {
+            return Ok(None);
+        }
+        let source = match msg.source_nickname() {
+            Some(source) => source,
+            None => return Ok(None),
+        };
+
+        let now = Utc::now().naive_utc();
+        let query = parse_command(text).map(|nick| nick.to_string());
+        let channel = channel_key(msg, target);
+        let row = LastSeen::new(&channel, source, now, text);
+        // look up before recording, so `λseen` about oneself
+        // doesn't answer with the command itself
+        let found = db::with_connection(move |conn| {
+            let found = match &query {
+                Some(nick) => Some((nick.clone(), last_seen(conn, &channel, nick)?)),
+                None => None,
+            };
+            record(conn, &row)?;
+            Ok::<_, anyhow::Error>(found)
+        })
+        .await
+        .context("Cannot update last seen")?;
+
+        Ok(found.map(|(nick, seen)| {
+            let reply = match seen {
+                None => format!("{} n'a jamais été vu ici", nick),
+                Some(seen) => format!(
+                    "{} a été vu pour la dernière fois il y a {}, en disant: {}",
+                    nick,
+                    format_ago(now - seen.last_seen),
+                    seen.message
+                ),
+            };
+            Command::PRIVMSG(target.clone(), reply).into()
+        }))
+    }
+}
+
+fn record(conn: &SqliteConnection, row: &LastSeen) -> anyhow::Result<()> {
+    diesel::replace_into(seen::table)
+        .values(row)
+        .execute(conn)
+        .with_context(|| format!("Cannot save last seen {:?}", row))?;
+    Ok(())
+}
+
+fn last_seen(
+    conn: &SqliteConnection,
+    channel: &str,
+    nick: &str,
+) -> anyhow::Result<Option<LastSeen>> {
+    dsl::seen
+        .filter(dsl::channel.eq(channel))
+        .filter(dsl::nick.eq(nick.to_lowercase()))
+        .first::<LastSeen>(conn)
+        .optional()
+        .with_context(|| format!("Cannot load last seen for {} in {}", nick, channel))
+}
+
+/// `λseen <nick>`
+fn parse_command(input: &str) -> Option<&str> {
+    all_consuming(delimited(
+        tuple((command_prefix, tag("seen"), multispace1)),
+        take_while1(|c: char| !c.is_whitespace()),
+        multispace0,
+    ))(input)
+    .finish()
+    .map(|x| x.1)
+    .ok()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    async fn test_parse_command() {
+        assert_eq!(parse_command("λseen charlie"), Some("charlie"));
+        assert_eq!(parse_command("λseen charlie_[m] "), Some("charlie_[m]"));
+        assert_eq!(parse_command("λseen"), None);
+        assert_eq!(parse_command("λseen charlie bob"), None);
+    }
+
+    #[test]
+    async fn test_truncate_message() {
+        let now = Utc::now().naive_utc();
+        let long = "a".repeat(MAX_MESSAGE_CHARS + 10);
+        let row = LastSeen::new("#chan", "Charlie", now, &long);
+        assert_eq!(row.nick, "charlie");
+        assert_eq!(row.message.chars().count(), MAX_MESSAGE_CHARS);
+        assert!(row.message.ends_with('…'));
+
+        let exact = "é".repeat(MAX_MESSAGE_CHARS);
+        assert_eq!(
+            LastSeen::new("#chan", "charlie", now, &exact).message,
+            exact
+        );
+    }
+
+    #[test]
+    async fn test_seen_store() {
+        let conn = SqliteConnection::establish(":memory:").unwrap();
+        db::run_migrations(&conn).unwrap();
+
+        let t0 = chrono::NaiveDate::from_ymd(2024, 1, 1).and_hms(9, 0, 0);
+        let t1 = chrono::NaiveDate::from_ymd(2024, 1, 1).and_hms(10, 0, 0);
+        record(&conn, &LastSeen::new("#chan", "charlie", t0, "coucou")).unwrap();
+        record(&conn, &LastSeen::new("#chan", "Charlie", t1, "re")).unwrap();
+        record(&conn, &LastSeen::new("#other", "charlie", t0, "ailleurs")).unwrap();
+
+        assert_eq!(
+            last_seen(&conn, "#chan", "CHARLIE").unwrap(),
+            Some(LastSeen::new("#chan", "charlie", t1, "re")),
+            "only the last message is kept, whatever the case of the nick"
+        );
+        assert_eq!(last_seen(&conn, "#chan", "bob").unwrap(), None);
+    }
+}
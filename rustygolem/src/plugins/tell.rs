@@ -0,0 +1,276 @@
+use crate::db;
+use crate::schema::memos::{self, dsl};
+use crate::utils::messages::format_ago;
+use crate::utils::parser::command_prefix;
+use anyhow::Context;
+use async_trait::async_trait;
+use chrono::{NaiveDateTime, Utc};
+use diesel::prelude::*;
+use irc::proto::{Command, Message};
+use nom::bytes::complete::{tag, take_while1};
+use nom::character::complete::multispace1;
+use nom::combinator::{all_consuming, map, rest, verify};
+use nom::sequence::{preceded, separated_pair, tuple};
+use nom::Finish;
+use plugin_core::utils::network::channel_key;
+use plugin_core::{CommandHelp, Initialised, Plugin, Result};
+
+/// so nobody can flood someone with memos
+const MAX_PENDING_MEMOS: i64 = 5;
+
+/// Memos left for people who aren't around, delivered the next time
+/// they speak in the channel.
+pub struct Tell {}
+
+#[derive(Debug, PartialEq, Queryable)]
+struct Memo {
+    id: i32,
+    channel: String,
+    /// lowercased, nicks are case insensitive
+    recipient: String,
+    sender: String,
+    sent_at: NaiveDateTime,
+    message: String,
+}
+
+#[derive(Debug, Insertable)]
+#[table_name = "memos"]
+struct NewMemo {
+    /// see `channel_key`
+    channel: String,
+    recipient: String,
+    sender: String,
+    sent_at: NaiveDateTime,
+    message: String,
+}
+
+impl NewMemo {
+    fn new(
+        channel: &str,
+        recipient: &str,
+        sender: &str,
+        sent_at: NaiveDateTime,
+        message: &str,
+    ) -> Self {
+        NewMemo {
+            channel: channel.to_string(),
+            recipient: recipient.to_lowercase(),
+            sender: sender.to_string(),
+            sent_at,
+            message: message.to_string(),
+        }
+    }
+}
+
+#[async_trait]
+impl Plugin for Tell {
+    async fn init(_config: &plugin_core::Config) -> Result<Initialised> {
+        Ok(Initialised::from(Tell {}))
+    }
+
+    fn get_name(&self) -> &'static str {
+        "tell"
+    }
+
+    fn commands(&self) -> Vec<CommandHelp> {
+        vec![CommandHelp::new(
+            "tell",
+            "<nick> <message>",
+            "transmet le message à nick la prochaine fois qu'il parle dans le channel",
+        )]
+    }
+
+    async fn in_message(&self, msg: &Message) -> Result<Option<Message>> {
+        self.in_msg(msg).await
+    }
+}
+
+impl Tell {
+    async fn in_msg(&self, msg: &Message) -> Result<Option<Message>> {
+        let (target, text) = match &msg.command {
+            Command::PRIVMSG(target, text) => (target, text),
+            _ => return Ok(None),
+        };
+        if !target.starts_with('#') {
+            return Ok(None);
+        }
+        let source = match msg.source_nickname() {
+            Some(source) => source.to_string(),
+            None => return Ok(None),
+        };
+
+        let now = Utc::now().naive_utc();
+        let channel = channel_key(msg, target);
+        let reply = match parse_command(text) {
+            Some((recipient, message)) => {
+                let memo = NewMemo::new(&channel, recipient, &source, now, message);
+                let recipient = recipient.to_string();
+                let saved = db::with_connection(move |conn| add(conn, &memo))
+                    .await
+                    .context("Cannot save memo")?;
+                Some(if saved {
+                    format!("{}: ok, je transmettrai à {}", source, recipient)
+                } else {
+                    format!(
+                        "{}: {} a déjà trop de messages en attente",
+                        source, recipient
+                    )
+                })
+            }
+            None => {
+                let nick = source.clone();
+                let memos = db::with_connection(move |conn| take_pending(conn, &channel, &nick))
+                    .await
+                    .context("Cannot deliver memos")?;
+                format_memos(&source, &memos, now)
+            }
+        };
+
+        Ok(reply.map(|reply| Command::PRIVMSG(target.clone(), reply).into()))
+    }
+}
+
+/// Returns false without saving when the recipient has too many pending memos
+fn add(conn: &SqliteConnection, memo: &NewMemo) -> anyhow::Result<bool> {
+    let pending = dsl::memos
+        .filter(dsl::channel.eq(&memo.channel))
+        .filter(dsl::recipient.eq(&memo.recipient))
+        .count()
+        .get_result::<i64>(conn)
+        .with_context(|| format!("Cannot count memos for {}", memo.recipient))?;
+    if pending >= MAX_PENDING_MEMOS {
+        return Ok(false);
+    }
+    diesel::insert_into(memos::table)
+        .values(memo)
+        .execute(conn)
+        .with_context(|| format!("Cannot save memo {:?}", memo))?;
+    Ok(true)
+}
+
+/// Remove and return the memos left for `nick` in `channel`, oldest first
+fn take_pending(conn: &SqliteConnection, channel: &str, nick: &str) -> anyhow::Result<Vec<Memo>> {
+    conn.transaction::<_, diesel::result::Error, _>(|| {
+        let pending = dsl::memos
+            .filter(dsl::channel.eq(channel))
+            .filter(dsl::recipient.eq(nick.to_lowercase()))
+            .order_by(dsl::id)
+            .load::<Memo>(conn)?;
+        let ids = pending.iter().map(|m| m.id).collect::<Vec<_>>();
+        diesel::delete(dsl::memos.filter(dsl::id.eq_any(ids))).execute(conn)?;
+        Ok(pending)
+    })
+    .with_context(|| format!("Cannot take memos for {} in {}", nick, channel))
+}
+
+/// All the memos for `nick` in a single message, `None` when there are none
+fn format_memos(nick: &str, memos: &[Memo], now: NaiveDateTime) -> Option<String> {
+    if memos.is_empty() {
+        return None;
+    }
+    let memos = memos
+        .iter()
+        .map(|m| {
+            format!(
+                "{} (il y a {}): {}",
+                m.sender,
+                format_ago(now - m.sent_at),
+                m.message
+            )
+        })
+        .collect::<Vec<_>>();
+    Some(format!("{}: {}", nick, memos.join(" | ")))
+}
+
+/// `λtell <nick> <message>`
+fn parse_command(input: &str) -> Option<(&str, &str)> {
+    all_consuming(preceded(
+        tuple((command_prefix, tag("tell"), multispace1)),
+        separated_pair(
+            take_while1(|c: char| !c.is_whitespace()),
+            multispace1,
+            map(verify(rest, |s: &str| !s.trim().is_empty()), str::trim_end),
+        ),
+    ))(input)
+    .finish()
+    .map(|x| x.1)
+    .ok()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn at(hour: u32) -> NaiveDateTime {
+        chrono::NaiveDate::from_ymd(2024, 1, 1).and_hms(hour, 0, 0)
+    }
+
+    #[test]
+    async fn test_parse_command() {
+        assert_eq!(
+            parse_command("λtell charlie you forgot the config "),
+            Some(("charlie", "you forgot the config"))
+        );
+        assert_eq!(parse_command("λtell charlie"), None, "needs a message");
+        assert_eq!(parse_command("λtell charlie   "), None, "needs a message");
+    }
+
+    #[test]
+    async fn test_memo_store() {
+        let conn = SqliteConnection::establish(":memory:").unwrap();
+        db::run_migrations(&conn).unwrap();
+
+        let memo = |recipient: &str, message: &str| {
+            NewMemo::new("#chan", recipient, "bob", at(9), message)
+        };
+        for i in 0..MAX_PENDING_MEMOS {
+            assert!(add(&conn, &memo("Charlie", &format!("memo {}", i))).unwrap());
+        }
+        assert!(
+            !add(&conn, &memo("charlie", "one too many")).unwrap(),
+            "capped per recipient"
+        );
+        assert!(add(&conn, &memo("alice", "coucou")).unwrap());
+
+        assert_eq!(take_pending(&conn, "#other", "charlie").unwrap(), vec![]);
+        let memos = take_pending(&conn, "#chan", "CHARLIE").unwrap();
+        assert_eq!(
+            memos.iter().map(|m| m.message.as_str()).collect::<Vec<_>>(),
+            vec!["memo 0", "memo 1", "memo 2", "memo 3", "memo 4"]
+        );
+        assert_eq!(
+            take_pending(&conn, "#chan", "charlie").unwrap(),
+            vec![],
+            "memos are only delivered once"
+        );
+        assert_eq!(take_pending(&conn, "#chan", "alice").unwrap().len(), 1);
+    }
+
+    #[test]
+    async fn test_format_memos() {
+        let memo = |id, sender: &str, hour, message: &str| Memo {
+            id,
+            channel: "#chan".to_string(),
+            recipient: "charlie".to_string(),
+            sender: sender.to_string(),
+            sent_at: at(hour),
+            message: message.to_string(),
+        };
+        assert_eq!(format_memos("Charlie", &[], at(12)), None);
+        assert_eq!(
+            format_memos(
+                "Charlie",
+                &[
+                    memo(1, "bob", 9, "you forgot the config"),
+                    memo(2, "alice", 11, "coucou")
+                ],
+                at(12)
+            ),
+            Some(
+                "Charlie: bob (il y a 3h): you forgot the config | alice (il y a 1h): coucou"
+                    .to_string()
+            )
+        );
+    }
+}
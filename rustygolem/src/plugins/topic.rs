@@ -0,0 +1,218 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use crate::utils::parser::command_prefix;
+use async_trait::async_trait;
+use irc::proto::{Command, Message, Response};
+use nom::bytes::complete::tag;
+use nom::character::complete::{multispace0, multispace1};
+use nom::combinator::{all_consuming, map, opt, rest};
+use nom::sequence::{preceded, terminated, tuple};
+use nom::{Finish, IResult};
+use plugin_core::utils::network::channel_key;
+use plugin_core::utils::owners::Owners;
+use plugin_core::{CommandHelp, Initialised, Plugin, Result};
+
+pub struct Topic {
+    owners: Owners,
+    /// current topic for each channel key, as reported by the server
+    topics: Mutex<HashMap<String, String>>,
+}
+
+#[async_trait]
+impl Plugin for Topic {
+    async fn init(config: &plugin_core::Config) -> Result<Initialised> {
+        Ok(Initialised::from(Topic {
+            owners: config.owners.clone(),
+            topics: Default::default(),
+        }))
+    }
+
+    fn get_name(&self) -> &'static str {
+        "topic"
+    }
+
+    fn commands(&self) -> Vec<CommandHelp> {
+        vec![
+            CommandHelp::new("topic", "", "le topic du channel"),
+            CommandHelp::new("topic", "set <topic>", "change le topic du channel"),
+        ]
+    }
+
+    async fn in_message(&self, msg: &Message) -> Result<Option<Message>> {
+        self.track_topic(msg);
+        Ok(self.in_msg(msg))
+    }
+}
+
+impl Topic {
+    /// Keep track of topic changes, and of the topic sent by the server
+    /// when joining a channel.
+    fn track_topic(&self, msg: &Message) {
+        let mut topics = self.topics.lock().expect("topic lock");
+        match &msg.command {
+            Command::TOPIC(chan, Some(topic)) => {
+                topics.insert(channel_key(msg, chan), topic.to_string());
+            }
+            Command::Response(Response::RPL_TOPIC, args) => {
+                if let [_nick, chan, topic] = &args[..] {
+                    topics.insert(channel_key(msg, chan), topic.to_string());
+                }
+            }
+            Command::Response(Response::RPL_NOTOPIC, args) => {
+                if let [_nick, chan, ..] = &args[..] {
+                    topics.remove(&channel_key(msg, chan));
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn in_msg(&self, msg: &Message) -> Option<Message> {
+        let privmsg = match &msg.command {
+            Command::PRIVMSG(_source, privmsg) => privmsg,
+            _ => return None,
+        };
+        let cmd = parse_command(privmsg)?;
+        let channel = msg.response_target()?;
+
+        match cmd {
+            TopicCmd::Get => {
+                let topics = self.topics.lock().expect("topic lock");
+                let reply = match topics.get(&channel_key(msg, channel)) {
+                    Some(topic) if !topic.is_empty() => format!("Topic de {channel}: {topic}"),
+                    _ => format!("Pas de topic connu pour {channel}"),
+                };
+                Some(Command::PRIVMSG(channel.to_string(), reply).into())
+            }
+            TopicCmd::Set(_) if !self.owners.is_owner(msg) => Some(
+                Command::PRIVMSG(
+                    channel.to_string(),
+                    "Touche pas au topic, t'es pas mon patron !".to_string(),
+                )
+                .into(),
+            ),
+            TopicCmd::Set(topic) => {
+                Some(Command::TOPIC(channel.to_string(), Some(topic.to_string())).into())
+            }
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum TopicCmd<'input> {
+    Get,
+    Set(&'input str),
+}
+
+fn parse_command(input: &str) -> Option<TopicCmd> {
+    all_consuming(terminated(parse_topic, multispace0))(input)
+        .finish()
+        .map(|x| x.1)
+        .ok()
+}
+
+fn parse_topic(input: &str) -> IResult<&str, TopicCmd> {
+    preceded(
+        tuple((command_prefix, tag("topic"))),
+        map(
+            opt(preceded(
+                tuple((multispace1, tag("set"), multispace1)),
+                rest,
+            )),
+            |mb_topic: Option<&str>| match mb_topic {
+                Some(topic) => TopicCmd::Set(topic.trim_end()),
+                None => TopicCmd::Get,
+            },
+        ),
+    )(input)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use plugin_core::utils::network::with_network;
+    use pretty_assertions::assert_eq;
+
+    fn topic_plugin() -> Topic {
+        Topic {
+            owners: Owners::new(vec!["charlie".to_string()]),
+            topics: Default::default(),
+        }
+    }
+
+    fn message(raw: &str) -> Message {
+        raw.parse().unwrap()
+    }
+
+    #[test]
+    async fn test_parse_command() {
+        assert_eq!(parse_command("λtopic"), Some(TopicCmd::Get));
+        assert_eq!(
+            parse_command("λtopic set coucou les gens"),
+            Some(TopicCmd::Set("coucou les gens"))
+        );
+        assert_eq!(parse_command("λtopic set"), None, "set needs a topic");
+        assert_eq!(parse_command("λtopical"), None);
+    }
+
+    #[test]
+    async fn test_track_topic() {
+        let plugin = topic_plugin();
+        plugin.track_topic(&message(
+            ":irc.libera.chat 332 rustygolem #coucou :initial topic",
+        ));
+        assert_eq!(
+            plugin.topics.lock().unwrap().get("#coucou"),
+            Some(&"initial topic".to_string()),
+            "topic from RPL_TOPIC when joining"
+        );
+
+        plugin.track_topic(&message(":bob!bob@host TOPIC #coucou :new topic"));
+        assert_eq!(
+            plugin.topics.lock().unwrap().get("#coucou"),
+            Some(&"new topic".to_string()),
+            "topic updated on TOPIC event"
+        );
+
+        plugin.track_topic(&message(":bob!bob@host TOPIC #other :other topic"));
+        assert_eq!(
+            plugin.topics.lock().unwrap().get("#coucou"),
+            Some(&"new topic".to_string()),
+            "topics are tracked per channel"
+        );
+
+        plugin.track_topic(&with_network(
+            message(":bob!bob@host TOPIC #coucou :oftc topic"),
+            Some("irc.oftc.net"),
+        ));
+        assert_eq!(
+            plugin.topics.lock().unwrap().get("#coucou"),
+            Some(&"new topic".to_string()),
+            "topics are tracked per server"
+        );
+    }
+
+    #[test]
+    async fn test_set_topic_owner_only() {
+        let plugin = topic_plugin();
+
+        let resp = plugin.in_msg(&message(
+            ":charlie!c@host PRIVMSG #coucou :λtopic set hello",
+        ));
+        assert_eq!(
+            resp.map(|m| m.command),
+            Some(Command::TOPIC(
+                "#coucou".to_string(),
+                Some("hello".to_string())
+            )),
+            "owner can set the topic"
+        );
+
+        let resp = plugin.in_msg(&message(":bob!b@host PRIVMSG #coucou :λtopic set hello"));
+        assert!(
+            matches!(resp.map(|m| m.command), Some(Command::PRIVMSG(_, _))),
+            "non owner cannot set the topic"
+        );
+    }
+}
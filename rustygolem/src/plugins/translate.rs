@@ -0,0 +1,169 @@
+use crate::utils::libretranslate::{self, Language};
+use crate::utils::parser::command_prefix;
+use async_trait::async_trait;
+use irc::proto::{Command, Message};
+use nom::bytes::complete::{tag, take_while1};
+use nom::character::complete::{multispace0, multispace1};
+use nom::combinator::{all_consuming, map, rest, verify};
+use nom::sequence::{preceded, terminated, tuple};
+use nom::Finish;
+use plugin_core::config::ConfigSection;
+use plugin_core::{CommandHelp, Initialised, Plugin, Result};
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+struct TranslateConfig {
+    /// base url of a libretranslate instance, shared with λurbain
+    libretranslate_url: Option<String>,
+}
+
+impl ConfigSection for TranslateConfig {
+    const SECTION: Option<&'static str> = None;
+    const SCHEMA: &'static str = "{ libretranslate_url : Optional Text }";
+}
+
+pub struct Translate {
+    client: reqwest::Client,
+    libretranslate_url: Option<String>,
+}
+
+#[async_trait]
+impl Plugin for Translate {
+    async fn init(config: &plugin_core::Config) -> Result<Initialised> {
+        let translate_config: TranslateConfig = plugin_core::config::load(&config.config_path)?;
+        if translate_config.libretranslate_url.is_none() {
+            log::warn!("Translate plugin is missing libretranslate_url.");
+        }
+        Ok(Initialised::from(Translate {
+            client: config.http_client.clone(),
+            libretranslate_url: translate_config.libretranslate_url,
+        }))
+    }
+
+    fn get_name(&self) -> &'static str {
+        "translate"
+    }
+
+    fn commands(&self) -> Vec<CommandHelp> {
+        vec![CommandHelp::new(
+            "translate",
+            "<de> <vers> <texte>",
+            "traduit le texte, par exemple λtranslate en fr hello",
+        )]
+    }
+
+    async fn in_message(&self, msg: &Message) -> Result<Option<Message>> {
+        self.in_msg(msg).await
+    }
+}
+
+impl Translate {
+    async fn in_msg(&self, msg: &Message) -> Result<Option<Message>> {
+        let privmsg = match &msg.command {
+            Command::PRIVMSG(_source, privmsg) => privmsg,
+            _ => return Ok(None),
+        };
+        let (response_target, (source, target, text)) =
+            match (msg.response_target(), parse_command(privmsg)) {
+                (Some(response_target), Some(cmd)) => (response_target, cmd),
+                _ => return Ok(None),
+            };
+
+        let reply = self.handle_command(source, target, text).await;
+        Ok(Some(
+            Command::PRIVMSG(response_target.to_string(), reply).into(),
+        ))
+    }
+
+    async fn handle_command(&self, source: &str, target: &str, text: &str) -> String {
+        let base_url = match &self.libretranslate_url {
+            Some(url) => url,
+            None => return "Pas d'instance libretranslate configurée".to_string(),
+        };
+        let (source, target) = match languages(source, target) {
+            Ok(langs) => langs,
+            Err(reply) => return reply,
+        };
+        match libretranslate::translate(&self.client, base_url, source, target, text).await {
+            Ok(translated) => translated,
+            Err(err) => {
+                log::warn!("Cannot translate {text} from {source:?} to {target:?}: {err:#}");
+                format!(
+                    "Pas de traduction de {} vers {}: {err:#}",
+                    source.code(),
+                    target.code()
+                )
+            }
+        }
+    }
+}
+
+/// The error is the reply, listing the known languages
+fn languages(source: &str, target: &str) -> std::result::Result<(Language, Language), String> {
+    let unknown = |code: &str| {
+        let known = Language::ALL
+            .iter()
+            .map(|lang| lang.code())
+            .collect::<Vec<_>>();
+        format!("Connais pas la langue {code}, essaie {}", known.join(", "))
+    };
+    let source_lang = Language::from_code(source).ok_or_else(|| unknown(source))?;
+    let target_lang = Language::from_code(target).ok_or_else(|| unknown(target))?;
+    if source_lang == target_lang {
+        return Err(format!("Pas besoin de traduire de {source} vers {target}"));
+    }
+    Ok((source_lang, target_lang))
+}
+
+/// `λtranslate <source> <target> <text>`
+fn parse_command(input: &str) -> Option<(&str, &str, &str)> {
+    let lang = || take_while1(|c: char| c.is_ascii_alphabetic());
+    let text = map(verify(rest, |t: &str| !t.trim().is_empty()), str::trim_end);
+    let cmd = preceded(
+        tuple((command_prefix, tag("translate"), multispace1)),
+        tuple((
+            lang(),
+            preceded(multispace1, lang()),
+            preceded(multispace1, text),
+        )),
+    );
+    all_consuming(terminated(cmd, multispace0))(input)
+        .finish()
+        .map(|x| x.1)
+        .ok()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    async fn test_parse_command() {
+        assert_eq!(
+            parse_command("λtranslate en fr hello world "),
+            Some(("en", "fr", "hello world"))
+        );
+        assert_eq!(parse_command("λtranslate en fr"), None, "need a text");
+        assert_eq!(parse_command("λtranslate english hello"), None);
+    }
+
+    #[test]
+    async fn test_languages() {
+        assert_eq!(
+            languages("en", "FR"),
+            Ok((Language::English, Language::French))
+        );
+        assert_eq!(
+            languages("en", "xx"),
+            Err(
+                "Connais pas la langue xx, essaie ar, zh, nl, en, fr, de, it, ja, pl, pt, ru, es, uk"
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            languages("fr", "fr"),
+            Err("Pas besoin de traduire de fr vers fr".to_string())
+        );
+    }
+}
@@ -0,0 +1,208 @@
+use crate::utils::libretranslate::{self, Language};
+use crate::utils::parser::command_prefix;
+use anyhow::Context;
+use async_trait::async_trait;
+use irc::proto::{Command, Message};
+use nom::bytes::complete::{tag, take_till1};
+use nom::character::complete::{multispace0, multispace1};
+use nom::combinator::{all_consuming, map};
+use nom::sequence::{preceded, terminated, tuple};
+use nom::Finish;
+use plugin_core::config::ConfigSection;
+use plugin_core::utils::parser::with_target;
+use plugin_core::{CommandHelp, Initialised, Plugin, Result};
+use serde::Deserialize;
+
+/// definitions can be very long, they are truncated to that many characters
+const MAX_DEFINITION_LENGTH: usize = 400;
+
+#[derive(Deserialize)]
+struct UrbainConfig {
+    /// base url of a libretranslate instance, to translate the definitions
+    /// in french. They are given in english when not set.
+    libretranslate_url: Option<String>,
+}
+
+impl ConfigSection for UrbainConfig {
+    const SECTION: Option<&'static str> = None;
+    const SCHEMA: &'static str = "{ libretranslate_url : Optional Text }";
+}
+
+pub struct Urbain {
+    client: reqwest::Client,
+    libretranslate_url: Option<String>,
+}
+
+#[async_trait]
+impl Plugin for Urbain {
+    async fn init(config: &plugin_core::Config) -> Result<Initialised> {
+        let urbain_config: UrbainConfig = plugin_core::config::load(&config.config_path)?;
+        Ok(Initialised::from(Urbain {
+            client: config.http_client.clone(),
+            libretranslate_url: urbain_config.libretranslate_url,
+        }))
+    }
+
+    fn get_name(&self) -> &'static str {
+        "urbain"
+    }
+
+    fn commands(&self) -> Vec<CommandHelp> {
+        vec![CommandHelp::new(
+            "urbain",
+            "<mots>",
+            "la définition d'urban dictionary",
+        )]
+    }
+
+    async fn in_message(&self, msg: &Message) -> Result<Option<Message>> {
+        self.in_msg(msg).await
+    }
+}
+
+impl Urbain {
+    async fn in_msg(&self, msg: &Message) -> Result<Option<Message>> {
+        let privmsg = match &msg.command {
+            Command::PRIVMSG(_source, privmsg) => privmsg,
+            _ => return Ok(None),
+        };
+        let (response_target, (words, mb_target)) =
+            match (msg.response_target(), parse_command(privmsg)) {
+                (Some(response_target), Some(cmd)) => (response_target, cmd),
+                _ => return Ok(None),
+            };
+
+        let reply = match self.handle_command(words).await {
+            Ok(reply) => reply,
+            Err(err) => format!("Error while looking up {words}: {err:#}"),
+        };
+        let reply = crate::utils::messages::with_target(&reply, &mb_target);
+        Ok(Some(
+            Command::PRIVMSG(response_target.to_string(), reply).into(),
+        ))
+    }
+
+    async fn handle_command(&self, words: &str) -> anyhow::Result<String> {
+        let definition = match fetch_definition(&self.client, words).await? {
+            Some(definition) => clean_definition(&definition),
+            None => return Ok(format!("Pas de définition pour {words}")),
+        };
+
+        let definition = match &self.libretranslate_url {
+            None => definition,
+            Some(url) => match libretranslate::translate(
+                &self.client,
+                url,
+                Language::English,
+                Language::French,
+                &definition,
+            )
+            .await
+            {
+                Ok(translated) => translated,
+                Err(err) => {
+                    // libretranslate instances come and go, the english version will do
+                    log::warn!("Cannot translate definition of {words}: {err:#}");
+                    definition
+                }
+            },
+        };
+        Ok(format!("{words}: {definition}"))
+    }
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+struct UrbanResponse {
+    list: Vec<UrbanDefinition>,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+struct UrbanDefinition {
+    definition: String,
+}
+
+/// The top definition, if any
+async fn fetch_definition(client: &reqwest::Client, words: &str) -> anyhow::Result<Option<String>> {
+    let resp = client
+        .get("https://api.urbandictionary.com/v0/define")
+        .query(&[("term", words)])
+        .send()
+        .await
+        .context("cannot query urban dictionary")?
+        .json::<UrbanResponse>()
+        .await
+        .context("unexpected response from urban dictionary")?;
+    Ok(resp.list.into_iter().next().map(|d| d.definition))
+}
+
+/// On a single line, without the `[links]` markup, and truncated
+fn clean_definition(definition: &str) -> String {
+    let definition = definition
+        .replace(['[', ']'], "")
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ");
+    if definition.chars().count() <= MAX_DEFINITION_LENGTH {
+        return definition;
+    }
+    let mut truncated = definition
+        .chars()
+        .take(MAX_DEFINITION_LENGTH - 1)
+        .collect::<String>();
+    truncated.push('…');
+    truncated
+}
+
+/// `λurbain <words> [> target]`
+fn parse_command(input: &str) -> Option<(&str, Option<&str>)> {
+    let cmd = preceded(
+        tuple((command_prefix, tag("urbain"), multispace1)),
+        with_target(map(take_till1(|c| c == '>'), str::trim)),
+    );
+    all_consuming(terminated(cmd, multispace0))(input)
+        .finish()
+        .map(|x| x.1)
+        .ok()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    async fn test_parse_command() {
+        assert_eq!(parse_command("λurbain yeet"), Some(("yeet", None)));
+        assert_eq!(
+            parse_command("λurbain no cap > charlie"),
+            Some(("no cap", Some("charlie")))
+        );
+        assert_eq!(parse_command("λurbain"), None);
+        assert_eq!(parse_command("λurbain > charlie"), None, "need words");
+    }
+
+    #[test]
+    async fn test_parse_response() {
+        let resp = serde_json::from_str::<UrbanResponse>(
+            r#"{"list": [{"definition": "To [throw] something", "word": "yeet", "thumbs_up": 42}]}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            resp.list,
+            vec![UrbanDefinition {
+                definition: "To [throw] something".to_string()
+            }]
+        );
+    }
+
+    #[test]
+    async fn test_clean_definition() {
+        assert_eq!(
+            clean_definition("To [throw] something\r\n\r\nwith  [force]"),
+            "To throw something with force"
+        );
+        let long = clean_definition(&"a".repeat(MAX_DEFINITION_LENGTH + 1));
+        assert_eq!(long.chars().count(), MAX_DEFINITION_LENGTH);
+        assert!(long.ends_with('…'));
+    }
+}
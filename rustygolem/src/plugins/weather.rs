@@ -0,0 +1,265 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::utils::parser::command_prefix;
+use anyhow::Context;
+use async_trait::async_trait;
+use irc::proto::{Command, Message};
+use nom::bytes::complete::{tag, take_till1};
+use nom::character::complete::{multispace0, multispace1};
+use nom::combinator::{all_consuming, map};
+use nom::sequence::{preceded, terminated, tuple};
+use nom::Finish;
+use plugin_core::utils::parser::with_target;
+use plugin_core::{CommandHelp, Initialised, Plugin, Result};
+use serde::Deserialize;
+
+/// Weather of a city is only fetched once in that window
+const CACHE_TTL: Duration = Duration::from_secs(10 * 60);
+const FETCH_TIMEOUT: Duration = Duration::from_secs(10);
+
+pub struct Weather {
+    client: reqwest::Client,
+    /// lowercased city -> when the reply was built, and the reply
+    cache: Mutex<HashMap<String, (Instant, String)>>,
+}
+
+#[async_trait]
+impl Plugin for Weather {
+    async fn init(config: &plugin_core::Config) -> Result<Initialised> {
+        Ok(Initialised::from(Weather {
+            client: config.http_client.clone(),
+            cache: Mutex::new(HashMap::new()),
+        }))
+    }
+
+    fn get_name(&self) -> &'static str {
+        "weather"
+    }
+
+    fn commands(&self) -> Vec<CommandHelp> {
+        vec![CommandHelp::new(
+            "weather",
+            "<ville>",
+            "le temps qu'il fait en ce moment",
+        )]
+    }
+
+    async fn in_message(&self, msg: &Message) -> Result<Option<Message>> {
+        self.in_msg(msg).await
+    }
+}
+
+impl Weather {
+    async fn in_msg(&self, msg: &Message) -> Result<Option<Message>> {
+        let privmsg = match &msg.command {
+            Command::PRIVMSG(_source, privmsg) => privmsg,
+            _ => return Ok(None),
+        };
+        let (response_target, (city, mb_target)) =
+            match (msg.response_target(), parse_command(privmsg)) {
+                (Some(response_target), Some(cmd)) => (response_target, cmd),
+                _ => return Ok(None),
+            };
+
+        let reply = match self.cached(city, Instant::now()) {
+            Some(reply) => reply,
+            None => match current_weather(&self.client, city).await {
+                Ok(reply) => {
+                    self.cache_reply(city, &reply, Instant::now());
+                    reply
+                }
+                Err(err) => format!("Error while getting the weather for {city}: {err:#}"),
+            },
+        };
+        let reply = crate::utils::messages::with_target(&reply, &mb_target);
+        Ok(Some(
+            Command::PRIVMSG(response_target.to_string(), reply).into(),
+        ))
+    }
+
+    fn cached(&self, city: &str, now: Instant) -> Option<String> {
+        let cache = self.cache.lock().expect("weather cache lock");
+        cache
+            .get(&city.to_lowercase())
+            .filter(|(at, _)| now.saturating_duration_since(*at) < CACHE_TTL)
+            .map(|(_, reply)| reply.clone())
+    }
+
+    fn cache_reply(&self, city: &str, reply: &str, now: Instant) {
+        let mut cache = self.cache.lock().expect("weather cache lock");
+        cache.retain(|_, (at, _)| now.saturating_duration_since(*at) < CACHE_TTL);
+        cache.insert(city.to_lowercase(), (now, reply.to_string()));
+    }
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+struct GeocodingResponse {
+    /// absent when nothing matches
+    #[serde(default)]
+    results: Vec<Place>,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+struct Place {
+    name: String,
+    country: Option<String>,
+    latitude: f64,
+    longitude: f64,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+struct ForecastResponse {
+    current_weather: CurrentWeather,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+struct CurrentWeather {
+    /// °C
+    temperature: f64,
+    /// km/h
+    windspeed: f64,
+    weathercode: u32,
+}
+
+/// The reply for the city, including when it doesn't exist
+async fn current_weather(client: &reqwest::Client, city: &str) -> anyhow::Result<String> {
+    let places = client
+        .get("https://geocoding-api.open-meteo.com/v1/search")
+        .query(&[("name", city), ("count", "1"), ("language", "fr")])
+        .timeout(FETCH_TIMEOUT)
+        .send()
+        .await
+        .context("cannot query open-meteo geocoding")?
+        .json::<GeocodingResponse>()
+        .await
+        .context("unexpected response from open-meteo geocoding")?;
+    let place = match places.results.into_iter().next() {
+        Some(place) => place,
+        None => return Ok(format!("Connais pas de ville {city}")),
+    };
+
+    let forecast = client
+        .get("https://api.open-meteo.com/v1/forecast")
+        .query(&[
+            ("latitude", place.latitude.to_string()),
+            ("longitude", place.longitude.to_string()),
+            ("current_weather", "true".to_string()),
+        ])
+        .timeout(FETCH_TIMEOUT)
+        .send()
+        .await
+        .context("cannot query open-meteo forecast")?
+        .json::<ForecastResponse>()
+        .await
+        .context("unexpected response from open-meteo forecast")?;
+    Ok(format_weather(&place, &forecast.current_weather))
+}
+
+/// `Paris (France): ciel dégagé, 12.5°C, vent 10 km/h`
+fn format_weather(place: &Place, weather: &CurrentWeather) -> String {
+    let name = match &place.country {
+        Some(country) => format!("{} ({})", place.name, country),
+        None => place.name.clone(),
+    };
+    format!(
+        "{}: {}, {:.1}°C, vent {:.0} km/h",
+        name,
+        describe(weather.weathercode),
+        weather.temperature,
+        weather.windspeed
+    )
+}
+
+/// WMO weather interpretation codes
+/// https://open-meteo.com/en/docs#weathervariables
+fn describe(weathercode: u32) -> &'static str {
+    match weathercode {
+        0 => "ciel dégagé",
+        1 => "plutôt dégagé",
+        2 => "partiellement nuageux",
+        3 => "couvert",
+        45 | 48 => "brouillard",
+        51 | 53 | 55 => "bruine",
+        56 | 57 => "bruine verglaçante",
+        61 | 63 | 65 => "pluie",
+        66 | 67 => "pluie verglaçante",
+        71 | 73 | 75 | 77 => "neige",
+        80 | 81 | 82 => "averses",
+        85 | 86 => "averses de neige",
+        95 => "orage",
+        96 | 99 => "orage avec grêle",
+        _ => "temps inconnu",
+    }
+}
+
+/// `λweather <city> [> target]`
+fn parse_command(input: &str) -> Option<(&str, Option<&str>)> {
+    let cmd = preceded(
+        tuple((command_prefix, tag("weather"), multispace1)),
+        with_target(map(take_till1(|c| c == '>'), str::trim)),
+    );
+    all_consuming(terminated(cmd, multispace0))(input)
+        .finish()
+        .map(|x| x.1)
+        .ok()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    async fn test_parse_command() {
+        assert_eq!(parse_command("λweather Paris"), Some(("Paris", None)));
+        assert_eq!(
+            parse_command("λweather Saint Malo > charlie"),
+            Some(("Saint Malo", Some("charlie")))
+        );
+        assert_eq!(parse_command("λweather"), None);
+        assert_eq!(parse_command("λweather > charlie"), None, "need a city");
+    }
+
+    #[test]
+    async fn test_parse_responses() {
+        let places = serde_json::from_str::<GeocodingResponse>(
+            r#"{"results": [{"id": 2988507, "name": "Paris", "latitude": 48.85341,
+                "longitude": 2.3488, "country": "France", "timezone": "Europe/Paris"}]}"#,
+        )
+        .unwrap();
+        assert_eq!(places.results[0].name, "Paris");
+        assert_eq!(
+            serde_json::from_str::<GeocodingResponse>(r#"{"generationtime_ms": 0.5}"#).unwrap(),
+            GeocodingResponse { results: vec![] },
+            "unknown city"
+        );
+
+        let forecast = serde_json::from_str::<ForecastResponse>(
+            r#"{"latitude": 48.86, "current_weather": {"temperature": 12.5,
+                "windspeed": 9.7, "winddirection": 250, "weathercode": 3, "time": "2024-01-01T09:00"}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            format_weather(&places.results[0], &forecast.current_weather),
+            "Paris (France): couvert, 12.5°C, vent 10 km/h"
+        );
+    }
+
+    #[test]
+    async fn test_cache() {
+        let plugin = Weather {
+            client: reqwest::Client::new(),
+            cache: Mutex::new(HashMap::new()),
+        };
+        let now = Instant::now();
+        assert_eq!(plugin.cached("Paris", now), None);
+        plugin.cache_reply("Paris", "Paris: couvert", now);
+        assert_eq!(
+            plugin.cached("paris", now + Duration::from_secs(60)),
+            Some("Paris: couvert".to_string())
+        );
+        assert_eq!(plugin.cached("Paris", now + CACHE_TTL), None, "expired");
+    }
+}
@@ -0,0 +1,131 @@
+use std::collections::HashSet;
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+use irc::proto::{Command, Message};
+use plugin_core::config::ConfigSection;
+use plugin_core::utils::network::channel_key;
+use plugin_core::utils::nicks::OwnNicks;
+use plugin_core::{Initialised, Plugin, Result};
+use serde::Deserialize;
+
+/// `{nick}` is replaced by the nick of whoever joined
+const DEFAULT_GREETING: &str = "Bienvenue {nick} !";
+
+#[derive(Default, Deserialize)]
+struct WelcomeConfig {
+    /// channels where the newcomers are greeted
+    channels: Vec<String>,
+    greeting: Option<String>,
+}
+
+impl ConfigSection for WelcomeConfig {
+    const SECTION: Option<&'static str> = Some("welcome");
+    const SCHEMA: &'static str = "{ channels : List Text, greeting : Optional Text }";
+}
+
+/// Greets people joining a channel, only the first time they join since
+/// the bot started, so that flaky connections don't get greeted each time.
+/// Blacklisted users (other bots) are not greeted.
+pub struct Welcome {
+    own_nicks: OwnNicks,
+    channels: Vec<String>,
+    greeting: String,
+    /// (lowercased channel key, lowercased nick)
+    greeted: Mutex<HashSet<(String, String)>>,
+}
+
+#[async_trait]
+impl Plugin for Welcome {
+    async fn init(config: &plugin_core::Config) -> Result<Initialised> {
+        let welcome_config: WelcomeConfig =
+            plugin_core::config::load_or_default(&config.config_path)?;
+        Ok(Initialised::from(Welcome {
+            own_nicks: config.own_nicks.clone(),
+            channels: welcome_config.channels,
+            greeting: welcome_config
+                .greeting
+                .unwrap_or_else(|| DEFAULT_GREETING.to_string()),
+            greeted: Mutex::new(HashSet::new()),
+        }))
+    }
+
+    fn get_name(&self) -> &'static str {
+        "welcome"
+    }
+
+    async fn in_message(&self, msg: &Message) -> Result<Option<Message>> {
+        Ok(self.in_msg(msg))
+    }
+}
+
+impl Welcome {
+    fn in_msg(&self, msg: &Message) -> Option<Message> {
+        let channel = match &msg.command {
+            Command::JOIN(channel, _, _) => channel,
+            _ => return None,
+        };
+        let nick = msg.source_nickname()?;
+        if self.own_nicks.is_own_nick(nick)
+            || !self
+                .channels
+                .iter()
+                .any(|c| c.eq_ignore_ascii_case(channel))
+        {
+            return None;
+        }
+
+        let first_join = self.greeted.lock().expect("welcome lock").insert((
+            channel_key(msg, channel).to_lowercase(),
+            nick.to_lowercase(),
+        ));
+        if !first_join {
+            return None;
+        }
+        let greeting = self.greeting.replace("{nick}", nick);
+        Some(Command::PRIVMSG(channel.to_string(), greeting).into())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use plugin_core::test_util::{join, privmsg, reply_text};
+    use pretty_assertions::assert_eq;
+
+    fn welcome() -> Welcome {
+        Welcome {
+            own_nicks: OwnNicks::new("golem"),
+            channels: vec!["#coucou".to_string()],
+            greeting: DEFAULT_GREETING.to_string(),
+            greeted: Mutex::new(HashSet::new()),
+        }
+    }
+
+    #[test]
+    async fn test_greets_once() {
+        let plugin = welcome();
+        let greeting = plugin.in_msg(&join("charlie", "#coucou"));
+        assert_eq!(
+            greeting.as_ref().and_then(|m| m.response_target()),
+            Some("#coucou")
+        );
+        assert_eq!(reply_text(greeting), "Bienvenue charlie !");
+        assert_eq!(
+            plugin.in_msg(&join("Charlie", "#coucou")),
+            None,
+            "already greeted"
+        );
+    }
+
+    #[test]
+    async fn test_ignored() {
+        let plugin = welcome();
+        assert_eq!(plugin.in_msg(&join("golem", "#coucou")), None, "self");
+        assert_eq!(plugin.in_msg(&join("charlie", "#other")), None);
+        assert_eq!(
+            plugin.in_msg(&privmsg("charlie", "#coucou", "coucou")),
+            None
+        );
+    }
+}
@@ -1,7 +1,106 @@
 table! {
-    crypto_rate (date, coin) {
+    aliases (name) {
+        name -> Text,
+        expansion -> Text,
+    }
+}
+
+table! {
+    crypto_alert (channel, nick, coin, direction, threshold) {
+        channel -> Text,
+        nick -> Text,
+        coin -> Text,
+        direction -> Text,
+        threshold -> Float,
+    }
+}
+
+table! {
+    crypto_rate (date, coin, currency) {
         date -> Timestamp,
         coin -> Text,
         rate -> Float,
+        currency -> Text,
+    }
+}
+
+table! {
+    crypto_watch (channel, coin) {
+        channel -> Text,
+        coin -> Text,
+        interval_secs -> BigInt,
+        next_fire -> Timestamp,
+    }
+}
+
+table! {
+    reminders (id) {
+        id -> Integer,
+        nick -> Text,
+        channel -> Text,
+        fire_at -> Timestamp,
+        message -> Text,
+    }
+}
+
+table! {
+    seen (channel, nick) {
+        channel -> Text,
+        nick -> Text,
+        last_seen -> Timestamp,
+        message -> Text,
+    }
+}
+
+table! {
+    memos (id) {
+        id -> Integer,
+        channel -> Text,
+        recipient -> Text,
+        sender -> Text,
+        sent_at -> Timestamp,
+        message -> Text,
+    }
+}
+
+table! {
+    karma (channel, thing) {
+        channel -> Text,
+        thing -> Text,
+        score -> Integer,
+    }
+}
+
+table! {
+    quotes (channel, id) {
+        channel -> Text,
+        id -> Integer,
+        added_by -> Text,
+        text -> Text,
+        added_at -> Timestamp,
+    }
+}
+
+table! {
+    feed_state (feed) {
+        feed -> Text,
+        last_entry_id -> Text,
+    }
+}
+
+table! {
+    channel_logs (id) {
+        id -> Integer,
+        logged_at -> Timestamp,
+        channel -> Text,
+        nick -> Text,
+        text -> Text,
+    }
+}
+
+table! {
+    lastfm_users (irc_nick) {
+        irc_nick -> Text,
+        lastfm_user -> Text,
     }
 }
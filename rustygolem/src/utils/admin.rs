@@ -0,0 +1,98 @@
+use crate::utils::parser::command_prefix;
+use irc::proto::Command;
+use nom::branch::alt;
+use nom::bytes::complete::{tag, take_while1};
+use nom::character::complete::{multispace0, multispace1};
+use nom::combinator::{all_consuming, map, rest, verify};
+use nom::sequence::{pair, preceded, separated_pair, terminated, tuple};
+use nom::Finish;
+
+/// Commands the owners can send to control the bot
+#[derive(Debug, PartialEq, Eq)]
+pub enum AdminCmd<'input> {
+    Join(&'input str),
+    Part(&'input str),
+    /// channel and text
+    Say(&'input str, &'input str),
+}
+
+impl AdminCmd<'_> {
+    pub fn to_command(&self) -> Command {
+        match self {
+            AdminCmd::Join(channel) => Command::JOIN(channel.to_string(), None, None),
+            AdminCmd::Part(channel) => Command::PART(channel.to_string(), None),
+            AdminCmd::Say(channel, text) => Command::PRIVMSG(channel.to_string(), text.to_string()),
+        }
+    }
+}
+
+/// `λadmin join #chan`, `λadmin part #chan` or `λadmin say #chan <text>`
+pub fn parse_command(input: &str) -> Option<AdminCmd> {
+    let channel = || {
+        verify(take_while1(|c: char| !c.is_whitespace()), |c: &str| {
+            c.starts_with('#') || c.starts_with('&')
+        })
+    };
+    let text = map(verify(rest, |t: &str| !t.trim().is_empty()), str::trim_end);
+    let cmd = preceded(
+        tuple((command_prefix, tag("admin"), multispace1)),
+        alt((
+            map(
+                preceded(pair(tag("join"), multispace1), channel()),
+                AdminCmd::Join,
+            ),
+            map(
+                preceded(pair(tag("part"), multispace1), channel()),
+                AdminCmd::Part,
+            ),
+            map(
+                preceded(
+                    pair(tag("say"), multispace1),
+                    separated_pair(channel(), multispace1, text),
+                ),
+                |(channel, text)| AdminCmd::Say(channel, text),
+            ),
+        )),
+    );
+    all_consuming(terminated(cmd, multispace0))(input)
+        .finish()
+        .map(|x| x.1)
+        .ok()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    async fn test_parse_command() {
+        assert_eq!(
+            parse_command("λadmin join #coucou"),
+            Some(AdminCmd::Join("#coucou"))
+        );
+        assert_eq!(
+            parse_command("λadmin part #coucou "),
+            Some(AdminCmd::Part("#coucou"))
+        );
+        assert_eq!(
+            parse_command("λadmin say #coucou  coucou tout le monde"),
+            Some(AdminCmd::Say("#coucou", "coucou tout le monde"))
+        );
+        assert_eq!(parse_command("λadmin say #coucou"), None, "need a text");
+        assert_eq!(parse_command("λadmin join coucou"), None, "not a channel");
+        assert_eq!(parse_command("λadmin quit"), None);
+    }
+
+    #[test]
+    async fn test_to_command() {
+        assert_eq!(
+            AdminCmd::Say("#coucou", "hello").to_command(),
+            Command::PRIVMSG("#coucou".to_string(), "hello".to_string())
+        );
+        assert_eq!(
+            AdminCmd::Part("#coucou").to_command(),
+            Command::PART("#coucou".to_string(), None)
+        );
+    }
+}
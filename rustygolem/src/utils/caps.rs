@@ -0,0 +1,165 @@
+//! IRCv3 capability negotiation helpers
+//! https://ircv3.net/specs/extensions/capability-negotiation
+use irc::proto::{CapSubCommand, Command, Message};
+
+/// Capabilities requested when none are given in the config
+pub const DEFAULT_CAPABILITIES: &[&str] = &[
+    "sasl",
+    "server-time",
+    "account-tag",
+    "away-notify",
+    "echo-message",
+    "multi-prefix",
+];
+
+/// Returns the list of capabilities carried by a CAP reply, and whether
+/// more lines are coming (multiline CAP LS replies).
+fn cap_list(cmd: &Command) -> Option<(&CapSubCommand, Vec<String>, bool)> {
+    match cmd {
+        Command::CAP(_, sub, Some(star), Some(caps)) if star == "*" => {
+            Some((sub, parse_caps(caps), true))
+        }
+        Command::CAP(_, sub, Some(caps), None) => Some((sub, parse_caps(caps), false)),
+        _ => None,
+    }
+}
+
+/// CAP LS 302 can advertise values, like sasl=PLAIN,EXTERNAL, only keep the names
+fn parse_caps(caps: &str) -> Vec<String> {
+    caps.split_whitespace()
+        .map(|c| c.split('=').next().unwrap_or(c).to_string())
+        .collect()
+}
+
+/// Capabilities advertised in a line of the CAP LS reply,
+/// and whether more lines are coming.
+pub fn ls_reply(msg: &Message) -> Option<(Vec<String>, bool)> {
+    match cap_list(&msg.command) {
+        Some((CapSubCommand::LS, caps, more)) => Some((caps, more)),
+        _ => None,
+    }
+}
+
+/// The capabilities from `wanted` which are supported by the server,
+/// keeping the order of `wanted`.
+pub fn to_request(wanted: &[String], available: &[String]) -> Vec<String> {
+    wanted
+        .iter()
+        .filter(|cap| available.contains(cap))
+        .cloned()
+        .collect()
+}
+
+/// A single CAP REQ for all the given capabilities
+pub fn cap_req(caps: &[String]) -> Command {
+    Command::CAP(None, CapSubCommand::REQ, None, Some(caps.join(" ")))
+}
+
+/// Which requested capabilities were acknowledged or rejected by the server
+#[derive(Debug, Default, PartialEq)]
+pub struct CapSummary {
+    pub acked: Vec<String>,
+    pub naked: Vec<String>,
+}
+
+impl CapSummary {
+    /// Account for a CAP ACK or CAP NAK reply.
+    /// Returns false if the message is neither.
+    pub fn record(&mut self, msg: &Message) -> bool {
+        match cap_list(&msg.command) {
+            Some((CapSubCommand::ACK, caps, _)) => self.acked.extend(caps),
+            Some((CapSubCommand::NAK, caps, _)) => self.naked.extend(caps),
+            _ => return false,
+        }
+        true
+    }
+
+    pub fn is_acked(&self, cap: &str) -> bool {
+        self.acked.iter().any(|c| c == cap)
+    }
+}
+
+/// With echo-message, the server sends back the messages the bot sent.
+pub fn is_echo(msg: &Message, own_nick: &str) -> bool {
+    matches!(msg.command, Command::PRIVMSG(_, _) | Command::NOTICE(_, _))
+        && msg.source_nickname() == Some(own_nick)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn message(raw: &str) -> Message {
+        raw.parse().unwrap()
+    }
+
+    fn caps(cs: &[&str]) -> Vec<String> {
+        cs.iter().map(|c| c.to_string()).collect()
+    }
+
+    #[test]
+    async fn test_ls_reply() {
+        assert_eq!(
+            ls_reply(&message(
+                ":irc.libera.chat CAP * LS * :account-notify sasl=PLAIN,EXTERNAL"
+            )),
+            Some((caps(&["account-notify", "sasl"]), true))
+        );
+        assert_eq!(
+            ls_reply(&message(":irc.libera.chat CAP * LS :multi-prefix")),
+            Some((caps(&["multi-prefix"]), false))
+        );
+        assert_eq!(
+            ls_reply(&message(":irc.libera.chat CAP * ACK :multi-prefix")),
+            None
+        );
+    }
+
+    #[test]
+    async fn test_cap_req() {
+        let wanted = caps(DEFAULT_CAPABILITIES);
+        let available = caps(&["multi-prefix", "sasl", "account-notify", "server-time"]);
+        let requested = to_request(&wanted, &available);
+        assert_eq!(requested, caps(&["sasl", "server-time", "multi-prefix"]));
+
+        let req: Message = cap_req(&requested).into();
+        assert_eq!(
+            req.to_string(),
+            "CAP REQ :sasl server-time multi-prefix\r\n"
+        );
+    }
+
+    #[test]
+    async fn test_ack_nak_accounting() {
+        let mut summary = CapSummary::default();
+        assert!(summary.record(&message(
+            ":irc.libera.chat CAP rustygolem ACK :sasl multi-prefix"
+        )));
+        assert!(summary.record(&message(
+            ":irc.libera.chat CAP rustygolem NAK :echo-message"
+        )));
+        assert!(!summary.record(&message(":irc.libera.chat CAP * LS :sasl")));
+
+        assert_eq!(
+            summary,
+            CapSummary {
+                acked: caps(&["sasl", "multi-prefix"]),
+                naked: caps(&["echo-message"]),
+            }
+        );
+        assert!(summary.is_acked("sasl"));
+        assert!(!summary.is_acked("echo-message"));
+    }
+
+    #[test]
+    async fn test_is_echo() {
+        let msg = message(":rustygolem!golem@host PRIVMSG #chan :coucou");
+        assert!(is_echo(&msg, "rustygolem"));
+        assert!(!is_echo(&msg, "someone_else"));
+        assert!(!is_echo(
+            &message(":rustygolem!golem@host JOIN #chan"),
+            "rustygolem"
+        ));
+    }
+}
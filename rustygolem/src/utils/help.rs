@@ -0,0 +1,93 @@
+use plugin_core::utils::parser;
+use plugin_core::CommandHelp;
+
+/// `λhelp [> target]` or `λhelp <command> [> target]`.
+/// Returns the optional command and the optional target.
+pub fn parse_command(input: &str) -> Option<(Option<&str>, Option<&str>)> {
+    if let Some(mb_target) = parser::single_command("help", input) {
+        return Some((None, mb_target));
+    }
+    parser::command_with_arg("help", input).map(|(cmd, mb_target)| (Some(cmd), mb_target))
+}
+
+/// The lines to reply, one per command when asking about a specific one.
+/// Commands are shown with the given prefix.
+pub fn help_lines(commands: &[CommandHelp], cmd: Option<&str>, prefix: &str) -> Vec<String> {
+    match cmd {
+        None => {
+            let mut names = commands
+                .iter()
+                .map(|c| format!("{}{}", prefix, c.name))
+                .collect::<Vec<_>>();
+            names.sort();
+            names.dedup();
+            vec![format!(
+                "Commandes: {} ({}help <commande> pour les détails)",
+                names.join(", "),
+                prefix
+            )]
+        }
+        Some(cmd) => {
+            let lines = commands
+                .iter()
+                .filter(|c| c.name == cmd)
+                .map(|c| {
+                    let syntax = format!("{}{} {}", prefix, c.name, c.args);
+                    format!("{}: {}", syntax.trim_end(), c.description)
+                })
+                .collect::<Vec<_>>();
+            if lines.is_empty() {
+                vec![format!(
+                    "Connais pas {p}{}, essaye {p}help",
+                    cmd,
+                    p = prefix
+                )]
+            } else {
+                lines
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn commands() -> Vec<CommandHelp> {
+        vec![
+            CommandHelp::new("topic", "", "le topic du channel"),
+            CommandHelp::new("crypto", "<coin>", "cours actuel"),
+            CommandHelp::new("topic", "set <topic>", "change le topic"),
+        ]
+    }
+
+    #[test]
+    async fn test_parse_command() {
+        assert_eq!(parse_command("λhelp"), Some((None, None)));
+        assert_eq!(
+            parse_command("λhelp crypto > charlie"),
+            Some((Some("crypto"), Some("charlie")))
+        );
+        assert_eq!(parse_command("λhelpme"), None);
+    }
+
+    #[test]
+    async fn test_help_lines() {
+        assert_eq!(
+            help_lines(&commands(), None, "λ"),
+            vec!["Commandes: λcrypto, λtopic (λhelp <commande> pour les détails)"]
+        );
+        assert_eq!(
+            help_lines(&commands(), Some("topic"), "λ"),
+            vec![
+                "λtopic: le topic du channel",
+                "λtopic set <topic>: change le topic"
+            ]
+        );
+        assert_eq!(
+            help_lines(&commands(), Some("wut"), "!"),
+            vec!["Connais pas !wut, essaye !help"]
+        );
+    }
+}
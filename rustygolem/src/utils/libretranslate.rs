@@ -0,0 +1,136 @@
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+
+/// Languages known by libretranslate, an instance may not have all of them
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Language {
+    Arabic,
+    Chinese,
+    Dutch,
+    English,
+    French,
+    German,
+    Italian,
+    Japanese,
+    Polish,
+    Portuguese,
+    Russian,
+    Spanish,
+    Ukrainian,
+}
+
+impl Language {
+    pub const ALL: [Language; 13] = [
+        Language::Arabic,
+        Language::Chinese,
+        Language::Dutch,
+        Language::English,
+        Language::French,
+        Language::German,
+        Language::Italian,
+        Language::Japanese,
+        Language::Polish,
+        Language::Portuguese,
+        Language::Russian,
+        Language::Spanish,
+        Language::Ukrainian,
+    ];
+
+    /// ISO 639-1 code, as used by libretranslate
+    pub fn code(self) -> &'static str {
+        match self {
+            Language::Arabic => "ar",
+            Language::Chinese => "zh",
+            Language::Dutch => "nl",
+            Language::English => "en",
+            Language::French => "fr",
+            Language::German => "de",
+            Language::Italian => "it",
+            Language::Japanese => "ja",
+            Language::Polish => "pl",
+            Language::Portuguese => "pt",
+            Language::Russian => "ru",
+            Language::Spanish => "es",
+            Language::Ukrainian => "uk",
+        }
+    }
+
+    pub fn from_code(code: &str) -> Option<Language> {
+        Language::ALL
+            .into_iter()
+            .find(|lang| lang.code().eq_ignore_ascii_case(code))
+    }
+}
+
+#[derive(Serialize)]
+struct TranslateRequest<'a> {
+    q: &'a str,
+    source: &'a str,
+    target: &'a str,
+    format: &'a str,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TranslateResponse {
+    translated_text: String,
+}
+
+/// What libretranslate says about the requests it refuses,
+/// like a language pair it can't translate
+#[derive(Deserialize)]
+struct ErrorResponse {
+    error: String,
+}
+
+pub async fn translate(
+    client: &reqwest::Client,
+    base_url: &str,
+    source: Language,
+    target: Language,
+    text: &str,
+) -> anyhow::Result<String> {
+    let url = format!("{}/translate", base_url.trim_end_matches('/'));
+    let resp = client
+        .post(url)
+        .json(&TranslateRequest {
+            q: text,
+            source: source.code(),
+            target: target.code(),
+            format: "text",
+        })
+        .send()
+        .await
+        .context("cannot query libretranslate")?;
+    if resp.status() == reqwest::StatusCode::BAD_REQUEST {
+        let error = resp
+            .json::<ErrorResponse>()
+            .await
+            .map(|e| e.error)
+            .unwrap_or_else(|_| "bad request".to_string());
+        anyhow::bail!("libretranslate refused it: {error}");
+    }
+    let resp = resp
+        .error_for_status()
+        .context("libretranslate error")?
+        .json::<TranslateResponse>()
+        .await
+        .context("unexpected response from libretranslate")?;
+    Ok(resp.translated_text)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    async fn test_language_codes() {
+        assert_eq!(Language::from_code("fr"), Some(Language::French));
+        assert_eq!(Language::from_code("EN"), Some(Language::English));
+        assert_eq!(Language::from_code("xx"), None);
+        for lang in Language::ALL {
+            assert_eq!(Language::from_code(lang.code()), Some(lang));
+        }
+    }
+}
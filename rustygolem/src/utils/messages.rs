@@ -1,6 +1,127 @@
+use irc::proto::{Command, Message};
+
 pub fn with_target(msg: &str, mb_target: &Option<&str>) -> String {
     let target = mb_target
         .map(|t| format!("{}: ", t))
         .unwrap_or_else(|| "".to_string());
     format!("{}{}", target, msg)
 }
+
+/// Lines are at most 512 bytes, including the CRLF
+const MAX_LINE_BYTES: usize = 510;
+/// room left for the `:nick!user@host ` prefix the server adds when
+/// relaying our messages
+const PREFIX_RESERVE: usize = 100;
+
+/// Split a PRIVMSG whose line would be too long for the server into several
+/// ones, in order. Other messages are returned unchanged.
+pub fn split_long_privmsg(msg: &Message) -> Vec<Message> {
+    let (target, text) = match &msg.command {
+        Command::PRIVMSG(target, text) => (target, text),
+        _ => return vec![msg.clone()],
+    };
+    let overhead = PREFIX_RESERVE + "PRIVMSG ".len() + target.len() + " :".len();
+    split_text(text, MAX_LINE_BYTES.saturating_sub(overhead))
+        .into_iter()
+        .map(|part| Message {
+            tags: msg.tags.clone(),
+            prefix: msg.prefix.clone(),
+            command: Command::PRIVMSG(target.clone(), part.to_string()),
+        })
+        .collect()
+}
+
+/// Split the text in chunks of at most `max_bytes`, on a space when
+/// possible, and never inside a utf-8 character.
+fn split_text(text: &str, max_bytes: usize) -> Vec<&str> {
+    let mut parts = vec![];
+    let mut rest = text;
+    while rest.len() > max_bytes {
+        let mut cut = max_bytes;
+        while cut > 0 && !rest.is_char_boundary(cut) {
+            cut -= 1;
+        }
+        if let Some(space) = rest[..cut].rfind(' ').filter(|&i| i > 0) {
+            cut = space;
+        }
+        if cut == 0 {
+            // less than a single character fits, give up splitting
+            break;
+        }
+        parts.push(rest[..cut].trim_end());
+        rest = rest[cut..].trim_start();
+    }
+    if !rest.is_empty() || parts.is_empty() {
+        parts.push(rest);
+    }
+    parts
+}
+
+/// Only the biggest unit: `42s`, `5min`, `3h` or `12d`
+pub fn format_ago(elapsed: chrono::Duration) -> String {
+    let secs = elapsed.num_seconds().max(0);
+    match secs {
+        s if s < 60 => format!("{}s", s),
+        s if s < 60 * 60 => format!("{}min", s / 60),
+        s if s < 24 * 60 * 60 => format!("{}h", s / (60 * 60)),
+        s => format!("{}d", s / (24 * 60 * 60)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    async fn test_format_ago() {
+        assert_eq!(format_ago(chrono::Duration::seconds(42)), "42s");
+        assert_eq!(format_ago(chrono::Duration::minutes(61)), "1h");
+        assert_eq!(format_ago(chrono::Duration::hours(3)), "3h");
+        assert_eq!(format_ago(chrono::Duration::days(12)), "12d");
+    }
+
+    #[test]
+    async fn test_split_text() {
+        assert_eq!(split_text("coucou", 10), vec!["coucou"]);
+        assert_eq!(split_text("", 10), vec![""]);
+        assert_eq!(
+            split_text("coucou les amis", 10),
+            vec!["coucou", "les amis"],
+            "on word boundaries"
+        );
+        assert_eq!(
+            split_text("abcdefghijkl", 5),
+            vec!["abcde", "fghij", "kl"],
+            "long words are cut"
+        );
+        assert_eq!(
+            split_text("ééééé", 5),
+            vec!["éé", "éé", "é"],
+            "never inside a character"
+        );
+    }
+
+    #[test]
+    async fn test_split_long_privmsg() {
+        let text = "a ".repeat(300);
+        let msg: Message = Command::PRIVMSG("#chan".to_string(), text.clone()).into();
+        let parts = split_long_privmsg(&msg);
+        assert_eq!(parts.len(), 2);
+        for part in &parts {
+            let line = part.to_string();
+            assert!(line.len() + PREFIX_RESERVE <= 512, "too long: {}", line);
+        }
+        let texts = parts
+            .iter()
+            .map(|m| match &m.command {
+                Command::PRIVMSG(_, t) => t.as_str(),
+                _ => unreachable!(),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(texts.join(" "), text);
+
+        let join: Message = Command::JOIN("#chan".to_string(), None, None).into();
+        assert_eq!(split_long_privmsg(&join), vec![join]);
+    }
+}
@@ -1,2 +1,9 @@
+pub mod admin;
+pub mod caps;
+pub mod help;
+pub mod libretranslate;
 pub mod messages;
 pub mod parser;
+pub mod plugin_toggle;
+pub mod rejoin;
+pub mod throttle;
@@ -26,13 +26,7 @@ pub fn word<'a, E: ParseError<&'a str>>(input: &'a str) -> IResult<&'a str, &'a
     recognize(many1(alphanumeric1))(input)
 }
 
-/// Utility to parse common command prefix
-pub fn command_prefix(input: &str) -> nom::IResult<&str, &str> {
-    nom::branch::alt((
-        nom::bytes::complete::is_a("&"),
-        nom::bytes::complete::is_a("λ"),
-    ))(input)
-}
+pub use plugin_core::utils::parser::command_prefix;
 
 /// Parse a single command with an optional target
 /// Returns None if the parser fails
@@ -0,0 +1,175 @@
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+
+use crate::utils::parser::command_prefix;
+use nom::branch::alt;
+use nom::bytes::complete::{tag, take_while1};
+use nom::character::complete::{multispace0, multispace1};
+use nom::combinator::{all_consuming, map, value};
+use nom::sequence::{preceded, terminated, tuple};
+use nom::Finish;
+
+/// Names of the plugins disabled with `λplugin disable`, until the bot restarts.
+/// Disabled plugins don't see the incoming messages, aren't listed by λhelp,
+/// and what their `run` loop sends is dropped. The loop itself keeps going,
+/// so that enabling the plugin again takes effect right away.
+/// Cloning is cheap and all clones share the same set.
+#[derive(Debug, Clone, Default)]
+pub struct DisabledPlugins {
+    names: Arc<Mutex<HashSet<String>>>,
+}
+
+impl DisabledPlugins {
+    pub fn contains(&self, name: &str) -> bool {
+        self.names
+            .lock()
+            .expect("disabled plugins lock")
+            .contains(name)
+    }
+
+    /// Returns false if the plugin was already disabled
+    pub fn disable(&self, name: &str) -> bool {
+        self.names
+            .lock()
+            .expect("disabled plugins lock")
+            .insert(name.to_string())
+    }
+
+    /// Returns false if the plugin wasn't disabled
+    pub fn enable(&self, name: &str) -> bool {
+        self.names
+            .lock()
+            .expect("disabled plugins lock")
+            .remove(name)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum PluginCmd<'input> {
+    List,
+    Enable(&'input str),
+    Disable(&'input str),
+}
+
+impl PluginCmd<'_> {
+    /// Only the owners can change which plugins are enabled
+    pub fn needs_owner(&self) -> bool {
+        !matches!(self, PluginCmd::List)
+    }
+}
+
+/// `λplugins`, `λplugin enable <name>` or `λplugin disable <name>`
+pub fn parse_command(input: &str) -> Option<PluginCmd> {
+    let name = || take_while1(|c: char| !c.is_whitespace());
+    let cmd = preceded(
+        command_prefix,
+        alt((
+            value(PluginCmd::List, tag("plugins")),
+            preceded(
+                tuple((tag("plugin"), multispace1)),
+                alt((
+                    map(
+                        preceded(tuple((tag("enable"), multispace1)), name()),
+                        PluginCmd::Enable,
+                    ),
+                    map(
+                        preceded(tuple((tag("disable"), multispace1)), name()),
+                        PluginCmd::Disable,
+                    ),
+                )),
+            ),
+        )),
+    );
+    all_consuming(terminated(cmd, multispace0))(input)
+        .finish()
+        .map(|x| x.1)
+        .ok()
+}
+
+/// Runs the command against the loaded plugins, and returns the reply
+pub fn handle_command(cmd: &PluginCmd, loaded: &[&str], disabled: &DisabledPlugins) -> String {
+    let known = |name: &str| loaded.contains(&name);
+    match cmd {
+        PluginCmd::List => {
+            let mut names = loaded
+                .iter()
+                .map(|name| {
+                    if disabled.contains(name) {
+                        format!("{} (désactivé)", name)
+                    } else {
+                        name.to_string()
+                    }
+                })
+                .collect::<Vec<_>>();
+            names.sort();
+            format!("Plugins: {}", names.join(", "))
+        }
+        PluginCmd::Enable(name) | PluginCmd::Disable(name) if !known(name) => {
+            format!("Connais pas le plugin {}", name)
+        }
+        PluginCmd::Enable(name) => {
+            if disabled.enable(name) {
+                format!("Plugin {} activé", name)
+            } else {
+                format!("Le plugin {} est déjà activé", name)
+            }
+        }
+        PluginCmd::Disable(name) => {
+            if disabled.disable(name) {
+                format!("Plugin {} désactivé", name)
+            } else {
+                format!("Le plugin {} est déjà désactivé", name)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    async fn test_parse_command() {
+        assert_eq!(parse_command("λplugins"), Some(PluginCmd::List));
+        assert_eq!(
+            parse_command("λplugin disable karma "),
+            Some(PluginCmd::Disable("karma"))
+        );
+        assert_eq!(
+            parse_command("λplugin  enable karma"),
+            Some(PluginCmd::Enable("karma"))
+        );
+        assert_eq!(parse_command("λplugin disable"), None);
+        assert_eq!(parse_command("λplugins karma"), None);
+    }
+
+    #[test]
+    async fn test_handle_command() {
+        let loaded = ["url", "karma", "crypto"];
+        let disabled = DisabledPlugins::default();
+
+        assert_eq!(
+            handle_command(&PluginCmd::Disable("karma"), &loaded, &disabled),
+            "Plugin karma désactivé"
+        );
+        assert!(disabled.contains("karma"));
+        assert_eq!(
+            handle_command(&PluginCmd::Disable("karma"), &loaded, &disabled),
+            "Le plugin karma est déjà désactivé"
+        );
+        assert_eq!(
+            handle_command(&PluginCmd::List, &loaded, &disabled),
+            "Plugins: crypto, karma (désactivé), url"
+        );
+        assert_eq!(
+            handle_command(&PluginCmd::Enable("karma"), &loaded, &disabled),
+            "Plugin karma activé"
+        );
+        assert!(!disabled.contains("karma"));
+        assert_eq!(
+            handle_command(&PluginCmd::Disable("nope"), &loaded, &disabled),
+            "Connais pas le plugin nope"
+        );
+    }
+}
@@ -0,0 +1,89 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// A kick this long after the previous one in the same channel
+/// starts the count of attempts again
+const RESET_AFTER: Duration = Duration::from_secs(60 * 60);
+
+/// Decide when to rejoin a channel the bot got kicked from. The delay
+/// doubles with each kick, and the bot stays out after `max_attempts`
+/// kicks in a row, to not fight with a determined op.
+pub struct RejoinPolicy {
+    delay: Duration,
+    max_attempts: u32,
+    /// channels where the bot stays out once kicked
+    excluded: Vec<String>,
+    /// number of kicks in a row, and when the last one happened, per channel
+    kicks: Mutex<HashMap<String, (u32, Instant)>>,
+}
+
+impl RejoinPolicy {
+    pub fn new(delay: Duration, max_attempts: u32, excluded: Vec<String>) -> Self {
+        RejoinPolicy {
+            delay,
+            max_attempts,
+            excluded,
+            kicks: Default::default(),
+        }
+    }
+
+    /// How long to wait before rejoining `channel` after a kick at `now`,
+    /// or None to stay out.
+    pub fn on_kick(&self, channel: &str, now: Instant) -> Option<Duration> {
+        if self
+            .excluded
+            .iter()
+            .any(|c| c.eq_ignore_ascii_case(channel))
+        {
+            return None;
+        }
+        let mut kicks = self.kicks.lock().expect("rejoin policy lock");
+        let count = match kicks.get(channel) {
+            Some((count, last)) if now.saturating_duration_since(*last) < RESET_AFTER => count + 1,
+            _ => 1,
+        };
+        kicks.insert(channel.to_string(), (count, now));
+        if count > self.max_attempts {
+            return None;
+        }
+        Some(self.delay.saturating_mul(2u32.saturating_pow(count - 1)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    async fn test_rejoin_backoff() {
+        let secs = Duration::from_secs;
+        let policy = RejoinPolicy::new(secs(10), 3, vec![]);
+        let now = Instant::now();
+        assert_eq!(policy.on_kick("#chan", now), Some(secs(10)));
+        assert_eq!(policy.on_kick("#chan", now + secs(30)), Some(secs(20)));
+        assert_eq!(
+            policy.on_kick("#other", now + secs(30)),
+            Some(secs(10)),
+            "counted per channel"
+        );
+        assert_eq!(policy.on_kick("#chan", now + secs(60)), Some(secs(40)));
+        assert_eq!(
+            policy.on_kick("#chan", now + secs(120)),
+            None,
+            "gives up after too many kicks"
+        );
+        assert_eq!(
+            policy.on_kick("#chan", now + secs(120) + RESET_AFTER),
+            Some(secs(10)),
+            "starts again after a while"
+        );
+    }
+
+    #[test]
+    async fn test_excluded_channels() {
+        let policy = RejoinPolicy::new(Duration::from_secs(10), 3, vec!["#Quiet".to_string()]);
+        assert_eq!(policy.on_kick("#quiet", Instant::now()), None);
+    }
+}
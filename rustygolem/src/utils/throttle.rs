@@ -0,0 +1,177 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use irc::proto::{Command, Message};
+
+/// Guard against the bot repeating itself: a message identical to the
+/// previous one sent to the same target within `window` is suppressed.
+pub struct DuplicateGuard {
+    window: Duration,
+    /// last message sent to each target, and when it was sent
+    last_sent: Mutex<HashMap<String, (String, Instant)>>,
+}
+
+impl DuplicateGuard {
+    pub fn new(window: Duration) -> Self {
+        DuplicateGuard {
+            window,
+            last_sent: Default::default(),
+        }
+    }
+
+    /// Returns false if the message is a duplicate and should not be sent.
+    /// Only PRIVMSG and NOTICE are considered, everything else is always allowed.
+    pub fn allow(&self, msg: &Message) -> bool {
+        let (target, text) = match &msg.command {
+            Command::PRIVMSG(target, text) | Command::NOTICE(target, text) => (target, text),
+            _ => return true,
+        };
+
+        let now = Instant::now();
+        let mut last_sent = self.last_sent.lock().expect("duplicate guard lock");
+        match last_sent.get(target) {
+            Some((last_text, sent_at))
+                if last_text == text && now.duration_since(*sent_at) < self.window =>
+            {
+                false
+            }
+            _ => {
+                last_sent.insert(target.to_string(), (text.to_string(), now));
+                true
+            }
+        }
+    }
+}
+
+/// Token bucket pacing the messages sent to the server, so that the bot
+/// doesn't get kicked for flooding. Allows `burst` messages at once, and
+/// then `rate` messages per second.
+pub struct RateLimiter {
+    rate: f64,
+    burst: f64,
+    /// available tokens, and when they were last refilled
+    bucket: Mutex<(f64, Instant)>,
+}
+
+impl RateLimiter {
+    /// A rate of 0 disables the limit
+    pub fn new(rate: f64, burst: u32) -> Self {
+        let burst = f64::from(burst.max(1));
+        RateLimiter {
+            rate,
+            burst,
+            bucket: Mutex::new((burst, Instant::now())),
+        }
+    }
+
+    /// Wait until a message can be sent
+    pub async fn acquire(&self) {
+        while let Err(wait) = self.try_acquire(Instant::now()) {
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    /// Take a token if one is available at `now`, otherwise returns
+    /// how long to wait for the next one.
+    fn try_acquire(&self, now: Instant) -> Result<(), Duration> {
+        if self.rate <= 0.0 {
+            return Ok(());
+        }
+        let mut bucket = self.bucket.lock().expect("rate limiter lock");
+        let (tokens, last) = *bucket;
+        let elapsed = now.saturating_duration_since(last).as_secs_f64();
+        let tokens = (tokens + elapsed * self.rate).min(self.burst);
+        if tokens >= 1.0 {
+            *bucket = (tokens - 1.0, now);
+            Ok(())
+        } else {
+            *bucket = (tokens, now);
+            Err(Duration::from_secs_f64((1.0 - tokens) / self.rate))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn privmsg(target: &str, text: &str) -> Message {
+        Command::PRIVMSG(target.to_string(), text.to_string()).into()
+    }
+
+    #[test]
+    async fn test_suppress_consecutive_duplicates() {
+        let guard = DuplicateGuard::new(Duration::from_secs(5));
+        assert!(guard.allow(&privmsg("#chan", "coucou")));
+        assert!(
+            !guard.allow(&privmsg("#chan", "coucou")),
+            "identical consecutive message is suppressed"
+        );
+        assert!(
+            guard.allow(&privmsg("#other", "coucou")),
+            "duplicates are tracked per target"
+        );
+        assert!(
+            guard.allow(&privmsg("#chan", "autre chose")),
+            "a different message passes"
+        );
+        assert!(
+            guard.allow(&privmsg("#chan", "coucou")),
+            "not a duplicate of the immediately previous message"
+        );
+        assert!(guard.allow(&Command::PONG("server".to_string(), None).into()));
+        assert!(
+            guard.allow(&Command::PONG("server".to_string(), None).into()),
+            "only PRIVMSG and NOTICE are throttled"
+        );
+    }
+
+    #[test]
+    async fn test_window_expires() {
+        let guard = DuplicateGuard::new(Duration::from_millis(10));
+        assert!(guard.allow(&privmsg("#chan", "coucou")));
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(
+            guard.allow(&privmsg("#chan", "coucou")),
+            "can repeat once the window expired"
+        );
+    }
+
+    #[test]
+    async fn test_rate_limiter() {
+        let limiter = RateLimiter::new(2.0, 3);
+        let start = Instant::now();
+        for _ in 0..3 {
+            assert_eq!(limiter.try_acquire(start), Ok(()), "burst");
+        }
+        assert_eq!(
+            limiter.try_acquire(start),
+            Err(Duration::from_millis(500)),
+            "bucket is empty"
+        );
+        assert_eq!(
+            limiter.try_acquire(start + Duration::from_millis(500)),
+            Ok(()),
+            "refilled at the given rate"
+        );
+        assert_eq!(limiter.try_acquire(start + Duration::from_secs(60)), Ok(()));
+        assert_eq!(limiter.try_acquire(start + Duration::from_secs(60)), Ok(()));
+        assert_eq!(limiter.try_acquire(start + Duration::from_secs(60)), Ok(()));
+        assert!(
+            limiter
+                .try_acquire(start + Duration::from_secs(60))
+                .is_err(),
+            "refills up to the burst only"
+        );
+    }
+
+    #[test]
+    async fn test_rate_limiter_disabled() {
+        let limiter = RateLimiter::new(0.0, 1);
+        let now = Instant::now();
+        for _ in 0..100 {
+            assert_eq!(limiter.try_acquire(now), Ok(()));
+        }
+    }
+}